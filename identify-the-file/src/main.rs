@@ -1,42 +1,460 @@
 #![crate_name = "identify_the_file"]
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
 use itf_core::{
-    file_point_calculator::FilePointCalculator, file_processor, pattern::Pattern,
-    pattern_handler::PatternHandler, utils,
+    embedded_rule,
+    error::ItfError,
+    evaluation::{self, Evaluation},
+    file_point_calculator::{MagicOnlyScoring, ScoringConfig},
+    identifier::{ExtensionFilter, Identifier, MatchOutcome, MatchResult, RankBy},
+    interner, interop,
+    pattern::{
+        BuildOptions, BuildProgress, BuildReport, CrossValidation, NegativeValidation, Pattern,
+        PatternCompression, PatternQuality, ProgressSink, QualityGrade, SampleWeights,
+        DEFAULT_MIN_SAMPLES, RECOMMENDED_MIN_SAMPLES,
+    },
+    pattern_archive::ArchiveKind,
+    pattern_bundle::PatternBundle,
+    pattern_conflicts,
+    pattern_diff::PatternDiff,
+    pattern_editor::{self, PatternEdit},
+    pattern_fetch,
+    pattern_handler::{DuplicatePolicy, LoadReport, PatternHandler, PatternSource},
+    pattern_index::PatternIndex,
+    pattern_lint::{self, LintIssue, LintSeverity},
+    pattern_report::{self, ReportFormat},
+    pattern_signing,
+    result_template::ResultTemplate,
+    string_blocklist::StringBlocklist,
+    utils,
 };
-use prettytable::{Cell, Row, Table};
+use notify::{EventKind, RecursiveMode, Watcher};
+use prettytable::{format, Cell, Row, Table};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::{env, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    error::Error,
+    fs,
+    io::{self, IsTerminal, Write},
+    iter,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 #[derive(Parser)]
 #[command(
     name = "Identify The File",
     about = "A CLI application designed to identify files or build patterns to aid with file type identification.",
     version = "0.1.0",
-    author = "sciguyryan <sciguyryan@gmail.com>"
+    author = "sciguyryan <sciguyryan@gmail.com>",
+    after_help = "Environment:\n  ITF_PATTERN_DIR  The pattern directory (or bundle, or zip or tar archive) used when -p/--pattern-source-dir isn't given. \
+    If neither is given then ./patterns is used, falling back to the built-in patterns if it doesn't exist.\n  \
+    NO_COLOR         Disables colored output when set to a non-empty value, unless --color=always is given."
 )]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// The maximum number of threads to be used for identification and pattern building.
+    /// Defaults to the number of logical CPUs.
+    #[arg(long, global = true, value_name = "N")]
+    threads: Option<usize>,
+
+    /// When tables should be displayed with color. Automatic color is disabled when the output isn't
+    /// a terminal, or when NO_COLOR is set.
+    #[arg(long, global = true, value_enum, default_value_t = ColorMode::Auto, value_name = "WHEN")]
+    color: ColorMode,
+
+    /// Display tables without borders or color, which is better suited to logs and CI output.
+    #[arg(long, global = true, default_value_t = false)]
+    plain: bool,
+
+    /// Display which pattern files were loaded, skipped or couldn't be loaded.
+    #[arg(long, global = true, default_value_t = false)]
+    verbose: bool,
+
+    /// How a pattern that has the same UUID as another pattern should be handled.
+    #[arg(long, global = true, value_enum, default_value_t = DuplicateMode::KeepFirst, value_name = "POLICY")]
+    on_duplicate_uuid: DuplicateMode,
+
+    /// A directory of patterns that override those of the pattern source, replacing any pattern with the same
+    /// UUID. May be specified multiple times, with later directories taking precedence.
+    #[arg(long = "override-dir", global = true, value_name = "DIR")]
+    override_dirs: Vec<String>,
+
+    /// The UUID or name of a pattern that shouldn't be loaded. May be specified multiple times. Patterns can
+    /// also be disabled by listing them, one per line, within an itf-disabled.txt file in the pattern directory.
+    #[arg(long = "disable", global = true, value_name = "PATTERN")]
+    disabled_patterns: Vec<String>,
+
+    /// A TOML configuration file. The weights by which the points awarded for each criterion of a match are
+    /// multiplied may be given within its [scoring] section, as "sequences", "strings", "regexes", "entropy"
+    /// and "extension". Each weight defaults to 1.
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<String>,
+}
+
+/// The MIME type to be reported when the type of a file couldn't be determined.
+const UNKNOWN_MIMETYPE: &str = "application/octet-stream";
+/// The extended attribute in which the detected MIME type of a file is stored.
+#[cfg(unix)]
+const XATTR_MIME_TYPE: &str = "user.mime_type";
+/// The extended attribute in which the UUID of the pattern matching a file is stored.
+#[cfg(unix)]
+const XATTR_PATTERN_UUID: &str = "user.itf.pattern_uuid";
+/// The time for which a watched file must remain unchanged before it is identified.
+const WATCH_SETTLE_TIME: Duration = Duration::from_millis(500);
+/// The environment variable giving the pattern source, when one isn't specified on the command line.
+const PATTERN_DIR_ENV_VAR: &str = "ITF_PATTERN_DIR";
+/// The exit code given when a file matched no pattern in quiet mode.
+const EXIT_NO_MATCH: i32 = 1;
+/// The exit code given when the top match for a file was ambiguous in quiet mode.
+const EXIT_AMBIGUOUS: i32 = 2;
+/// The exit code given when a command failed.
+const EXIT_ERROR: i32 = 3;
+/// The environment variable that disables automatic color when set to a non-empty value. See <https://no-color.org>.
+const NO_COLOR_ENV_VAR: &str = "NO_COLOR";
+
+/// The settings chosen by the global options, which apply to every command.
+struct CliOptions {
+    /// When tables should be displayed with color, as chosen by `--color`.
+    color: ColorMode,
+    /// Should tables be displayed without borders or color, as chosen by `--plain`?
+    plain: bool,
+    /// Should the diagnostic output requested by `--verbose` be displayed?
+    verbose: bool,
+    /// How patterns with duplicate UUIDs should be handled, as chosen by `--on-duplicate-uuid`.
+    duplicate_policy: DuplicatePolicy,
+    /// The directories of patterns layered over the pattern source, as chosen by `--override-dir`.
+    override_dirs: Vec<String>,
+    /// The UUIDs or names of the patterns that shouldn't be loaded, as chosen by `--disable`.
+    disabled_patterns: Vec<String>,
+    /// The weights by which matches are scored, as given by the configuration file chosen by `--config`.
+    scoring_config: Option<ScoringConfig>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ColorMode {
+    /// Use color when the output is a terminal and NO_COLOR isn't set.
+    Auto,
+    /// Always use color, even when the output is redirected.
+    Always,
+    /// Never use color.
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum DuplicateMode {
+    /// Refuse to load the patterns.
+    Error,
+    /// Keep the pattern that was loaded first.
+    KeepFirst,
+    /// Keep the pattern whose file was modified most recently.
+    KeepNewest,
+}
+
+impl From<DuplicateMode> for DuplicatePolicy {
+    fn from(mode: DuplicateMode) -> Self {
+        match mode {
+            DuplicateMode::Error => DuplicatePolicy::Error,
+            DuplicateMode::KeepFirst => DuplicatePolicy::KeepFirst,
+            DuplicateMode::KeepNewest => DuplicatePolicy::KeepNewest,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// A table of the matching patterns.
+    Table,
+    /// The MIME type of the top match only.
+    Mime,
+    /// A JSON array containing an object for each file, giving its path (`file`) and its matching patterns
+    /// (`results`). Each result gives the `uuid`, `name`, `mime`, `extension`, `points`, `max_points`
+    /// and `percentage` of the match. A file that couldn't be read instead gives an `error`.
+    Json,
+    /// Comma-separated values, with a row for each file giving the name, MIME type and percentage of the
+    /// top match, and whether the result is ambiguous.
+    Csv,
+    /// As with CSV, but separated by tabs.
+    Tsv,
+}
+
+impl OutputFormat {
+    /// The delimiter between the fields of a row, for the delimited output formats.
+    fn delimiter(&self) -> char {
+        if *self == OutputFormat::Tsv {
+            '\t'
+        } else {
+            ','
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum RankMode {
+    /// The percentage of the maximum points that were awarded.
+    Percentage,
+    /// The percentage, weighted by the amount of evidence considered by the pattern, so that more detailed
+    /// patterns outrank trivial ones.
+    Specificity,
+}
+
+impl From<RankMode> for RankBy {
+    fn from(mode: RankMode) -> Self {
+        match mode {
+            RankMode::Percentage => RankBy::Percentage,
+            RankMode::Specificity => RankBy::Specificity,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ScoringMode {
+    /// Score every criterion of a pattern, using the weights given by the configuration file, if any.
+    Default,
+    /// Only score the byte sequences and regexes of a pattern, disregarding its strings, composition and
+    /// extensions. Patterns without any byte sequences or regexes won't match.
+    MagicOnly,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum LintOutputFormat {
+    /// One line per issue.
+    Text,
+    /// A JSON array listing the issues found in each pattern file.
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ExportFormat {
+    /// Rules in the magic format used by file(1) and libmagic.
+    Magic,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ImportFormat {
+    /// TrID XML definitions.
+    Trid,
+    /// YARA rules. Only simple rules can be converted, and any unsupported parts are reported and skipped.
+    Yara,
+}
+
+impl ImportFormat {
+    /// The extension of the definition files, used when converting a directory of definitions.
+    fn extension(&self) -> &'static str {
+        match self {
+            ImportFormat::Trid => "xml",
+            ImportFormat::Yara => "yar",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum CompressionFormat {
+    /// gzip, written with a .json.gz extension.
+    Gzip,
+    /// Zstandard, written with a .json.zst extension.
+    Zstd,
+}
+
+impl From<CompressionFormat> for PatternCompression {
+    fn from(format: CompressionFormat) -> Self {
+        match format {
+            CompressionFormat::Gzip => PatternCompression::Gzip,
+            CompressionFormat::Zstd => PatternCompression::Zstd,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ReportOutputFormat {
+    /// A Markdown document.
+    Markdown,
+    /// A standalone HTML document.
+    Html,
+}
+
+impl From<ReportOutputFormat> for ReportFormat {
+    fn from(format: ReportOutputFormat) -> Self {
+        match format {
+            ReportOutputFormat::Markdown => ReportFormat::Markdown,
+            ReportOutputFormat::Html => ReportFormat::Html,
+        }
+    }
+}
+
+/// A progress bar displaying the progress of a pattern build. This is written to stderr.
+struct BuildProgressBar {
+    bar: ProgressBar,
+}
+
+impl Default for BuildProgressBar {
+    fn default() -> Self {
+        let bar = ProgressBar::new(0);
+        bar.set_style(
+            ProgressStyle::with_template("{bar:40} {pos}/{len} files, {msg} [{elapsed_precise}]")
+                .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+
+        Self { bar }
+    }
+}
+
+impl ProgressSink for BuildProgressBar {
+    fn update(&mut self, progress: &BuildProgress) {
+        self.bar.set_length(progress.total_files as u64);
+        self.bar.set_position(progress.files_processed as u64);
+        self.bar.set_message(format!(
+            "{} sequences, {} strings",
+            progress.sequence_count, progress.string_count
+        ));
+    }
+
+    fn finish(&mut self) {
+        self.bar.finish_and_clear();
+    }
 }
 
+// The commands are only parsed once, so the size difference between them is of no concern.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     Identify {
-        #[arg(short, long, default_value = "", value_name = "DIR")]
-        pattern_source_dir: String,
+        /// The pattern directory, bundle or archive. May be specified multiple times, in which case the patterns
+        /// of every source are merged, with any patterns that have the same UUID being reported as conflicts.
+        #[arg(short = 'p', long = "pattern-source-dir", value_name = "DIR")]
+        pattern_source_dirs: Vec<String>,
 
+        /// Only load the patterns whose file path contains this string. Patterns may also be selected with
+        /// "uuid:..." or "name:...", and several selectors may be given, separated by commas.
         #[arg(short, long, default_value = "", value_name = "example.mkv.json")]
         target_pattern: String,
 
         #[arg(short, long, default_value_t = -1)]
         result_count: i32,
 
-        #[arg(value_name = "FILE")]
-        file: String,
+        /// The file name to use for extension matching when reading from stdin.
+        #[arg(long, value_name = "NAME")]
+        name_hint: Option<String>,
+
+        /// A file in which a precompiled copy of the patterns will be cached, to speed up loading.
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<String>,
+
+        /// Only parse each pattern in full once it could match a file, which speeds up loading a large
+        /// pattern directory. Ignored when a cache file or bundle is used.
+        #[arg(long, default_value_t = false)]
+        lazy: bool,
+
+        /// The minimum percentage that a result must achieve to be displayed.
+        #[arg(long, default_value_t = 0.0, value_name = "PERCENT")]
+        min_confidence: f32,
+
+        /// Display a per-criterion breakdown of the points awarded to each result.
+        #[arg(long, default_value_t = false)]
+        explain: bool,
+
+        /// The format in which the results should be displayed.
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        output: OutputFormat,
+
+        /// Only load patterns that have been signed by one of the trusted keys. Any pattern that isn't will be rejected.
+        #[arg(long, default_value_t = false, requires = "trusted_keys")]
+        require_signed: bool,
+
+        /// A file listing the public keys of the trusted pattern signers, one per line. May be specified multiple times.
+        #[arg(long, value_name = "FILE", requires = "require_signed")]
+        trusted_keys: Vec<String>,
+
+        /// Only try the patterns that belong to this category (e.g. "video"). May be specified multiple times.
+        #[arg(long = "category", value_name = "CATEGORY")]
+        categories: Vec<String>,
+
+        /// Only load patterns distributed under this license (e.g. "MIT"). May be specified multiple times.
+        /// The built-in patterns are always loaded.
+        #[arg(long = "allow-license", value_name = "LICENSE")]
+        allowed_licenses: Vec<String>,
+
+        /// An additional compound extension to recognise when matching file extensions (e.g. "tar.lz4").
+        /// May be specified multiple times. Common compound extensions, such as "tar.gz", are always recognised.
+        #[arg(long = "compound-extension", value_name = "EXT")]
+        compound_extensions: Vec<String>,
+
+        /// The manner in which the results should be ranked.
+        #[arg(long, value_enum, default_value_t = RankMode::Percentage)]
+        rank_by: RankMode,
+
+        /// The policy by which the points awarded to a match are scored.
+        #[arg(long, value_enum, default_value_t = ScoringMode::Default)]
+        scoring: ScoringMode,
+
+        /// Test the patterns that list the extension of a file first, only testing the other patterns
+        /// if none of them match. This greatly reduces the number of patterns tested against most files.
+        #[arg(long, default_value_t = false)]
+        extension_first: bool,
+
+        /// Only test the patterns that list the extension of a file. Files without an extension are still
+        /// tested against every pattern.
+        #[arg(long, default_value_t = false)]
+        strict_extension: bool,
+
+        /// Group the results for the patterns of the same format family (e.g. Matroska and WebM), showing the
+        /// most specific pattern as the top match and the others as related matches.
+        #[arg(long, default_value_t = false)]
+        group_families: bool,
+
+        /// Identify the files within the subdirectories of a directory, as well as those directly within it.
+        #[arg(short = 'R', long, default_value_t = false)]
+        recursive: bool,
+
+        /// The maximum depth of the subdirectories to be searched when identifying a directory recursively.
+        #[arg(long, value_name = "DEPTH", requires = "recursive")]
+        max_depth: Option<usize>,
+
+        /// When identifying several files, display the number of files of each type, followed by the files that
+        /// couldn't be identified, rather than a result per file. The summary is given as JSON with "--output json".
+        #[arg(long, default_value_t = false)]
+        summary: bool,
+
+        /// Display only the name of the top match, if there is one. The exit code will be 0 for a confident match,
+        /// 1 if there was no match (including data that only appears to be plain text), 2 if the match was
+        /// ambiguous and 3 if an error occurred, such as the file or the patterns not being readable.
+        #[arg(short, long, default_value_t = false, conflicts_with_all = ["output", "explain", "summary"])]
+        quiet: bool,
+
+        /// Display the top match for each file using a template, such as "{name}\t{mime}\t{percentage}".
+        /// The placeholders are {file}, {name}, {mime}, {extension}, {uuid}, {points}, {max_points} and {percentage}.
+        #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["output", "explain", "summary", "quiet"])]
+        format: Option<String>,
+
+        /// Store the MIME type of the top match, and the UUID of its pattern, within the extended attributes
+        /// of each file (user.mime_type and user.itf.pattern_uuid), where the platform supports them.
+        #[arg(long, default_value_t = false)]
+        xattr: bool,
+
+        /// Don't display a progress bar when identifying several files. The progress bar is only displayed
+        /// when stderr is a terminal.
+        #[arg(long, default_value_t = false)]
+        no_progress: bool,
+
+        /// The files to identify, or "-" to read from stdin. Directories and globs (e.g. "downloads/**/*.bin")
+        /// may also be given, in which case every file within them will be identified.
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<String>,
     },
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Pattern {
+        #[command(subcommand)]
+        action: Option<PatternCommands>,
+
         #[arg(short, long, default_value = "")]
         user_name: String,
 
@@ -55,6 +473,14 @@ enum Commands {
         #[arg(short, long, default_value = "")]
         mimetypes: String,
 
+        /// The priority of the pattern, used to order results with the same match percentage.
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
+
+        /// A category to which the file type belongs (e.g. "video"). May be specified multiple times.
+        #[arg(long = "category", value_name = "CATEGORY")]
+        categories: Vec<String>,
+
         #[arg(long, default_value_t = false)]
         no_strings: bool,
 
@@ -64,287 +490,3226 @@ enum Commands {
         #[arg(long, default_value_t = false)]
         no_composition: bool,
 
-        #[arg(value_name = "EXT")]
-        extension: String,
+        #[arg(long, default_value_t = false)]
+        no_masks: bool,
 
-        #[arg(value_name = "PATH")]
-        path: String,
+        /// Don't compute the chi-square statistic and byte bigram profile of the sample files.
+        #[arg(long, default_value_t = false)]
+        no_extended_composition: bool,
 
-        #[arg(value_name = "OUTPUT_DIR")]
-        output_directory: Option<String>,
-    },
-    Refine {},
-}
+        /// Compute a fuzzy hash from the sample files, to help identify files with a similar overall structure.
+        #[arg(long, default_value_t = false)]
+        fuzzy_hash: bool,
 
-fn main() {
-    let cli = Cli::parse();
+        /// A directory of files that the pattern must not match. Strings found within them will be dropped from the pattern.
+        #[arg(long, value_name = "DIR")]
+        negative_dir: Option<String>,
 
-    match &cli.command {
-        Commands::Identify {
-            pattern_source_dir: _,
-            target_pattern: _,
-            result_count: _,
-            file: _,
-        } => {
-            process_identify_command(&cli.command);
-        }
-        Commands::Pattern {
-            user_name: _,
-            email: _,
-            name: _,
-            description: _,
-            known_extensions: _,
-            mimetypes: _,
-            no_strings: _,
-            no_sequences: _,
-            no_composition: _,
-            extension: _,
-            path: _,
-            output_directory: _,
-        } => {
-            process_pattern_command(&cli.command);
-        }
-        Commands::Refine {} => {
-            todo!();
-        }
-    }
-}
+        /// Exclude any sample files that remove most of the common byte sequences, as they may have been mislabeled.
+        #[arg(long, default_value_t = false)]
+        exclude_outliers: bool,
 
-fn built_pattern_handler(source_directory: &str, target_pattern: &str) -> PatternHandler {
-    let mut pattern_handler = PatternHandler::default();
+        /// The weight to give a specific sample file, such as a canonical reference file. May be specified multiple times.
+        #[arg(long, value_name = "NAME=WEIGHT", conflicts_with = "weight_recent")]
+        weight: Vec<String>,
 
-    // By default we'll look at the path /patterns/ relative to the path of the executable.
-    // If the source path is specified then we will attempt to load the patterns from there instead.
-    let pattern_source = if source_directory.is_empty() {
-        if let Ok(p) = env::current_dir() {
-            let mut temp = p.clone();
-            temp.push("patterns");
-            temp
-        } else {
-            eprintln!("Unable to get the current working directory, and no definition source specified. Unable to continue.");
-            return pattern_handler;
-        }
-    } else {
-        PathBuf::from(source_directory)
-    };
+        /// Give more recently modified sample files a greater weight.
+        #[arg(long, default_value_t = false)]
+        weight_recent: bool,
 
-    if !utils::directory_exists(&pattern_source) {
-        eprintln!("The specified pattern source directory doesn't exist. Unable to continue.");
-        return pattern_handler;
-    }
+        /// A file listing additional strings that shouldn't be added to the pattern, one per line.
+        /// Any string containing one of the entries will be excluded, along with a built-in list of common strings.
+        #[arg(long, value_name = "FILE")]
+        string_blocklist: Option<String>,
 
-    pattern_handler.read(pattern_source, target_pattern);
+        /// Replace masked bytes that only take a few values with regexes. This requires the sample files to be read twice.
+        #[arg(long, default_value_t = false)]
+        synthesize_regexes: bool,
 
-    pattern_handler
-}
+        /// A known byte sequence to add to the pattern, given as a decimal offset and hex bytes (e.g. 0:89504E47).
+        /// Any overlapping sequences derived from the sample files will be replaced. May be specified multiple times.
+        #[arg(long, value_name = "OFFSET:HEXBYTES", value_parser = parse_sequence)]
+        sequence: Vec<(usize, Vec<u8>)>,
 
-#[inline]
-fn match_patterns<'a>(pattern_handler: &'a PatternHandler, path: &str) -> Vec<PatternMatch<'a>> {
-    let chunk = file_processor::read_file_header_chunk(path).expect("failed to read sample file");
+        /// A byte regex to add to the pattern, given as a decimal offset at which it must match and the regex (e.g. 0:GIF8[79]a).
+        /// May be specified multiple times.
+        #[arg(long, value_name = "OFFSET:REGEX", value_parser = parse_regex)]
+        regex: Vec<(usize, String)>,
 
-    let mut point_store: Vec<PatternMatch> = pattern_handler
-        .patterns
-        .par_iter()
-        .filter_map(|pattern| {
-            let points = FilePointCalculator::compute(pattern, &chunk, path, true);
-            if points > 0 {
-                Some(PatternMatch::new(
-                    &pattern.type_data.uuid,
-                    points,
-                    pattern.max_points,
-                ))
-            } else {
-                None
-            }
-        })
-        .collect();
+        /// Display a report on the quality of the pattern once it has been built.
+        #[arg(long, default_value_t = false)]
+        report: bool,
 
-    // Sort the results by percentage match score, descending.
-    point_store.sort_unstable_by(|a, b| b.percentage.partial_cmp(&a.percentage).unwrap());
+        /// Split the sample files into N folds, and score each fold against a pattern built from the others.
+        /// This helps to detect a pattern that is over-fitted to a homogeneous set of sample files.
+        #[arg(long, value_name = "N")]
+        cross_validate: Option<usize>,
 
-    point_store
-}
+        /// The minimum number of sample files from which the pattern may be built.
+        #[arg(long, default_value_t = RECOMMENDED_MIN_SAMPLES, value_name = "N")]
+        min_samples: usize,
 
-#[derive(Debug)]
-struct PatternMatch<'a> {
-    pub uuid: &'a str,
-    pub points: usize,
-    pub max_points: usize,
-    pub percentage: f32,
-}
+        /// Build the pattern even if there are fewer sample files than the minimum, displaying a warning instead.
+        #[arg(long, default_value_t = false)]
+        allow_few_samples: bool,
 
-impl<'a> PatternMatch<'a> {
-    pub fn new(uuid: &'a str, points: usize, max_points: usize) -> Self {
-        Self {
-            uuid,
-            points,
-            max_points,
-            percentage: utils::round_to_dp(points as f32 / max_points as f32 * 100.0, 1),
-        }
-    }
-}
+        /// Select the sample files using a glob relative to PATH (e.g. "**/*.mkv"), rather than by their extension.
+        /// Globs starting with "!" exclude any matching files (e.g. "!corrupt/**"). May be specified multiple times.
+        #[arg(long, value_name = "GLOB")]
+        glob: Vec<String>,
 
-fn print_results(results: &[PatternMatch], handler: &PatternHandler) {
-    let mut table = Table::new();
+        /// Compress the written pattern file. Defaults to gzip if no format is given.
+        #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "gzip")]
+        compress: Option<CompressionFormat>,
 
-    // Add a row for the header.
-    table.add_row(Row::new(vec![
-        Cell::new("Rank").style_spec("b"),
-        Cell::new("Name").style_spec("b"),
-        Cell::new("Points").style_spec("b"),
-        Cell::new("Max Points").style_spec("b"),
-        Cell::new("Percentage").style_spec("b"),
-    ]));
+        /// The extension of the sample files. Multiple extensions may be given as a comma-separated list (e.g. jpg,jpeg,jfif).
+        #[arg(value_name = "EXT", required = true)]
+        extension: Option<String>,
 
-    for (i, result) in results.iter().enumerate() {
-        let p = handler
-            .patterns
-            .iter()
-            .find(|pattern| pattern.type_data.uuid == result.uuid)
-            .unwrap();
+        #[arg(value_name = "PATH", required = true)]
+        path: Option<String>,
 
-        // The values are rounded to 1 d.p., so we don't need to worry about the edge-case
-        // floating point issues.
-        let colour = match result.percentage {
-            0.0..=33.3 => "Fr",
-            33.4..=66.66 => "Fy",
-            66.67..=100.0 => "Fg",
-            _ => "Fw",
-        };
+        #[arg(value_name = "OUTPUT_DIR")]
+        output_directory: Option<String>,
+    },
+    Refine {
+        #[arg(short, long, default_value = "")]
+        user_name: String,
 
-        table.add_row(Row::new(vec![
-            Cell::new(&(i + 1).to_string()).style_spec(colour),
-            Cell::new(&p.type_data.name).style_spec(colour),
-            Cell::new(&result.points.to_string()).style_spec(colour),
-            Cell::new(&result.max_points.to_string()).style_spec(colour),
-            Cell::new(&result.percentage.to_string()).style_spec(colour),
-        ]));
-    }
+        #[arg(short, long, default_value = "")]
+        email: String,
 
-    table.printstd();
-}
+        /// The file to which the refined pattern should be written. The pattern file will be overwritten if not specified.
+        #[arg(short, long, value_name = "FILE")]
+        output_file: Option<String>,
 
-fn process_identify_command(cmd: &Commands) {
-    if let Commands::Identify {
-        pattern_source_dir: source_directory,
-        target_pattern,
-        result_count,
-        file,
-    } = cmd
-    {
-        if !utils::file_exists(file) {
-            eprintln!("The specified sample file path '{file}' doesn't exist.");
-            return;
-        }
+        /// The pattern file to be refined.
+        #[arg(value_name = "PATTERN")]
+        pattern_file: String,
 
-        let pattern_handler = built_pattern_handler(source_directory, target_pattern);
-        if pattern_handler.is_empty() {
-            eprintln!("No applicable patterns were found. Unable to continue.");
-            return;
-        }
+        #[arg(value_name = "EXT")]
+        extension: String,
 
-        let mut results = match_patterns(&pattern_handler, file);
+        #[arg(value_name = "PATH")]
+        path: String,
+    },
+    /// Export patterns into the signature format used by another file identification tool.
+    Export {
+        #[arg(short, long, default_value = "", value_name = "DIR")]
+        pattern_source_dir: String,
 
-        // Only retail a set number of results, if specified.
-        if *result_count != -1 {
-            results.truncate(*result_count as usize);
-        }
+        /// Only load the patterns whose file path contains this string. Patterns may also be selected with
+        /// "uuid:..." or "name:...", and several selectors may be given, separated by commas.
+        #[arg(short, long, default_value = "", value_name = "example.mkv.json")]
+        target_pattern: String,
 
-        print_results(&results, &pattern_handler);
-    }
-}
+        /// The format into which the patterns should be exported.
+        #[arg(short, long, value_enum)]
+        format: ExportFormat,
 
-fn process_pattern_command(cmd: &Commands) {
-    if let Commands::Pattern {
-        user_name,
-        email,
-        name,
-        description,
-        known_extensions,
-        mimetypes,
-        no_strings,
-        no_sequences,
-        no_composition,
-        extension,
-        path,
-        output_directory,
-    } = cmd
-    {
-        if !utils::directory_exists(path) {
-            eprintln!("The specified target folder '{path}' doesn't exist.");
-            return;
-        }
+        /// The file to which the exported patterns should be written.
+        #[arg(value_name = "OUTPUT")]
+        output_file: String,
+    },
+    /// Convert the signature definitions used by another file identification tool into pattern files.
+    Import {
+        /// The format of the definitions.
+        #[arg(short, long, value_enum)]
+        format: ImportFormat,
 
-        let extension = extension.trim_start_matches('.');
-        if extension.is_empty() {
-            eprintln!("The target extension may not be empty.");
-            return;
-        }
+        /// The directory into which the pattern files should be written.
+        #[arg(short, long, value_name = "DIR")]
+        output_directory: String,
 
-        if *no_strings && *no_sequences && *no_composition {
-            eprintln!(
-                "No pattern matching options were enabled, therefore no pattern can be created."
-            );
-            return;
-        }
+        /// Compress the written pattern files. Defaults to gzip if no format is given.
+        #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "gzip")]
+        compress: Option<CompressionFormat>,
 
-        let mut extensions: Vec<String> = if known_extensions.is_empty() {
+        /// The license under which the definitions are distributed (e.g. "MIT"), used for any definition that doesn't specify its own.
+        #[arg(long, value_name = "LICENSE")]
+        license: Option<String>,
+
+        /// The URL from which the definitions were obtained.
+        #[arg(long, value_name = "URL")]
+        origin_url: Option<String>,
+
+        /// The definition files, or directories of definition files, to be converted.
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<String>,
+    },
+    /// Combine pattern files into a single bundle file, or extract the patterns from a bundle.
+    Bundle {
+        #[command(subcommand)]
+        action: BundleCommands,
+    },
+    /// Generate an index of the pattern files within a directory, so that specific patterns can be loaded without
+    /// parsing every pattern file. The index must be regenerated whenever the pattern files are changed.
+    Index {
+        #[arg(value_name = "DIR")]
+        directory: String,
+    },
+    /// Sign pattern files, so that they can be verified before being loaded.
+    Sign {
+        /// The file containing the secret key with which the patterns should be signed.
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            required_unless_present = "generate_key"
+        )]
+        key: Option<String>,
+
+        /// Write each signature to a .sig file beside the pattern file, rather than embedding it within the pattern.
+        #[arg(long, default_value_t = false)]
+        detached: bool,
+
+        /// Generate a new key pair, writing the secret key to FILE and the public key to FILE.pub.
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["key", "detached", "paths"])]
+        generate_key: Option<String>,
+
+        /// The pattern files, or directories of pattern files, to be signed.
+        #[arg(value_name = "PATH", required_unless_present = "generate_key")]
+        paths: Vec<String>,
+    },
+    /// Check pattern files for definitions that are likely to cause false positives.
+    Lint {
+        /// The format in which the issues should be displayed.
+        #[arg(short, long, value_enum, default_value_t = LintOutputFormat::Text)]
+        output: LintOutputFormat,
+
+        /// Fail if any warnings are found, as well as errors.
+        #[arg(long, default_value_t = false)]
+        deny_warnings: bool,
+
+        /// The pattern files, or directories of pattern files, to be checked.
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<String>,
+    },
+    /// Compile pattern files into Rust source code defining a set of embedded rules, so that a program can
+    /// identify files without reading or parsing any pattern files.
+    CompileRules {
+        /// The file to which the source code should be written. The source code will be printed if not specified.
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<String>,
+
+        /// The pattern files, or directories of pattern files, to be compiled.
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<String>,
+    },
+    /// Produce statistics describing the files within a directory, such as the number and total size of the files
+    /// of each type, the files whose extension doesn't match their type and the files that couldn't be identified.
+    Stats {
+        #[arg(short, long, default_value = "", value_name = "DIR")]
+        pattern_source_dir: String,
+
+        /// Only load the patterns whose file path contains this string. Patterns may also be selected with
+        /// "uuid:..." or "name:...", and several selectors may be given, separated by commas.
+        #[arg(short, long, default_value = "", value_name = "example.mkv.json")]
+        target_pattern: String,
+
+        /// A file in which a precompiled copy of the patterns will be cached, to speed up loading.
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<String>,
+
+        /// The minimum percentage that a result must achieve for a file to be considered identified.
+        #[arg(long, default_value_t = 0.0, value_name = "PERCENT")]
+        min_confidence: f32,
+
+        /// Include the files within the subdirectories of the directory, as well as those directly within it.
+        #[arg(short = 'R', long, default_value_t = false)]
+        recursive: bool,
+
+        /// Display the statistics as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+
+        /// The directory containing the files.
+        #[arg(value_name = "DIR")]
+        directory: String,
+    },
+    /// Measure the accuracy of the patterns against a corpus of sample files whose types are known, reporting
+    /// the precision and recall of each pattern along with any files that were misidentified.
+    Test {
+        #[arg(short, long, default_value = "", value_name = "DIR")]
+        pattern_source_dir: String,
+
+        /// Only load the patterns whose file path contains this string. Patterns may also be selected with
+        /// "uuid:..." or "name:...", and several selectors may be given, separated by commas.
+        #[arg(short, long, default_value = "", value_name = "example.mkv.json")]
+        target_pattern: String,
+
+        /// A file in which a precompiled copy of the patterns will be cached, to speed up loading.
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<String>,
+
+        /// The minimum percentage that a result must achieve for a file to be considered identified.
+        #[arg(long, default_value_t = 0.0, value_name = "PERCENT")]
+        min_confidence: f32,
+
+        /// The minimum percentage of the files that must be identified correctly for the test to pass.
+        #[arg(long, default_value_t = 0.0, value_name = "PERCENT")]
+        min_accuracy: f32,
+
+        /// Display the results as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+
+        /// A directory in which each subdirectory is named after the UUID of the pattern that the files within it
+        /// should match, or a CSV file giving the path of each file followed by the UUID. Files labeled "unknown"
+        /// shouldn't match any pattern.
+        #[arg(value_name = "CORPUS")]
+        corpus: String,
+    },
+    /// Identify the files within a directory and rename those whose extension doesn't match their detected type,
+    /// such as files recovered by data carving.
+    FixExtensions {
+        #[arg(short, long, default_value = "", value_name = "DIR")]
+        pattern_source_dir: String,
+
+        /// Only load the patterns whose file path contains this string. Patterns may also be selected with
+        /// "uuid:..." or "name:...", and several selectors may be given, separated by commas.
+        #[arg(short, long, default_value = "", value_name = "example.mkv.json")]
+        target_pattern: String,
+
+        /// A file in which a precompiled copy of the patterns will be cached, to speed up loading.
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<String>,
+
+        /// The minimum percentage that a result must achieve for a file to be renamed.
+        #[arg(long, default_value_t = 0.0, value_name = "PERCENT")]
+        min_confidence: f32,
+
+        /// Rename the files within the subdirectories of the directory, as well as those directly within it.
+        #[arg(short = 'R', long, default_value_t = false)]
+        recursive: bool,
+
+        /// Display the files that would be renamed, without renaming them.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// The directory containing the files to be renamed.
+        #[arg(value_name = "DIR")]
+        directory: String,
+    },
+    /// Watch a directory, identifying files as they are created or modified. This runs until interrupted.
+    Watch {
+        #[arg(short, long, default_value = "", value_name = "DIR")]
+        pattern_source_dir: String,
+
+        /// Only load the patterns whose file path contains this string. Patterns may also be selected with
+        /// "uuid:..." or "name:...", and several selectors may be given, separated by commas.
+        #[arg(short, long, default_value = "", value_name = "example.mkv.json")]
+        target_pattern: String,
+
+        /// A file in which a precompiled copy of the patterns will be cached, to speed up loading.
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<String>,
+
+        /// The minimum percentage that a result must achieve to be displayed.
+        #[arg(long, default_value_t = 0.0, value_name = "PERCENT")]
+        min_confidence: f32,
+
+        /// Watch the subdirectories of the directory, as well as the directory itself.
+        #[arg(short = 'R', long, default_value_t = false)]
+        recursive: bool,
+
+        /// Display the top match for each file using a template, such as "{name}\t{mime}\t{percentage}".
+        /// The placeholders are {file}, {name}, {mime}, {extension}, {uuid}, {points}, {max_points} and {percentage}.
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
+
+        /// A file to which the results should also be appended.
+        #[arg(long, value_name = "FILE")]
+        log: Option<String>,
+
+        /// Also watch the pattern directory (or bundle), reloading the patterns whenever a pattern file is
+        /// added, modified or removed.
+        #[arg(long, default_value_t = false)]
+        reload_patterns: bool,
+
+        /// The directory to be watched.
+        #[arg(value_name = "DIR")]
+        directory: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PatternCommands {
+    /// Merge two patterns for the same format that were built from different sample sets.
+    Merge {
+        /// The file to which the merged pattern should be written. The pattern will be printed if not specified.
+        #[arg(short, long, value_name = "FILE")]
+        output_file: Option<String>,
+
+        #[arg(value_name = "FIRST")]
+        first: String,
+
+        #[arg(value_name = "SECOND")]
+        second: String,
+    },
+    /// Download a pattern bundle, or a directory of pattern files, from a remote repository.
+    /// Files that haven't changed since they were last fetched won't be downloaded again.
+    Fetch {
+        /// The directory into which the patterns should be downloaded. This will be created if it doesn't exist.
+        #[arg(short, long, value_name = "DIR", default_value = "")]
+        pattern_source_dir: String,
+
+        /// Allow the patterns to be downloaded over plain HTTP. Only HTTPS URLs are accepted otherwise.
+        #[arg(long, default_value_t = false)]
+        allow_insecure: bool,
+
+        /// The URL of a pattern bundle, of a pattern index, or of a directory (ending with "/") containing a pattern index.
+        #[arg(value_name = "URL")]
+        url: String,
+    },
+    /// Update the patterns that were fetched into a directory, downloading only the files that have changed.
+    Update {
+        /// The directory containing the fetched patterns.
+        #[arg(short, long, value_name = "DIR", default_value = "")]
+        pattern_source_dir: String,
+
+        /// Restore the set of patterns that was replaced by the last update, rather than updating.
+        #[arg(long, default_value_t = false)]
+        rollback: bool,
+
+        /// Allow the patterns to be downloaded over plain HTTP. Only HTTPS sources are updated otherwise.
+        #[arg(long, default_value_t = false)]
+        allow_insecure: bool,
+    },
+    /// Show the differences between two versions of a pattern, such as before and after a refinement.
+    Diff {
+        /// Display the differences as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+
+        #[arg(value_name = "OLD")]
+        old: String,
+
+        #[arg(value_name = "NEW")]
+        new: String,
+    },
+    /// Update the checksums of pattern files that have been edited by hand. A pattern whose contents
+    /// don't match its checksum is treated as corrupt, and won't be loaded.
+    Checksum {
+        /// The pattern files, or directories of pattern files, to be updated.
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<String>,
+    },
+    /// Render a pattern as a human-readable document, listing its byte sequences, strings, entropy
+    /// and submitter history.
+    Report {
+        /// The file to which the report should be written. The report is displayed if this isn't specified.
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<String>,
+
+        /// The format of the report. Defaults to HTML if the output file ends with ".html", otherwise Markdown.
+        #[arg(short, long, value_enum)]
+        format: Option<ReportOutputFormat>,
+
+        #[arg(value_name = "PATTERN_FILE")]
+        pattern_file: String,
+    },
+    /// Interactively edit a pattern, displaying its metadata, byte sequences (as hex), strings and entropy
+    /// bounds, and validating each change before it's applied. Use "help" within the editor to list the commands.
+    Edit {
+        /// The file to which the edited pattern should be written. The original file is replaced if this isn't specified.
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<String>,
+
+        #[arg(value_name = "PATTERN_FILE")]
+        pattern_file: String,
+    },
+    /// Compare every loaded pattern with every other, listing the pairs whose required byte sequences are
+    /// identical, or where those of one are a subset of those of the other. Such patterns will always match
+    /// the same files, and should be reviewed or linked as members of the same format family.
+    CheckConflicts {
+        #[arg(short, long, default_value = "", value_name = "DIR")]
+        pattern_source_dir: String,
+
+        /// Only load the patterns whose file path contains this string. Patterns may also be selected with
+        /// "uuid:..." or "name:...", and several selectors may be given, separated by commas.
+        #[arg(short, long, default_value = "", value_name = "example.mkv.json")]
+        target_pattern: String,
+
+        /// Display the conflicts as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BundleCommands {
+    /// Create a bundle from a set of pattern files.
+    Create {
+        /// The bundle file to be written (e.g. patterns.itfb).
+        #[arg(value_name = "BUNDLE")]
+        bundle_file: String,
+
+        /// The pattern files, or directories of pattern files, to be added to the bundle.
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<String>,
+    },
+    /// Extract the patterns within a bundle into pattern files.
+    Extract {
+        /// Compress the written pattern files. Defaults to gzip if no format is given.
+        #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "gzip")]
+        compress: Option<CompressionFormat>,
+
+        #[arg(value_name = "BUNDLE")]
+        bundle_file: String,
+
+        #[arg(value_name = "OUTPUT_DIR")]
+        output_directory: String,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if let Some(threads) = cli.threads {
+        if let Err(e) = utils::set_thread_limit(threads) {
+            eprintln!("{e}");
+            process::exit(EXIT_ERROR);
+        }
+    }
+
+    let scoring_config = match &cli.config {
+        Some(config_file) => match ScoringConfig::from_config_file(config_file) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("Failed to read the configuration file '{config_file}': {e}");
+                process::exit(EXIT_ERROR);
+            }
+        },
+        None => None,
+    };
+
+    let options = CliOptions {
+        color: cli.color,
+        plain: cli.plain,
+        verbose: cli.verbose,
+        duplicate_policy: cli.on_duplicate_uuid.into(),
+        override_dirs: cli.override_dirs.clone(),
+        disabled_patterns: cli.disabled_patterns.clone(),
+        scoring_config,
+    };
+
+    let result = match &cli.command {
+        Commands::Identify {
+            pattern_source_dirs: _,
+            target_pattern: _,
+            result_count: _,
+            name_hint: _,
+            cache_file: _,
+            lazy: _,
+            min_confidence: _,
+            explain: _,
+            output: _,
+            require_signed: _,
+            trusted_keys: _,
+            categories: _,
+            allowed_licenses: _,
+            compound_extensions: _,
+            rank_by: _,
+            scoring: _,
+            extension_first: _,
+            strict_extension: _,
+            group_families: _,
+            recursive: _,
+            max_depth: _,
+            summary: _,
+            quiet: _,
+            format: _,
+            xattr: _,
+            no_progress: _,
+            files: _,
+        } => process_identify_command(&cli.command, &options),
+        Commands::Pattern {
+            action: Some(action),
+            ..
+        } => process_pattern_action(action, &options),
+        Commands::Pattern {
+            action: None,
+            user_name: _,
+            email: _,
+            name: _,
+            description: _,
+            known_extensions: _,
+            mimetypes: _,
+            priority: _,
+            categories: _,
+            no_strings: _,
+            no_sequences: _,
+            no_composition: _,
+            no_masks: _,
+            no_extended_composition: _,
+            fuzzy_hash: _,
+            negative_dir: _,
+            exclude_outliers: _,
+            weight: _,
+            weight_recent: _,
+            string_blocklist: _,
+            synthesize_regexes: _,
+            sequence: _,
+            regex: _,
+            report: _,
+            cross_validate: _,
+            min_samples: _,
+            allow_few_samples: _,
+            glob: _,
+            compress: _,
+            extension: _,
+            path: _,
+            output_directory: _,
+        } => process_pattern_command(&cli.command),
+        Commands::Refine {
+            user_name: _,
+            email: _,
+            output_file: _,
+            pattern_file: _,
+            extension: _,
+            path: _,
+        } => process_refine_command(&cli.command),
+        Commands::Export {
+            pattern_source_dir: _,
+            target_pattern: _,
+            format: _,
+            output_file: _,
+        } => process_export_command(&cli.command, &options),
+        Commands::Import {
+            format: _,
+            output_directory: _,
+            compress: _,
+            license: _,
+            origin_url: _,
+            paths: _,
+        } => process_import_command(&cli.command),
+        Commands::Bundle { action } => process_bundle_action(action),
+        Commands::Index { directory: _ } => process_index_command(&cli.command),
+        Commands::Sign {
+            key: _,
+            detached: _,
+            generate_key: _,
+            paths: _,
+        } => process_sign_command(&cli.command),
+        Commands::Lint {
+            output: _,
+            deny_warnings: _,
+            paths: _,
+        } => process_lint_command(&cli.command),
+        Commands::CompileRules { out: _, paths: _ } => process_compile_rules_command(&cli.command),
+        Commands::Stats {
+            pattern_source_dir: _,
+            target_pattern: _,
+            cache_file: _,
+            min_confidence: _,
+            recursive: _,
+            json: _,
+            directory: _,
+        } => process_stats_command(&cli.command, &options),
+        Commands::Test {
+            pattern_source_dir: _,
+            target_pattern: _,
+            cache_file: _,
+            min_confidence: _,
+            min_accuracy: _,
+            json: _,
+            corpus: _,
+        } => process_test_command(&cli.command, &options),
+        Commands::FixExtensions {
+            pattern_source_dir: _,
+            target_pattern: _,
+            cache_file: _,
+            min_confidence: _,
+            recursive: _,
+            dry_run: _,
+            directory: _,
+        } => process_fix_extensions_command(&cli.command, &options),
+        Commands::Watch {
+            pattern_source_dir: _,
+            target_pattern: _,
+            cache_file: _,
+            min_confidence: _,
+            recursive: _,
+            format: _,
+            log: _,
+            reload_patterns: _,
+            directory: _,
+        } => process_watch_command(&cli.command, &options),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        process::exit(EXIT_ERROR);
+    }
+}
+
+/// Create an [`Identifier`] for a set of patterns, scoring matches with the weights given by the configuration
+/// file, if one was specified.
+///
+/// # Arguments
+///
+/// * `pattern_handler` - The [`PatternHandler`] containing the loaded patterns.
+/// * `options` - The [`CliOptions`] chosen by the global options.
+fn new_identifier(pattern_handler: PatternHandler, options: &CliOptions) -> Identifier {
+    let identifier = Identifier::new(pattern_handler);
+    match options.scoring_config {
+        Some(config) => identifier.with_scoring_strategy(config),
+        None => identifier,
+    }
+}
+
+/// The options that control how the patterns are loaded for a command.
+#[derive(Clone, Copy, Default)]
+struct LoadOptions<'a> {
+    /// The pattern source given on the command line, which is empty if none was given.
+    source_directory: &'a str,
+    /// Any further pattern sources, to be merged with the first.
+    additional_sources: &'a [String],
+    /// The selector of the patterns that should be loaded, which is empty to load every pattern.
+    target_pattern: &'a str,
+    /// A file in which a precompiled copy of the patterns is cached, if any.
+    cache_file: Option<&'a str>,
+    /// The files containing the keys of the trusted signers, if patterns must be signed to be loaded.
+    trusted_key_files: Option<&'a [String]>,
+    /// The licenses under which a pattern must be distributed to be loaded, if any restriction applies.
+    allowed_licenses: &'a [String],
+    /// Should the parsing of the patterns be deferred until they are needed?
+    lazy: bool,
+}
+
+fn built_pattern_handler(
+    load_options: &LoadOptions,
+    options: &CliOptions,
+) -> Result<PatternHandler, Box<dyn Error>> {
+    let LoadOptions {
+        source_directory,
+        additional_sources,
+        target_pattern,
+        cache_file,
+        trusted_key_files,
+        allowed_licenses,
+        lazy,
+    } = *load_options;
+    let mut pattern_handler = PatternHandler::default();
+
+    if let Some(files) = trusted_key_files {
+        let mut trusted_keys = Vec::new();
+        for file in files {
+            trusted_keys.extend(pattern_signing::read_trusted_keys(file)?);
+        }
+        pattern_handler.require_signed(trusted_keys);
+    }
+
+    if !allowed_licenses.is_empty() {
+        pattern_handler.allow_licenses(allowed_licenses.to_vec());
+    }
+
+    pattern_handler.set_duplicate_policy(options.duplicate_policy);
+    if !options.disabled_patterns.is_empty() {
+        pattern_handler.disable_patterns(options.disabled_patterns.clone());
+    }
+
+    let source_directory = configured_pattern_source(source_directory);
+    let pattern_source = resolve_pattern_source(&source_directory)?;
+
+    // A bundle file or an archive of pattern files may be given in place of a directory of pattern files.
+    let is_archive =
+        utils::file_exists(&pattern_source) && ArchiveKind::from_path(&pattern_source).is_some();
+    let is_bundle = utils::file_exists(&pattern_source) && !is_archive;
+    let is_directory = utils::directory_exists(&pattern_source);
+    let override_dirs = &options.override_dirs;
+
+    // The built-in rules are only used when no pattern source has been configured and
+    // there is no default pattern directory.
+    let use_builtin = source_directory.is_empty() && !is_archive && !is_bundle && !is_directory;
+    if use_builtin && override_dirs.is_empty() {
+        eprintln!("No pattern directory was found, so only the built-in patterns will be used.");
+        pattern_handler.read_builtin(target_pattern)?;
+        return Ok(pattern_handler);
+    }
+
+    if !use_builtin && !is_archive && !is_bundle && !is_directory {
+        return Err(
+            "The specified pattern source directory doesn't exist. Unable to continue.".into(),
+        );
+    }
+
+    if let Some(missing) = additional_sources.iter().find(|s| !Path::new(s).exists()) {
+        return Err(
+            format!("The pattern source '{missing}' doesn't exist. Unable to continue.").into(),
+        );
+    }
+
+    // The cache always contains every pattern, so it can't be used when targeting a specific pattern.
+    let mut result = match cache_file {
+        _ if use_builtin => pattern_handler
+            .read_builtin(target_pattern)
+            .map(|_| LoadReport::default()),
+        _ if is_archive => pattern_handler.read_archive(pattern_source, target_pattern),
+        _ if is_bundle => pattern_handler.read_bundle(pattern_source, target_pattern),
+        Some(cache_file) if target_pattern.is_empty() => {
+            pattern_handler.read_cached(pattern_source, cache_file)
+        }
+        _ if lazy => pattern_handler.read_lazy(pattern_source, target_pattern),
+        _ => pattern_handler.read(pattern_source, target_pattern),
+    };
+
+    // Any additional sources are merged with the first, with conflicting UUIDs handled by the duplicate policy.
+    for source in additional_sources {
+        if !matches!(result, Ok(_) | Err(ItfError::NoPatternsFound)) {
+            break;
+        }
+        result = if utils::file_exists(source) && ArchiveKind::from_path(source).is_none() {
+            pattern_handler.read_bundle(source, target_pattern)
+        } else {
+            pattern_handler.read(source, target_pattern)
+        };
+    }
+
+    // The override directories are layered over the pattern sources, so that their patterns take precedence.
+    if !override_dirs.is_empty() && matches!(result, Ok(_) | Err(ItfError::NoPatternsFound)) {
+        let sources: Vec<_> = override_dirs
+            .iter()
+            .map(|dir| PatternSource::Directory(PathBuf::from(dir)))
+            .collect();
+        result = pattern_handler.read_layered(&sources, target_pattern);
+    }
+
+    let report = pattern_handler.load_report();
+    if options.verbose {
+        eprint!("{report}");
+        eprint!("{}", pattern_handler.load_stats());
+    } else {
+        if !report.failed.is_empty() {
+            eprintln!(
+                "{} pattern files couldn't be loaded and were ignored. Use --verbose for details.",
+                report.failed.len()
+            );
+        }
+        // A duplicate is usually an accident, and so is always worth mentioning.
+        for (source, reason) in &report.duplicates {
+            eprintln!("The pattern '{source}' was ignored because {reason}.");
+        }
+    }
+
+    match result {
+        Ok(_) => {
+            // Patterns written by older versions are upgraded as they are loaded.
+            for pattern in &pattern_handler.patterns {
+                if !pattern.migrations.is_empty() {
+                    eprintln!(
+                        "The pattern '{}' was written in an older format and has been upgraded: {}.",
+                        pattern.type_data.name,
+                        pattern.migrations.join(", ")
+                    );
+                }
+            }
+
+            Ok(pattern_handler)
+        }
+        Err(ItfError::NoPatternsFound) => {
+            Err("No applicable patterns were found. Unable to continue.".into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Get the pattern source that has been configured, if any.
+///
+/// # Arguments
+///
+/// * `source_directory` - The pattern source given on the command line, which is empty if none was given.
+///
+/// # Returns
+///
+/// The source given on the command line, if there is one, otherwise the value of `ITF_PATTERN_DIR`,
+/// if it's set and isn't empty. An empty string is returned if neither has been given.
+fn configured_pattern_source(source_directory: &str) -> String {
+    if !source_directory.is_empty() {
+        return source_directory.to_string();
+    }
+
+    env::var(PATTERN_DIR_ENV_VAR).unwrap_or_default()
+}
+
+fn resolve_pattern_source(source_directory: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let source_directory = &configured_pattern_source(source_directory);
+
+    // By default we'll look at the path /patterns/ relative to the path of the executable.
+    // If the source path is specified then we will attempt to load the patterns from there instead.
+    if source_directory.is_empty() {
+        if let Ok(p) = env::current_dir() {
+            let mut temp = p.clone();
+            temp.push("patterns");
+            Ok(temp)
+        } else {
+            Err("Unable to get the current working directory, and no definition source specified. Unable to continue.".into())
+        }
+    } else {
+        Ok(PathBuf::from(source_directory))
+    }
+}
+
+fn parse_regex(arg: &str) -> Result<(usize, String), String> {
+    let invalid = || format!("'{arg}' must be in the form OFFSET:REGEX");
+
+    let (offset, regex) = arg.split_once(':').ok_or_else(invalid)?;
+    let offset = offset.trim().parse::<usize>().map_err(|_| invalid())?;
+    if regex.is_empty() {
+        return Err(invalid());
+    }
+
+    // Validate the regex now, rather than after the sample files have been scanned.
+    Pattern::default()
+        .add_regex(offset, regex)
+        .map_err(|e| e.to_string())?;
+
+    Ok((offset, regex.to_string()))
+}
+
+fn parse_sequence(arg: &str) -> Result<(usize, Vec<u8>), String> {
+    let invalid = || format!("'{arg}' must be in the form OFFSET:HEXBYTES");
+
+    let (offset, hex) = arg.split_once(':').ok_or_else(invalid)?;
+    let offset = offset.trim().parse::<usize>().map_err(|_| invalid())?;
+
+    let hex: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if hex.is_empty() || !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+
+    Ok((offset, bytes))
+}
+
+fn print_explanations(outcome: &MatchOutcome, options: &CliOptions) {
+    let MatchOutcome::Identified(results) = outcome else {
+        return;
+    };
+
+    let mut table = Table::new();
+
+    // Add a row for the header.
+    table.add_row(Row::new(vec![
+        Cell::new("Rank").style_spec("b"),
+        Cell::new("Name").style_spec("b"),
+        Cell::new("Sequences").style_spec("b"),
+        Cell::new("Masked").style_spec("b"),
+        Cell::new("Trailers").style_spec("b"),
+        Cell::new("Regexes").style_spec("b"),
+        Cell::new("Strings").style_spec("b"),
+        Cell::new("Entropy").style_spec("b"),
+        Cell::new("Composition").style_spec("b"),
+        Cell::new("Fuzzy Hash").style_spec("b"),
+        Cell::new("Confidence").style_spec("b"),
+        Cell::new("Extension").style_spec("b"),
+    ]));
+
+    for (i, result) in results.iter().enumerate() {
+        let report = &result.report;
+        table.add_row(Row::new(vec![
+            Cell::new(&(i + 1).to_string()),
+            Cell::new(&result.name),
+            Cell::new(&format!("{:.1}", report.sequence_points)),
+            Cell::new(&format!("{:.1}", report.masked_sequence_points)),
+            Cell::new(&format!("{:.1}", report.trailer_sequence_points)),
+            Cell::new(&format!("{:.1}", report.regex_points)),
+            Cell::new(&format!("{:.1}", report.string_points)),
+            Cell::new(&format!("{:.1}", report.entropy_points)),
+            Cell::new(&format!("{:.1}", report.composition_points)),
+            Cell::new(&format!("{:.1}", report.fuzzy_hash_points)),
+            Cell::new(&format!("x{:.2}", report.confidence_factor)),
+            Cell::new(&format!("{:.1}", report.extension_points)),
+        ]));
+    }
+
+    print_table(&mut table, options);
+}
+
+/// Print a table to the standard output, in the style chosen by the global options.
+///
+/// # Arguments
+///
+/// * `table` - The table.
+/// * `options` - The [`CliOptions`] chosen by the global options.
+fn print_table(table: &mut Table, options: &CliOptions) {
+    let (color, plain) = (options.color, options.plain);
+    if plain {
+        table.set_format(*format::consts::FORMAT_CLEAN);
+    }
+
+    let no_color = env::var_os(NO_COLOR_ENV_VAR).is_some_and(|v| !v.is_empty());
+    _ = match color {
+        _ if plain => table.print(&mut io::stdout()),
+        ColorMode::Always => table.print_tty(true),
+        ColorMode::Auto if !no_color => table.print_tty(false),
+        ColorMode::Auto | ColorMode::Never => table.print(&mut io::stdout()),
+    };
+}
+
+fn print_mimetype(outcome: &MatchOutcome) {
+    let mimetype = outcome
+        .top()
+        .and_then(|r| r.mimetype.as_deref())
+        .unwrap_or(UNKNOWN_MIMETYPE);
+
+    println!("{mimetype}");
+}
+
+fn print_negative_validation(validation: &NegativeValidation) {
+    // This is written to stderr, so that it won't be mixed in with a pattern written to stdout.
+    eprintln!(
+        "Validated the pattern against {} negative files, dropping {} strings.",
+        validation.total_scanned_files, validation.dropped_strings
+    );
+
+    if !validation.false_positives.is_empty() {
+        eprintln!("The following files still match the pattern:");
+        for (file, percentage) in &validation.false_positives {
+            eprintln!("  {file} ({percentage}%)");
+        }
+    }
+}
+
+fn print_outliers(report: &BuildReport, excluded: bool) {
+    if report.outliers.is_empty() {
+        return;
+    }
+
+    // As above, this is written to stderr.
+    if excluded {
+        eprintln!("The following sample files may be mislabeled, and were excluded:");
+    } else {
+        eprintln!("The following sample files may be mislabeled:");
+    }
+    for file in &report.outliers {
+        eprintln!("  {file}");
+    }
+}
+
+fn print_cross_validation(validation: &CrossValidation) {
+    // As above, this is written to stderr.
+    eprintln!(
+        "Cross-validated over {} folds: minimum {}%, average {}%.",
+        validation.folds, validation.min_percentage, validation.average_percentage
+    );
+
+    let unmatched: Vec<&String> = validation
+        .scores
+        .iter()
+        .filter(|(_, p)| *p == 0.0)
+        .map(|(f, _)| f)
+        .collect();
+    if !unmatched.is_empty() {
+        eprintln!("The following sample files weren't matched when held out:");
+        for file in unmatched {
+            eprintln!("  {file}");
+        }
+    }
+}
+
+fn print_quality(quality: &PatternQuality) {
+    // As above, this is written to stderr.
+    eprintln!("Pattern quality: {}", quality.grade);
+    eprintln!("  Sample files:   {}", quality.total_samples);
+    eprintln!("  Anchored bytes: {}", quality.anchored_bytes);
+    eprintln!("  Strings:        {}", quality.string_count);
+    match quality.entropy_spread {
+        Some(spread) => eprintln!("  Entropy spread: {spread:.3}"),
+        None => eprintln!("  Entropy spread: n/a"),
+    }
+
+    if quality.grade == QualityGrade::Weak {
+        eprintln!("This pattern is weak, and is likely to produce false positives. Consider adding more sample files.");
+    }
+}
+
+fn print_results(outcome: &MatchOutcome, families: Option<&Identifier>, options: &CliOptions) {
+    let results = match outcome {
+        MatchOutcome::Identified(results) => results,
+        MatchOutcome::Unknown => {
+            println!("Unknown");
+            return;
+        }
+    };
+
+    // The related results of a family are ranked beneath the primary result, such as 1.1 and 1.2.
+    let groups = families.map(|identifier| identifier.group_by_family(results));
+    let rows: Vec<(String, &str, &MatchResult)> = match &groups {
+        Some(groups) => {
+            let mut rows = Vec::new();
+            for (i, group) in groups.iter().enumerate() {
+                rows.push(((i + 1).to_string(), "", &group.primary));
+                for (j, related) in group.related.iter().enumerate() {
+                    rows.push((format!("{}.{}", i + 1, j + 1), "  ", related));
+                }
+            }
+            rows
+        }
+        None => results
+            .iter()
+            .enumerate()
+            .map(|(i, r)| ((i + 1).to_string(), "", r))
+            .collect(),
+    };
+
+    let mut table = Table::new();
+
+    // Add a row for the header.
+    table.add_row(Row::new(vec![
+        Cell::new("Rank").style_spec("b"),
+        Cell::new("Name").style_spec("b"),
+        Cell::new("MIME Type").style_spec("b"),
+        Cell::new("Points").style_spec("b"),
+        Cell::new("Max Points").style_spec("b"),
+        Cell::new("Percentage").style_spec("b"),
+    ]));
+
+    for (rank, indent, result) in rows {
+        // The values are rounded to 1 d.p., so we don't need to worry about the edge-case
+        // floating point issues.
+        let colour = match result.percentage {
+            0.0..=33.3 => "Fr",
+            33.4..=66.66 => "Fy",
+            66.67..=100.0 => "Fg",
+            _ => "Fw",
+        };
+
+        table.add_row(Row::new(vec![
+            Cell::new(&rank).style_spec(colour),
+            Cell::new(&format!("{indent}{}", result.name)).style_spec(colour),
+            Cell::new(result.mimetype.as_deref().unwrap_or("")).style_spec(colour),
+            Cell::new(&result.points.to_string()).style_spec(colour),
+            Cell::new(&result.max_points.to_string()).style_spec(colour),
+            Cell::new(&result.percentage.to_string()).style_spec(colour),
+        ]));
+    }
+
+    print_table(&mut table, options);
+}
+
+fn collect_sample_files(paths: &[String], max_depth: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path == "-" {
+            return Err(
+                "Data can only be read from stdin when no other files are specified.".into(),
+            );
+        } else if utils::directory_exists(path) {
+            files.extend(utils::list_files_to_depth(path, max_depth));
+        } else if utils::file_exists(path) {
+            files.push(path.clone());
+        } else if utils::is_glob(path) {
+            let matches = utils::list_files_of_glob(path)?;
+            if matches.is_empty() {
+                eprintln!("The glob '{path}' didn't match any files.");
+            }
+            files.extend(matches);
+        } else {
+            return Err(format!("The specified sample file path '{path}' doesn't exist.").into());
+        }
+    }
+
+    // A file may have been selected by more than one path.
+    let mut seen = HashSet::new();
+    files.retain(|f| seen.insert(f.clone()));
+
+    if files.is_empty() {
+        return Err("No files were found to identify.".into());
+    }
+
+    Ok(files)
+}
+
+/// The options controlling the identification of a batch of files.
+struct BatchOptions<'a> {
+    /// The format in which the results should be displayed.
+    output: OutputFormat,
+    /// The template used to display the top match for each file, overriding the output format.
+    template: Option<&'a ResultTemplate>,
+    /// The maximum number of results to be retained for each file, or -1 for no limit.
+    result_count: i32,
+    /// Should the number of files of each type be displayed, rather than a result per file?
+    summary: bool,
+    /// Should a progress bar be displayed?
+    show_progress: bool,
+    /// Should the detected type of each file be stored within its extended attributes?
+    write_xattrs: bool,
+    /// Should the results for the patterns of the same format family be grouped?
+    group_families: bool,
+}
+
+fn identify_files(
+    identifier: &Identifier,
+    files: &[String],
+    options: &BatchOptions,
+    cli_options: &CliOptions,
+) -> Result<(), Box<dyn Error>> {
+    let progress = if options.show_progress {
+        let bar = ProgressBar::new(files.len() as u64);
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{bar:40} {pos}/{len} files [{elapsed_precise}, ETA {eta}] {wide_msg}",
+            )
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+        bar
+    } else {
+        ProgressBar::hidden()
+    };
+
+    // The identifier, and the patterns that it holds, are shared between all of the files.
+    let outcomes: Vec<_> = utils::in_thread_pool(|| {
+        files
+            .par_iter()
+            .map(|file| {
+                progress.set_message(file.clone());
+                let outcome = identifier.identify_path(file).map(|mut results| {
+                    if options.group_families {
+                        results = order_by_family(identifier, results);
+                    }
+                    if options.result_count != -1 {
+                        results.truncate(options.result_count as usize);
+                    }
+                    MatchOutcome::from(results)
+                });
+                progress.inc(1);
+                outcome
+            })
+            .collect()
+    });
+    progress.finish_and_clear();
+
+    for (file, outcome) in files.iter().zip(&outcomes) {
+        match outcome {
+            Ok(outcome) if options.write_xattrs => store_result_xattrs(file, outcome),
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to read '{file}': {e}"),
+        }
+    }
+
+    if let Some(template) = options.template {
+        for (file, outcome) in files.iter().zip(&outcomes) {
+            // The files that couldn't be read have already been reported.
+            if let Ok(outcome) = outcome {
+                println!("{}", template.render(file, outcome.top(), UNKNOWN_MIMETYPE));
+            }
+        }
+        return Ok(());
+    }
+
+    if options.summary {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for outcome in &outcomes {
+            let name = describe_outcome(outcome);
+            match counts.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((name, 1)),
+            }
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let unidentified: Vec<_> = files
+            .iter()
+            .zip(&outcomes)
+            .filter(|(_, outcome)| matches!(outcome, Ok(MatchOutcome::Unknown)))
+            .map(|(file, _)| file)
+            .collect();
+
+        if options.output == OutputFormat::Json {
+            let json = serde_json::json!({
+                "total_files": files.len(),
+                "types": counts
+                    .iter()
+                    .map(|(name, count)| serde_json::json!({ "name": name, "files": count }))
+                    .collect::<Vec<_>>(),
+                "unidentified": unidentified,
+            });
+            println!("{}", serde_json::to_string(&json)?);
+            return Ok(());
+        }
+
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Name").style_spec("b"),
+            Cell::new("Files").style_spec("b"),
+        ]));
+        for (name, count) in counts {
+            table.add_row(Row::new(vec![
+                Cell::new(&name),
+                Cell::new(&count.to_string()),
+            ]));
+        }
+        print_table(&mut table, cli_options);
+        println!("{} files were scanned.", files.len());
+
+        if !unidentified.is_empty() {
+            println!();
+            println!("The following files couldn't be identified:");
+            for file in unidentified {
+                println!("  {file}");
+            }
+        }
+        return Ok(());
+    }
+
+    match options.output {
+        OutputFormat::Table => {
+            let mut table = Table::new();
+            table.add_row(Row::new(vec![
+                Cell::new("File").style_spec("b"),
+                Cell::new("Name").style_spec("b"),
+                Cell::new("MIME Type").style_spec("b"),
+                Cell::new("Percentage").style_spec("b"),
+            ]));
+            for (file, outcome) in files.iter().zip(&outcomes) {
+                let top = outcome.as_ref().ok().and_then(MatchOutcome::top);
+                table.add_row(Row::new(vec![
+                    Cell::new(file),
+                    Cell::new(&describe_outcome(outcome)),
+                    Cell::new(top.and_then(|r| r.mimetype.as_deref()).unwrap_or("")),
+                    Cell::new(&top.map_or(String::new(), |r| r.percentage.to_string())),
+                ]));
+            }
+            print_table(&mut table, cli_options);
+        }
+        OutputFormat::Mime => {
+            for (file, outcome) in files.iter().zip(&outcomes) {
+                let mimetype = outcome
+                    .as_ref()
+                    .ok()
+                    .and_then(MatchOutcome::top)
+                    .and_then(|r| r.mimetype.as_deref())
+                    .unwrap_or(UNKNOWN_MIMETYPE);
+                println!("{file}: {mimetype}");
+            }
+        }
+        OutputFormat::Json => {
+            let json: Vec<_> = files
+                .iter()
+                .zip(&outcomes)
+                .map(|(file, outcome)| match outcome {
+                    Ok(outcome) => {
+                        json_results(file, outcome, options.group_families.then_some(identifier))
+                    }
+                    Err(e) => serde_json::json!({ "file": file, "error": e.to_string() }),
+                })
+                .collect();
+            println!("{}", serde_json::to_string(&json)?);
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            print_delimited(files, &outcomes, options.output.delimiter());
+        }
+    }
+
+    Ok(())
+}
+
+/// Store the detected type of a file within its extended attributes, reporting any failure.
+fn store_result_xattrs(file: &str, outcome: &MatchOutcome) {
+    let Some(top) = outcome.top() else {
+        return;
+    };
+
+    if let Err(e) = write_result_xattrs(file, top) {
+        eprintln!("Failed to write the extended attributes of '{file}': {e}");
+    }
+}
+
+#[cfg(unix)]
+fn write_result_xattrs(file: &str, result: &MatchResult) -> io::Result<()> {
+    if let Some(mimetype) = &result.mimetype {
+        xattr::set(file, XATTR_MIME_TYPE, mimetype.as_bytes())?;
+    }
+
+    // The plain text fallback isn't produced by a pattern.
+    if !result.uuid.is_empty() {
+        xattr::set(file, XATTR_PATTERN_UUID, result.uuid.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_result_xattrs(_file: &str, _result: &MatchResult) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes aren't supported on this platform",
+    ))
+}
+
+fn describe_outcome(outcome: &Result<MatchOutcome, ItfError>) -> String {
+    match outcome {
+        Ok(outcome) => outcome
+            .top()
+            .map_or("Unknown".to_string(), |r| r.name.clone()),
+        Err(_) => "Unreadable".to_string(),
+    }
+}
+
+fn print_delimited(files: &[String], outcomes: &[Result<MatchOutcome, ItfError>], delimiter: char) {
+    let field = |value: &str| {
+        if delimiter == '\t' {
+            // TSV has no quoting, so any characters that would break the row are replaced.
+            value.replace(['\t', '\r', '\n'], " ")
+        } else if value.contains([delimiter, '"', '\r', '\n']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+    let row = |fields: &[&str]| {
+        let fields: Vec<_> = fields.iter().map(|f| field(f)).collect();
+        println!("{}", fields.join(&delimiter.to_string()));
+    };
+
+    row(&["file", "name", "mime", "percentage", "ambiguous"]);
+    for (file, outcome) in files.iter().zip(outcomes) {
+        let top = outcome.as_ref().ok().and_then(MatchOutcome::top);
+        let ambiguous = outcome.as_ref().is_ok_and(MatchOutcome::is_ambiguous);
+        row(&[
+            file,
+            &describe_outcome(outcome),
+            top.and_then(|r| r.mimetype.as_deref())
+                .unwrap_or(UNKNOWN_MIMETYPE),
+            &top.map_or(String::new(), |r| r.percentage.to_string()),
+            if ambiguous { "true" } else { "false" },
+        ]);
+    }
+}
+
+fn json_results(
+    file: &str,
+    outcome: &MatchOutcome,
+    families: Option<&Identifier>,
+) -> serde_json::Value {
+    let results = match outcome {
+        MatchOutcome::Identified(results) => results.as_slice(),
+        MatchOutcome::Unknown => &[],
+    };
+
+    match families {
+        Some(identifier) => {
+            serde_json::json!({ "file": file, "results": identifier.group_by_family(results) })
+        }
+        None => serde_json::json!({ "file": file, "results": results }),
+    }
+}
+
+/// Reorder a set of results so that the results for each format family are adjacent, with the primary
+/// result of each family first.
+///
+/// # Arguments
+///
+/// * `identifier` - The identifier that produced the results.
+/// * `results` - The results, in rank order.
+fn order_by_family(identifier: &Identifier, results: Vec<MatchResult>) -> Vec<MatchResult> {
+    identifier
+        .group_by_family(&results)
+        .into_iter()
+        .flat_map(|group| iter::once(group.primary).chain(group.related))
+        .collect()
+}
+
+/// Get the exit code describing the results of identifying a file in quiet mode.
+///
+/// # Arguments
+///
+/// * `results` - The results, in rank order, before any are discarded.
+///
+/// # Returns
+///
+/// 0 for a confident match, [`EXIT_NO_MATCH`] if there was no match, or if the data only appears to be
+/// plain text, and [`EXIT_AMBIGUOUS`] if the top match was ambiguous.
+fn quiet_exit_code(results: &[MatchResult]) -> i32 {
+    match results.first() {
+        None => EXIT_NO_MATCH,
+        Some(top) if top.is_text_fallback() => EXIT_NO_MATCH,
+        Some(_) if MatchOutcome::from(results.to_vec()).is_ambiguous() => EXIT_AMBIGUOUS,
+        Some(_) => 0,
+    }
+}
+
+fn process_identify_command(cmd: &Commands, options: &CliOptions) -> Result<(), Box<dyn Error>> {
+    if let Commands::Identify {
+        pattern_source_dirs,
+        target_pattern,
+        result_count,
+        name_hint,
+        cache_file,
+        lazy,
+        min_confidence,
+        explain,
+        output,
+        require_signed,
+        trusted_keys,
+        categories,
+        allowed_licenses,
+        compound_extensions,
+        rank_by,
+        scoring,
+        extension_first,
+        strict_extension,
+        group_families,
+        recursive,
+        max_depth,
+        summary,
+        quiet,
+        format,
+        xattr,
+        no_progress,
+        files,
+    } = cmd
+    {
+        let template = format.as_deref().map(ResultTemplate::parse).transpose()?;
+
+        // A single file is identified in detail, while anything else is identified as a batch.
+        let single_file = match files.as_slice() {
+            [file] if file == "-" || utils::file_exists(file) => Some(file),
+            _ => None,
+        };
+        if *quiet && single_file.is_none() {
+            return Err(match files.as_slice() {
+                [path] if !Path::new(path).exists() && !utils::is_glob(path) => {
+                    format!("The specified sample file path '{path}' doesn't exist.").into()
+                }
+                _ => "Only a single file may be identified in quiet mode.".into(),
+            });
+        }
+
+        let batch_files = if single_file.is_none() {
+            let max_depth = if *recursive {
+                max_depth.unwrap_or(usize::MAX)
+            } else {
+                1
+            };
+            collect_sample_files(files, max_depth)?
+        } else {
+            vec![]
+        };
+
+        let (source_directory, additional_sources) = match pattern_source_dirs.split_first() {
+            Some((first, rest)) => (first.as_str(), rest),
+            None => ("", &[][..]),
+        };
+        let mut pattern_handler = built_pattern_handler(
+            &LoadOptions {
+                source_directory,
+                additional_sources,
+                target_pattern,
+                cache_file: cache_file.as_deref(),
+                trusted_key_files: require_signed.then_some(trusted_keys.as_slice()),
+                allowed_licenses,
+                lazy: *lazy,
+            },
+            options,
+        )?;
+
+        if !categories.is_empty() {
+            pattern_handler.retain_categories(categories);
+            if pattern_handler.is_empty() {
+                return Err(
+                    "None of the loaded patterns belong to the specified categories.".into(),
+                );
+            }
+        }
+
+        let mut all_compound_extensions: Vec<String> = utils::DEFAULT_COMPOUND_EXTENSIONS
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        all_compound_extensions.extend(compound_extensions.iter().map(|e| e.to_uppercase()));
+
+        let mut identifier = new_identifier(pattern_handler, options)
+            .with_min_confidence(*min_confidence)
+            .with_text_fallback(true)
+            .with_compound_extensions(all_compound_extensions)
+            .with_rank_by((*rank_by).into())
+            .with_extension_filter(if *strict_extension {
+                ExtensionFilter::Strict
+            } else if *extension_first {
+                ExtensionFilter::Prefer
+            } else {
+                ExtensionFilter::Off
+            });
+        if *scoring == ScoringMode::MagicOnly {
+            identifier = identifier.with_scoring_strategy(MagicOnlyScoring);
+        }
+
+        let Some(file) = single_file else {
+            let batch_options = BatchOptions {
+                output: *output,
+                template: template.as_ref(),
+                result_count: *result_count,
+                summary: *summary,
+                show_progress: !*no_progress && io::stderr().is_terminal(),
+                write_xattrs: *xattr,
+                group_families: *group_families,
+            };
+            return identify_files(&identifier, &batch_files, &batch_options, options);
+        };
+
+        let mut results = if file == "-" {
+            identifier.identify_reader(io::stdin().lock(), name_hint.as_deref())?
+        } else {
+            identifier.identify_path(file)?
+        };
+
+        if *group_families {
+            results = order_by_family(&identifier, results);
+        }
+
+        // The outcome is judged before the results are truncated, so that a close runner-up is still considered.
+        let quiet_exit_code = quiet_exit_code(&results);
+
+        // Only retail a set number of results, if specified.
+        if *result_count != -1 {
+            results.truncate(*result_count as usize);
+        }
+
+        let outcome = MatchOutcome::from(results);
+        // Data read from stdin has no file in which the attributes could be stored.
+        if *xattr && file != "-" {
+            store_result_xattrs(file, &outcome);
+        }
+
+        if let Some(template) = &template {
+            println!("{}", template.render(file, outcome.top(), UNKNOWN_MIMETYPE));
+            return Ok(());
+        }
+
+        if *quiet {
+            if let Some(top) = outcome.top().filter(|r| !r.is_text_fallback()) {
+                println!("{}", top.name);
+            }
+
+            process::exit(quiet_exit_code);
+        }
+
+        match output {
+            OutputFormat::Table => {
+                print_results(&outcome, group_families.then_some(&identifier), options);
+                if *explain {
+                    print_explanations(&outcome, options);
+                }
+            }
+            OutputFormat::Mime => print_mimetype(&outcome),
+            OutputFormat::Json => {
+                let json = [json_results(
+                    file,
+                    &outcome,
+                    group_families.then_some(&identifier),
+                )];
+                println!("{}", serde_json::to_string(&json)?);
+            }
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                print_delimited(
+                    std::slice::from_ref(file),
+                    &[Ok(outcome.clone())],
+                    output.delimiter(),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn process_pattern_command(cmd: &Commands) -> Result<(), Box<dyn Error>> {
+    if let Commands::Pattern {
+        action: _,
+        user_name,
+        email,
+        name,
+        description,
+        known_extensions,
+        mimetypes,
+        priority,
+        categories,
+        no_strings,
+        no_sequences,
+        no_composition,
+        no_masks,
+        no_extended_composition,
+        fuzzy_hash,
+        negative_dir,
+        exclude_outliers,
+        weight,
+        weight_recent,
+        string_blocklist,
+        synthesize_regexes,
+        sequence,
+        regex,
+        report,
+        cross_validate,
+        min_samples,
+        allow_few_samples,
+        glob,
+        compress,
+        extension,
+        path,
+        output_directory,
+    } = cmd
+    {
+        // Clap guarantees that these are present when no action was given.
+        let (Some(extension), Some(path)) = (extension, path) else {
+            return Err("The target extension and path must be specified.".into());
+        };
+
+        if !utils::directory_exists(path) {
+            return Err(format!("The specified target folder '{path}' doesn't exist.").into());
+        }
+
+        let target_extensions = utils::split_extensions(extension);
+        if target_extensions.is_empty() {
+            return Err("The target extension may not be empty.".into());
+        }
+
+        if let Some(d) = negative_dir {
+            if !utils::directory_exists(d) {
+                return Err(format!("The specified negative folder '{d}' doesn't exist.").into());
+            }
+        }
+
+        if *no_strings && *no_sequences && *no_composition {
+            return Err(
+                "No pattern matching options were enabled, therefore no pattern can be created."
+                    .into(),
+            );
+        }
+
+        let mut extensions: Vec<String> = if known_extensions.is_empty() {
+            vec![]
+        } else {
+            known_extensions
+                .split(',')
+                .collect::<Vec<&str>>()
+                .iter()
+                .map(|s| s.to_uppercase())
+                .collect()
+        };
+
+        // Every extension used to find the sample files is a known extension for the file type.
+        for upper_ext in &target_extensions {
+            if !extensions.contains(upper_ext) {
+                extensions.push(upper_ext.clone());
+            }
+        }
+
+        let mimetypes: Vec<String> = if mimetypes.is_empty() {
             vec![]
         } else {
-            known_extensions
-                .split(',')
-                .collect::<Vec<&str>>()
-                .iter()
-                .map(|s| s.to_uppercase())
+            mimetypes
+                .split(',')
+                .collect::<Vec<&str>>()
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        };
+
+        let mut pattern = Pattern::new(name, description, extensions, mimetypes);
+        pattern.type_data.priority = *priority;
+        pattern.type_data.categories = categories
+            .iter()
+            .map(|c| interner::intern(&c.to_lowercase()))
+            .collect();
+        pattern.add_submitter_data(user_name, email);
+
+        //let now = std::time::Instant::now();
+
+        let mut blocklist = StringBlocklist::default();
+        if let Some(f) = string_blocklist {
+            if !utils::file_exists(f) {
+                return Err(
+                    format!("The specified string blocklist file '{f}' doesn't exist.").into(),
+                );
+            }
+
+            blocklist.extend_from_file(f)?;
+        }
+
+        let options = BuildOptions {
+            scan_strings: !*no_strings,
+            scan_byte_sequences: !*no_sequences,
+            scan_byte_distribution: !*no_composition,
+            mask_sequences: !*no_masks,
+            scan_extended_composition: !*no_extended_composition,
+            fuzzy_hash: *fuzzy_hash,
+            exclude_outliers: *exclude_outliers,
+            string_blocklist: blocklist,
+            synthesize_regexes: *synthesize_regexes,
+            min_samples: if *allow_few_samples {
+                DEFAULT_MIN_SAMPLES
+            } else {
+                *min_samples
+            },
+        };
+
+        let weights = if *weight_recent {
+            SampleWeights::Recent
+        } else if weight.is_empty() {
+            SampleWeights::Uniform
+        } else {
+            let mut weights = Vec::with_capacity(weight.len());
+            for w in weight {
+                let parsed = w
+                    .split_once('=')
+                    .and_then(|(name, value)| Some((name, value.parse::<f32>().ok()?)));
+                let Some((name, value)) = parsed else {
+                    return Err(format!(
+                        "The sample weight '{w}' must be in the form NAME=WEIGHT."
+                    )
+                    .into());
+                };
+
+                weights.push((name.to_string(), value));
+            }
+
+            SampleWeights::Explicit(weights.into_iter().collect())
+        };
+
+        let files = if glob.is_empty() {
+            utils::list_files_of_types(path, &target_extensions)
+        } else {
+            utils::list_files_matching(path, glob)?
+        };
+
+        let mut progress = BuildProgressBar::default();
+        let build_report =
+            pattern.build_patterns_from_files(&files, &options, &weights, &mut progress)?;
+        print_outliers(&build_report, *exclude_outliers);
+
+        let total_samples = pattern.other_data.total_scanned_files;
+        if total_samples < *min_samples {
+            // As above, this is written to stderr.
+            eprintln!("WARNING: the pattern was built from only {total_samples} sample files, fewer than the minimum of {min_samples}. It is unlikely to reliably identify other files of this type.");
+        }
+
+        for (position, bytes) in sequence {
+            pattern.add_sequence(*position, bytes.clone());
+        }
+
+        for (position, r) in regex {
+            let points = pattern.add_regex(*position, r)?;
+
+            // As above, this is written to stderr.
+            eprintln!("The regex '{r}' at offset {position} will award up to {points} points.");
+        }
+
+        //println!("{}", now.elapsed().as_secs_f64());
+
+        if let Some(d) = negative_dir {
+            let validation = pattern.validate_against_negative(d)?;
+            print_negative_validation(&validation);
+        }
+
+        if *report {
+            print_quality(&pattern.quality());
+        }
+
+        if let Some(folds) = cross_validate {
+            let validation = pattern.cross_validate_files(&files, &options, *folds)?;
+            print_cross_validation(&validation);
+        }
+
+        if let Some(d) = output_directory {
+            if !utils::directory_exists(d) {
+                return Err(format!("The specified output directory '{d}' doesn't exist.").into());
+            }
+
+            let compression = compress.map(PatternCompression::from).unwrap_or_default();
+            if let Err(e) = pattern.write(d, compression) {
+                return Err(format!("Failed to write pattern file: {e}").into());
+            }
+
+            println!("The pattern file has been successfully written to the specified directory!");
+        } else {
+            let json = serde_json::to_string(&pattern)?;
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
+
+fn process_pattern_action(
+    action: &PatternCommands,
+    options: &CliOptions,
+) -> Result<(), Box<dyn Error>> {
+    match action {
+        PatternCommands::Merge {
+            output_file,
+            first,
+            second,
+        } => {
+            for file in [first, second] {
+                if !utils::file_exists(file) {
+                    return Err(
+                        format!("The specified pattern file '{file}' doesn't exist.").into(),
+                    );
+                }
+            }
+
+            let mut pattern = Pattern::from_file(first)?;
+            let other = Pattern::from_file(second)?;
+            pattern.merge(&other);
+
+            if let Some(f) = output_file {
+                if let Err(e) = pattern.write_file(f) {
+                    return Err(format!("Failed to write pattern file: {e}").into());
+                }
+
+                println!(
+                    "The patterns have been merged, and now cover {} sample files.",
+                    pattern.other_data.total_scanned_files
+                );
+            } else {
+                let json = serde_json::to_string(&pattern)?;
+                println!("{json}");
+            }
+        }
+        PatternCommands::Fetch {
+            pattern_source_dir,
+            allow_insecure,
+            url,
+        } => {
+            let directory = resolve_pattern_source(pattern_source_dir)?;
+            let summary = pattern_fetch::fetch(url, &directory, *allow_insecure)?;
+
+            println!(
+                "{} pattern files have been written to '{}', and {} were unchanged.",
+                summary.written,
+                directory.display(),
+                summary.unchanged
+            );
+        }
+        PatternCommands::Update {
+            pattern_source_dir,
+            rollback,
+            allow_insecure,
+        } => {
+            let directory = resolve_pattern_source(pattern_source_dir)?;
+
+            if *rollback {
+                let sources = pattern_fetch::rollback(&directory)?;
+                println!("The patterns have been rolled back to the previous set.");
+                for source in &sources.sources {
+                    println!("{}: version {}", source.url, source.version);
+                }
+                return Ok(());
+            }
+
+            for report in pattern_fetch::update(&directory, *allow_insecure)? {
+                println!("{}: version {}", report.url, report.version);
+                if report.changes.is_empty() {
+                    println!("  No definitions have changed.");
+                }
+                for (label, names) in [
+                    ("Added", &report.changes.added),
+                    ("Removed", &report.changes.removed),
+                    ("Modified", &report.changes.modified),
+                ] {
+                    for name in names {
+                        println!("  {label}: {name}");
+                    }
+                }
+            }
+        }
+        PatternCommands::Diff { json, old, new } => {
+            for file in [old, new] {
+                if !utils::file_exists(file) {
+                    return Err(
+                        format!("The specified pattern file '{file}' doesn't exist.").into(),
+                    );
+                }
+            }
+
+            let diff = PatternDiff::between(&Pattern::from_file(old)?, &Pattern::from_file(new)?);
+            if *json {
+                println!("{}", serde_json::to_string(&diff)?);
+            } else {
+                print!("{diff}");
+            }
+        }
+        PatternCommands::Checksum { paths } => {
+            let mut files = Vec::new();
+            for path in paths {
+                if utils::directory_exists(path) {
+                    files.extend(PatternHandler::list_pattern_files(path));
+                } else if utils::file_exists(path) {
+                    files.push(path.clone());
+                } else {
+                    return Err(format!("The specified path '{path}' doesn't exist.").into());
+                }
+            }
+
+            let mut updated = 0;
+            for file in &files {
+                let pattern = Pattern::from_file(file)
+                    .map_err(|e| format!("Failed to read '{file}': {e}"))?;
+                if pattern.verify_checksum().is_err() || pattern.checksum.is_none() {
+                    pattern.write_file(file)?;
+                    updated += 1;
+                }
+            }
+
+            println!(
+                "The checksums of {updated} of {} pattern files have been updated.",
+                files.len()
+            );
+        }
+        PatternCommands::Report {
+            out,
+            format,
+            pattern_file,
+        } => {
+            if !utils::file_exists(pattern_file) {
+                return Err(
+                    format!("The specified pattern file '{pattern_file}' doesn't exist.").into(),
+                );
+            }
+
+            let format = match (format, out) {
+                (Some(format), _) => (*format).into(),
+                (None, Some(out)) => ReportFormat::from_path(out),
+                (None, None) => ReportFormat::Markdown,
+            };
+            let report = pattern_report::render(&Pattern::from_file(pattern_file)?, format);
+
+            match out {
+                Some(out) => {
+                    fs::write(out, report)?;
+                    println!("The report has been written to '{out}'.");
+                }
+                None => print!("{report}"),
+            }
+        }
+        PatternCommands::Edit { out, pattern_file } => {
+            edit_pattern(pattern_file, out.as_deref().unwrap_or(pattern_file))?;
+        }
+        PatternCommands::CheckConflicts {
+            pattern_source_dir,
+            target_pattern,
+            json,
+        } => {
+            let pattern_handler = built_pattern_handler(
+                &LoadOptions {
+                    source_directory: pattern_source_dir,
+                    target_pattern,
+                    ..Default::default()
+                },
+                options,
+            )?;
+
+            let conflicts = pattern_conflicts::find_conflicts(&pattern_handler);
+            if *json {
+                println!("{}", serde_json::to_string(&conflicts)?);
+            } else {
+                for conflict in &conflicts {
+                    println!("{conflict}");
+                }
+                println!(
+                    "{} conflicts were found between {} patterns.",
+                    conflicts.len(),
+                    pattern_handler.iter().count()
+                );
+            }
+
+            if !conflicts.is_empty() {
+                return Err(format!(
+                    "{} pairs of patterns will always match the same files.",
+                    conflicts.len()
+                )
+                .into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the interactive pattern editor, reading the commands from the standard input.
+///
+/// # Arguments
+///
+/// * `pattern_file` - The pattern file to be edited.
+/// * `out` - The file to which the edited pattern should be written.
+fn edit_pattern(pattern_file: &str, out: &str) -> Result<(), Box<dyn Error>> {
+    let mut pattern = Pattern::from_file(pattern_file)
+        .map_err(|e| format!("Failed to read '{pattern_file}': {e}"))?;
+    if pattern.signature.is_some() {
+        eprintln!("The pattern is signed. Any edits will remove its signature.");
+    }
+
+    print!("{}", pattern_editor::describe(&pattern));
+    println!("Enter a command, or \"help\" to list the commands.");
+
+    let mut edited = false;
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+
+        line.clear();
+        if io::stdin().read_line(&mut line)? == 0 {
+            if edited {
+                eprintln!("The input ended before the pattern was saved, so the edits have been discarded.");
+            }
+            return Ok(());
+        }
+
+        match line.trim() {
+            "" => {}
+            "help" => {
+                println!("{}", pattern_editor::EDIT_COMMANDS);
+                println!("show                                         Display the pattern.");
+                println!("lint                                         Check the pattern for likely false positives.");
+                println!(
+                    "save                                         Write the pattern and exit."
+                );
+                println!("quit                                         Exit without writing the pattern.");
+            }
+            "show" => print!("{}", pattern_editor::describe(&pattern)),
+            "lint" => {
+                let issues = pattern_lint::lint(&pattern);
+                for issue in &issues {
+                    println!("{issue}");
+                }
+                if issues.is_empty() {
+                    println!("No issues were found.");
+                }
+            }
+            "save" => {
+                pattern.write_file(out)?;
+                for issue in pattern_lint::lint(&pattern) {
+                    eprintln!("{issue}");
+                }
+                println!("The pattern has been written to '{out}'.");
+                return Ok(());
+            }
+            "quit" if edited => {
+                println!("There are unsaved edits. Use \"save\" to write them, or \"quit!\" to discard them.")
+            }
+            "quit" | "quit!" => return Ok(()),
+            command => match PatternEdit::parse(command).and_then(|e| e.apply(&mut pattern)) {
+                Ok(()) => edited = true,
+                Err(e) => eprintln!("{e}"),
+            },
+        }
+    }
+}
+
+fn process_export_command(cmd: &Commands, options: &CliOptions) -> Result<(), Box<dyn Error>> {
+    if let Commands::Export {
+        pattern_source_dir,
+        target_pattern,
+        format,
+        output_file,
+    } = cmd
+    {
+        let pattern_handler = built_pattern_handler(
+            &LoadOptions {
+                source_directory: pattern_source_dir,
+                target_pattern,
+                ..Default::default()
+            },
+            options,
+        )?;
+
+        let mut output = String::new();
+        let mut exported = 0;
+        for pattern in pattern_handler.iter() {
+            let rules = match format {
+                ExportFormat::Magic => interop::magic::to_magic(pattern),
+            };
+
+            match rules {
+                Some(rules) => {
+                    output.push_str(&rules);
+                    output.push('\n');
+                    exported += 1;
+                }
+                None => eprintln!(
+                    "The pattern '{}' has nothing that can be exported, and was skipped.",
+                    pattern.type_data.name
+                ),
+            }
+        }
+
+        if let Err(e) = fs::write(output_file, output) {
+            return Err(format!("Failed to write the exported patterns: {e}").into());
+        }
+
+        println!("{exported} patterns have been exported to '{output_file}'.");
+    }
+
+    Ok(())
+}
+
+fn process_import_command(cmd: &Commands) -> Result<(), Box<dyn Error>> {
+    if let Commands::Import {
+        format,
+        output_directory,
+        compress,
+        license,
+        origin_url,
+        paths,
+    } = cmd
+    {
+        if !utils::directory_exists(output_directory) {
+            return Err(format!(
+                "The specified output directory '{output_directory}' doesn't exist."
+            )
+            .into());
+        }
+
+        let mut files = Vec::new();
+        for path in paths {
+            if utils::directory_exists(path) {
+                files.extend(utils::list_files_of_type(path, format.extension()));
+            } else if utils::file_exists(path) {
+                files.push(path.clone());
+            } else {
+                return Err(format!("The specified path '{path}' doesn't exist.").into());
+            }
+        }
+
+        let compression = compress.map(PatternCompression::from).unwrap_or_default();
+
+        // A definition that can't be converted is reported, so that the others are still converted.
+        let mut failed = 0;
+        for file in &files {
+            let contents = match fs::read(file) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(e) => {
+                    eprintln!("{file}: {e}");
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            // A YARA file may contain several rules, each of which gives a separate pattern.
+            let result = match format {
+                ImportFormat::Trid => interop::trid::from_trid_xml(&contents).map(|p| vec![p]),
+                ImportFormat::Yara => interop::yara::from_yara(&contents).map(|import| {
+                    for warning in &import.warnings {
+                        eprintln!("{file}: warning: {warning}");
+                    }
+                    import.patterns
+                }),
+            };
+            let patterns = match result {
+                Ok(patterns) => patterns,
+                Err(e) => {
+                    eprintln!("{file}: {e}");
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            for mut pattern in patterns {
+                let other_data = &mut pattern.other_data;
+                if let (Some(license), true) = (license, other_data.license.is_empty()) {
+                    other_data.license.clone_from(license);
+                }
+                if let Some(origin_url) = origin_url {
+                    other_data.origin_url.clone_from(origin_url);
+                }
+
+                match pattern.write(output_directory, compression) {
+                    Ok(path) => println!("{file}: written to '{}'", path.display()),
+                    Err(e) => {
+                        eprintln!("{file}: {e}");
+                        failed += 1;
+                    }
+                }
+            }
+        }
+
+        println!(
+            "{} of {} definitions were converted.",
+            files.len() - failed,
+            files.len()
+        );
+
+        if failed > 0 {
+            return Err(format!("{failed} definitions could not be converted.").into());
+        }
+    }
+
+    Ok(())
+}
+
+fn process_bundle_action(action: &BundleCommands) -> Result<(), Box<dyn Error>> {
+    match action {
+        BundleCommands::Create { bundle_file, paths } => {
+            let mut files = Vec::new();
+            for path in paths {
+                if utils::directory_exists(path) {
+                    files.extend(PatternHandler::list_pattern_files(path));
+                } else if utils::file_exists(path) {
+                    files.push(path.clone());
+                } else {
+                    return Err(format!("The specified path '{path}' doesn't exist.").into());
+                }
+            }
+
+            let mut patterns = Vec::with_capacity(files.len());
+            for file in &files {
+                match Pattern::from_file(file) {
+                    Ok(pattern) => patterns.push(pattern),
+                    Err(e) => return Err(format!("Failed to read '{file}': {e}").into()),
+                }
+            }
+
+            if patterns.is_empty() {
+                return Err("No pattern files were found to bundle.".into());
+            }
+
+            PatternBundle::new(&patterns)?.write_file(bundle_file)?;
+
+            println!(
+                "{} patterns have been bundled into '{bundle_file}'.",
+                patterns.len()
+            );
+        }
+        BundleCommands::Extract {
+            compress,
+            bundle_file,
+            output_directory,
+        } => {
+            if !utils::directory_exists(output_directory) {
+                return Err(format!(
+                    "The specified output directory '{output_directory}' doesn't exist."
+                )
+                .into());
+            }
+
+            let bundle = PatternBundle::from_file(bundle_file)?;
+            let compression = compress.map(PatternCompression::from).unwrap_or_default();
+            for pattern in bundle.read_patterns()? {
+                let path = pattern.write(output_directory, compression)?;
+                println!(
+                    "{}: written to '{}'",
+                    pattern.type_data.name,
+                    path.display()
+                );
+            }
+
+            println!(
+                "{} patterns have been extracted from '{bundle_file}'.",
+                bundle.header.entries.len()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn process_index_command(cmd: &Commands) -> Result<(), Box<dyn Error>> {
+    if let Commands::Index { directory } = cmd {
+        if !utils::directory_exists(directory) {
+            return Err(format!("The specified directory '{directory}' doesn't exist.").into());
+        }
+
+        let index = PatternIndex::build(directory)?;
+        let path = index.write(directory)?;
+
+        println!(
+            "{} pattern files have been indexed in '{}'.",
+            index.entries.len(),
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn process_sign_command(cmd: &Commands) -> Result<(), Box<dyn Error>> {
+    if let Commands::Sign {
+        key,
+        detached,
+        generate_key,
+        paths,
+    } = cmd
+    {
+        if let Some(key_file) = generate_key {
+            let key = pattern_signing::generate_key();
+            let public_key_file = format!("{key_file}.pub");
+            fs::write(key_file, utils::encode_hex(&key.to_bytes()))?;
+            fs::write(
+                &public_key_file,
+                utils::encode_hex(key.verifying_key().as_bytes()),
+            )?;
+
+            // The secret key should only be readable by its owner.
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(key_file, fs::Permissions::from_mode(0o600))?;
+            }
+
+            println!(
+                "The secret key has been written to '{key_file}' and the public key to '{public_key_file}'."
+            );
+            return Ok(());
+        }
+
+        // Clap guarantees that the key is present when a key isn't being generated.
+        let Some(key) = key else {
+            return Err("The secret key must be specified.".into());
+        };
+        let key = pattern_signing::signing_key_from_hex(&fs::read_to_string(key)?)?;
+
+        let mut files = Vec::new();
+        for path in paths {
+            if utils::directory_exists(path) {
+                files.extend(PatternHandler::list_pattern_files(path));
+            } else if utils::file_exists(path) {
+                files.push(path.clone());
+            } else {
+                return Err(format!("The specified path '{path}' doesn't exist.").into());
+            }
+        }
+
+        for file in &files {
+            let mut pattern =
+                Pattern::from_file(file).map_err(|e| format!("Failed to read '{file}': {e}"))?;
+            let signature = pattern_signing::sign(&pattern, &key)?;
+
+            if *detached {
+                fs::write(
+                    pattern_signing::detached_signature_path(file),
+                    serde_json::to_string(&signature)?,
+                )?;
+            } else {
+                pattern.signature = Some(signature);
+                pattern.write_file(file)?;
+            }
+        }
+
+        println!("{} pattern files have been signed.", files.len());
+    }
+
+    Ok(())
+}
+
+fn process_compile_rules_command(cmd: &Commands) -> Result<(), Box<dyn Error>> {
+    if let Commands::CompileRules { out, paths } = cmd {
+        let mut files = Vec::new();
+        for path in paths {
+            if utils::directory_exists(path) {
+                files.extend(PatternHandler::list_pattern_files(path));
+            } else if utils::file_exists(path) {
+                files.push(path.clone());
+            } else {
+                return Err(format!("The specified path '{path}' doesn't exist.").into());
+            }
+        }
+
+        let mut patterns = Vec::new();
+        for file in &files {
+            patterns.push(
+                Pattern::from_file(file).map_err(|e| format!("Failed to read '{file}': {e}"))?,
+            );
+        }
+
+        let compiled = embedded_rule::compile_rules(&patterns);
+        for warning in &compiled.warnings {
+            eprintln!("Warning: {warning}.");
+        }
+
+        if let Some(out) = out {
+            fs::write(out, &compiled.source)?;
+            println!("{} rules have been written to '{out}'.", patterns.len());
+        } else {
+            print!("{}", compiled.source);
+        }
+    }
+
+    Ok(())
+}
+
+fn process_stats_command(cmd: &Commands, options: &CliOptions) -> Result<(), Box<dyn Error>> {
+    if let Commands::Stats {
+        pattern_source_dir: source_directory,
+        target_pattern,
+        cache_file,
+        min_confidence,
+        recursive,
+        json,
+        directory,
+    } = cmd
+    {
+        if !utils::directory_exists(directory) {
+            return Err(format!("The specified directory '{directory}' doesn't exist.").into());
+        }
+
+        let pattern_handler = built_pattern_handler(
+            &LoadOptions {
+                source_directory,
+                target_pattern,
+                cache_file: cache_file.as_deref(),
+                ..Default::default()
+            },
+            options,
+        )?;
+        let identifier = new_identifier(pattern_handler, options)
+            .with_min_confidence(*min_confidence)
+            .with_text_fallback(true);
+
+        let files = utils::list_files_to_depth(directory, if *recursive { usize::MAX } else { 1 });
+        let outcomes: Vec<_> = utils::in_thread_pool(|| {
+            files
+                .par_iter()
+                .map(|file| identifier.identify_path(file).map(MatchOutcome::from))
                 .collect()
+        });
+
+        // The statistics for each type, given as the name of the type followed by the number of files,
+        // the total size of the files and the number of files with a mismatched extension.
+        let mut types: Vec<(String, usize, u64, usize)> = Vec::new();
+        let mut mismatched = Vec::new();
+        let mut unidentified = Vec::new();
+        let mut unreadable = Vec::new();
+        let mut total_bytes = 0;
+        for (file, outcome) in files.iter().zip(&outcomes) {
+            let top = match outcome {
+                Ok(outcome) => outcome.top(),
+                Err(e) => {
+                    unreadable.push((file, e.to_string()));
+                    continue;
+                }
+            };
+            let size = fs::metadata(file).map_or(0, |m| m.len());
+            total_bytes += size;
+
+            let Some(top) = top else {
+                unidentified.push(file);
+                continue;
+            };
+
+            let expected = mismatched_extensions(&identifier, file, top);
+            if let Some(expected) = expected {
+                mismatched.push((file, &top.name, expected));
+            }
+
+            let index = match types.iter().position(|(name, ..)| *name == top.name) {
+                Some(i) => i,
+                None => {
+                    types.push((top.name.clone(), 0, 0, 0));
+                    types.len() - 1
+                }
+            };
+            let entry = &mut types[index];
+            entry.1 += 1;
+            entry.2 += size;
+            entry.3 += expected.is_some() as usize;
+        }
+        types.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        if *json {
+            let json = serde_json::json!({
+                "total_files": files.len(),
+                "total_bytes": total_bytes,
+                "types": types
+                    .iter()
+                    .map(|(name, count, bytes, mismatched)| serde_json::json!({
+                        "name": name,
+                        "files": count,
+                        "bytes": bytes,
+                        "mismatched": mismatched,
+                    }))
+                    .collect::<Vec<_>>(),
+                "mismatched": mismatched
+                    .iter()
+                    .map(|(file, name, expected)| serde_json::json!({
+                        "file": file,
+                        "name": name,
+                        "expected_extensions": expected,
+                    }))
+                    .collect::<Vec<_>>(),
+                "unidentified": unidentified,
+                "unreadable": unreadable
+                    .iter()
+                    .map(|(file, error)| serde_json::json!({ "file": file, "error": error }))
+                    .collect::<Vec<_>>(),
+            });
+            println!("{}", serde_json::to_string(&json)?);
+            return Ok(());
+        }
+
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Name").style_spec("b"),
+            Cell::new("Files").style_spec("b"),
+            Cell::new("Bytes").style_spec("b"),
+            Cell::new("Mismatched").style_spec("b"),
+        ]));
+        for (name, count, bytes, mismatched) in &types {
+            table.add_row(Row::new(vec![
+                Cell::new(name),
+                Cell::new(&count.to_string()),
+                Cell::new(&bytes.to_string()),
+                Cell::new(&mismatched.to_string()),
+            ]));
+        }
+        print_table(&mut table, options);
+        println!(
+            "{} files ({total_bytes} bytes) were scanned, of which {} couldn't be identified and {} couldn't be read.",
+            files.len(),
+            unidentified.len(),
+            unreadable.len()
+        );
+
+        if !mismatched.is_empty() {
+            println!();
+            println!("The following files have an extension that doesn't match their type:");
+            for (file, name, expected) in &mismatched {
+                println!(
+                    "  {file} ({name}, expected {})",
+                    expected.join(", ").to_lowercase()
+                );
+            }
+        }
+
+        if !unidentified.is_empty() {
+            println!();
+            println!("The following files couldn't be identified:");
+            for file in &unidentified {
+                println!("  {file}");
+            }
+        }
+
+        if !unreadable.is_empty() {
+            println!();
+            println!("The following files couldn't be read:");
+            for (file, error) in &unreadable {
+                println!("  {file} ({error})");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn process_test_command(cmd: &Commands, options: &CliOptions) -> Result<(), Box<dyn Error>> {
+    if let Commands::Test {
+        pattern_source_dir: source_directory,
+        target_pattern,
+        cache_file,
+        min_confidence,
+        min_accuracy,
+        json,
+        corpus,
+    } = cmd
+    {
+        let labeled = if utils::directory_exists(corpus) {
+            evaluation::labels_from_directory(corpus)?
+        } else if utils::file_exists(corpus) {
+            evaluation::labels_from_csv(corpus)?
+        } else {
+            return Err(format!("The specified corpus '{corpus}' doesn't exist.").into());
+        };
+        if labeled.is_empty() {
+            return Err(format!("No labeled files were found within '{corpus}'.").into());
+        }
+
+        let pattern_handler = built_pattern_handler(
+            &LoadOptions {
+                source_directory,
+                target_pattern,
+                cache_file: cache_file.as_deref(),
+                ..Default::default()
+            },
+            options,
+        )?;
+        let identifier =
+            new_identifier(pattern_handler, options).with_min_confidence(*min_confidence);
+
+        let name_of = |label: &str| {
+            identifier
+                .pattern_handler()
+                .pattern_by_uuid(label)
+                .map(|p| p.type_data.name.clone())
         };
+        let mut unknown_labels: Vec<_> = labeled
+            .iter()
+            .map(|(_, label)| label)
+            .filter(|label| *label != evaluation::UNKNOWN_LABEL && name_of(label).is_none())
+            .collect();
+        unknown_labels.sort_unstable();
+        unknown_labels.dedup();
+        for label in unknown_labels {
+            eprintln!("The label '{label}' doesn't match the UUID of any loaded pattern.");
+        }
 
-        let upper_ext = extension.to_uppercase();
-        if !extensions.contains(&upper_ext) {
-            extensions.push(upper_ext);
+        let outcomes: Vec<_> = utils::in_thread_pool(|| {
+            labeled
+                .par_iter()
+                .map(|(file, _)| identifier.identify_path(file).map(MatchOutcome::from))
+                .collect()
+        });
+
+        let mut evaluation = Evaluation::default();
+        let mut misidentified = Vec::new();
+        for ((file, expected), outcome) in labeled.iter().zip(outcomes) {
+            let outcome = match outcome {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    eprintln!("Failed to identify '{file}', so it has been skipped: {e}");
+                    continue;
+                }
+            };
+            let identified = outcome.top().map(|r| r.uuid.as_str());
+
+            evaluation.record(expected, identified);
+            if identified.unwrap_or(evaluation::UNKNOWN_LABEL) != expected {
+                misidentified.push((
+                    file,
+                    expected,
+                    identified.unwrap_or(evaluation::UNKNOWN_LABEL).to_string(),
+                ));
+            }
         }
+        let scores = evaluation.scores();
+        let accuracy = evaluation.accuracy();
 
-        let mimetypes: Vec<String> = if mimetypes.is_empty() {
-            vec![]
+        if *json {
+            let json = serde_json::json!({
+                "total_files": evaluation.total,
+                "correct": evaluation.correct,
+                "accuracy": accuracy,
+                "patterns": scores
+                    .iter()
+                    .map(|score| serde_json::json!({
+                        "name": name_of(&score.label),
+                        "score": score,
+                    }))
+                    .collect::<Vec<_>>(),
+                "confusion": evaluation.confusion,
+                "misidentified": misidentified
+                    .iter()
+                    .map(|(file, expected, identified)| serde_json::json!({
+                        "file": file,
+                        "expected": expected,
+                        "identified": identified,
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+            println!("{}", serde_json::to_string(&json)?);
         } else {
-            mimetypes
-                .split(',')
-                .collect::<Vec<&str>>()
-                .iter()
-                .map(|s| s.to_string())
+            let display_name = |label: &str| {
+                name_of(label).unwrap_or_else(|| match label {
+                    evaluation::UNKNOWN_LABEL => "Unknown".to_string(),
+                    _ => label.to_string(),
+                })
+            };
+
+            let mut table = Table::new();
+            table.add_row(Row::new(vec![
+                Cell::new("Name").style_spec("b"),
+                Cell::new("UUID").style_spec("b"),
+                Cell::new("Correct").style_spec("b"),
+                Cell::new("False Positives").style_spec("b"),
+                Cell::new("False Negatives").style_spec("b"),
+                Cell::new("Precision").style_spec("b"),
+                Cell::new("Recall").style_spec("b"),
+            ]));
+            for score in &scores {
+                table.add_row(Row::new(vec![
+                    Cell::new(&display_name(&score.label)),
+                    Cell::new(&score.label),
+                    Cell::new(&score.true_positives.to_string()),
+                    Cell::new(&score.false_positives.to_string()),
+                    Cell::new(&score.false_negatives.to_string()),
+                    Cell::new(&format!("{:.2}", score.precision)),
+                    Cell::new(&format!("{:.2}", score.recall)),
+                ]));
+            }
+            print_table(&mut table, options);
+
+            if !misidentified.is_empty() {
+                println!();
+                println!("The following files were misidentified:");
+                for (file, expected, identified) in &misidentified {
+                    println!(
+                        "  {file} (expected {}, identified as {})",
+                        display_name(expected),
+                        display_name(identified)
+                    );
+                }
+            }
+
+            println!();
+            println!(
+                "{} of {} files were identified correctly ({accuracy:.2}%).",
+                evaluation.correct, evaluation.total
+            );
+        }
+
+        if accuracy < *min_accuracy {
+            return Err(format!(
+                "The accuracy ({accuracy:.2}%) is below the minimum of {min_accuracy:.2}%."
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+fn process_fix_extensions_command(
+    cmd: &Commands,
+    options: &CliOptions,
+) -> Result<(), Box<dyn Error>> {
+    if let Commands::FixExtensions {
+        pattern_source_dir: source_directory,
+        target_pattern,
+        cache_file,
+        min_confidence,
+        recursive,
+        dry_run,
+        directory,
+    } = cmd
+    {
+        if !utils::directory_exists(directory) {
+            return Err(format!("The specified directory '{directory}' doesn't exist.").into());
+        }
+
+        let pattern_handler = built_pattern_handler(
+            &LoadOptions {
+                source_directory,
+                target_pattern,
+                cache_file: cache_file.as_deref(),
+                ..Default::default()
+            },
+            options,
+        )?;
+        let identifier =
+            new_identifier(pattern_handler, options).with_min_confidence(*min_confidence);
+
+        let files = utils::list_files_to_depth(directory, if *recursive { usize::MAX } else { 1 });
+        let outcomes: Vec<_> = utils::in_thread_pool(|| {
+            files
+                .par_iter()
+                .map(|file| identifier.identify_path(file).map(MatchOutcome::from))
                 .collect()
+        });
+
+        // The new names are claimed as they are chosen, so that a dry run reports the same names as a real run.
+        let mut claimed = HashSet::new();
+        let mut renamed = 0;
+        let mut failed = 0;
+        for (file, outcome) in files.iter().zip(outcomes) {
+            let outcome = match outcome {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    eprintln!("Failed to read '{file}': {e}");
+                    continue;
+                }
+            };
+            let Some(top) = outcome.top() else {
+                continue;
+            };
+            if outcome.is_ambiguous() {
+                eprintln!("Skipping '{file}', as it could be one of several types.");
+                continue;
+            }
+
+            let Some(known_extensions) = mismatched_extensions(&identifier, file, top) else {
+                continue;
+            };
+
+            let new_path = unclaimed_path(file, &known_extensions[0].to_lowercase(), &claimed);
+            if *dry_run {
+                println!("Would rename '{file}' to '{}'.", new_path.display());
+            } else if let Err(e) = fs::rename(file, &new_path) {
+                eprintln!("Failed to rename '{file}' to '{}': {e}", new_path.display());
+                failed += 1;
+                continue;
+            } else {
+                println!("Renamed '{file}' to '{}'.", new_path.display());
+            }
+            claimed.insert(new_path);
+            renamed += 1;
+        }
+
+        if *dry_run {
+            println!("{renamed} of {} files would be renamed.", files.len());
+        } else {
+            println!("{renamed} of {} files have been renamed.", files.len());
+            if failed > 0 {
+                println!("{failed} files could not be renamed.");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the known extensions of the type detected for a file, if the file has none of them.
+fn mismatched_extensions<'a>(
+    identifier: &'a Identifier,
+    file: &str,
+    result: &MatchResult,
+) -> Option<&'a [Arc<str>]> {
+    let pattern = identifier.pattern_handler().pattern_by_uuid(&result.uuid)?;
+
+    let known_extensions = &pattern.type_data.known_extensions;
+    if known_extensions.is_empty()
+        || known_extensions
+            .iter()
+            .any(|e| utils::has_file_extension(file, e))
+    {
+        None
+    } else {
+        Some(known_extensions)
+    }
+}
+
+/// Get the path to which a file should be renamed in order to give it a new extension, avoiding any existing files.
+fn unclaimed_path(file: &str, extension: &str, claimed: &HashSet<PathBuf>) -> PathBuf {
+    let path = Path::new(file);
+    let stem = path
+        .file_stem()
+        .map_or(String::new(), |s| s.to_string_lossy().to_string());
+
+    let mut new_path = path.with_file_name(format!("{stem}.{extension}"));
+    let mut i = 1;
+    while new_path.exists() || claimed.contains(&new_path) {
+        new_path = path.with_file_name(format!("{stem}_{i}.{extension}"));
+        i += 1;
+    }
+
+    new_path
+}
+
+fn process_watch_command(cmd: &Commands, options: &CliOptions) -> Result<(), Box<dyn Error>> {
+    if let Commands::Watch {
+        pattern_source_dir: source_directory,
+        target_pattern,
+        cache_file,
+        min_confidence,
+        recursive,
+        format,
+        log: log_path,
+        reload_patterns,
+        directory,
+    } = cmd
+    {
+        if !utils::directory_exists(directory) {
+            return Err(format!("The specified directory '{directory}' doesn't exist.").into());
+        }
+
+        let template = format.as_deref().map(ResultTemplate::parse).transpose()?;
+        let mut log = match log_path {
+            Some(path) => Some(
+                fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?,
+            ),
+            None => None,
         };
 
-        let mut pattern = Pattern::new(name, description, extensions, mimetypes);
-        pattern.add_submitter_data(user_name, email);
+        // Writing to the log, or changing the patterns, mustn't cause those files to be identified in turn.
+        let mut ignored_paths = Vec::new();
+        if let Some(path) = log_path {
+            ignored_paths.push(fs::canonicalize(path)?);
+        }
 
-        //let now = std::time::Instant::now();
+        let pattern_handler = built_pattern_handler(
+            &LoadOptions {
+                source_directory,
+                target_pattern,
+                cache_file: cache_file.as_deref(),
+                ..Default::default()
+            },
+            options,
+        )?;
+        let mut identifier = new_identifier(pattern_handler, options)
+            .with_min_confidence(*min_confidence)
+            .with_text_fallback(true);
 
-        pattern.build_patterns_from_data(
-            path,
-            extension,
-            !*no_strings,
-            !*no_sequences,
-            !*no_composition,
-        );
+        // The pattern source is watched separately, so that its events aren't mistaken for files to be identified.
+        let (reload_sender, reload_receiver) = mpsc::channel();
+        let mut pattern_watcher = notify::recommended_watcher(reload_sender)?;
+        if *reload_patterns {
+            let pattern_source = resolve_pattern_source(source_directory)?;
+            if pattern_source.exists() {
+                pattern_watcher.watch(&pattern_source, RecursiveMode::Recursive)?;
+                ignored_paths.push(fs::canonicalize(&pattern_source)?);
+            } else {
+                eprintln!("Only the built-in patterns are in use, so there are no pattern files to watch.");
+            }
+        }
 
-        //println!("{}", now.elapsed().as_secs_f64());
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let mode = if *recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(Path::new(directory), mode)?;
+        eprintln!("Watching '{directory}' for new or modified files. Press Ctrl+C to stop.");
 
-        if let Some(d) = output_directory {
-            if !utils::directory_exists(d) {
-                return;
+        // A file is usually modified many times while it's being written, so it is only identified once
+        // no further changes have been made to it for a short while.
+        let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+        let mut patterns_changed: Option<Instant> = None;
+        loop {
+            for event in reload_receiver.try_iter() {
+                match event {
+                    Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                        patterns_changed = Some(Instant::now());
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Failed to watch the pattern files: {e}"),
+                }
+            }
+
+            // As with the watched files, the patterns are only reloaded once they have stopped changing.
+            if patterns_changed.is_some_and(|changed| changed.elapsed() >= WATCH_SETTLE_TIME) {
+                patterns_changed = None;
+                match identifier.reload_patterns() {
+                    Ok(report) => {
+                        eprintln!("The patterns have been reloaded.");
+                        if options.verbose {
+                            eprint!("{report}");
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to reload the patterns, so the previous patterns are still in use: {e}")
+                    }
+                }
+            }
+
+            match receiver.recv_timeout(WATCH_SETTLE_TIME) {
+                Ok(Ok(event)) => {
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                        for path in event.paths {
+                            if !is_ignored_path(&path, &ignored_paths) {
+                                pending.insert(path, Instant::now());
+                            }
+                        }
+                    }
+                }
+                Ok(Err(e)) => eprintln!("Failed to watch '{directory}': {e}"),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            let settled: Vec<PathBuf> = pending
+                .iter()
+                .filter(|(_, changed)| changed.elapsed() >= WATCH_SETTLE_TIME)
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in settled {
+                pending.remove(&path);
+                if !path.is_file() {
+                    continue;
+                }
+
+                let file = path.to_string_lossy();
+                let line = match identifier.identify_path(&file) {
+                    Ok(results) => {
+                        let top = results.first();
+                        match &template {
+                            Some(template) => template.render(&file, top, UNKNOWN_MIMETYPE),
+                            None => match top {
+                                // The plain text fallback doesn't award any points.
+                                Some(r) if r.max_points == 0 => format!("{file}: {}", r.name),
+                                Some(r) => format!("{file}: {} ({}%)", r.name, r.percentage),
+                                None => format!("{file}: Unknown"),
+                            },
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to read '{file}': {e}");
+                        continue;
+                    }
+                };
+
+                println!("{line}");
+                if let Some(log) = &mut log {
+                    writeln!(log, "{line}")?;
+                }
             }
+        }
+    }
+
+    Ok(())
+}
+
+/// Is a path one of a set of canonical paths, or within one of them?
+fn is_ignored_path(path: &Path, ignored_paths: &[PathBuf]) -> bool {
+    // A file that has since been removed can't be canonicalized, though its directory may still be.
+    let path = fs::canonicalize(path)
+        .or_else(|e| match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => fs::canonicalize(parent).map(|p| p.join(name)),
+            _ => Err(e),
+        })
+        .unwrap_or_else(|_| path.to_path_buf());
+    ignored_paths.iter().any(|p| path.starts_with(p))
+}
 
-            if let Err(e) = pattern.write(d) {
-                eprintln!("Failed to write pattern file: {e:?}");
+fn process_lint_command(cmd: &Commands) -> Result<(), Box<dyn Error>> {
+    if let Commands::Lint {
+        output,
+        deny_warnings,
+        paths,
+    } = cmd
+    {
+        let mut files = Vec::new();
+        for path in paths {
+            if utils::directory_exists(path) {
+                files.extend(PatternHandler::list_pattern_files(path));
+            } else if utils::file_exists(path) {
+                files.push(path.clone());
             } else {
+                return Err(format!("The specified path '{path}' doesn't exist.").into());
+            }
+        }
+
+        // A pattern that can't be loaded is reported as an issue, so that the other files are still checked.
+        let results: Vec<(String, Vec<LintIssue>)> = files
+            .into_iter()
+            .map(|file| {
+                let issues = match Pattern::from_file(&file) {
+                    Ok(pattern) => pattern_lint::lint(&pattern),
+                    Err(e) => vec![LintIssue {
+                        severity: LintSeverity::Error,
+                        code: "invalid-pattern",
+                        message: e.to_string(),
+                    }],
+                };
+                (file, issues)
+            })
+            .collect();
+
+        let count = |severity: LintSeverity| {
+            results
+                .iter()
+                .flat_map(|(_, issues)| issues)
+                .filter(|i| i.severity == severity)
+                .count()
+        };
+        let (errors, warnings) = (count(LintSeverity::Error), count(LintSeverity::Warning));
+
+        match output {
+            LintOutputFormat::Text => {
+                for (file, issues) in &results {
+                    for issue in issues {
+                        println!("{file}: {issue}");
+                    }
+                }
                 println!(
-                    "The pattern file has been successfully written to the specified directory!"
+                    "{errors} errors and {warnings} warnings were found in {} pattern files.",
+                    results.len()
                 );
             }
-        } else {
-            let json = serde_json::to_string(&pattern).unwrap();
-            println!("{json}");
+            LintOutputFormat::Json => {
+                let json: Vec<_> = results
+                    .iter()
+                    .map(|(file, issues)| serde_json::json!({ "file": file, "issues": issues }))
+                    .collect();
+                println!("{}", serde_json::to_string(&json)?);
+            }
+        }
+
+        if errors > 0 || (*deny_warnings && warnings > 0) {
+            return Err(
+                format!("Linting failed with {errors} errors and {warnings} warnings.").into(),
+            );
         }
     }
+
+    Ok(())
+}
+
+fn process_refine_command(cmd: &Commands) -> Result<(), Box<dyn Error>> {
+    if let Commands::Refine {
+        user_name,
+        email,
+        output_file,
+        pattern_file,
+        extension,
+        path,
+    } = cmd
+    {
+        if !utils::file_exists(pattern_file) {
+            return Err(
+                format!("The specified pattern file '{pattern_file}' doesn't exist.").into(),
+            );
+        }
+
+        if !utils::directory_exists(path) {
+            return Err(format!("The specified target folder '{path}' doesn't exist.").into());
+        }
+
+        if utils::split_extensions(extension).is_empty() {
+            return Err("The target extension may not be empty.".into());
+        }
+
+        let mut pattern = Pattern::from_file(pattern_file)?;
+        pattern.refine_from_data(path, extension, user_name, email)?;
+
+        let output_file = output_file.as_deref().unwrap_or(pattern_file);
+        if let Err(e) = pattern.write_file(output_file) {
+            return Err(format!("Failed to write pattern file: {e}").into());
+        }
+
+        println!(
+            "The pattern has been refined, and now covers {} sample files.",
+            pattern.other_data.total_scanned_files
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_main {
+    use std::{env, fs};
+
+    use super::is_ignored_path;
+
+    #[test]
+    fn test_is_ignored_path() {
+        let test_dir = env::temp_dir().join(format!("itf-test-ignored-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        let pattern_dir = test_dir.join("patterns");
+        fs::create_dir_all(&pattern_dir).expect("failed to create test directory");
+        for file in ["watch.log", "sample.bin", "patterns/png.json"] {
+            fs::write(test_dir.join(file), b"TEST").unwrap();
+        }
+
+        let ignored_paths = [
+            fs::canonicalize(test_dir.join("watch.log")).unwrap(),
+            fs::canonicalize(&pattern_dir).unwrap(),
+        ];
+        let is_ignored = |path: &str| is_ignored_path(&test_dir.join(path), &ignored_paths);
+
+        // The events of the log and pattern files may not give their canonical paths.
+        let log_ignored = is_ignored("patterns/../watch.log");
+        let pattern_ignored = is_ignored("./patterns/png.json");
+        let sample_ignored = is_ignored("sample.bin");
+        let removed_pattern_ignored = is_ignored("patterns/../patterns/removed.json");
+        let removed_sample_ignored = is_ignored("patterns/../removed.bin");
+        _ = fs::remove_dir_all(&test_dir);
+
+        assert!(log_ignored);
+        assert!(pattern_ignored);
+        assert!(!sample_ignored);
+        assert!(removed_pattern_ignored);
+        assert!(!removed_sample_ignored);
+    }
 }
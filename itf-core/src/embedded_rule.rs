@@ -0,0 +1,608 @@
+use crate::{interner, pattern::Pattern};
+
+/// A minimal description of a file format, compiled into a program so that files can be identified
+/// without reading or parsing any pattern files.
+///
+/// # Notes
+///
+/// The built-in rules for the most common file formats are given by [`EMBEDDED_RULES`]. Further rules can
+/// be generated from pattern files with [`compile_rules`].
+pub struct EmbeddedRule {
+    /// The name of the file format.
+    pub name: &'static str,
+    /// A description of the file format.
+    pub description: &'static str,
+    /// The fixed UUID of the rule, which is kept stable so that results can be compared between runs.
+    pub uuid: &'static str,
+    /// The known extensions of the file format.
+    pub extensions: &'static [&'static str],
+    /// The known MIME types of the file format, with the preferred type first.
+    pub mimetypes: &'static [&'static str],
+    /// The categories to which the file format belongs.
+    pub categories: &'static [&'static str],
+    /// The byte sequences that the file format requires, given as the position followed by the bytes.
+    pub sequences: &'static [(usize, &'static [u8])],
+    /// The byte sequences that the file format requires, given as the distance from the start of the sequence
+    /// to the end of the file followed by the bytes.
+    pub trailer_sequences: &'static [(usize, &'static [u8])],
+    /// The byte sequences containing masked bytes, given as the position followed by the bytes.
+    pub masked_sequences: &'static [(usize, &'static [Option<u8>])],
+    /// The strings that may be associated with the file format.
+    pub strings: &'static [&'static str],
+    /// The priority of the rule, used to order results with the same match percentage.
+    pub priority: i32,
+}
+
+impl EmbeddedRule {
+    /// An empty rule, used to fill in the fields that a rule doesn't need.
+    pub const EMPTY: EmbeddedRule = EmbeddedRule {
+        name: "",
+        description: "",
+        uuid: "",
+        extensions: &[],
+        mimetypes: &[],
+        categories: &[],
+        sequences: &[],
+        trailer_sequences: &[],
+        masked_sequences: &[],
+        strings: &[],
+        priority: 0,
+    };
+
+    /// Convert the rule into a [`Pattern`].
+    ///
+    /// # Returns
+    ///
+    /// A pattern containing the byte sequences of the rule, with its attributes computed so that it's ready for matching.
+    pub fn to_pattern(&self) -> Pattern {
+        let mut pattern = Pattern::new(
+            self.name,
+            self.description,
+            self.extensions.iter().map(|e| e.to_string()).collect(),
+            self.mimetypes.iter().map(|m| m.to_string()).collect(),
+        );
+        pattern.type_data.uuid = self.uuid.to_string();
+        pattern.type_data.categories = interner::intern_all(self.categories);
+        pattern.type_data.priority = self.priority;
+        pattern.data.sequences = interner::intern_sequences(self.sequences.iter().copied());
+        pattern.data.trailer_sequences = self
+            .trailer_sequences
+            .iter()
+            .map(|(distance, bytes)| (*distance, bytes.to_vec()))
+            .collect();
+        pattern.data.masked_sequences = self
+            .masked_sequences
+            .iter()
+            .map(|(position, bytes)| (*position, bytes.to_vec()))
+            .collect();
+        pattern.data.strings = self.strings.iter().map(|s| s.to_string()).collect();
+        pattern.compute_attributes();
+
+        pattern
+    }
+}
+
+/// Convert every embedded rule into a [`Pattern`].
+pub fn embedded_patterns() -> Vec<Pattern> {
+    EMBEDDED_RULES
+        .iter()
+        .map(EmbeddedRule::to_pattern)
+        .collect()
+}
+
+/// Rust source code defining a set of [`EmbeddedRule`]s, generated by [`compile_rules`].
+pub struct CompiledRules {
+    /// The generated source code.
+    pub source: String,
+    /// Descriptions of the parts of the patterns that could not be represented as embedded rules.
+    pub warnings: Vec<String>,
+}
+
+/// Generate Rust source code defining an [`EmbeddedRule`] for each of a set of patterns.
+///
+/// # Arguments
+///
+/// * `patterns` - The patterns.
+///
+/// # Returns
+///
+/// The generated source code, which defines a `RULES` static that can be passed to [`PatternHandler::read_rules`].
+///
+/// # Notes
+///
+/// Only the type data, the positional, trailer and masked byte sequences and the strings are embedded. The
+/// statistical attributes of a pattern are always omitted, and a warning is given for any other criteria that
+/// are omitted, as a rule lacking those may match more widely than its pattern.
+///
+/// [`PatternHandler::read_rules`]: crate::pattern_handler::PatternHandler::read_rules
+pub fn compile_rules(patterns: &[Pattern]) -> CompiledRules {
+    let mut source = String::from(
+        "// This file was generated from a set of pattern files. Any changes will be lost if it's regenerated.\n\n",
+    );
+    source.push_str("pub static RULES: &[itf_core::embedded_rule::EmbeddedRule] = &[\n");
+
+    let mut warnings = Vec::new();
+    for pattern in patterns {
+        let type_data = &pattern.type_data;
+        let data = &pattern.data;
+
+        let omitted: Vec<_> = [
+            ("ranged sequences", data.ranged_sequences.is_empty()),
+            (
+                "alternative sequences",
+                data.alternative_sequences.is_empty(),
+            ),
+            ("regexes", data.regexes.is_empty()),
+            ("anchored strings", data.anchored_strings.is_empty()),
+        ]
+        .iter()
+        .filter(|(_, is_empty)| !is_empty)
+        .map(|(criteria, _)| *criteria)
+        .collect();
+        if !omitted.is_empty() {
+            warnings.push(format!(
+                "the {} of '{}' can't be embedded, and have been omitted",
+                omitted.join(", "),
+                type_data.name
+            ));
+        }
+
+        let mut strings: Vec<_> = data.strings.iter().collect();
+        strings.sort_unstable();
+
+        source.push_str("    itf_core::embedded_rule::EmbeddedRule {\n");
+        source.push_str(&format!("        name: {:?},\n", type_data.name));
+        source.push_str(&format!(
+            "        description: {:?},\n",
+            type_data.description
+        ));
+        source.push_str(&format!("        uuid: {:?},\n", type_data.uuid));
+        source.push_str(&format!(
+            "        extensions: &{:?},\n",
+            type_data.known_extensions
+        ));
+        source.push_str(&format!(
+            "        mimetypes: &{:?},\n",
+            type_data.known_mimetypes
+        ));
+        source.push_str(&format!(
+            "        categories: &{:?},\n",
+            type_data.categories
+        ));
+        source.push_str(&format!(
+            "        sequences: &[{}],\n",
+            sequence_list(&data.sequences)
+        ));
+        source.push_str(&format!(
+            "        trailer_sequences: &[{}],\n",
+            sequence_list(&data.trailer_sequences)
+        ));
+        source.push_str(&format!(
+            "        masked_sequences: &[{}],\n",
+            data.masked_sequences
+                .iter()
+                .map(|(position, bytes)| format!("({position}, &{bytes:?})"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        source.push_str(&format!("        strings: &{strings:?},\n"));
+        source.push_str(&format!("        priority: {},\n", type_data.priority));
+        source.push_str("    },\n");
+    }
+    source.push_str("];\n");
+
+    CompiledRules { source, warnings }
+}
+
+/// Format a list of byte sequences as the elements of a Rust slice.
+///
+/// # Arguments
+///
+/// * `sequences` - The sequences, given as the position followed by the bytes.
+fn sequence_list<B: AsRef<[u8]>>(sequences: &[(usize, B)]) -> String {
+    sequences
+        .iter()
+        .map(|(position, bytes)| format!("({position}, {})", byte_string_literal(bytes.as_ref())))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Format bytes as a Rust byte string literal.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes.
+fn byte_string_literal(bytes: &[u8]) -> String {
+    let mut literal = String::from("b\"");
+    for b in bytes {
+        match b {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            0x20..=0x7E => literal.push(*b as char),
+            _ => literal.push_str(&format!("\\x{b:02X}")),
+        }
+    }
+    literal.push('"');
+
+    literal
+}
+
+/// The built-in rules for the most common file formats.
+pub static EMBEDDED_RULES: &[EmbeddedRule] = &[
+    EmbeddedRule {
+        name: "PNG",
+        description: "Portable Network Graphics image",
+        uuid: "e425026e-71af-592d-8eee-0e53fe6ad340",
+        extensions: &["PNG"],
+        mimetypes: &["image/png"],
+        categories: &["image"],
+        sequences: &[(0, b"\x89PNG\r\n\x1A\n")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "JPEG",
+        description: "JPEG image",
+        uuid: "f8f9969f-d7c0-572e-ac08-d8915674c209",
+        extensions: &["JPG", "JPEG", "JFIF"],
+        mimetypes: &["image/jpeg"],
+        categories: &["image"],
+        sequences: &[(0, b"\xFF\xD8\xFF")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "GIF",
+        description: "Graphics Interchange Format image",
+        uuid: "e3e9bacf-abb7-53be-8733-b9855c38bc34",
+        extensions: &["GIF"],
+        mimetypes: &["image/gif"],
+        categories: &["image"],
+        sequences: &[(0, b"GIF8")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "BMP",
+        description: "Windows bitmap image",
+        uuid: "b441f05a-f965-59dc-b55e-cdb6700d65a6",
+        extensions: &["BMP", "DIB"],
+        mimetypes: &["image/bmp"],
+        categories: &["image"],
+        sequences: &[(0, b"BM")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "TIFF (little endian)",
+        description: "Tagged Image File Format image, in little endian byte order",
+        uuid: "e24200d8-ceea-5ed0-800a-a39f04aa4731",
+        extensions: &["TIF", "TIFF"],
+        mimetypes: &["image/tiff"],
+        categories: &["image"],
+        sequences: &[(0, b"II*\x00")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "TIFF (big endian)",
+        description: "Tagged Image File Format image, in big endian byte order",
+        uuid: "b8180c2e-64f9-5320-a014-f9288cbbaafe",
+        extensions: &["TIF", "TIFF"],
+        mimetypes: &["image/tiff"],
+        categories: &["image"],
+        sequences: &[(0, b"MM\x00*")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "WebP",
+        description: "WebP image",
+        uuid: "33d20e7c-1987-5304-851c-54c7b8b0cd42",
+        extensions: &["WEBP"],
+        mimetypes: &["image/webp"],
+        categories: &["image"],
+        sequences: &[(0, b"RIFF"), (8, b"WEBP")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "ICO",
+        description: "Windows icon",
+        uuid: "1dccc1bf-8876-5582-bea3-a6a8b825d433",
+        extensions: &["ICO"],
+        mimetypes: &["image/vnd.microsoft.icon"],
+        categories: &["image"],
+        sequences: &[(0, b"\x00\x00\x01\x00")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "PSD",
+        description: "Adobe Photoshop document",
+        uuid: "af60c69d-cf83-5bb5-bc67-1f0e73f24ff3",
+        extensions: &["PSD"],
+        mimetypes: &["image/vnd.adobe.photoshop"],
+        categories: &["image"],
+        sequences: &[(0, b"8BPS")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "PDF",
+        description: "Portable Document Format document",
+        uuid: "3c2db473-8747-5ecf-a2ae-8ab3efc3f526",
+        extensions: &["PDF"],
+        mimetypes: &["application/pdf"],
+        categories: &["document"],
+        sequences: &[(0, b"%PDF-")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "ZIP",
+        description: "ZIP archive",
+        uuid: "9d28b716-2ae4-5d66-9d9b-82f1d51d88e2",
+        extensions: &["ZIP"],
+        mimetypes: &["application/zip"],
+        categories: &["archive"],
+        sequences: &[(0, b"PK\x03\x04")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "gzip",
+        description: "gzip compressed data",
+        uuid: "16d5e516-efe8-5eb6-9517-0b0ddf7a7d19",
+        extensions: &["GZ"],
+        mimetypes: &["application/gzip"],
+        categories: &["archive"],
+        sequences: &[(0, b"\x1F\x8B")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "bzip2",
+        description: "bzip2 compressed data",
+        uuid: "a7549495-4220-5a37-bff0-d03b9aec154d",
+        extensions: &["BZ2"],
+        mimetypes: &["application/x-bzip2"],
+        categories: &["archive"],
+        sequences: &[(0, b"BZh")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "XZ",
+        description: "XZ compressed data",
+        uuid: "0e2d9eac-98d7-58d2-9cea-51de255d575c",
+        extensions: &["XZ"],
+        mimetypes: &["application/x-xz"],
+        categories: &["archive"],
+        sequences: &[(0, b"\xFD7zXZ\x00")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "Zstandard",
+        description: "Zstandard compressed data",
+        uuid: "d24501c3-e0e5-5a59-ba1b-8d65045ceace",
+        extensions: &["ZST"],
+        mimetypes: &["application/zstd"],
+        categories: &["archive"],
+        sequences: &[(0, b"\x28\xB5\x2F\xFD")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "7-Zip",
+        description: "7-Zip archive",
+        uuid: "5897cc0e-c90e-596c-b1a2-562d66673fa9",
+        extensions: &["7Z"],
+        mimetypes: &["application/x-7z-compressed"],
+        categories: &["archive"],
+        sequences: &[(0, b"7z\xBC\xAF\x27\x1C")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "RAR",
+        description: "RAR archive",
+        uuid: "812fa856-0606-5aff-b98c-28879d4dcc6a",
+        extensions: &["RAR"],
+        mimetypes: &["application/vnd.rar"],
+        categories: &["archive"],
+        sequences: &[(0, b"Rar!\x1A\x07")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "ELF",
+        description: "Executable and Linkable Format binary",
+        uuid: "8b539e90-1d2a-5dc5-a89c-a47617f4f33a",
+        extensions: &["ELF", "SO", "O"],
+        mimetypes: &["application/x-elf"],
+        categories: &["executable"],
+        sequences: &[(0, b"\x7FELF")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "Windows executable",
+        description: "DOS or Windows executable",
+        uuid: "d99979ad-9262-5ef4-8f39-2acb68a3806e",
+        extensions: &["EXE", "DLL", "SYS"],
+        mimetypes: &["application/vnd.microsoft.portable-executable"],
+        categories: &["executable"],
+        sequences: &[(0, b"MZ")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "Mach-O (64-bit)",
+        description: "64-bit Mach-O binary",
+        uuid: "74092107-2a1b-53b6-b40c-f87f1c300cc7",
+        extensions: &["DYLIB", "BUNDLE"],
+        mimetypes: &["application/x-mach-binary"],
+        categories: &["executable"],
+        sequences: &[(0, b"\xCF\xFA\xED\xFE")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "Java class",
+        description: "Compiled Java class",
+        uuid: "e4f88ef5-9e46-5251-92d3-7c81616711d6",
+        extensions: &["CLASS"],
+        mimetypes: &["application/java-vm"],
+        categories: &["executable"],
+        sequences: &[(0, b"\xCA\xFE\xBA\xBE")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "WebAssembly",
+        description: "WebAssembly binary module",
+        uuid: "7d5212d7-9396-5b9e-9019-be6c8cf7c5e0",
+        extensions: &["WASM"],
+        mimetypes: &["application/wasm"],
+        categories: &["executable"],
+        sequences: &[(0, b"\x00asm")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "SQLite",
+        description: "SQLite 3 database",
+        uuid: "54462d05-b93e-5fd8-9081-42b329f301c4",
+        extensions: &["SQLITE", "SQLITE3", "DB"],
+        mimetypes: &["application/vnd.sqlite3"],
+        categories: &["database"],
+        sequences: &[(0, b"SQLite format 3\x00")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "WAV",
+        description: "Waveform audio",
+        uuid: "47388af6-ff4d-5def-addb-38eab9d71876",
+        extensions: &["WAV"],
+        mimetypes: &["audio/wav"],
+        categories: &["audio"],
+        sequences: &[(0, b"RIFF"), (8, b"WAVE")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "AVI",
+        description: "Audio Video Interleave video",
+        uuid: "ecb165f6-15f1-50e2-b405-0ab5a32fc914",
+        extensions: &["AVI"],
+        mimetypes: &["video/x-msvideo"],
+        categories: &["video"],
+        sequences: &[(0, b"RIFF"), (8, b"AVI ")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "FLAC",
+        description: "Free Lossless Audio Codec audio",
+        uuid: "8d59d4d2-4ac9-5441-ad5b-10e3b5e57669",
+        extensions: &["FLAC"],
+        mimetypes: &["audio/flac"],
+        categories: &["audio"],
+        sequences: &[(0, b"fLaC")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "Ogg",
+        description: "Ogg container",
+        uuid: "2fcb0716-e67d-54f6-b467-e412e0248cdc",
+        extensions: &["OGG", "OGA", "OGV", "OPUS"],
+        mimetypes: &["audio/ogg", "video/ogg"],
+        categories: &["audio"],
+        sequences: &[(0, b"OggS")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "MP3 (ID3)",
+        description: "MPEG audio with an ID3v2 tag",
+        uuid: "a6f83291-08ee-5bfa-8c7f-547faafaa278",
+        extensions: &["MP3"],
+        mimetypes: &["audio/mpeg"],
+        categories: &["audio"],
+        sequences: &[(0, b"ID3")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "Matroska",
+        description: "Matroska or WebM container",
+        uuid: "84f99e7f-be5e-5027-81a6-4f8a2becb435",
+        extensions: &["MKV", "MKA", "WEBM"],
+        mimetypes: &["video/x-matroska", "video/webm"],
+        categories: &["video"],
+        sequences: &[(0, b"\x1A\x45\xDF\xA3")],
+        ..EmbeddedRule::EMPTY
+    },
+    EmbeddedRule {
+        name: "MPEG-4",
+        description: "ISO base media file, such as MPEG-4 video or audio",
+        uuid: "2f3113ae-496b-5e40-8f3f-748cd08ed86c",
+        extensions: &["MP4", "M4A", "M4V", "MOV"],
+        mimetypes: &["video/mp4", "audio/mp4"],
+        categories: &["video"],
+        sequences: &[(4, b"ftyp")],
+        ..EmbeddedRule::EMPTY
+    },
+];
+
+#[cfg(test)]
+mod tests_embedded_rule {
+    use hashbrown::HashSet;
+
+    use crate::{
+        identifier::Identifier, interner::intern_sequences, pattern_handler::PatternHandler,
+    };
+
+    use crate::pattern::Pattern;
+
+    use super::{byte_string_literal, compile_rules, embedded_patterns, EMBEDDED_RULES};
+
+    #[test]
+    fn test_embedded_rules_valid() {
+        let mut uuids = HashSet::new();
+        let mut names = HashSet::new();
+        for rule in EMBEDDED_RULES {
+            assert!(
+                uuids.insert(rule.uuid),
+                "duplicate uuid for '{}'",
+                rule.name
+            );
+            assert!(names.insert(rule.name), "duplicate name '{}'", rule.name);
+            assert!(!rule.sequences.is_empty());
+        }
+
+        assert!(embedded_patterns().iter().all(|p| p.max_points > 0));
+    }
+
+    #[test]
+    fn test_embedded_rules_identify() {
+        let mut handler = PatternHandler::default();
+        handler.read_builtin("").unwrap();
+        let identifier = Identifier::new(handler);
+
+        let mut png = b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR".to_vec();
+        png.resize(512, 0);
+        let results = identifier.identify_bytes(&png, Some("image.png"));
+        assert_eq!(results[0].name, "PNG");
+
+        let mut wav = b"RIFF\x24\x00\x00\x00WAVEfmt ".to_vec();
+        wav.resize(512, 0);
+        let results = identifier.identify_bytes(&wav, None);
+        assert_eq!(results[0].name, "WAV");
+    }
+
+    #[test]
+    fn test_compile_rules() {
+        assert_eq!(byte_string_literal(b"A\"\\\x00\xFF"), r#"b"A\"\\\x00\xFF""#);
+
+        let mut pattern = Pattern::new("Test \"quoted\"", "test", vec!["TEST".to_string()], vec![]);
+        pattern.data.sequences = intern_sequences(vec![(0, b"\x7FTEST".to_vec())]);
+        pattern.data.masked_sequences = vec![(8, vec![Some(1), None])];
+        pattern.data.strings.insert("second".to_string());
+        pattern.data.strings.insert("first".to_string());
+        pattern.data.regexes = vec![(0, "T+".to_string())];
+
+        let compiled = compile_rules(&[pattern]);
+        assert!(compiled.source.contains(r#"name: "Test \"quoted\"","#));
+        assert!(compiled
+            .source
+            .contains(r#"sequences: &[(0, b"\x7FTEST")],"#));
+        assert!(compiled
+            .source
+            .contains("masked_sequences: &[(8, &[Some(1), None])],"));
+        assert!(compiled
+            .source
+            .contains(r#"strings: &["first", "second"],"#));
+        assert!(compiled.source.contains(r#"extensions: &["TEST"],"#));
+
+        // The regex can't be embedded.
+        assert_eq!(compiled.warnings.len(), 1);
+        assert!(compiled.warnings[0].contains("regexes"));
+    }
+}
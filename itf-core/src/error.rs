@@ -0,0 +1,107 @@
+use std::{error::Error, fmt, io};
+
+#[derive(Debug)]
+pub enum ItfError {
+    /// An IO error occurred while reading or writing a file.
+    Io(io::Error),
+    /// The data could not be parsed as a pattern.
+    JsonParse(String),
+    /// The pattern was parsed, but the contents are not valid.
+    InvalidPattern(String),
+    /// No applicable patterns could be found.
+    NoPatternsFound,
+    /// Too few sample files were supplied to perform the requested operation.
+    InsufficientSamples { required: usize, found: usize },
+    /// A glob used to select files could not be parsed.
+    InvalidGlob(String),
+    /// The pattern was written in a format newer than this version of the library understands.
+    UnsupportedFormatVersion { found: u32, supported: u32 },
+    /// The data could not be parsed as a pattern bundle.
+    InvalidBundle(String),
+    /// The pattern isn't signed by a trusted key, or its signature is invalid.
+    InvalidSignature(String),
+    /// The patterns could not be fetched from a remote repository.
+    Fetch(String),
+    /// The contents of the pattern don't match its checksum, and so it may have been truncated or damaged.
+    CorruptPattern(String),
+    /// An output template could not be parsed.
+    InvalidTemplate(String),
+    /// An edit to a pattern isn't valid.
+    InvalidEdit(String),
+    /// The labels of a corpus of sample files could not be read.
+    InvalidLabels(String),
+    /// The data could not be read as an archive of pattern files.
+    InvalidArchive(String),
+    /// A configuration file could not be parsed, or contains invalid settings.
+    InvalidConfig(String),
+    /// Two patterns have the same UUID, and so would be ambiguous. The patterns are identified by their sources.
+    DuplicateUuid {
+        uuid: String,
+        first: String,
+        second: String,
+    },
+}
+
+impl fmt::Display for ItfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItfError::Io(e) => write!(f, "IO error: {e}"),
+            ItfError::JsonParse(e) => write!(f, "failed to parse pattern: {e}"),
+            ItfError::InvalidPattern(e) => write!(f, "invalid pattern: {e}"),
+            ItfError::NoPatternsFound => write!(f, "no applicable patterns were found"),
+            ItfError::InsufficientSamples { required, found } => write!(
+                f,
+                "at least {required} sample files are required, but only {found} were found"
+            ),
+            ItfError::InvalidGlob(e) => write!(f, "invalid glob: {e}"),
+            ItfError::UnsupportedFormatVersion { found, supported } => write!(
+                f,
+                "pattern format version {found} is newer than the latest supported version ({supported})"
+            ),
+            ItfError::InvalidBundle(e) => write!(f, "invalid pattern bundle: {e}"),
+            ItfError::InvalidSignature(e) => write!(f, "signature verification failed: {e}"),
+            ItfError::Fetch(e) => write!(f, "failed to fetch patterns: {e}"),
+            ItfError::CorruptPattern(e) => write!(f, "corrupt pattern: {e}"),
+            ItfError::InvalidTemplate(e) => write!(f, "invalid output template: {e}"),
+            ItfError::InvalidEdit(e) => write!(f, "invalid edit: {e}"),
+            ItfError::InvalidLabels(e) => write!(f, "invalid labels: {e}"),
+            ItfError::InvalidArchive(e) => write!(f, "invalid pattern archive: {e}"),
+            ItfError::InvalidConfig(e) => write!(f, "invalid configuration: {e}"),
+            ItfError::DuplicateUuid {
+                uuid,
+                first,
+                second,
+            } => write!(
+                f,
+                "the patterns '{first}' and '{second}' have the same UUID ({uuid})"
+            ),
+        }
+    }
+}
+
+impl Error for ItfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ItfError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ItfError {
+    fn from(e: io::Error) -> Self {
+        ItfError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ItfError {
+    fn from(e: serde_json::Error) -> Self {
+        ItfError::JsonParse(e.to_string())
+    }
+}
+
+impl From<simd_json::Error> for ItfError {
+    fn from(e: simd_json::Error) -> Self {
+        ItfError::JsonParse(e.to_string())
+    }
+}
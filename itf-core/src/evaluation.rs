@@ -0,0 +1,280 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde_derive::Serialize;
+
+use crate::{error::ItfError, utils};
+
+/// The label given to files that shouldn't match any pattern.
+pub const UNKNOWN_LABEL: &str = "unknown";
+
+/// The precision and recall of a single pattern within an [`Evaluation`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LabelScore {
+    /// The label, which is the UUID of the pattern or [`UNKNOWN_LABEL`].
+    pub label: String,
+    /// The number of files with this label that were identified correctly.
+    pub true_positives: usize,
+    /// The number of files with another label that were identified as this label.
+    pub false_positives: usize,
+    /// The number of files with this label that were identified as another label.
+    pub false_negatives: usize,
+    /// The percentage of the files identified as this label that were expected to have it.
+    pub precision: f32,
+    /// The percentage of the files expected to have this label that were identified as it.
+    pub recall: f32,
+}
+
+/// The results of identifying a labeled corpus of sample files, used to measure the accuracy of a set of patterns.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Evaluation {
+    /// The number of files identified as each label, keyed by the expected label and then by the identified label.
+    pub confusion: BTreeMap<String, BTreeMap<String, usize>>,
+    /// The total number of files.
+    pub total: usize,
+    /// The number of files that were identified correctly.
+    pub correct: usize,
+}
+
+impl Evaluation {
+    /// Record the result of identifying a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - The expected label of the file.
+    /// * `identified` - The UUID of the top result for the file, or `None` if it wasn't identified.
+    pub fn record(&mut self, expected: &str, identified: Option<&str>) {
+        let identified = identified.unwrap_or(UNKNOWN_LABEL);
+
+        *self
+            .confusion
+            .entry(expected.to_string())
+            .or_default()
+            .entry(identified.to_string())
+            .or_default() += 1;
+        self.total += 1;
+        self.correct += (expected == identified) as usize;
+    }
+
+    /// Get the percentage of the files that were identified correctly.
+    ///
+    /// # Notes
+    ///
+    /// An empty evaluation is considered to be entirely accurate.
+    pub fn accuracy(&self) -> f32 {
+        if self.total == 0 {
+            return 100.0;
+        }
+
+        self.correct as f32 / self.total as f32 * 100.0
+    }
+
+    /// Compute the precision and recall of each label that was either expected or identified.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the scores, sorted by label. The precision or recall of a label is zero if it was
+    /// never identified or never expected, respectively.
+    pub fn scores(&self) -> Vec<LabelScore> {
+        let mut labels: Vec<&String> = self
+            .confusion
+            .iter()
+            .flat_map(|(expected, identified)| std::iter::once(expected).chain(identified.keys()))
+            .collect();
+        labels.sort_unstable();
+        labels.dedup();
+
+        let percentage = |count: usize, total: usize| {
+            if total == 0 {
+                0.0
+            } else {
+                count as f32 / total as f32 * 100.0
+            }
+        };
+
+        labels
+            .into_iter()
+            .map(|label| {
+                let count = |expected: &str, identified: &str| {
+                    self.confusion
+                        .get(expected)
+                        .and_then(|m| m.get(identified))
+                        .copied()
+                        .unwrap_or(0)
+                };
+                let true_positives = count(label, label);
+                let expected: usize = self.confusion.get(label).map_or(0, |m| m.values().sum());
+                let identified: usize = self.confusion.keys().map(|e| count(e, label)).sum();
+
+                LabelScore {
+                    label: label.clone(),
+                    true_positives,
+                    false_positives: identified - true_positives,
+                    false_negatives: expected - true_positives,
+                    precision: percentage(true_positives, identified),
+                    recall: percentage(true_positives, expected),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Read the labeled sample files from a directory, in which each subdirectory is named after the
+/// UUID of the pattern that the files within it are expected to match.
+///
+/// # Arguments
+///
+/// * `directory` - The directory. Files within a subdirectory named [`UNKNOWN_LABEL`] shouldn't match any pattern.
+///
+/// # Returns
+///
+/// A vector of the sample files, each given as the path of the file followed by its label.
+///
+/// # Notes
+///
+/// The files within the nested subdirectories of a label directory are also included, while any
+/// files directly within the directory are ignored.
+pub fn labels_from_directory<P: AsRef<Path>>(
+    directory: P,
+) -> Result<Vec<(String, String)>, ItfError> {
+    let mut labeled = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let label = path.file_name().unwrap_or_default().to_string_lossy();
+        labeled.extend(
+            utils::list_files_to_depth(&path, usize::MAX)
+                .into_iter()
+                .map(|file| (file, label.to_string())),
+        );
+    }
+    labeled.sort_unstable();
+
+    Ok(labeled)
+}
+
+/// Read the labeled sample files from a CSV file, in which each line gives the path of a file
+/// followed by its label, such as `samples/a.png,0f2a7c1e-...`.
+///
+/// # Arguments
+///
+/// * `csv_file` - The CSV file. Relative paths are resolved against the directory containing this file.
+///
+/// # Returns
+///
+/// A vector of the sample files, each given as the path of the file followed by its label, or an
+/// error if the file couldn't be read or a line doesn't contain a label.
+///
+/// # Notes
+///
+/// Empty lines, lines starting with `#` and a header line starting with `file` are ignored.
+/// Fields may be enclosed within double quotes.
+pub fn labels_from_csv<P: AsRef<Path>>(csv_file: P) -> Result<Vec<(String, String)>, ItfError> {
+    let csv_file = csv_file.as_ref();
+    let base = csv_file.parent().unwrap_or(Path::new(""));
+    let unquote = |field: &str| field.trim().trim_matches('"').to_string();
+
+    let mut labeled = Vec::new();
+    for (i, line) in fs::read_to_string(csv_file)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // The label can't contain a comma, unlike the path of the file.
+        let Some((file, label)) = line.rsplit_once(',') else {
+            return Err(ItfError::InvalidLabels(format!(
+                "line {} of '{}' doesn't give a label",
+                i + 1,
+                csv_file.display()
+            )));
+        };
+        let (file, label) = (unquote(file), unquote(label));
+        if i == 0 && file.eq_ignore_ascii_case("file") {
+            continue;
+        }
+
+        labeled.push((base.join(file).to_string_lossy().to_string(), label));
+    }
+
+    Ok(labeled)
+}
+
+#[cfg(test)]
+mod tests_evaluation {
+    use std::{env, fs};
+
+    use super::{labels_from_csv, labels_from_directory, Evaluation, UNKNOWN_LABEL};
+
+    #[test]
+    fn test_evaluation() {
+        let mut evaluation = Evaluation::default();
+        assert_eq!(evaluation.accuracy(), 100.0);
+
+        evaluation.record("png", Some("png"));
+        evaluation.record("png", Some("png"));
+        evaluation.record("png", Some("jpeg"));
+        evaluation.record("jpeg", Some("jpeg"));
+        evaluation.record(UNKNOWN_LABEL, Some("jpeg"));
+        evaluation.record("gif", None);
+
+        assert_eq!(evaluation.total, 6);
+        assert_eq!(evaluation.correct, 3);
+        assert_eq!(evaluation.accuracy(), 50.0);
+        assert_eq!(evaluation.confusion["png"]["jpeg"], 1);
+        assert_eq!(evaluation.confusion["gif"][UNKNOWN_LABEL], 1);
+
+        let scores = evaluation.scores();
+        let labels: Vec<_> = scores.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, vec!["gif", "jpeg", "png", UNKNOWN_LABEL]);
+
+        let jpeg = &scores[1];
+        assert_eq!(
+            (
+                jpeg.true_positives,
+                jpeg.false_positives,
+                jpeg.false_negatives
+            ),
+            (1, 2, 0)
+        );
+        assert_eq!(jpeg.recall, 100.0);
+        assert!((jpeg.precision - 100.0 / 3.0).abs() < 0.001);
+
+        let gif = &scores[0];
+        assert_eq!((gif.precision, gif.recall), (0.0, 0.0));
+        assert!((scores[2].recall - 200.0 / 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_labels() {
+        let test_dir = env::temp_dir().join(format!("itf-test-labels-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(test_dir.join("png").join("nested")).unwrap();
+        fs::create_dir_all(test_dir.join(UNKNOWN_LABEL)).unwrap();
+        fs::write(test_dir.join("png").join("a.png"), b"a").unwrap();
+        fs::write(test_dir.join("png").join("nested").join("b.png"), b"b").unwrap();
+        fs::write(test_dir.join(UNKNOWN_LABEL).join("c.bin"), b"c").unwrap();
+        fs::write(
+            test_dir.join("labels.csv"),
+            "file,uuid\n# A comment.\n\npng/a.png,png\n\"x,y.bin\",\"unknown\"\n",
+        )
+        .unwrap();
+
+        let labeled = labels_from_directory(&test_dir).unwrap();
+        let labels: Vec<_> = labeled.iter().map(|(_, l)| l.as_str()).collect();
+        assert_eq!(labels, vec!["png", "png", UNKNOWN_LABEL]);
+
+        let labeled = labels_from_csv(test_dir.join("labels.csv")).unwrap();
+        assert_eq!(labeled.len(), 2);
+        assert!(labeled[0].0.ends_with("a.png"));
+        assert!(labeled[1].0.ends_with("x,y.bin"));
+        assert_eq!(labeled[1].1, UNKNOWN_LABEL);
+
+        fs::write(test_dir.join("invalid.csv"), "png/a.png\n").unwrap();
+        assert!(labels_from_csv(test_dir.join("invalid.csv")).is_err());
+
+        _ = fs::remove_dir_all(&test_dir);
+    }
+}
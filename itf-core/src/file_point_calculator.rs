@@ -1,61 +1,438 @@
+use std::{fs, path::Path};
+
 use hashbrown::HashSet;
+use serde_derive::Deserialize;
 
-use crate::{file_processor, pattern::Pattern, utils};
+use crate::{
+    error::ItfError,
+    file_processor::{self, FileSample},
+    fuzzy,
+    pattern::{Pattern, PatternData},
+    utils,
+};
 
 /// The maximum number of points to be awarded for entropy matching.
 pub const MAX_ENTROPY_POINTS: f32 = 15.0;
-/// The amount by which the total file count will be scaled to create the confidence factor.
-pub const CONFIDENCE_SCALE_FACTOR: f32 = 1.0 / 3.0;
+/// The maximum number of points to be awarded for each entropy window match.
+pub const MAX_ENTROPY_WINDOW_POINTS: f32 = 5.0;
+/// The distance outside of a stored entropy range at which no entropy window points will be awarded.
+pub const ENTROPY_WINDOW_TOLERANCE: f32 = 1.0;
+/// The maximum number of points to be awarded for a chi-square statistic match.
+pub const MAX_CHI_SQUARE_POINTS: f32 = 5.0;
+/// The distance (in orders of magnitude) outside of a stored chi-square range at which no points will be awarded.
+pub const CHI_SQUARE_LOG_TOLERANCE: f32 = 1.0;
+/// The maximum number of points to be awarded for a byte bigram profile match.
+pub const MAX_BIGRAM_POINTS: f32 = 5.0;
+/// The maximum number of points to be awarded for a fuzzy hash match.
+pub const MAX_FUZZY_HASH_POINTS: f32 = 10.0;
+/// The weight given to the points of a byte sequence that no sample file contained. Sequences that every
+/// sample file contained are given a weight of 1, with the weight varying linearly between the two.
+pub const MIN_SEQUENCE_SUPPORT_WEIGHT: f32 = 0.5;
 /// The number of points to be awarded for a file extension match.
 pub const FILE_EXTENSION_POINTS: f32 = 5.0;
 
+/// A per-criterion breakdown of the points awarded to a chunk of data for a given [`Pattern`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchReport {
+    /// The points awarded for exact and ranged byte sequence matches.
+    pub sequence_points: f32,
+    /// The points awarded for masked byte sequence matches.
+    pub masked_sequence_points: f32,
+    /// The points awarded for trailer byte sequence matches.
+    pub trailer_sequence_points: f32,
+    /// The points awarded for regex matches.
+    pub regex_points: f32,
+    /// The points awarded for string matches.
+    pub string_points: f32,
+    /// The points awarded for the entropy similarity.
+    pub entropy_points: f32,
+    /// The points awarded for the chi-square statistic and byte bigram profile similarity.
+    pub composition_points: f32,
+    /// The points awarded for the fuzzy hash similarity.
+    pub fuzzy_hash_points: f32,
+    /// The factor by which the above points were scaled, or 1 if no scaling was applied.
+    pub confidence_factor: f32,
+    /// The points awarded for a file extension match. These are never scaled.
+    pub extension_points: f32,
+}
+
+impl MatchReport {
+    /// Get the total number of points awarded, after scaling.
+    pub fn total(&self) -> usize {
+        let scaled = (self.sequence_points
+            + self.masked_sequence_points
+            + self.trailer_sequence_points
+            + self.regex_points
+            + self.string_points
+            + self.entropy_points
+            + self.composition_points
+            + self.fuzzy_hash_points)
+            * self.confidence_factor;
+
+        (scaled + self.extension_points).round() as usize
+    }
+
+    /// Get the number of points awarded for the positional criteria (byte sequences, masked sequences,
+    /// trailer sequences and regexes), after scaling.
+    pub fn positional_total(&self) -> usize {
+        ((self.sequence_points
+            + self.masked_sequence_points
+            + self.trailer_sequence_points
+            + self.regex_points)
+            * self.confidence_factor)
+            .round() as usize
+    }
+
+    /// Get a copy of this report in which the points awarded for each criterion have been multiplied by
+    /// its weight.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The [`ScoringConfig`] giving the weights.
+    pub fn weighted(&self, config: &ScoringConfig) -> Self {
+        Self {
+            sequence_points: self.sequence_points * config.sequences,
+            masked_sequence_points: self.masked_sequence_points * config.sequences,
+            trailer_sequence_points: self.trailer_sequence_points * config.sequences,
+            regex_points: self.regex_points * config.regexes,
+            string_points: self.string_points * config.strings,
+            entropy_points: self.entropy_points * config.entropy,
+            extension_points: self.extension_points * config.extension,
+            ..*self
+        }
+    }
+}
+
+/// A policy by which the per-criterion breakdown of the points awarded to a chunk of data is reduced to
+/// a single score.
+///
+/// # Notes
+///
+/// The percentage of a match is given by its score relative to the maximum score that the pattern could
+/// have been awarded under the same strategy, so the two methods must agree for a perfect match to score 100%.
+pub trait ScoringStrategy: Send + Sync {
+    /// Compute the number of points awarded for a match against a given [`Pattern`].
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern that was tested against.
+    /// * `report` - The [`MatchReport`] of the points awarded for each criterion.
+    ///
+    /// # Returns
+    ///
+    /// The number of points awarded. A score of 0 is treated as no match.
+    fn score(&self, pattern: &Pattern, report: &MatchReport) -> usize;
+
+    /// Compute the maximum number of points that could be awarded for a match against a given [`Pattern`].
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern.
+    ///
+    /// # Returns
+    ///
+    /// The maximum number of points. A maximum of 0 is treated as no match.
+    fn max_points(&self, pattern: &Pattern) -> usize {
+        self.score(pattern, &pattern.max_report())
+    }
+}
+
+/// The default [`ScoringStrategy`], which totals the points awarded for every criterion.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultScoring;
+
+impl ScoringStrategy for DefaultScoring {
+    fn score(&self, _pattern: &Pattern, report: &MatchReport) -> usize {
+        report.total()
+    }
+
+    fn max_points(&self, pattern: &Pattern) -> usize {
+        pattern.max_points
+    }
+}
+
+/// A strict [`ScoringStrategy`], which only awards points for the positional criteria of a pattern (its
+/// "magic" byte sequences and regexes). Strings, composition and extensions are disregarded, and so
+/// patterns without any positional criteria will never match.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MagicOnlyScoring;
+
+impl ScoringStrategy for MagicOnlyScoring {
+    fn score(&self, _pattern: &Pattern, report: &MatchReport) -> usize {
+        report.positional_total()
+    }
+}
+
+/// The weights by which the points awarded for each criterion are multiplied when a match is scored,
+/// allowing the trade-off between false positives and false negatives to be tuned. This may be used as
+/// a [`ScoringStrategy`].
+///
+/// # Notes
+///
+/// Every weight defaults to 1, giving the same scores as [`DefaultScoring`]. A criterion with a weight of 0
+/// doesn't award any points, though a required criterion (such as a byte sequence) must still be met for a
+/// file to match.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringConfig {
+    /// The weight of the points awarded for byte sequence matches, including masked and trailer sequences.
+    pub sequences: f32,
+    /// The weight of the points awarded for string matches, including anchored strings.
+    pub strings: f32,
+    /// The weight of the points awarded for regex matches.
+    pub regexes: f32,
+    /// The weight of the points awarded for the entropy similarity, including the entropy windows.
+    pub entropy: f32,
+    /// The weight of the points awarded for a file extension match.
+    pub extension: f32,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            sequences: 1.0,
+            strings: 1.0,
+            regexes: 1.0,
+            entropy: 1.0,
+            extension: 1.0,
+        }
+    }
+}
+
+/// The sections of a configuration file. Any sections that aren't listed here are ignored.
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    scoring: ScoringConfig,
+}
+
+impl ScoringConfig {
+    /// Read the weights from the `[scoring]` section of a configuration file, given as TOML.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The contents of the configuration file.
+    ///
+    /// # Returns
+    ///
+    /// The [`ScoringConfig`], with any weights that weren't given set to their defaults. An error will be
+    /// returned if the file can't be parsed, or if any weight is negative or isn't finite.
+    pub fn from_config_str(input: &str) -> Result<Self, ItfError> {
+        let config: ConfigFile =
+            toml::from_str(input).map_err(|e| ItfError::InvalidConfig(e.to_string()))?;
+        let scoring = config.scoring;
+
+        for (name, weight) in [
+            ("sequences", scoring.sequences),
+            ("strings", scoring.strings),
+            ("regexes", scoring.regexes),
+            ("entropy", scoring.entropy),
+            ("extension", scoring.extension),
+        ] {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(ItfError::InvalidConfig(format!(
+                    "the weight of '{name}' must be a non-negative number, but was {weight}"
+                )));
+            }
+        }
+
+        Ok(scoring)
+    }
+
+    /// Read the weights from the `[scoring]` section of a configuration file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the configuration file.
+    pub fn from_config_file<P: AsRef<Path>>(path: P) -> Result<Self, ItfError> {
+        Self::from_config_str(&fs::read_to_string(path)?)
+    }
+}
+
+impl ScoringStrategy for ScoringConfig {
+    fn score(&self, _pattern: &Pattern, report: &MatchReport) -> usize {
+        report.weighted(self).total()
+    }
+}
+
 #[derive(Default)]
 pub struct FilePointCalculator {}
 
 impl FilePointCalculator {
-    pub fn compute(pattern: &Pattern, chunk: &[u8], path: &str, apply_confidence: bool) -> usize {
+    /// Compute the number of points awarded to a chunk of data for a given [`Pattern`].
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to be tested against.
+    /// * `sample` - The [`FileSample`] of the data to be tested.
+    /// * `path` - The path or file name of the data, if known. This is used for extension matching.
+    /// * `compound_extensions` - The compound extensions (e.g. `TAR.GZ`) to be recognised when matching the extension.
+    /// * `apply_confidence` - Should the points be scaled by the confidence factor of the pattern?
+    ///
+    /// # Notes
+    ///
+    /// The points are scored using the [`DefaultScoring`] strategy.
+    pub fn compute<S: AsRef<str>>(
+        pattern: &Pattern,
+        sample: &FileSample,
+        path: Option<&str>,
+        compound_extensions: &[S],
+        apply_confidence: bool,
+    ) -> usize {
+        Self::compute_with(
+            &DefaultScoring,
+            pattern,
+            sample,
+            path,
+            compound_extensions,
+            apply_confidence,
+        )
+    }
+
+    /// Compute the number of points awarded to a chunk of data for a given [`Pattern`], using a specific
+    /// [`ScoringStrategy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The [`ScoringStrategy`] by which the points are scored.
+    /// * `pattern` - The pattern to be tested against.
+    /// * `sample` - The [`FileSample`] of the data to be tested.
+    /// * `path` - The path or file name of the data, if known. This is used for extension matching.
+    /// * `compound_extensions` - The compound extensions (e.g. `TAR.GZ`) to be recognised when matching the extension.
+    /// * `apply_confidence` - Should the points be scaled by the confidence factor of the pattern?
+    pub fn compute_with<S: AsRef<str>>(
+        strategy: &dyn ScoringStrategy,
+        pattern: &Pattern,
+        sample: &FileSample,
+        path: Option<&str>,
+        compound_extensions: &[S],
+        apply_confidence: bool,
+    ) -> usize {
+        Self::compute_report(pattern, sample, path, compound_extensions, apply_confidence)
+            .map_or(0, |report| strategy.score(pattern, &report))
+    }
+
+    /// Compute a per-criterion breakdown of the points awarded to a chunk of data for a given [`Pattern`].
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to be tested against.
+    /// * `sample` - The [`FileSample`] of the data to be tested.
+    /// * `path` - The path or file name of the data, if known. This is used for extension matching.
+    /// * `compound_extensions` - The compound extensions (e.g. `TAR.GZ`) to be recognised when matching the extension.
+    /// * `apply_confidence` - Should the points be scaled by the confidence factor of the pattern?
+    ///
+    /// # Returns
+    ///
+    /// A [`MatchReport`], or `None` if a required criterion was not met.
+    pub fn compute_report<S: AsRef<str>>(
+        pattern: &Pattern,
+        sample: &FileSample,
+        path: Option<&str>,
+        compound_extensions: &[S],
+        apply_confidence: bool,
+    ) -> Option<MatchReport> {
+        let chunk = &sample.head[..];
         let mut frequencies = [0; 256];
 
-        if pattern.data.should_scan_sequences() || pattern.data.should_scan_composition() {
+        if pattern.data.should_scan_sequences()
+            || pattern.data.should_scan_composition()
+            || pattern.data.should_scan_extended_composition()
+        {
             file_processor::count_byte_frequencies(chunk, &mut frequencies);
         }
 
-        let mut points = 0.0;
+        let mut report = MatchReport {
+            confidence_factor: 1.0,
+            ..Default::default()
+        };
 
         if pattern.data.should_scan_sequences() {
             let (p, success) = Self::test_byte_sequences(pattern, chunk);
 
             // Byte sequence matches, if specified, MUST be present for a file to match the pattern.
             if !success {
-                return 0;
+                return None;
+            }
+
+            report.sequence_points = p;
+        }
+
+        if pattern.data.should_scan_masked_sequences() {
+            let (p, success) = Self::test_masked_sequences(pattern, chunk);
+
+            // As with byte sequences, masked sequences MUST be present for a file to match the pattern.
+            if !success {
+                return None;
+            }
+
+            report.masked_sequence_points = p;
+        }
+
+        if pattern.data.should_scan_trailer_sequences() {
+            let (p, success) = Self::test_trailer_sequences(pattern, &sample.tail);
+
+            // As with byte sequences, trailer sequences MUST be present for a file to match the pattern.
+            if !success {
+                return None;
+            }
+
+            report.trailer_sequence_points = p;
+        }
+
+        if pattern.data.should_scan_regexes() {
+            let (p, success) = Self::test_regexes(pattern, chunk);
+
+            // As with byte sequences, regexes MUST match for a file to match the pattern.
+            if !success {
+                return None;
             }
 
-            points += p;
+            report.regex_points = p;
         }
 
         if pattern.data.should_scan_strings() {
-            points += Self::test_file_strings(pattern, chunk);
+            report.string_points = Self::test_file_strings(pattern, sample);
+        }
+
+        if pattern.data.should_scan_anchored_strings() {
+            report.string_points += Self::test_anchored_strings(pattern, chunk);
         }
 
         if pattern.data.should_scan_composition() {
-            points += Self::test_entropy_deviation(pattern, &frequencies);
+            report.entropy_points = Self::test_entropy_deviation(pattern, &frequencies);
+        }
+
+        if pattern.data.should_scan_entropy_windows() {
+            report.entropy_points += Self::test_entropy_windows(pattern, chunk);
+        }
+
+        if pattern.data.should_scan_extended_composition() {
+            report.composition_points = Self::test_chi_square(pattern, &frequencies)
+                + Self::test_bigram_profile(pattern, chunk);
+        }
+
+        if pattern.data.should_scan_fuzzy_hash() {
+            report.fuzzy_hash_points = Self::test_fuzzy_hash(pattern, chunk);
         }
 
         // Scale the relevant points by the confidence factor derived from the total files scanned.
         if apply_confidence {
-            points *= pattern.confidence_factor;
+            report.confidence_factor = pattern.confidence_factor;
         }
 
         // The file extension is considered a separate factor and doesn't scale with the number
         // of scanned files.
-        points += Self::test_file_extension(pattern, path);
+        if let Some(path) = path {
+            report.extension_points = Self::test_file_extension(pattern, path, compound_extensions);
+        }
 
-        points.round() as usize
+        Some(report)
     }
 
     #[inline(always)]
     fn test_byte_sequences(pattern: &Pattern, bytes: &[u8]) -> (f32, bool) {
-        if !pattern.data.should_scan_sequences() || pattern.data.sequences.is_empty() {
+        if !pattern.data.should_scan_sequences() {
             return (0.0, true);
         }
 
@@ -65,8 +442,8 @@ impl FilePointCalculator {
         // letting is bail the loop early. Though this is likely something that will
         // only come up with small files.
         let bytes_len = bytes.len();
-        let mut points = 0;
-        for (start, sequence) in &pattern.data.sequences {
+        let mut points = 0.0;
+        for (i, (start, sequence)) in pattern.data.sequences.iter().enumerate() {
             let len = sequence.len();
             let end = start.saturating_add(len);
             if *start > bytes_len || end > bytes_len {
@@ -74,17 +451,171 @@ impl FilePointCalculator {
             }
 
             unsafe {
-                if sequence != bytes.get_unchecked(*start..end) {
+                if **sequence != *bytes.get_unchecked(*start..end) {
                     return (0.0, false);
                 }
             }
 
+            points += len as f32 * pattern.sequence_weight(i);
+        }
+
+        // Ranged sequences may start anywhere within their window.
+        for (min_offset, max_offset, sequence) in &pattern.data.ranged_sequences {
+            let end = max_offset.saturating_add(sequence.len()).min(bytes_len);
+            if *min_offset >= end {
+                return (0.0, false);
+            }
+
+            if file_processor::find_slice(&bytes[*min_offset..end], sequence).is_none() {
+                return (0.0, false);
+            }
+
+            points += sequence.len() as f32;
+        }
+
+        // Any one of the alternatives of a group must be present.
+        for group in &pattern.data.alternative_sequences {
+            let matched = group.iter().any(|(start, sequence)| {
+                bytes.get(*start..start.saturating_add(sequence.len())) == Some(&sequence[..])
+            });
+            if !matched {
+                return (0.0, false);
+            }
+
+            points += PatternData::alternative_points(group) as f32;
+        }
+
+        (points, true)
+    }
+
+    #[inline(always)]
+    fn test_fuzzy_hash(pattern: &Pattern, bytes: &[u8]) -> f32 {
+        let Some((block_size, digest)) = &pattern.data.fuzzy_hash else {
+            return 0.0;
+        };
+
+        let target = fuzzy::hash(bytes, *block_size);
+        MAX_FUZZY_HASH_POINTS * fuzzy::similarity(digest, &target)
+    }
+
+    #[inline(always)]
+    fn test_masked_sequences(pattern: &Pattern, bytes: &[u8]) -> (f32, bool) {
+        if !pattern.data.should_scan_masked_sequences() {
+            return (0.0, true);
+        }
+
+        let bytes_len = bytes.len();
+        let mut points = 0;
+        for (start, sequence) in &pattern.data.masked_sequences {
+            let end = start.saturating_add(sequence.len());
+            if end > bytes_len {
+                return (0.0, false);
+            }
+
+            // Masked bytes will match anything, and don't award any points.
+            for (expected, actual) in sequence.iter().zip(&bytes[*start..end]) {
+                match expected {
+                    Some(b) if b == actual => points += 1,
+                    Some(_) => return (0.0, false),
+                    None => {}
+                }
+            }
+        }
+
+        (points as f32, true)
+    }
+
+    #[inline(always)]
+    fn test_trailer_sequences(pattern: &Pattern, tail: &[u8]) -> (f32, bool) {
+        if !pattern.data.should_scan_trailer_sequences() {
+            return (0.0, true);
+        }
+
+        // Trailer sequences are sorted by their distance from the end of the file - largest first.
+        let tail_len = tail.len();
+        let mut points = 0;
+        for (offset, sequence) in &pattern.data.trailer_sequences {
+            let len = sequence.len();
+            if *offset > tail_len || len > *offset {
+                return (0.0, false);
+            }
+
+            let start = tail_len - offset;
+            if sequence != &tail[start..start + len] {
+                return (0.0, false);
+            }
+
             points += len;
         }
 
         (points as f32, true)
     }
 
+    #[inline(always)]
+    fn test_regexes(pattern: &Pattern, bytes: &[u8]) -> (f32, bool) {
+        let mut points = 0;
+        for compiled in &pattern.compiled_regexes {
+            let Some(bytes) = bytes.get(compiled.position..) else {
+                return (0.0, false);
+            };
+
+            if !compiled.regex.is_match(bytes) {
+                return (0.0, false);
+            }
+
+            points += compiled.points;
+        }
+
+        (points as f32, true)
+    }
+
+    #[inline(always)]
+    fn test_anchored_strings(pattern: &Pattern, bytes: &[u8]) -> f32 {
+        pattern
+            .data
+            .anchored_strings
+            .iter()
+            .filter(|(position, string)| file_processor::has_string_at(bytes, *position, string))
+            .map(|(_, string)| string.len() as f32)
+            .sum()
+    }
+
+    #[inline(always)]
+    fn test_bigram_profile(pattern: &Pattern, bytes: &[u8]) -> f32 {
+        if pattern.data.bigram_profile.is_empty() {
+            return 0.0;
+        }
+
+        let mut counts = [0; file_processor::BIGRAM_PROFILE_SIZE];
+        file_processor::count_bigrams(bytes, &mut counts);
+        let profile = file_processor::compute_bigram_profile(&counts);
+
+        // The histogram intersection gives the proportion of the two profiles that overlap.
+        let intersection: f32 = pattern
+            .data
+            .bigram_profile
+            .iter()
+            .zip(&profile)
+            .map(|(a, b)| a.min(*b))
+            .sum();
+
+        MAX_BIGRAM_POINTS * intersection.min(1.0)
+    }
+
+    #[inline(always)]
+    fn test_chi_square(pattern: &Pattern, frequencies: &[usize; 256]) -> f32 {
+        let Some((min, max)) = pattern.data.chi_square else {
+            return 0.0;
+        };
+
+        // The statistic can vary by orders of magnitude, so the distance is computed on a log scale.
+        let target = utils::calculate_chi_square(frequencies).max(1.0).log10();
+        let (min, max) = (min.max(1.0).log10(), max.max(1.0).log10());
+        let distance = (min - target).max(target - max).max(0.0);
+
+        MAX_CHI_SQUARE_POINTS * (1.0 - distance / CHI_SQUARE_LOG_TOLERANCE).max(0.0)
+    }
+
     #[inline(always)]
     fn test_entropy_deviation(pattern: &Pattern, frequencies: &[usize; 256]) -> f32 {
         let reference_entropy = pattern.data.average_entropy;
@@ -106,10 +637,40 @@ impl FilePointCalculator {
     }
 
     #[inline(always)]
-    fn test_file_extension(pattern: &Pattern, path: &str) -> f32 {
+    fn test_entropy_windows(pattern: &Pattern, bytes: &[u8]) -> f32 {
+        let entropies = file_processor::compute_window_entropies(bytes);
+
+        let mut points = 0.0;
+        for (window, min, max) in &pattern.data.entropy_windows {
+            // If the data doesn't extend into the window then no points can be awarded.
+            let Some(Some(entropy)) = entropies.get(*window) else {
+                continue;
+            };
+
+            // Scale the points linearly based on the distance outside of the stored range.
+            let distance = (min - entropy).max(entropy - max).max(0.0);
+            points +=
+                MAX_ENTROPY_WINDOW_POINTS * (1.0 - distance / ENTROPY_WINDOW_TOLERANCE).max(0.0);
+        }
+
+        points
+    }
+
+    #[inline(always)]
+    fn test_file_extension<S: AsRef<str>>(
+        pattern: &Pattern,
+        path: &str,
+        compound_extensions: &[S],
+    ) -> f32 {
+        // A file with a compound extension, such as a .tar.gz file, is also a file of its last extension.
+        let known_extensions = &pattern.type_data.known_extensions;
+        let compound = utils::get_compound_file_extension(path, compound_extensions);
         let ext = utils::get_file_extension(path);
 
-        if pattern.type_data.known_extensions.contains(&ext) {
+        if known_extensions
+            .iter()
+            .any(|e| **e == *compound || **e == *ext)
+        {
             FILE_EXTENSION_POINTS
         } else {
             0.0
@@ -117,13 +678,16 @@ impl FilePointCalculator {
     }
 
     #[inline(always)]
-    fn test_file_strings(pattern: &Pattern, bytes: &[u8]) -> f32 {
+    fn test_file_strings(pattern: &Pattern, sample: &FileSample) -> f32 {
         if !pattern.data.should_scan_strings() || pattern.data.strings.is_empty() {
             return 0.0;
         }
 
-        let strings: HashSet<String> =
-            HashSet::from_iter(file_processor::extract_file_strings(bytes));
+        // Strings may also be found within the middle of larger files.
+        let mut strings: HashSet<String> = file_processor::extract_file_strings(&sample.head);
+        if let Some(middle) = &sample.middle {
+            strings.extend(file_processor::extract_file_strings(middle));
+        }
 
         pattern
             .data
@@ -133,3 +697,67 @@ impl FilePointCalculator {
             .sum()
     }
 }
+
+#[cfg(test)]
+mod tests_file_point_calculator {
+    use crate::{error::ItfError, interner::intern_sequences, pattern::Pattern};
+
+    use super::{DefaultScoring, MatchReport, ScoringConfig, ScoringStrategy};
+
+    #[test]
+    fn test_scoring_config() {
+        // Weights that aren't given keep their defaults, and the other sections of the file are ignored.
+        let config = ScoringConfig::from_config_str(
+            "[patterns]\ndir = \"patterns\"\n\n[scoring]\nentropy = 0\nextension = 2.5\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            ScoringConfig {
+                entropy: 0.0,
+                extension: 2.5,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            ScoringConfig::from_config_str("").unwrap(),
+            ScoringConfig::default()
+        );
+        for invalid in [
+            "[scoring]\nstrings = -1",
+            "[scoring]\nmagic = 1",
+            "[scoring",
+        ] {
+            assert!(matches!(
+                ScoringConfig::from_config_str(invalid),
+                Err(ItfError::InvalidConfig(_))
+            ));
+        }
+
+        let mut pattern = Pattern::new("test", "test", vec![], vec![]);
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        pattern.data.strings.insert("IDENTIFYING".to_string());
+        pattern.other_data.total_scanned_files = 1;
+        pattern.compute_attributes();
+
+        // The default weights give the same scores as the default strategy.
+        let report = MatchReport {
+            sequence_points: 10.0,
+            entropy_points: 15.0,
+            confidence_factor: 1.0,
+            extension_points: 5.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            ScoringConfig::default().score(&pattern, &report),
+            DefaultScoring.score(&pattern, &report)
+        );
+        assert_eq!(
+            ScoringConfig::default().max_points(&pattern),
+            DefaultScoring.max_points(&pattern)
+        );
+
+        assert_eq!(config.score(&pattern, &report), 23);
+        assert!(config.max_points(&pattern) > pattern.max_points);
+    }
+}
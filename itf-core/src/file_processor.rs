@@ -1,10 +1,12 @@
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use rayon::prelude::*;
 use std::{
     fs::File,
-    io::{self, BufReader, Read},
+    io::{BufReader, ErrorKind, Read, Seek, SeekFrom},
 };
 
+use crate::{error::ItfError, utils};
+
 pub(crate) const ASCII_CHARACTER_STRING: &str =
     " !#$+,-./0123456789<=>?ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz";
 const ASCII_READABLE_CHARACTERS: &[u8] = ASCII_CHARACTER_STRING.as_bytes();
@@ -13,7 +15,15 @@ const ASCII_READABLE_CHARACTERS_SET: [bool; 256] =
 const ASCII_UPPERCASE_MAP: [char; 256] = generate_uppercase_map();
 
 /// The size of a file chunk to read. Larger is more accurate but slower.
-const FILE_CHUNK_SIZE: usize = 5 * 1024 * 1024; // 5 MB
+pub(crate) const FILE_CHUNK_SIZE: usize = 5 * 1024 * 1024; // 5 MB
+/// The size of the chunk to read from the end of a file, used for trailer sequences.
+pub const TAIL_CHUNK_SIZE: usize = 64 * 1024; // 64 KB
+/// The maximum number of bytes that will be read from a stream whose length isn't known in advance.
+pub const MAX_STREAM_READ_SIZE: usize = 64 * 1024 * 1024; // 64 MB
+/// The number of evenly spaced blocks taken from between the header and tail chunks of a large file.
+const MIDDLE_SAMPLE_COUNT: usize = 16;
+/// The size of each block taken from between the header and tail chunks of a large file.
+const MIDDLE_SAMPLE_BLOCK_SIZE: usize = 4 * 1024; // 4 KB
 /// The size of a byte chunk to be processed in parallel when computing byte distributions.
 const BYTE_COUNT_CHUNK_SIZE: usize = 512; // 512 B
 
@@ -25,6 +35,95 @@ pub const MAX_STRING_LENGTH: usize = 64;
 const MIN_BYTE_SEQUENCE_LENGTH: usize = 1;
 /// The maximum length of a byte sequence.
 const MAX_BYTE_SEQUENCE_LENGTH: usize = 16;
+/// The maximum number of consecutive differing bytes that may be masked within a sequence.
+const MAX_MASKED_GAP_LENGTH: usize = 4;
+/// The maximum length of a masked byte sequence.
+const MAX_MASKED_SEQUENCE_LENGTH: usize = 2 * MAX_BYTE_SEQUENCE_LENGTH;
+/// The maximum number of values that a masked byte may take for it to be described by a regex.
+pub(crate) const MAX_REGEX_CLASS_SIZE: usize = 4;
+/// The maximum number of distinct header variants that will be retained as alternatives.
+const MAX_HEADER_VARIANTS: usize = 4;
+/// The number of leading bytes used to distinguish the header variants, which is also the minimum length of a variant.
+const HEADER_VARIANT_KEY_LENGTH: usize = 2;
+/// The minimum number of sample files that must share a header variant. A variant seen in a single file
+/// is indistinguishable from a header that simply differs between files.
+const MIN_HEADER_VARIANT_SUPPORT: usize = 2;
+/// The number of buckets in a byte bigram profile. Each bucket represents a pair of high nibbles.
+pub const BIGRAM_PROFILE_SIZE: usize = 256;
+/// The regions (start inclusive, end exclusive) of a header chunk over which the windowed entropy is computed.
+pub const ENTROPY_WINDOWS: [(usize, usize); 3] = [
+    (0, 4 * 1024),
+    (4 * 1024, 64 * 1024),
+    (64 * 1024, FILE_CHUNK_SIZE),
+];
+
+/// A tuple containing a set of unmasked byte sequences, and a set of masked byte sequences.
+type IntersectedSequences = (Vec<(usize, Vec<u8>)>, Vec<(usize, Vec<Option<u8>>)>);
+
+/// The regions of a file that are sampled when building or matching a pattern.
+#[derive(Clone, Debug, Default)]
+pub struct FileSample {
+    /// The header chunk, taken from the start of the file.
+    pub head: Vec<u8>,
+    /// The tail chunk, taken from the end of the file. This may overlap the header chunk for smaller files.
+    pub tail: Vec<u8>,
+    /// A set of evenly spaced blocks taken from between the header and tail chunks, concatenated together.
+    /// This will only be present for files too large to be covered by the header and tail chunks.
+    pub middle: Option<Vec<u8>>,
+}
+
+impl FileSample {
+    /// Create a [`FileSample`] from a block of bytes that is held in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes, which should be the header chunk of a file.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            head: bytes.to_vec(),
+            tail: tail_slice(bytes).to_vec(),
+            middle: None,
+        }
+    }
+
+    /// Create a [`FileSample`] from the entire contents of a file that is held in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The entire contents of the file.
+    ///
+    /// # Notes
+    ///
+    /// The regions are the same as those that would be sampled by [`read_file_sample`].
+    pub fn from_file_bytes(bytes: &[u8]) -> Self {
+        let head = &bytes[..bytes.len().min(FILE_CHUNK_SIZE)];
+        if head.len() < FILE_CHUNK_SIZE {
+            return Self::from_bytes(head);
+        }
+
+        let tail = tail_slice(bytes);
+
+        // Only sample the middle of the file if there's a gap between the header and tail chunks.
+        let tail_start = bytes.len() - tail.len();
+        let stride = tail_start.saturating_sub(FILE_CHUNK_SIZE) / MIDDLE_SAMPLE_COUNT;
+        let middle = (stride > 0).then(|| {
+            let block_size = MIDDLE_SAMPLE_BLOCK_SIZE.min(stride);
+            (0..MIDDLE_SAMPLE_COUNT)
+                .flat_map(|i| {
+                    let start = FILE_CHUNK_SIZE + i * stride;
+                    &bytes[start..start + block_size]
+                })
+                .copied()
+                .collect()
+        });
+
+        Self {
+            head: head.to_vec(),
+            tail: tail.to_vec(),
+            middle,
+        }
+    }
+}
 
 /// Sieve a set of strings to retain only those that are present in all of the sets.
 ///
@@ -51,11 +150,11 @@ pub(crate) fn common_string_sieve(sets: &mut [Vec<&str>]) -> Vec<String> {
         new_common_strings.clear();
 
         for common_string in &common_strings {
-            if let Some(max_string) = set
-                .par_iter()
-                .filter_map(|string| largest_common_substring(string, common_string))
-                .max_by_key(|s| s.len())
-            {
+            if let Some(max_string) = utils::in_thread_pool(|| {
+                set.par_iter()
+                    .filter_map(|string| largest_common_substring(string, common_string))
+                    .max_by_key(|s| s.len())
+            }) {
                 new_common_strings.push(max_string);
             }
         }
@@ -84,6 +183,63 @@ pub(crate) fn common_string_sieve(sets: &mut [Vec<&str>]) -> Vec<String> {
     final_set
 }
 
+/// Compute a normalized byte bigram profile from a set of bigram counts.
+///
+/// # Arguments
+///
+/// * `counts` - The bigram counts, as produced by [`count_bigrams`].
+///
+/// # Returns
+///
+/// A vector containing the proportion of bigrams that fell within each bucket, or an empty vector if there were none.
+pub(crate) fn compute_bigram_profile(counts: &[usize; BIGRAM_PROFILE_SIZE]) -> Vec<f32> {
+    let total = counts.iter().sum::<usize>() as f32;
+    if total == 0.0 {
+        return vec![];
+    }
+
+    counts
+        .iter()
+        .map(|&count| utils::round_to_dp(count as f32 / total, 4))
+        .collect()
+}
+
+/// Compute the Shannon entropy of each of the [`ENTROPY_WINDOWS`] within a block of bytes.
+///
+/// # Arguments
+///
+/// * `bytes` - The header chunk of a file.
+///
+/// # Returns
+///
+/// A vector containing the entropy of each window, or `None` if the data doesn't extend into that window.
+pub(crate) fn compute_window_entropies(bytes: &[u8]) -> Vec<Option<f32>> {
+    ENTROPY_WINDOWS
+        .iter()
+        .map(|(start, end)| {
+            if *start >= bytes.len() {
+                return None;
+            }
+
+            let mut frequencies = [0; 256];
+            count_byte_frequencies(&bytes[*start..(*end).min(bytes.len())], &mut frequencies);
+            Some(utils::calculate_shannon_entropy(&frequencies))
+        })
+        .collect()
+}
+
+/// Count the byte bigrams within a block of bytes, grouped by the high nibble of each byte.
+///
+/// # Arguments
+///
+/// * `bytes` - A slice of bytes.
+/// * `counts` - A mutable reference to the array of bigram counts.
+pub(crate) fn count_bigrams(bytes: &[u8], counts: &mut [usize; BIGRAM_PROFILE_SIZE]) {
+    for pair in bytes.windows(2) {
+        counts[((pair[0] >> 4) as usize) << 4 | (pair[1] >> 4) as usize] += 1;
+    }
+}
+
 /// Count the number of instances of each byte within a slice of u8 values.
 ///
 /// # Arguments
@@ -92,26 +248,27 @@ pub(crate) fn common_string_sieve(sets: &mut [Vec<&str>]) -> Vec<String> {
 /// * `frequencies` - A mutable reference to the array of byte counts.
 #[inline(always)]
 pub fn count_byte_frequencies(data: &[u8], frequencies: &mut [usize; 256]) {
-    let mut accumulator = data
-        .par_chunks(BYTE_COUNT_CHUNK_SIZE)
-        .fold(
-            || [0; 256],
-            |mut local_frequencies, chunk| {
-                for &b in chunk {
-                    local_frequencies[b as usize] += 1;
-                }
-                local_frequencies
-            },
-        )
-        .reduce(
-            || [0; 256],
-            |mut acc, local| {
-                for (i, &count) in local.iter().enumerate() {
-                    acc[i] += count;
-                }
-                acc
-            },
-        );
+    let mut accumulator = utils::in_thread_pool(|| {
+        data.par_chunks(BYTE_COUNT_CHUNK_SIZE)
+            .fold(
+                || [0; 256],
+                |mut local_frequencies, chunk| {
+                    for &b in chunk {
+                        local_frequencies[b as usize] += 1;
+                    }
+                    local_frequencies
+                },
+            )
+            .reduce(
+                || [0; 256],
+                |mut acc, local| {
+                    for (i, &count) in local.iter().enumerate() {
+                        acc[i] += count;
+                    }
+                    acc
+                },
+            )
+    });
 
     // Add the original counts back into the overall total.
     for (i, &v) in frequencies.iter().enumerate() {
@@ -178,6 +335,113 @@ unsafe fn extract_matching_sequences(
     subsequences
 }
 
+/// Extract the printable strings from a slice of u8 values, along with the position at which each string starts.
+///
+/// # Arguments
+///
+/// * `bytes` - The slice of u8 values.
+///
+/// # Returns
+///
+/// A [`HashSet`] containing the start position and the extracted string.
+pub(crate) fn extract_anchored_strings(bytes: &[u8]) -> HashSet<(usize, String)> {
+    let mut strings = HashSet::with_capacity(128);
+    let mut string_buffer = String::with_capacity(MAX_STRING_LENGTH);
+    let mut start = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        if ASCII_READABLE_CHARACTERS_SET[*byte as usize] {
+            if string_buffer.is_empty() {
+                start = i;
+            }
+
+            string_buffer.push(ASCII_UPPERCASE_MAP[*byte as usize]);
+
+            if string_buffer.len() == MAX_STRING_LENGTH {
+                strings.insert((start, std::mem::take(&mut string_buffer)));
+            }
+        } else {
+            if string_buffer.len() >= MIN_STRING_LENGTH {
+                strings.insert((start, std::mem::take(&mut string_buffer)));
+            }
+
+            string_buffer.clear();
+        }
+    }
+
+    if string_buffer.len() >= MIN_STRING_LENGTH {
+        strings.insert((start, string_buffer));
+    }
+
+    strings
+}
+
+/// Extract masked sequences from a set of byte sequences. Sequences that are separated by a small
+/// number of differing bytes will be merged into a single sequence, with the differing bytes masked.
+///
+/// # Arguments
+///
+/// * `sequences` - A mutable reference to the vector of tuples giving the position of the sequence and the byte sequence.
+///
+/// # Returns
+///
+/// A vector of tuples containing the position of the masked sequence, and the masked bytes.
+///
+/// # Notes
+///
+/// Any sequences that were merged will be removed from the original sequence vector.
+pub(crate) fn extract_masked_sequences(
+    sequences: &mut Vec<(usize, Vec<u8>)>,
+) -> Vec<(usize, Vec<Option<u8>>)> {
+    sequences.sort_unstable_by_key(|b| b.0);
+
+    let mut exact_sequences = Vec::with_capacity(sequences.len());
+    let mut masked_sequences = Vec::new();
+    let mut run: Vec<(usize, Vec<u8>)> = Vec::new();
+
+    for sequence in std::mem::take(sequences) {
+        if let (Some((run_start, _)), Some((last_start, last_bytes))) = (run.first(), run.last()) {
+            let gap = sequence.0 - (last_start + last_bytes.len());
+            let run_length = sequence.0 + sequence.1.len() - run_start;
+            if gap > MAX_MASKED_GAP_LENGTH || run_length > MAX_MASKED_SEQUENCE_LENGTH {
+                merge_masked_run(&mut run, &mut exact_sequences, &mut masked_sequences);
+            }
+        }
+
+        run.push(sequence);
+    }
+
+    merge_masked_run(&mut run, &mut exact_sequences, &mut masked_sequences);
+
+    *sequences = exact_sequences;
+
+    masked_sequences
+}
+
+/// Find the positions at which a file differs from a set of byte sequences.
+///
+/// # Arguments
+///
+/// * `file_bytes` - The bytes of the file.
+/// * `sequences` - The byte sequences.
+/// * `positions` - A mutable reference to the set into which the differing positions will be added.
+///
+/// # Notes
+///
+/// Any part of a sequence that lies beyond the end of the file is considered to differ.
+pub(crate) fn find_differing_bytes(
+    file_bytes: &[u8],
+    sequences: &[(usize, Vec<u8>)],
+    positions: &mut HashSet<usize>,
+) {
+    for (start, sequence) in sequences {
+        for (i, byte) in sequence.iter().enumerate() {
+            if file_bytes.get(start + i) != Some(byte) {
+                positions.insert(start + i);
+            }
+        }
+    }
+}
+
 /// Extract valid strings from a slice of u8 values.
 ///
 /// # Arguments
@@ -232,7 +496,7 @@ pub(crate) fn extract_file_strings(bytes: &[u8]) -> HashSet<String> {
 ///
 /// An option - none if the needle wasn't located or the position of the first match.
 #[inline(always)]
-fn find_slice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+pub(crate) fn find_slice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
     use std::ptr;
 
     if needle.is_empty() {
@@ -319,6 +583,85 @@ fn has_common_elements(seq_1: &[u8], seq_2: &[u8]) -> bool {
     false
 }
 
+/// Does a slice of u8 values contain a string at a given position? The comparison is case-insensitive.
+///
+/// # Arguments
+///
+/// * `bytes` - The slice of u8 values.
+/// * `position` - The position at which the string should start.
+/// * `string` - The uppercase string, as produced by [`extract_anchored_strings`].
+pub(crate) fn has_string_at(bytes: &[u8], position: usize, string: &str) -> bool {
+    let end = position.saturating_add(string.len());
+    if end > bytes.len() {
+        return false;
+    }
+
+    bytes[position..end]
+        .iter()
+        .zip(string.chars())
+        .all(|(b, c)| ASCII_UPPERCASE_MAP[*b as usize] == c)
+}
+
+/// Intersect two sets of masked byte sequences, retaining only the bytes that are common to both.
+///
+/// # Arguments
+///
+/// * `sequences` - The first set of sequences. The boundaries of these sequences will be retained.
+/// * `other` - The second set of sequences.
+///
+/// # Returns
+///
+/// A tuple containing the resulting unmasked and masked byte sequences.
+///
+/// # Notes
+///
+/// Unmasked sequences can be intersected by first converting their bytes into `Some` values.
+/// A masked byte in either set will produce a masked byte in the result.
+pub(crate) fn intersect_sequences(
+    sequences: &[(usize, Vec<Option<u8>>)],
+    other: &[(usize, Vec<Option<u8>>)],
+) -> IntersectedSequences {
+    let other_bytes: HashMap<usize, Option<u8>> = other
+        .iter()
+        .flat_map(|(start, s)| s.iter().enumerate().map(move |(i, b)| (start + i, *b)))
+        .collect();
+
+    let mut exact_sequences = vec![];
+    let mut masked_sequences = vec![];
+
+    for (start, sequence) in sequences {
+        let mut run_start = *start;
+        let mut run: Vec<Option<u8>> = vec![];
+
+        for (i, byte) in sequence.iter().enumerate() {
+            let common = match (byte, other_bytes.get(&(start + i))) {
+                (Some(a), Some(Some(b))) if a == b => Some(Some(*a)),
+                (Some(_), Some(Some(_))) | (_, None) => None,
+                (_, Some(_)) => Some(None),
+            };
+
+            match common {
+                Some(b) => {
+                    if run.is_empty() {
+                        run_start = start + i;
+                    }
+                    run.push(b);
+                }
+                None => push_intersected_run(
+                    run_start,
+                    std::mem::take(&mut run),
+                    &mut exact_sequences,
+                    &mut masked_sequences,
+                ),
+            }
+        }
+
+        push_intersected_run(run_start, run, &mut exact_sequences, &mut masked_sequences);
+    }
+
+    (exact_sequences, masked_sequences)
+}
+
 /// Attempt to find the largest common substring between two string slices.
 ///
 /// # Arguments
@@ -348,6 +691,120 @@ fn largest_common_substring<'a>(str_1: &'a str, str_2: &str) -> Option<&'a str>
         .map(|window| unsafe { std::str::from_utf8_unchecked(window) })
 }
 
+/// Merge a run of neighbouring byte sequences into a single masked sequence.
+///
+/// # Arguments
+///
+/// * `run` - A mutable reference to the run of sequences, sorted by position. This will be emptied.
+/// * `exact_sequences` - A mutable reference to the vector of unmasked sequences.
+/// * `masked_sequences` - A mutable reference to the vector of masked sequences.
+///
+/// # Notes
+///
+/// A run without any gaps between its sequences doesn't require masking, and so
+/// the sequences will be returned to the unmasked vector.
+fn merge_masked_run(
+    run: &mut Vec<(usize, Vec<u8>)>,
+    exact_sequences: &mut Vec<(usize, Vec<u8>)>,
+    masked_sequences: &mut Vec<(usize, Vec<Option<u8>>)>,
+) {
+    let has_gaps = run.windows(2).any(|w| w[0].0 + w[0].1.len() != w[1].0);
+    if !has_gaps {
+        exact_sequences.append(run);
+        return;
+    }
+
+    let start = run[0].0;
+    let (last_start, last_bytes) = &run[run.len() - 1];
+    let mut masked = vec![None; last_start + last_bytes.len() - start];
+    for (position, bytes) in run.drain(..) {
+        for (i, b) in bytes.into_iter().enumerate() {
+            masked[position - start + i] = Some(b);
+        }
+    }
+
+    masked_sequences.push((start, masked));
+}
+
+/// Add a run of intersected bytes to the relevant set, based on whether any of the bytes are masked.
+///
+/// # Arguments
+///
+/// * `start` - The start position of the run.
+/// * `run` - The bytes in the run.
+/// * `exact_sequences` - A mutable reference to the vector of unmasked sequences.
+/// * `masked_sequences` - A mutable reference to the vector of masked sequences.
+fn push_intersected_run(
+    start: usize,
+    mut run: Vec<Option<u8>>,
+    exact_sequences: &mut Vec<(usize, Vec<u8>)>,
+    masked_sequences: &mut Vec<(usize, Vec<Option<u8>>)>,
+) {
+    // A masked sequence can't start or end with a masked byte.
+    while run.last() == Some(&None) {
+        run.pop();
+    }
+    let leading = run.iter().take_while(|b| b.is_none()).count();
+    run.drain(..leading);
+
+    if run.is_empty() {
+        return;
+    }
+
+    if run.iter().all(Option::is_some) {
+        exact_sequences.push((start + leading, run.into_iter().flatten().collect()));
+    } else {
+        masked_sequences.push((start + leading, run));
+    }
+}
+
+/// Mask the bytes at a set of positions within a set of byte sequences.
+///
+/// # Arguments
+///
+/// * `sequences` - A mutable reference to the vector of tuples giving the position of the sequence and the byte sequence.
+/// * `positions` - The positions of the bytes to be masked.
+///
+/// # Returns
+///
+/// A vector of tuples containing the position of each masked sequence, and the masked bytes.
+///
+/// # Notes
+///
+/// Any sequences that were masked will be removed from the original sequence vector.
+pub(crate) fn mask_sequence_positions(
+    sequences: &mut Vec<(usize, Vec<u8>)>,
+    positions: &HashSet<usize>,
+) -> Vec<(usize, Vec<Option<u8>>)> {
+    if positions.is_empty() {
+        return vec![];
+    }
+
+    let mut exact_sequences = Vec::with_capacity(sequences.len());
+    let mut masked_sequences = vec![];
+    for (start, sequence) in std::mem::take(sequences) {
+        let run = sequence
+            .into_iter()
+            .enumerate()
+            .map(|(i, b)| (!positions.contains(&(start + i))).then_some(b))
+            .collect();
+        push_intersected_run(start, run, &mut exact_sequences, &mut masked_sequences);
+    }
+
+    *sequences = exact_sequences;
+
+    masked_sequences
+}
+
+/// Append a byte to a regex, escaping it if needed.
+fn push_regex_byte(regex: &mut String, byte: u8) {
+    if byte.is_ascii_alphanumeric() {
+        regex.push(byte as char);
+    } else {
+        regex.push_str(&format!("\\x{byte:02X}"));
+    }
+}
+
 /// Attempt to read the header chunk of a file.
 ///
 /// # Arguments
@@ -357,7 +814,7 @@ fn largest_common_substring<'a>(str_1: &'a str, str_2: &str) -> Option<&'a str>
 /// # Returns
 ///
 /// A vector containing the u8 values if the data was successfully read, otherwise an error.
-pub fn read_file_header_chunk(file_path: &str) -> io::Result<Vec<u8>> {
+pub fn read_file_header_chunk(file_path: &str) -> Result<Vec<u8>, ItfError> {
     let file = File::open(file_path)?;
     let filesize = file.metadata()?.len() as usize;
     let read_size = filesize.min(FILE_CHUNK_SIZE);
@@ -368,6 +825,138 @@ pub fn read_file_header_chunk(file_path: &str) -> io::Result<Vec<u8>> {
     Ok(buffer)
 }
 
+/// Attempt to read a [`FileSample`] from a file.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file.
+///
+/// # Returns
+///
+/// A [`FileSample`] if the data was successfully read, otherwise an error.
+pub fn read_file_sample(file_path: &str) -> Result<FileSample, ItfError> {
+    let head = read_file_header_chunk(file_path)?;
+
+    // If the entire file fit into the header chunk then there's no need to read it again.
+    if head.len() < FILE_CHUNK_SIZE {
+        return Ok(FileSample::from_bytes(&head));
+    }
+
+    let tail = read_file_tail_chunk(file_path)?;
+
+    // Only sample the middle of the file if there's a gap between the header and tail chunks.
+    let mut file = File::open(file_path)?;
+    let tail_start = file.metadata()?.len() as usize - tail.len();
+    let stride = tail_start.saturating_sub(FILE_CHUNK_SIZE) / MIDDLE_SAMPLE_COUNT;
+    let middle = if stride > 0 {
+        let block_size = MIDDLE_SAMPLE_BLOCK_SIZE.min(stride);
+
+        let mut middle = vec![0; block_size * MIDDLE_SAMPLE_COUNT];
+        for (i, block) in middle.chunks_exact_mut(block_size).enumerate() {
+            file.seek(SeekFrom::Start((FILE_CHUNK_SIZE + i * stride) as u64))?;
+            file.read_exact(block)?;
+        }
+
+        Some(middle)
+    } else {
+        None
+    };
+
+    Ok(FileSample { head, tail, middle })
+}
+
+/// Attempt to read the tail chunk of a file.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file.
+///
+/// # Returns
+///
+/// A vector containing the final u8 values of the file if the data was successfully read, otherwise an error.
+pub fn read_file_tail_chunk(file_path: &str) -> Result<Vec<u8>, ItfError> {
+    let mut file = File::open(file_path)?;
+    let filesize = file.metadata()?.len() as usize;
+    let read_size = filesize.min(TAIL_CHUNK_SIZE);
+    file.seek(SeekFrom::Start((filesize - read_size) as u64))?;
+    let mut buffer = vec![0; read_size];
+    file.read_exact(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+/// Attempt to read the header chunk from a reader.
+///
+/// # Arguments
+///
+/// * `reader` - The source from which the data should be read.
+///
+/// # Returns
+///
+/// A vector containing up to the chunk size of u8 values if the data was successfully read, otherwise an error.
+pub fn read_header_chunk<R: Read>(reader: R) -> Result<Vec<u8>, ItfError> {
+    let mut buffer = Vec::new();
+    reader
+        .take(FILE_CHUNK_SIZE as u64)
+        .read_to_end(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+/// Attempt to read a [`FileSample`] from a reader.
+///
+/// # Arguments
+///
+/// * `reader` - The source from which the data should be read.
+///
+/// # Returns
+///
+/// A [`FileSample`] if the data was successfully read, otherwise an error.
+///
+/// # Notes
+///
+/// The reader will be consumed in order to locate the tail chunk, up to a total of [`MAX_STREAM_READ_SIZE`] bytes.
+/// Should the data be longer than this, the tail chunk will be taken from the end of the bytes that were read,
+/// so that an endless stream can't prevent the data from being identified.
+/// As the total length isn't known in advance, no middle sample will be taken.
+pub fn read_sample<R: Read>(mut reader: R) -> Result<FileSample, ItfError> {
+    let header = read_header_chunk(&mut reader)?;
+
+    // Keep a rolling window of the most recent bytes.
+    let mut remaining = reader.take((MAX_STREAM_READ_SIZE - FILE_CHUNK_SIZE) as u64);
+    let mut tail = Vec::with_capacity(TAIL_CHUNK_SIZE * 2);
+    let mut buffer = vec![0; TAIL_CHUNK_SIZE];
+    loop {
+        let read = match remaining.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        tail.extend_from_slice(&buffer[..read]);
+        if tail.len() > TAIL_CHUNK_SIZE {
+            tail.drain(..tail.len() - TAIL_CHUNK_SIZE);
+        }
+    }
+
+    // Any shortfall in the tail chunk will be made up from the end of the header chunk.
+    if tail.len() < TAIL_CHUNK_SIZE {
+        let header_tail = tail_slice(&header);
+        let needed = (TAIL_CHUNK_SIZE - tail.len()).min(header_tail.len());
+        tail.splice(
+            0..0,
+            header_tail[header_tail.len() - needed..].iter().copied(),
+        );
+    }
+
+    Ok(FileSample {
+        head: header,
+        tail,
+        middle: None,
+    })
+}
+
 /// Refine a common byte sequence set, based on a new u8 slice.
 ///
 /// # Arguments
@@ -404,6 +993,234 @@ pub fn refine_common_byte_sequences_v2(file_bytes: &[u8], sequences: &mut Vec<(u
     *sequences = final_sequences;
 }
 
+/// Reverse a set of sequences that were extracted from reversed tail chunks, converting them
+/// into trailer sequences.
+///
+/// # Arguments
+///
+/// * `sequences` - A mutable reference to the vector of tuples giving the position of the sequence and the byte sequence.
+///
+/// # Notes
+///
+/// Once converted, the position of each sequence gives the distance from the start of the sequence to the end of the file.
+pub(crate) fn reverse_trailer_sequences(sequences: &mut [(usize, Vec<u8>)]) {
+    for (index, sequence) in sequences {
+        sequence.reverse();
+        *index += sequence.len();
+    }
+}
+
+/// Synthesize a byte regex from a masked sequence, where each masked byte is replaced by
+/// a class listing the values that it was observed to take.
+///
+/// # Arguments
+///
+/// * `sequence` - The masked sequence.
+/// * `observed` - The values observed at each position of the sequence, or `None` if a position took too many values.
+///
+/// # Returns
+///
+/// The regex, or `None` if any of the masked bytes took too many values.
+pub(crate) fn synthesize_regex(
+    sequence: &[Option<u8>],
+    observed: &[Option<Vec<u8>>],
+) -> Option<String> {
+    // Unicode mode must be disabled for the escaped values to match individual bytes.
+    let mut regex = String::from("(?-u)");
+    for (byte, values) in sequence.iter().zip(observed) {
+        match (byte, values) {
+            (Some(b), _) => push_regex_byte(&mut regex, *b),
+            (None, Some(values)) if !values.is_empty() => {
+                let mut values = values.clone();
+                values.sort_unstable();
+
+                regex.push('[');
+                for b in values {
+                    push_regex_byte(&mut regex, b);
+                }
+                regex.push(']');
+            }
+            _ => return None,
+        }
+    }
+
+    Some(regex)
+}
+
+/// Strip any anchored strings that are entirely covered by a positional or masked byte sequence,
+/// since these would otherwise be scored twice.
+///
+/// # Arguments
+///
+/// * `strings` - A mutable reference to the vector of anchored strings.
+/// * `sequences` - The positional byte sequences.
+/// * `masked_sequences` - The masked byte sequences.
+pub(crate) fn strip_covered_anchored_strings<B: AsRef<[u8]>>(
+    strings: &mut Vec<(usize, String)>,
+    sequences: &[(usize, B)],
+    masked_sequences: &[(usize, Vec<Option<u8>>)],
+) {
+    let covers = |start: usize, len: usize, position: usize, string: &str| {
+        start <= position && position + string.len() <= start + len
+    };
+
+    strings.retain(|(position, string)| {
+        !sequences
+            .iter()
+            .any(|(start, s)| covers(*start, s.as_ref().len(), *position, string))
+            && !masked_sequences
+                .iter()
+                .any(|(start, s)| covers(*start, s.len(), *position, string))
+    });
+}
+
+/// Detect a small number of distinct headers shared by the sample files, such as the little and big endian
+/// variants of a format, which would otherwise leave no common sequence at the start of the file.
+///
+/// # Arguments
+///
+/// * `chunks` - The header chunks of the sample files.
+/// * `max_length` - The maximum length of a variant. This should be the position of the first byte covered by another sequence.
+///
+/// # Returns
+///
+/// A vector of the variants, each given as its position and the byte sequence, or an empty vector if there were
+/// too few or too many variants, or any variant was shared by too few sample files.
+///
+/// # Notes
+///
+/// The sample files are grouped by their leading bytes, with each variant being the longest prefix common to its group.
+pub(crate) fn detect_header_variants(
+    chunks: &[Vec<u8>],
+    max_length: usize,
+) -> Vec<(usize, Vec<u8>)> {
+    let max_length = max_length.min(MAX_BYTE_SEQUENCE_LENGTH);
+    if max_length < HEADER_VARIANT_KEY_LENGTH {
+        return vec![];
+    }
+
+    let mut variants: Vec<(Vec<u8>, usize)> = Vec::with_capacity(MAX_HEADER_VARIANTS);
+    for chunk in chunks {
+        let Some(key) = chunk.get(..HEADER_VARIANT_KEY_LENGTH) else {
+            return vec![];
+        };
+        let head = &chunk[..chunk.len().min(max_length)];
+
+        match variants.iter_mut().find(|(v, _)| v.starts_with(key)) {
+            Some((variant, support)) => {
+                *support += 1;
+                let common = variant.iter().zip(head).take_while(|(a, b)| a == b).count();
+                variant.truncate(common);
+            }
+            None => variants.push((head.to_vec(), 1)),
+        }
+    }
+
+    // As with sequences, purely null variants are unlikely to be helpful.
+    if !(2..=MAX_HEADER_VARIANTS).contains(&variants.len())
+        || variants
+            .iter()
+            .any(|(v, support)| *support < MIN_HEADER_VARIANT_SUPPORT || v.iter().all(|&b| b == 0))
+    {
+        return vec![];
+    }
+
+    let mut variants: Vec<(usize, Vec<u8>)> = variants.into_iter().map(|(v, _)| (0, v)).collect();
+    variants.sort_unstable();
+    variants
+}
+
+/// Merge any byte sequences that directly follow one another, provided that the merged sequence
+/// doesn't exceed the maximum sequence length.
+///
+/// # Arguments
+///
+/// * `sequences` - A mutable reference to the vector of tuples giving the position of the sequence and the byte sequence.
+///
+/// # Notes
+///
+/// Refining a set of sequences can leave the end of one sequence adjacent to the start of the next,
+/// as sequences are split at the maximum length when they are extracted.
+/// The merged sequences will be sorted by their position, ascending.
+pub(crate) fn merge_adjacent_sequences(sequences: &mut Vec<(usize, Vec<u8>)>) {
+    sequences.sort_unstable_by_key(|(start, _)| *start);
+
+    let mut merged: Vec<(usize, Vec<u8>)> = Vec::with_capacity(sequences.len());
+    for (start, sequence) in std::mem::take(sequences) {
+        match merged.last_mut() {
+            Some((s, b))
+                if *s + b.len() == start
+                    && b.len() + sequence.len() <= MAX_BYTE_SEQUENCE_LENGTH =>
+            {
+                b.extend_from_slice(&sequence);
+            }
+            _ => merged.push((start, sequence)),
+        }
+    }
+
+    *sequences = merged;
+}
+
+/// Strip any strings that are contained within a positional or masked byte sequence. As byte sequence
+/// matches aren't optional, such strings will be present within every matching file and would otherwise be scored twice.
+///
+/// # Arguments
+///
+/// * `strings` - A mutable reference to the vector of strings.
+/// * `sequences` - The positional byte sequences.
+/// * `masked_sequences` - The masked byte sequences.
+pub(crate) fn strip_sequence_covered_strings(
+    strings: &mut Vec<String>,
+    sequences: &[(usize, Vec<u8>)],
+    masked_sequences: &[(usize, Vec<Option<u8>>)],
+) {
+    // Strings are stored in uppercase, so the sequences must be too. Masked bytes can't cover a string.
+    let runs: Vec<Vec<u8>> = sequences
+        .iter()
+        .map(|(_, s)| s.to_ascii_uppercase())
+        .chain(masked_sequences.iter().flat_map(|(_, s)| {
+            s.split(Option::is_none)
+                .map(|run| run.iter().flatten().map(u8::to_ascii_uppercase).collect())
+        }))
+        .collect();
+
+    strings.retain(|string| {
+        let string = string.as_bytes();
+        !runs
+            .iter()
+            .any(|run| run.windows(string.len()).any(|w| w == string))
+    });
+}
+
+/// Strip any trailer sequences that are entirely covered by header sequences.
+/// This can only happen when every sample file has the same size, in which case the trailer
+/// sequences will be located at fixed positions from the start of the file.
+///
+/// # Arguments
+///
+/// * `trailer_sequences` - A mutable reference to the vector of trailer sequences.
+/// * `header_sequences` - A slice of the header sequences.
+/// * `masked_sequences` - A slice of the masked header sequences.
+/// * `file_size` - The common size of the sample files.
+pub(crate) fn strip_covered_trailer_sequences(
+    trailer_sequences: &mut Vec<(usize, Vec<u8>)>,
+    header_sequences: &[(usize, Vec<u8>)],
+    masked_sequences: &[(usize, Vec<Option<u8>>)],
+    file_size: usize,
+) {
+    trailer_sequences.retain(|(offset, sequence)| {
+        let start = file_size.saturating_sub(*offset);
+        !(start..start + sequence.len()).all(|i| {
+            header_sequences
+                .iter()
+                .any(|(s, b)| i >= *s && i < s + b.len())
+                || masked_sequences
+                    .iter()
+                    .any(|(s, b)| i >= *s && i < s + b.len() && b[i - s].is_some())
+        })
+    });
+}
+
 /// Strip sequences that don't conform to our requirements.
 ///
 /// # Arguments
@@ -415,3 +1232,64 @@ pub(crate) fn strip_unwanted_sequences(sequences: &mut Vec<(usize, Vec<u8>)>) {
     // 2. Any sequences that are purely null bytes. These are unlikely to be helpful.
     sequences.retain(|(_, b)| b.iter().all(|&x| x != 0) && b.len() >= MIN_BYTE_SEQUENCE_LENGTH);
 }
+
+/// Get the final section of a slice of bytes, up to the size of a tail chunk.
+///
+/// # Arguments
+///
+/// * `bytes` - The slice of u8 values.
+#[inline(always)]
+pub fn tail_slice(bytes: &[u8]) -> &[u8] {
+    &bytes[bytes.len().saturating_sub(TAIL_CHUNK_SIZE)..]
+}
+
+#[cfg(test)]
+mod tests_file_processor {
+    use std::io::{self, Read};
+
+    use super::{read_sample, FILE_CHUNK_SIZE, MAX_STREAM_READ_SIZE, TAIL_CHUNK_SIZE};
+
+    /// A reader that is interrupted before every read of the underlying reader, and counts the bytes read.
+    struct InterruptingReader<R> {
+        inner: R,
+        interrupted: bool,
+        total_read: usize,
+    }
+
+    impl<R: Read> Read for InterruptingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupted = !self.interrupted;
+            if self.interrupted {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+
+            let read = self.inner.read(buf)?;
+            self.total_read += read;
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn test_read_sample() {
+        let mut short = InterruptingReader {
+            inner: &b"HEADER-TAIL"[..],
+            interrupted: false,
+            total_read: 0,
+        };
+        let sample = read_sample(&mut short).expect("failed to read sample");
+        assert_eq!(sample.head, b"HEADER-TAIL");
+        assert_eq!(sample.tail, b"HEADER-TAIL");
+
+        // An endless stream is only read up to the limit.
+        let mut endless = InterruptingReader {
+            inner: b"HEADER".chain(io::repeat(b'A')),
+            interrupted: false,
+            total_read: 0,
+        };
+        let sample = read_sample(&mut endless).expect("failed to read sample");
+        assert_eq!(endless.total_read, MAX_STREAM_READ_SIZE);
+        assert_eq!(sample.head.len(), FILE_CHUNK_SIZE);
+        assert!(sample.head.starts_with(b"HEADER"));
+        assert_eq!(sample.tail, vec![b'A'; TAIL_CHUNK_SIZE]);
+    }
+}
@@ -0,0 +1,177 @@
+/// The characters used to encode a fuzzy hash digest.
+const DIGEST_CHARACTERS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// The target length of a fuzzy hash digest. The final character absorbs any overflow.
+const DIGEST_LENGTH: usize = 64;
+/// The smallest block size that may be used when computing a fuzzy hash.
+const MIN_BLOCK_SIZE: usize = 3;
+/// The size of the window used by the rolling hash.
+const ROLLING_WINDOW_SIZE: usize = 7;
+/// The FNV-1 offset basis.
+const FNV_OFFSET_BASIS: u32 = 0x2802_1967;
+/// The FNV-1 prime.
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// A rolling hash over the last [`ROLLING_WINDOW_SIZE`] bytes, used to locate the piece boundaries.
+#[derive(Default)]
+struct RollingHash {
+    window: [u8; ROLLING_WINDOW_SIZE],
+    position: usize,
+    h1: u32,
+    h2: u32,
+    h3: u32,
+}
+
+impl RollingHash {
+    fn update(&mut self, byte: u8) -> u32 {
+        let index = self.position % ROLLING_WINDOW_SIZE;
+
+        self.h2 = self.h2.wrapping_sub(self.h1);
+        self.h2 = self
+            .h2
+            .wrapping_add(ROLLING_WINDOW_SIZE as u32 * byte as u32);
+
+        self.h1 = self.h1.wrapping_add(byte as u32);
+        self.h1 = self.h1.wrapping_sub(self.window[index] as u32);
+
+        self.window[index] = byte;
+        self.position += 1;
+
+        self.h3 = (self.h3 << 5) ^ byte as u32;
+
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+/// Choose the block size that will give a digest of approximately [`DIGEST_LENGTH`] characters.
+///
+/// # Arguments
+///
+/// * `length` - The length of the data to be hashed.
+pub fn block_size_for(length: usize) -> usize {
+    let mut block_size = MIN_BLOCK_SIZE;
+    while block_size * DIGEST_LENGTH < length {
+        block_size *= 2;
+    }
+
+    block_size
+}
+
+/// Compute the context-triggered piecewise hash of a block of data, using a specific block size.
+///
+/// # Arguments
+///
+/// * `bytes` - The data to be hashed.
+/// * `block_size` - The block size, as given by [`block_size_for`].
+///
+/// # Returns
+///
+/// The digest string. Similar data hashed with the same block size will give similar digests.
+pub fn hash(bytes: &[u8], block_size: usize) -> String {
+    let mut rolling = RollingHash::default();
+    let mut piece_hash = FNV_OFFSET_BASIS;
+    let mut digest = String::with_capacity(DIGEST_LENGTH);
+
+    for &b in bytes {
+        piece_hash = piece_hash.wrapping_mul(FNV_PRIME) ^ b as u32;
+
+        // A piece ends whenever the rolling hash hits the trigger value, unless the digest is full.
+        let trigger = rolling.update(b) as usize % block_size == block_size - 1;
+        if trigger && digest.len() < DIGEST_LENGTH - 1 {
+            digest.push(DIGEST_CHARACTERS[piece_hash as usize % 64] as char);
+            piece_hash = FNV_OFFSET_BASIS;
+        }
+    }
+
+    if !bytes.is_empty() {
+        digest.push(DIGEST_CHARACTERS[piece_hash as usize % 64] as char);
+    }
+
+    digest
+}
+
+/// Compute the similarity of two digests that were computed with the same block size.
+///
+/// # Arguments
+///
+/// * `a` - The first digest.
+/// * `b` - The second digest.
+///
+/// # Returns
+///
+/// A value between 0 (entirely dissimilar) and 1 (identical).
+pub fn similarity(a: &str, b: &str) -> f32 {
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+
+    1.0 - edit_distance(a.as_bytes(), b.as_bytes()) as f32 / max_len as f32
+}
+
+/// Select the digest that is most similar to all of the others, so that it best represents the set.
+///
+/// # Arguments
+///
+/// * `digests` - The digests, all of which must have been computed with the same block size.
+pub fn representative(digests: &[String]) -> Option<&String> {
+    digests.iter().max_by(|a, b| {
+        let score = |d: &String| digests.iter().map(|o| similarity(d, o)).sum::<f32>();
+        score(a).total_cmp(&score(b))
+    })
+}
+
+/// Compute the Levenshtein edit distance between two byte strings.
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + (ca != cb) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests_fuzzy {
+    use super::{block_size_for, hash, representative, similarity};
+
+    fn sample(seed: u32, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_similar_data() {
+        let original = sample(1, 8192);
+        let block_size = block_size_for(original.len());
+
+        // Altering a few bytes should only affect a few pieces of the digest.
+        let mut altered = original.clone();
+        altered[1000..1010].fill(0);
+        let (a, b) = (hash(&original, block_size), hash(&altered, block_size));
+        assert!(similarity(&a, &b) > 0.8, "{a} vs {b}");
+
+        // But entirely different data should give an entirely different digest.
+        let c = hash(&sample(2, 8192), block_size);
+        assert!(similarity(&a, &c) < 0.5, "{a} vs {c}");
+    }
+
+    #[test]
+    fn test_representative() {
+        let digests = ["ABCD", "ABCE", "ABDD"].map(String::from);
+        assert_eq!(representative(&digests).map(String::as_str), Some("ABCD"));
+        assert!(representative(&[]).is_none());
+    }
+}
@@ -0,0 +1,595 @@
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::Serializer;
+use serde_derive::Serialize;
+use std::io::Read;
+
+use crate::{
+    error::ItfError,
+    file_point_calculator::{DefaultScoring, FilePointCalculator, MatchReport, ScoringStrategy},
+    file_processor::{self, FileSample},
+    pattern::Pattern,
+    pattern_handler::{LoadReport, PatternHandler},
+    text_analyzer::{self, TextInfo},
+    utils,
+};
+
+/// The MIME type given to results produced by the plain text fallback.
+pub const TEXT_MIMETYPE: &str = "text/plain";
+/// The maximum difference between the percentages of the top two results for an outcome to be considered ambiguous.
+pub const AMBIGUITY_MARGIN: f32 = 5.0;
+
+/// The ways in which the results of an identification can be ranked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RankBy {
+    /// Rank the results by the percentage of the maximum points that were awarded.
+    #[default]
+    Percentage,
+    /// Rank the results by their percentage, weighted by the amount of evidence that the pattern considers.
+    /// A pattern requiring only a few bytes can easily achieve a perfect match, and so is outranked by a more
+    /// detailed pattern with a slightly lower percentage.
+    Specificity,
+}
+
+/// The ways in which the extension of a file can be used to narrow the patterns that are tested against it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExtensionFilter {
+    /// Test every candidate pattern, regardless of the extension of the file.
+    #[default]
+    Off,
+    /// Test the patterns that list the extension of the file first, only falling back to the other candidate
+    /// patterns if none of them match.
+    Prefer,
+    /// Only test the patterns that list the extension of the file.
+    Strict,
+}
+
+/// The result of matching a single pattern.
+///
+/// When serialized, this gives the `uuid`, `name`, `mime`, `extension`, `points`, `max_points` and `percentage`
+/// of the result. This structure is relied upon by external tools, and so fields must not be renamed or removed.
+#[derive(Clone, Debug, Serialize)]
+pub struct MatchResult {
+    /// The UUID of the matching pattern.
+    pub uuid: String,
+    /// The name of the matching pattern.
+    pub name: String,
+    /// The best-guess MIME type of the matching pattern, if one is known.
+    #[serde(rename = "mime")]
+    pub mimetype: Option<String>,
+    /// The primary extension of the matching pattern, if one is known.
+    pub extension: Option<String>,
+    /// The number of points awarded for the match.
+    pub points: usize,
+    /// The maximum number of points that could have been awarded by the pattern.
+    pub max_points: usize,
+    /// The percentage of the maximum points that were awarded, rounded to 1 d.p.
+    #[serde(serialize_with = "serialize_percentage")]
+    pub percentage: f32,
+    /// The per-criterion breakdown of the points awarded.
+    #[serde(skip)]
+    pub report: MatchReport,
+    /// The priority of the matching pattern, used to break ties.
+    #[serde(skip)]
+    pub priority: i32,
+}
+
+/// Serialize a percentage as a f64 rounded to 1 d.p. For use with `#[serde(serialize_with)]`.
+///
+/// # Notes
+///
+/// Widening the f32 directly would expose its binary representation, giving a value such as 94.5999984741211
+/// in place of 94.6.
+fn serialize_percentage<S: Serializer>(percentage: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64((f64::from(*percentage) * 10.0).round() / 10.0)
+}
+
+impl MatchResult {
+    pub fn new(pattern: &Pattern, report: MatchReport) -> Self {
+        Self::scored(pattern, report, &DefaultScoring)
+    }
+
+    /// Create a result for a match against a pattern, scored using a specific [`ScoringStrategy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The matching pattern.
+    /// * `report` - The [`MatchReport`] of the points awarded for each criterion.
+    /// * `strategy` - The [`ScoringStrategy`] by which the points are scored.
+    pub fn scored(pattern: &Pattern, report: MatchReport, strategy: &dyn ScoringStrategy) -> Self {
+        let points = strategy.score(pattern, &report);
+        let max_points = strategy.max_points(pattern);
+
+        Self {
+            uuid: pattern.type_data.uuid.clone(),
+            name: pattern.type_data.name.clone(),
+            mimetype: pattern
+                .type_data
+                .known_mimetypes
+                .first()
+                .map(|m| m.to_string()),
+            extension: pattern
+                .type_data
+                .known_extensions
+                .first()
+                .map(|e| e.to_string()),
+            points,
+            max_points,
+            percentage: if max_points == 0 {
+                0.0
+            } else {
+                utils::round_to_dp(points as f32 / max_points as f32 * 100.0, 1)
+            },
+            report,
+            priority: pattern.type_data.priority,
+        }
+    }
+
+    /// Create a synthetic result describing data that matched no pattern, but appears to be plain text.
+    ///
+    /// # Arguments
+    ///
+    /// * `info` - The [`TextInfo`] describing the text.
+    pub fn from_text(info: &TextInfo) -> Self {
+        Self {
+            uuid: String::new(),
+            name: info.to_string(),
+            mimetype: Some(TEXT_MIMETYPE.to_string()),
+            extension: None,
+            points: 0,
+            max_points: 0,
+            percentage: 0.0,
+            report: MatchReport::default(),
+            priority: i32::MIN,
+        }
+    }
+
+    /// Is this a synthetic result describing plain text, created by [`MatchResult::from_text`], rather
+    /// than a match against a pattern?
+    pub fn is_text_fallback(&self) -> bool {
+        self.uuid.is_empty() && self.max_points == 0
+    }
+
+    /// Get the specificity score of this result, which combines the percentage with the magnitude of the
+    /// maximum number of points that could have been awarded by the pattern.
+    ///
+    /// # Notes
+    ///
+    /// The magnitude is taken logarithmically so that the percentage remains the dominant factor, with the
+    /// amount of evidence only deciding between results with similar percentages.
+    pub fn specificity(&self) -> f32 {
+        self.percentage * (self.max_points as f32 + 1.0).log2()
+    }
+
+    /// Get the score by which this result is ranked.
+    ///
+    /// # Arguments
+    ///
+    /// * `rank_by` - The ranking mode.
+    fn rank_score(&self, rank_by: RankBy) -> f32 {
+        match rank_by {
+            RankBy::Percentage => self.percentage,
+            RankBy::Specificity => self.specificity(),
+        }
+    }
+}
+
+/// The results for the patterns of a single format family, such as Matroska and WebM.
+///
+/// When serialized, this gives the fields of the primary result along with a `related` array of the other results.
+#[derive(Clone, Debug, Serialize)]
+pub struct FamilyMatch {
+    /// The result for the most specific pattern of the family that matched.
+    #[serde(flatten)]
+    pub primary: MatchResult,
+    /// The results for the other patterns of the family, in rank order.
+    pub related: Vec<MatchResult>,
+}
+
+/// The outcome of an identification attempt.
+#[derive(Clone, Debug)]
+pub enum MatchOutcome {
+    /// One or more patterns matched, sorted by percentage in descending order.
+    Identified(Vec<MatchResult>),
+    /// No pattern matched with sufficient confidence.
+    Unknown,
+}
+
+impl MatchOutcome {
+    /// Get the top match, if any.
+    pub fn top(&self) -> Option<&MatchResult> {
+        match self {
+            MatchOutcome::Identified(results) => results.first(),
+            MatchOutcome::Unknown => None,
+        }
+    }
+
+    /// Is this outcome [`MatchOutcome::Unknown`]?
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, MatchOutcome::Unknown)
+    }
+
+    /// Is this outcome ambiguous? That is, is the percentage of the runner-up within [`AMBIGUITY_MARGIN`]
+    /// of that of the top match?
+    pub fn is_ambiguous(&self) -> bool {
+        match self {
+            MatchOutcome::Identified(results) if results.len() > 1 => {
+                (results[0].percentage - results[1].percentage).abs() <= AMBIGUITY_MARGIN
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<Vec<MatchResult>> for MatchOutcome {
+    fn from(results: Vec<MatchResult>) -> Self {
+        if results.is_empty() {
+            MatchOutcome::Unknown
+        } else {
+            MatchOutcome::Identified(results)
+        }
+    }
+}
+
+pub struct Identifier {
+    pattern_handler: PatternHandler,
+    /// The minimum percentage that a result must achieve to be retained.
+    min_confidence: f32,
+    /// Should data that matches no pattern be analysed as plain text?
+    text_fallback: bool,
+    /// The compound extensions (e.g. `TAR.GZ`) that are recognised when matching file extensions.
+    compound_extensions: Vec<String>,
+    /// The manner in which the results are ranked.
+    rank_by: RankBy,
+    /// The manner in which the extension of a file narrows the patterns that are tested against it.
+    extension_filter: ExtensionFilter,
+    /// The policy by which the points awarded to a match are scored.
+    scoring_strategy: Box<dyn ScoringStrategy>,
+}
+
+impl Default for Identifier {
+    fn default() -> Self {
+        Self::new(PatternHandler::default())
+    }
+}
+
+impl Identifier {
+    pub fn new(mut pattern_handler: PatternHandler) -> Self {
+        pattern_handler.build_prefilter();
+
+        Self {
+            pattern_handler,
+            min_confidence: 0.0,
+            text_fallback: false,
+            compound_extensions: utils::DEFAULT_COMPOUND_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            rank_by: RankBy::default(),
+            extension_filter: ExtensionFilter::default(),
+            scoring_strategy: Box::new(DefaultScoring),
+        }
+    }
+
+    /// Set the minimum percentage that a result must achieve to be retained.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_confidence` - The minimum percentage, between 0 and 100.
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    /// Set whether data that matches no pattern should be analysed as plain text.
+    /// If the data appears to be text, a synthetic result describing its encoding will be returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `text_fallback` - Should the plain text fallback be enabled?
+    pub fn with_text_fallback(mut self, text_fallback: bool) -> Self {
+        self.text_fallback = text_fallback;
+        self
+    }
+
+    /// Set the compound extensions that are recognised when matching file extensions, such as `TAR.GZ`.
+    /// By default, the extensions given by [`utils::DEFAULT_COMPOUND_EXTENSIONS`] are recognised.
+    ///
+    /// # Arguments
+    ///
+    /// * `compound_extensions` - The compound extensions, with their components separated by a period.
+    pub fn with_compound_extensions(mut self, compound_extensions: Vec<String>) -> Self {
+        self.compound_extensions = compound_extensions;
+        self
+    }
+
+    /// Set the manner in which the results are ranked. By default, they are ranked by percentage.
+    ///
+    /// # Arguments
+    ///
+    /// * `rank_by` - The ranking mode.
+    pub fn with_rank_by(mut self, rank_by: RankBy) -> Self {
+        self.rank_by = rank_by;
+        self
+    }
+
+    /// Set the manner in which the extension of a file narrows the patterns that are tested against it.
+    /// By default, every candidate pattern is tested.
+    ///
+    /// # Arguments
+    ///
+    /// * `extension_filter` - The extension filter.
+    ///
+    /// # Notes
+    ///
+    /// The filter has no effect on data without a file name, or whose file name has no extension.
+    pub fn with_extension_filter(mut self, extension_filter: ExtensionFilter) -> Self {
+        self.extension_filter = extension_filter;
+        self
+    }
+
+    /// Set the policy by which the points awarded to a match are scored. By default, the [`DefaultScoring`]
+    /// strategy is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `scoring_strategy` - The [`ScoringStrategy`].
+    pub fn with_scoring_strategy<T: ScoringStrategy + 'static>(
+        mut self,
+        scoring_strategy: T,
+    ) -> Self {
+        self.scoring_strategy = Box::new(scoring_strategy);
+        self
+    }
+
+    /// Group a set of results by the format family of their patterns, as given by the parent UUIDs of the patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - The results, in rank order.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the groups, ordered by the rank of their best result.
+    ///
+    /// # Notes
+    ///
+    /// The primary result of a group is the one for the most specific (deepest) pattern whose percentage is within
+    /// [`AMBIGUITY_MARGIN`] of the best result of the group, so that a weak match against a specialised pattern
+    /// doesn't displace a strong match against the general pattern. Ties are resolved by rank.
+    pub fn group_by_family(&self, results: &[MatchResult]) -> Vec<FamilyMatch> {
+        // The members of each group, given as the root of the family followed by the depth and result of each member.
+        let mut groups: Vec<(&str, Vec<(usize, &MatchResult)>)> = Vec::new();
+        for result in results {
+            let lineage = self.pattern_handler.lineage(&result.uuid);
+            let root = lineage[lineage.len() - 1];
+            let member = (lineage.len(), result);
+            match groups.iter_mut().find(|(r, _)| *r == root) {
+                Some((_, members)) => members.push(member),
+                None => groups.push((root, vec![member])),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(_, members)| {
+                let best = members
+                    .iter()
+                    .map(|(_, r)| r.percentage)
+                    .fold(0.0, f32::max);
+                let primary = members
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, r))| best - r.percentage <= AMBIGUITY_MARGIN)
+                    .max_by(|(i, (a, _)), (j, (b, _))| a.cmp(b).then(j.cmp(i)))
+                    .map_or(0, |(i, _)| i);
+
+                FamilyMatch {
+                    primary: members[primary].1.clone(),
+                    related: members
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| *i != primary)
+                        .map(|(_, (_, r))| (*r).clone())
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Get a reference to the [`PatternHandler`] used by this identifier.
+    pub fn pattern_handler(&self) -> &PatternHandler {
+        &self.pattern_handler
+    }
+
+    /// Reload the patterns used by this identifier, so that any changes to the pattern files take effect.
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the reloaded patterns, or an error if they couldn't be reloaded, in which case the
+    /// previously loaded patterns will continue to be used. See [`PatternHandler::reload`].
+    pub fn reload_patterns(&mut self) -> Result<LoadReport, ItfError> {
+        self.pattern_handler.reload()
+    }
+
+    /// Attempt to identify a file on disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    ///
+    /// # Returns
+    ///
+    /// A vector of [`MatchResult`]s, sorted by percentage in descending order, or an error if the file could not be read.
+    pub fn identify_path(&self, path: &str) -> Result<Vec<MatchResult>, ItfError> {
+        let sample = file_processor::read_file_sample(path)?;
+
+        Ok(self.identify_sample(&sample, Some(path)))
+    }
+
+    /// Attempt to identify the data provided by a reader, such as a network stream or an archive entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The source of the data. This will be read to the end, up to a limit of
+    ///   [`file_processor::MAX_STREAM_READ_SIZE`] bytes, in order to locate the tail chunk.
+    /// * `file_name` - The name of the file from which the data originated, if known. Used for extension matching.
+    ///
+    /// # Returns
+    ///
+    /// A vector of [`MatchResult`]s, sorted by percentage in descending order, or an error if the data could not be read.
+    pub fn identify_reader<R: Read>(
+        &self,
+        reader: R,
+        file_name: Option<&str>,
+    ) -> Result<Vec<MatchResult>, ItfError> {
+        let sample = file_processor::read_sample(reader)?;
+
+        Ok(self.identify_sample(&sample, file_name))
+    }
+
+    /// Attempt to identify a block of bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - The bytes to be identified. This should be the header chunk of a file.
+    /// * `file_name` - The name of the file from which the bytes originated, if known. Used for extension matching.
+    ///
+    /// # Returns
+    ///
+    /// A vector of [`MatchResult`]s, sorted by percentage in descending order.
+    pub fn identify_bytes(&self, chunk: &[u8], file_name: Option<&str>) -> Vec<MatchResult> {
+        self.identify_sample(&FileSample::from_bytes(chunk), file_name)
+    }
+
+    /// Attempt to identify data based on a sample of its regions.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - The [`FileSample`] of the data.
+    /// * `file_name` - The name of the file from which the data originated, if known. Used for extension matching.
+    ///
+    /// # Returns
+    ///
+    /// A vector of [`MatchResult`]s, sorted by percentage in descending order.
+    pub fn identify_sample(
+        &self,
+        sample: &FileSample,
+        file_name: Option<&str>,
+    ) -> Vec<MatchResult> {
+        let candidates = self.pattern_handler.candidates(&sample.head);
+        let extension = file_name
+            .filter(|_| self.extension_filter != ExtensionFilter::Off)
+            .map(|f| {
+                (
+                    utils::get_compound_file_extension(f, &self.compound_extensions),
+                    utils::get_file_extension(f),
+                )
+            })
+            .filter(|(_, extension)| !extension.is_empty());
+
+        let mut results = match extension {
+            Some((compound, extension)) => {
+                let (listing, others): (Vec<_>, Vec<_>) = candidates.into_iter().partition(|p| {
+                    p.type_data
+                        .known_extensions
+                        .iter()
+                        .any(|e| **e == *compound || **e == *extension)
+                });
+
+                let results = self.match_candidates(&listing, sample, file_name);
+                if results.is_empty() && self.extension_filter == ExtensionFilter::Prefer {
+                    self.match_candidates(&others, sample, file_name)
+                } else {
+                    results
+                }
+            }
+            None => self.match_candidates(&candidates, sample, file_name),
+        };
+
+        // Sort the results by their ranking score, descending.
+        // Ties are broken by the pattern priority, with more specific patterns being listed first.
+        results.sort_unstable_by(|a, b| {
+            b.rank_score(self.rank_by)
+                .total_cmp(&a.rank_score(self.rank_by))
+                .then_with(|| b.priority.cmp(&a.priority))
+        });
+
+        if results.is_empty() && self.text_fallback {
+            if let Some(info) = text_analyzer::analyze(&sample.head) {
+                results.push(MatchResult::from_text(&info));
+            }
+        }
+
+        results
+    }
+
+    /// Match a sample of data against a set of candidate patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `candidates` - The candidate patterns.
+    /// * `sample` - The [`FileSample`] of the data.
+    /// * `file_name` - The name of the file from which the data originated, if known.
+    ///
+    /// # Returns
+    ///
+    /// An unsorted vector of the [`MatchResult`]s that achieved the minimum confidence.
+    fn match_candidates(
+        &self,
+        candidates: &[&Pattern],
+        sample: &FileSample,
+        file_name: Option<&str>,
+    ) -> Vec<MatchResult> {
+        utils::in_thread_pool(|| {
+            candidates
+                .par_iter()
+                .filter_map(|pattern| {
+                    let report = FilePointCalculator::compute_report(
+                        pattern,
+                        sample,
+                        file_name,
+                        &self.compound_extensions,
+                        true,
+                    )?;
+                    let result =
+                        MatchResult::scored(pattern, report, self.scoring_strategy.as_ref());
+                    // A strategy that can't award any points for the pattern can't produce a meaningful percentage.
+                    if result.points > 0
+                        && result.max_points > 0
+                        && result.percentage >= self.min_confidence
+                    {
+                        Some(result)
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests_identifier {
+    use crate::file_point_calculator::MatchReport;
+
+    use super::MatchResult;
+
+    #[test]
+    fn test_serialize_percentage() {
+        let result = MatchResult {
+            uuid: String::new(),
+            name: "test".to_string(),
+            mimetype: None,
+            extension: None,
+            points: 473,
+            max_points: 500,
+            percentage: 94.6,
+            report: MatchReport::default(),
+            priority: 0,
+        };
+
+        // The percentage is given as it's displayed, rather than as the nearest f64 to the f32.
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(json.contains(r#""percentage":94.6}"#), "{json}");
+    }
+}
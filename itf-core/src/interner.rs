@@ -0,0 +1,200 @@
+use hashbrown::{hash_map::DefaultHashBuilder, HashTable};
+use serde::{Deserialize, Deserializer};
+use std::{
+    hash::{BuildHasher, Hash},
+    sync::{Arc, Mutex, OnceLock, Weak},
+};
+
+/// The minimum number of entries that an interner will hold before its dead entries are pruned.
+const MIN_PRUNE_THRESHOLD: usize = 64;
+
+/// A list of positional byte sequences whose bytes have been interned.
+type InternedSequences = Vec<(usize, Arc<[u8]>)>;
+
+/// The strings that have been interned, shared between every pattern.
+static INTERNED_STRINGS: OnceLock<Interner<str>> = OnceLock::new();
+
+/// The byte sequences that have been interned, shared between every pattern.
+static INTERNED_SEQUENCES: OnceLock<Interner<[u8]>> = OnceLock::new();
+
+/// A set of shared values, each of which is only stored once.
+///
+/// # Notes
+///
+/// Only weak references to the values are held, so a value is dropped once no pattern uses it. The
+/// entries of the dropped values are pruned whenever the number of entries has doubled since the last prune.
+struct Interner<T: ?Sized> {
+    table: Mutex<InternTable<T>>,
+}
+
+struct InternTable<T: ?Sized> {
+    /// The hash of each value, along with a weak reference to it.
+    entries: HashTable<(u64, Weak<T>)>,
+    /// The hasher used to hash the values.
+    hasher: DefaultHashBuilder,
+    /// The number of entries at which the dead entries will next be pruned.
+    prune_threshold: usize,
+}
+
+impl<T: ?Sized + Hash + Eq> Interner<T>
+where
+    for<'a> Arc<T>: From<&'a T>,
+{
+    fn new() -> Self {
+        Self {
+            table: Mutex::new(InternTable {
+                entries: HashTable::new(),
+                hasher: DefaultHashBuilder::default(),
+                prune_threshold: MIN_PRUNE_THRESHOLD,
+            }),
+        }
+    }
+
+    /// Get the shared copy of a value, which is created if no shared copy is currently in use.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value.
+    fn intern(&self, value: &T) -> Arc<T> {
+        let mut table = self.table.lock().unwrap_or_else(|e| e.into_inner());
+        let hash = table.hasher.hash_one(value);
+
+        let existing = table
+            .entries
+            .find(hash, |(h, weak)| {
+                *h == hash && weak.upgrade().is_some_and(|v| *v == *value)
+            })
+            .and_then(|(_, weak)| weak.upgrade());
+        if let Some(shared) = existing {
+            return shared;
+        }
+
+        if table.entries.len() >= table.prune_threshold {
+            table.entries.retain(|(_, weak)| weak.strong_count() > 0);
+            table.prune_threshold = (table.entries.len() * 2).max(MIN_PRUNE_THRESHOLD);
+        }
+
+        let shared: Arc<T> = Arc::from(value);
+        table
+            .entries
+            .insert_unique(hash, (hash, Arc::downgrade(&shared)), |(h, _)| *h);
+        shared
+    }
+
+    /// The number of entries currently held, including those of values that have been dropped.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.table
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+}
+
+/// Get the shared copy of a string, so that each distinct string is only stored once regardless of
+/// how many patterns contain it.
+///
+/// # Arguments
+///
+/// * `string` - The string.
+///
+/// # Returns
+///
+/// The shared copy of the string, which is created if the string isn't currently interned.
+///
+/// # Notes
+///
+/// This is intended for the small set of strings that are repeated across many patterns, such as extensions,
+/// MIME types and categories. An interned string is dropped once every pattern that uses it has been dropped.
+pub fn intern(string: &str) -> Arc<str> {
+    INTERNED_STRINGS.get_or_init(Interner::new).intern(string)
+}
+
+/// Intern each of a list of strings.
+///
+/// # Arguments
+///
+/// * `strings` - The strings.
+pub fn intern_all<S: AsRef<str>>(strings: &[S]) -> Vec<Arc<str>> {
+    strings.iter().map(|s| intern(s.as_ref())).collect()
+}
+
+/// Get the shared copy of a byte sequence, so that a sequence common to many patterns, such as the
+/// header of a container format, is only stored once.
+///
+/// # Arguments
+///
+/// * `bytes` - The byte sequence.
+///
+/// # Returns
+///
+/// The shared copy of the byte sequence, which is created if the sequence isn't currently interned.
+pub fn intern_bytes(bytes: &[u8]) -> Arc<[u8]> {
+    INTERNED_SEQUENCES.get_or_init(Interner::new).intern(bytes)
+}
+
+/// Intern each of a list of positional byte sequences.
+///
+/// # Arguments
+///
+/// * `sequences` - The sequences, given as the position followed by the bytes.
+pub fn intern_sequences<B: AsRef<[u8]>>(
+    sequences: impl IntoIterator<Item = (usize, B)>,
+) -> InternedSequences {
+    sequences
+        .into_iter()
+        .map(|(position, bytes)| (position, intern_bytes(bytes.as_ref())))
+        .collect()
+}
+
+/// Deserialize a list of strings, interning each of them. For use with `#[serde(deserialize_with)]`.
+pub(crate) fn deserialize_interned<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Arc<str>>, D::Error> {
+    let strings = Vec::<String>::deserialize(deserializer)?;
+    Ok(intern_all(&strings))
+}
+
+/// Deserialize a list of positional byte sequences, interning each of them. For use with
+/// `#[serde(deserialize_with)]`.
+pub(crate) fn deserialize_interned_sequences<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<InternedSequences, D::Error> {
+    let sequences = Vec::<(usize, Vec<u8>)>::deserialize(deserializer)?;
+    Ok(intern_sequences(sequences))
+}
+
+#[cfg(test)]
+mod tests_interner {
+    use std::sync::Arc;
+
+    use super::{intern, intern_all, intern_bytes, Interner, MIN_PRUNE_THRESHOLD};
+
+    #[test]
+    fn test_intern() {
+        let first = intern("video/x-interner-test");
+        let second = intern(&String::from("video/x-interner-test"));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &intern("video/x-interner-other")));
+
+        let all = intern_all(&["video/x-interner-test", "video/x-interner-test"]);
+        assert!(all.iter().all(|s| Arc::ptr_eq(s, &first)));
+
+        let bytes = intern_bytes(b"INTERNER");
+        assert!(Arc::ptr_eq(&bytes, &intern_bytes(b"INTERNER")));
+    }
+
+    #[test]
+    fn test_intern_prune() {
+        let interner = Interner::<str>::new();
+        let kept = interner.intern("kept");
+
+        // Values that are no longer used are dropped, and their entries eventually pruned.
+        for i in 0..MIN_PRUNE_THRESHOLD * 4 {
+            interner.intern(&format!("dropped-{i}"));
+        }
+        assert!(interner.len() <= MIN_PRUNE_THRESHOLD * 2);
+        assert!(Arc::ptr_eq(&kept, &interner.intern("kept")));
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt::Write;
+
+use crate::{file_processor::FILE_CHUNK_SIZE, pattern::Pattern};
+
+/// The maximum number of tests in a single rule. Each test is nested one level deeper than the last,
+/// and libmagic limits the depth of the nesting.
+const MAX_RULE_TESTS: usize = 64;
+/// The maximum length of the value of a single test. Longer values are split across several tests.
+const MAX_TEST_LENGTH: usize = 64;
+/// The maximum number of rules that will be emitted for the combinations of alternative sequences.
+const MAX_ALTERNATIVE_RULES: usize = 16;
+
+/// A single test within a magic rule.
+#[derive(Clone, Debug, PartialEq)]
+enum MagicTest {
+    /// The bytes must be present at an offset from the start of the file.
+    At(usize, Vec<u8>),
+    /// The bytes must be present at an offset from the end of the file.
+    FromEnd(usize, Vec<u8>),
+    /// The bytes must be present within a range of offsets from the start of the file.
+    Search(usize, usize, Vec<u8>),
+    /// The string must be present, ignoring case, within a range of offsets from the start of the file.
+    SearchString(usize, usize, String),
+}
+
+impl MagicTest {
+    /// Write the test as a line of a magic rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The output string.
+    /// * `level` - The nesting level of the test.
+    /// * `description` - The description to be displayed when the test matches.
+    fn write(&self, out: &mut String, level: usize, description: &str) {
+        let indent = ">".repeat(level);
+        let (offset, kind, value) = match self {
+            MagicTest::At(offset, bytes) => (offset.to_string(), "string".to_string(), bytes),
+            MagicTest::FromEnd(offset, bytes) => {
+                (format!("-{offset}"), "string".to_string(), bytes)
+            }
+            MagicTest::Search(offset, range, bytes) => {
+                (offset.to_string(), format!("search/{range}"), bytes)
+            }
+            MagicTest::SearchString(offset, range, string) => {
+                // Strings are stored in uppercase, and the C flag matches these in either case.
+                let _ = writeln!(
+                    out,
+                    "{indent}{offset}\tsearch/{range}/C\t{}\t{description}",
+                    escape(string.as_bytes())
+                );
+                return;
+            }
+        };
+
+        let _ = writeln!(
+            out,
+            "{indent}{offset}\t{kind}\t{}\t{description}",
+            escape(value)
+        );
+    }
+}
+
+/// Convert a [`Pattern`] into file(1) magic rules.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+///
+/// # Returns
+///
+/// The magic rules, or `None` if the pattern has nothing that can be expressed as a magic rule.
+///
+/// # Notes
+///
+/// Every byte sequence of the pattern must match, so each test is nested within the previous one and only
+/// the final test gives the description. Each combination of alternative sequences gives a separate rule.
+/// The strings of the pattern are optional, and so are only used if the pattern has no anchored sequences.
+/// Regexes and the composition of the file can't be expressed, and are omitted.
+pub fn to_magic(pattern: &Pattern) -> Option<String> {
+    let data = &pattern.data;
+
+    let mut tests = Vec::new();
+    for (offset, sequence) in &data.sequences {
+        push_split(&mut tests, *offset, sequence, MagicTest::At);
+    }
+    for (offset, sequence) in &data.masked_sequences {
+        // Each run of unmasked bytes becomes a separate test.
+        let mut run_start = 0;
+        for run in sequence.split(Option::is_none) {
+            let bytes: Vec<u8> = run.iter().flatten().copied().collect();
+            if !bytes.is_empty() {
+                push_split(&mut tests, offset + run_start, &bytes, MagicTest::At);
+            }
+            run_start += run.len() + 1;
+        }
+    }
+    for (min, max, sequence) in &data.ranged_sequences {
+        tests.push(MagicTest::Search(*min, max - min + 1, sequence.clone()));
+    }
+    for (anchored_offset, string) in &data.anchored_strings {
+        tests.push(MagicTest::SearchString(*anchored_offset, 1, string.clone()));
+    }
+    tests.sort_by_key(|t| match t {
+        MagicTest::At(offset, _)
+        | MagicTest::Search(offset, _, _)
+        | MagicTest::SearchString(offset, _, _) => *offset,
+        MagicTest::FromEnd(..) => usize::MAX,
+    });
+    for (distance, sequence) in &data.trailer_sequences {
+        tests.push(MagicTest::FromEnd(*distance, sequence.clone()));
+    }
+
+    if tests.is_empty() && data.alternative_sequences.is_empty() {
+        let mut strings: Vec<&String> = data.strings.iter().collect();
+        strings.sort_unstable();
+        tests.extend(
+            strings
+                .into_iter()
+                .map(|s| MagicTest::SearchString(0, FILE_CHUNK_SIZE, s.clone())),
+        );
+    }
+
+    // Each combination of alternatives must be given as a separate rule, as a rule can only require all of its tests.
+    let mut rules = vec![tests];
+    for group in &data.alternative_sequences {
+        rules = rules
+            .iter()
+            .flat_map(|tests| {
+                group.iter().map(move |(offset, sequence)| {
+                    let mut tests = tests.clone();
+                    tests.insert(0, MagicTest::At(*offset, sequence.clone()));
+                    tests
+                })
+            })
+            .take(MAX_ALTERNATIVE_RULES)
+            .collect();
+    }
+
+    if rules.iter().all(Vec::is_empty) {
+        return None;
+    }
+
+    let type_data = &pattern.type_data;
+    let mut out = String::new();
+    let _ = writeln!(out, "# {} ({})", type_data.name, type_data.uuid);
+    for tests in rules.iter().filter(|t| !t.is_empty()) {
+        let tests = &tests[..tests.len().min(MAX_RULE_TESTS)];
+        for (level, test) in tests.iter().enumerate() {
+            let description = if level + 1 == tests.len() {
+                type_data.name.as_str()
+            } else {
+                ""
+            };
+            test.write(&mut out, level, description);
+        }
+
+        if let Some(mimetype) = type_data.known_mimetypes.first() {
+            let _ = writeln!(out, "!:mime\t{mimetype}");
+        }
+        if !type_data.known_extensions.is_empty() {
+            let extensions: Vec<String> = type_data
+                .known_extensions
+                .iter()
+                .map(|e| e.to_lowercase())
+                .collect();
+            let _ = writeln!(out, "!:ext\t{}", extensions.join("/"));
+        }
+    }
+
+    Some(out)
+}
+
+/// Add the tests for a sequence, splitting it into pieces that don't exceed the maximum length.
+///
+/// # Arguments
+///
+/// * `tests` - The tests.
+/// * `offset` - The offset of the sequence.
+/// * `sequence` - The byte sequence.
+/// * `make` - The constructor of the test.
+fn push_split(
+    tests: &mut Vec<MagicTest>,
+    offset: usize,
+    sequence: &[u8],
+    make: fn(usize, Vec<u8>) -> MagicTest,
+) {
+    for (i, piece) in sequence.chunks(MAX_TEST_LENGTH).enumerate() {
+        tests.push(make(offset + i * MAX_TEST_LENGTH, piece.to_vec()));
+    }
+}
+
+/// Escape a sequence of bytes for use as the value of a magic test.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes.
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b' ' => out.push_str("\\ "),
+            b'!'..=b'~' => out.push(*b as char),
+            _ => {
+                let _ = write!(out, "\\x{b:02x}");
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests_magic {
+    use crate::{file_processor::FILE_CHUNK_SIZE, interner::intern_sequences, pattern::Pattern};
+
+    use super::to_magic;
+
+    #[test]
+    fn test_to_magic() {
+        let mut pattern = Pattern::new(
+            "Test File",
+            "",
+            vec!["tst".to_string()],
+            vec!["application/x-test".to_string()],
+        );
+        assert!(to_magic(&pattern).is_none());
+
+        pattern.data.sequences =
+            intern_sequences(vec![(16, b"\x01TA IL".to_vec()), (0, b"MAGIC".to_vec())]);
+        pattern.data.masked_sequences = vec![(6, vec![Some(b'A'), None, Some(b'\\')])];
+        pattern.data.trailer_sequences = vec![(4, b"END!".to_vec())];
+        pattern.data.strings.insert("IGNORED".to_string());
+
+        let expected = format!(
+            "# Test File ({})\n\
+             0\tstring\tMAGIC\t\n\
+             >6\tstring\tA\t\n\
+             >>8\tstring\t\\\\\t\n\
+             >>>16\tstring\t\\x01TA\\ IL\t\n\
+             >>>>-4\tstring\tEND!\tTest File\n\
+             !:mime\tapplication/x-test\n\
+             !:ext\ttst\n",
+            pattern.type_data.uuid
+        );
+        assert_eq!(to_magic(&pattern).as_deref(), Some(expected.as_str()));
+    }
+
+    #[test]
+    fn test_to_magic_alternatives() {
+        let mut pattern = Pattern::new("Test", "", vec![], vec![]);
+        pattern.data.alternative_sequences = vec![vec![(0, b"II*".to_vec()), (0, b"MM*".to_vec())]];
+        pattern.data.sequences = intern_sequences(vec![(8, b"DATA".to_vec())]);
+
+        let magic = to_magic(&pattern).unwrap();
+        let lines: Vec<&str> = magic.lines().skip(1).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "0\tstring\tII*\t",
+                ">8\tstring\tDATA\tTest",
+                "0\tstring\tMM*\t",
+                ">8\tstring\tDATA\tTest"
+            ]
+        );
+
+        // Without any anchored sequences, the strings are searched for instead.
+        pattern.data.alternative_sequences.clear();
+        pattern.data.sequences.clear();
+        pattern.data.strings.insert("HEADER".to_string());
+        let magic = to_magic(&pattern).unwrap();
+        assert!(magic.ends_with(&format!("0\tsearch/{FILE_CHUNK_SIZE}/C\tHEADER\tTest\n")));
+    }
+}
@@ -0,0 +1,5 @@
+//! Conversion between patterns and the signature formats used by other file identification tools.
+
+pub mod magic;
+pub mod trid;
+pub mod yara;
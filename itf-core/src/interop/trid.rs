@@ -0,0 +1,220 @@
+use crate::{error::ItfError, interner, pattern::Pattern, utils};
+
+/// Convert a TrID XML definition into a [`Pattern`].
+///
+/// # Arguments
+///
+/// * `xml` - The contents of the TrID definition file.
+///
+/// # Returns
+///
+/// The converted pattern, or an error if the definition was missing its file type or contained invalid data.
+///
+/// # Notes
+///
+/// The front block patterns are converted into positional byte sequences and the global strings into strings.
+/// The name, extensions, MIME type, reference URL, author and number of scanned files are also preserved,
+/// and the pattern's source is recorded as TrID.
+pub fn from_trid_xml(xml: &str) -> Result<Pattern, ItfError> {
+    let invalid = |e: String| ItfError::InvalidPattern(format!("invalid TrID definition: {e}"));
+
+    let info = element(xml, "Info").unwrap_or_default();
+    let name = element(info, "FileType")
+        .map(unescape)
+        .filter(|n| !n.trim().is_empty())
+        .ok_or_else(|| invalid("missing file type".to_string()))?;
+
+    // Multiple extensions are separated by slashes (e.g. JPG/JPEG).
+    let extensions = element(info, "Ext")
+        .map(unescape)
+        .unwrap_or_default()
+        .split('/')
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+        .collect();
+    let mimetypes = element(info, "Mime")
+        .map(unescape)
+        .filter(|m| !m.trim().is_empty())
+        .into_iter()
+        .collect();
+    let extra_info = element(info, "ExtraInfo").unwrap_or_default();
+    let description = element(extra_info, "Rem").map(unescape).unwrap_or_default();
+
+    let mut pattern = Pattern::new(name.trim(), description.trim(), extensions, mimetypes);
+    pattern.other_data.file_format_url = element(extra_info, "RefURL")
+        .map(unescape)
+        .unwrap_or_default();
+    pattern.other_data.source = "TrID".to_string();
+    pattern.submitter_data.scanned_by = element(info, "User").map(unescape).unwrap_or_default();
+    pattern.submitter_data.scanned_by_email =
+        element(info, "E-Mail").map(unescape).unwrap_or_default();
+
+    let general = element(xml, "General").unwrap_or_default();
+    if let Some(files) = element(general, "FileNum") {
+        pattern.other_data.total_scanned_files = files
+            .trim()
+            .parse()
+            .map_err(|_| invalid(format!("invalid file count '{files}'")))?;
+    }
+
+    let front_block = element(xml, "FrontBlock").unwrap_or_default();
+    for block in elements(front_block, "Pattern") {
+        let position = element(block, "Pos").unwrap_or_default().trim();
+        let position: usize = position
+            .parse()
+            .map_err(|_| invalid(format!("invalid position '{position}'")))?;
+        let hex = element(block, "Bytes").unwrap_or_default().trim();
+        let bytes =
+            utils::decode_hex(hex).ok_or_else(|| invalid(format!("invalid bytes '{hex}'")))?;
+
+        if !bytes.is_empty() {
+            pattern
+                .data
+                .sequences
+                .push((position, interner::intern_bytes(&bytes)));
+        }
+    }
+    pattern
+        .data
+        .sequences
+        .sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+
+    // Strings are stored in uppercase, as they are when extracted from sample files.
+    let global_strings = element(xml, "GlobalStrings").unwrap_or_default();
+    pattern.data.strings = elements(global_strings, "String")
+        .into_iter()
+        .map(|s| unescape(s).to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    pattern.compute_attributes();
+
+    Ok(pattern)
+}
+
+/// Get the contents of the first element with a given tag.
+///
+/// # Arguments
+///
+/// * `xml` - The XML to be searched.
+/// * `tag` - The tag of the element.
+///
+/// # Notes
+///
+/// TrID definitions don't use attributes within the elements that are read, nor do they nest elements with
+/// the same tag, and so a full XML parser isn't required.
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    elements(xml, tag).into_iter().next()
+}
+
+/// Get the contents of every element with a given tag.
+///
+/// # Arguments
+///
+/// * `xml` - The XML to be searched.
+/// * `tag` - The tag of the elements.
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(end) = after.find(&close) else {
+            break;
+        };
+        found.push(&after[..end]);
+        rest = &after[end + close.len()..];
+    }
+
+    found
+}
+
+/// Replace the predefined XML entities with the characters that they represent.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests_trid {
+    use crate::interner::{intern_all, intern_sequences};
+
+    use super::from_trid_xml;
+
+    const DEFINITION: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<TrID ver="2.00">
+	<Info>
+		<FileType>Test &amp; Example Image</FileType>
+		<Ext>TST/TEST</Ext>
+		<Mime>image/x-test</Mime>
+		<ExtraInfo>
+			<Rem>A format used for testing.</Rem>
+			<RefURL>https://example.com/test</RefURL>
+		</ExtraInfo>
+		<User>Someone</User>
+		<E-Mail>someone@example.com</E-Mail>
+	</Info>
+	<General>
+		<FileNum>12</FileNum>
+		<CheckStrings>True</CheckStrings>
+	</General>
+	<FrontBlock>
+		<Pattern>
+			<Bytes>89544553540D0A</Bytes>
+			<Pos>0</Pos>
+		</Pattern>
+		<Pattern>
+			<Bytes>4844</Bytes>
+			<Pos>12</Pos>
+		</Pattern>
+	</FrontBlock>
+	<GlobalStrings>
+		<String>Header</String>
+		<String>DATA</String>
+	</GlobalStrings>
+</TrID>"#;
+
+    #[test]
+    fn test_from_trid_xml() {
+        let pattern = from_trid_xml(DEFINITION).expect("failed to convert the TrID definition");
+
+        assert_eq!(pattern.type_data.name, "Test & Example Image");
+        assert_eq!(pattern.type_data.description, "A format used for testing.");
+        assert_eq!(
+            pattern.type_data.known_extensions,
+            intern_all(&["TST", "TEST"])
+        );
+        assert_eq!(
+            pattern.type_data.known_mimetypes,
+            intern_all(&["image/x-test"])
+        );
+        assert_eq!(
+            pattern.other_data.file_format_url,
+            "https://example.com/test"
+        );
+        assert_eq!(pattern.other_data.total_scanned_files, 12);
+        assert_eq!(pattern.other_data.source, "TrID");
+        assert_eq!(pattern.submitter_data.scanned_by, "Someone");
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(12, b"HD".to_vec()), (0, b"\x89TEST\r\n".to_vec())])
+        );
+        assert!(pattern.data.strings.contains("HEADER"));
+        assert!(pattern.data.strings.contains("DATA"));
+        assert!(pattern.max_points > 0);
+    }
+
+    #[test]
+    fn test_from_trid_xml_invalid() {
+        assert!(from_trid_xml("<TrID><Info></Info></TrID>").is_err());
+        assert!(from_trid_xml(&DEFINITION.replace("4844", "48G4")).is_err());
+    }
+}
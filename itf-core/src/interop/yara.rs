@@ -0,0 +1,905 @@
+use std::{collections::HashMap, fmt::Write};
+
+use regex::Regex;
+
+use crate::{
+    error::ItfError,
+    file_processor::FILE_CHUNK_SIZE,
+    interner,
+    pattern::{CompiledRegex, Pattern},
+};
+
+/// The modifiers of text strings that don't change which bytes are matched.
+const IGNORED_MODIFIERS: &[&str] = &["ascii", "private"];
+
+/// The patterns converted from a file of YARA rules, and the parts of the rules that couldn't be converted.
+#[derive(Default)]
+pub struct YaraImport {
+    /// The patterns converted from the rules, in the order in which the rules were defined.
+    pub patterns: Vec<Pattern>,
+    /// A description of each part of the rules that couldn't be converted and was skipped.
+    pub warnings: Vec<String>,
+}
+
+/// A string defined within the strings section of a YARA rule.
+#[derive(Clone, Debug, PartialEq)]
+enum YaraString {
+    /// A text string, which is matched without regard to case if `nocase` is set.
+    Text { bytes: Vec<u8>, nocase: bool },
+    /// A hex string, in which a wildcard byte is represented by a `None` value.
+    Hex(Vec<Option<u8>>),
+    /// A regex, with any flags already applied.
+    Regex(String),
+}
+
+/// Convert the simple YARA rules within a file into [`Pattern`]s.
+///
+/// # Arguments
+///
+/// * `source` - The contents of the YARA rule file.
+///
+/// # Returns
+///
+/// The converted patterns along with a warning for each part of the rules that was skipped, or an
+/// error if the rules couldn't be parsed.
+///
+/// # Notes
+///
+/// Only conditions that join terms with `and` can be converted, as every attribute of a pattern must match.
+/// The supported terms are:
+///
+/// * `$a at N`, which gives a positional byte sequence, masked sequence or regex.
+/// * `$a in (N..M)` and `$a`, which give a ranged sequence. An unanchored string is only searched
+///   for within the first chunk of the file.
+/// * `uint8(N) == V`, along with the 16 and 32 bit variants (and their `be` forms), which give a positional byte sequence.
+/// * `all of them`, which treats every string as though it had been given alone.
+///
+/// Other terms are skipped with a warning, and a rule that can't be expressed at all (such as one that uses
+/// `or` or `not`) is skipped entirely. The `license` meta field, if present, is recorded as the license of the pattern.
+pub fn from_yara(source: &str) -> Result<YaraImport, ItfError> {
+    let source = strip_comments(source);
+    let rule_start =
+        Regex::new(r"\b(?:(?:private|global)\s+)*rule\s+([A-Za-z_]\w*)\s*(?::[^{]*)?\{")
+            .expect("the rule regex should be valid");
+
+    let mut import = YaraImport::default();
+    let mut offset = 0;
+    while let Some(captures) = rule_start.captures_at(&source, offset) {
+        let name = captures[1].to_string();
+        let body_start = captures.get(0).map_or(0, |m| m.end());
+        let body_end = find_closing_brace(&source, body_start)
+            .ok_or_else(|| invalid(format!("the rule '{name}' is unterminated")))?;
+        offset = body_end + 1;
+
+        if let Some(pattern) =
+            convert_rule(&name, &source[body_start..body_end], &mut import.warnings)?
+        {
+            import.patterns.push(pattern);
+        }
+    }
+
+    Ok(import)
+}
+
+/// Convert the body of a single YARA rule into a [`Pattern`].
+///
+/// # Arguments
+///
+/// * `name` - The name of the rule.
+/// * `body` - The body of the rule, without the enclosing braces.
+/// * `warnings` - The warnings, to which any skipped parts of the rule will be added.
+///
+/// # Returns
+///
+/// The pattern, `None` if the rule was skipped, or an error if the rule is malformed.
+fn convert_rule(
+    name: &str,
+    body: &str,
+    warnings: &mut Vec<String>,
+) -> Result<Option<Pattern>, ItfError> {
+    let sections = split_sections(body);
+    let meta = parse_meta(sections.get("meta").copied().unwrap_or_default());
+
+    let find_meta = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| meta.get(*k))
+            .cloned()
+            .unwrap_or_default()
+    };
+    let extensions = find_meta(&["extensions", "extension", "ext", "file_extension"])
+        .split([',', '/', ' '])
+        .map(|e| e.trim().trim_start_matches('.').to_uppercase())
+        .filter(|e| !e.is_empty())
+        .collect();
+    let mimetypes = Some(find_meta(&["mimetype", "mime_type", "mime"]))
+        .filter(|m| !m.is_empty())
+        .into_iter()
+        .collect();
+
+    let mut pattern = Pattern::new(name, &find_meta(&["description"]), extensions, mimetypes);
+    pattern.other_data.file_format_url = find_meta(&["reference", "url"]);
+    pattern.submitter_data.scanned_by = find_meta(&["author"]);
+    pattern.other_data.source = "YARA".to_string();
+    pattern.other_data.license = find_meta(&["license"]);
+
+    let mut strings = HashMap::new();
+    for (id, definition) in parse_strings(sections.get("strings").copied().unwrap_or_default())
+        .map_err(|e| invalid(format!("{name}: {e}")))?
+    {
+        let string = definition.map_err(|reason| {
+            warnings.push(format!(
+                "{name}: the string ${id} uses {reason}, and was skipped"
+            ));
+        });
+        strings.insert(id, string);
+    }
+
+    let condition = sections
+        .get("condition")
+        .map(|c| c.split_whitespace().collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+    // The operators must be distinguished from string identifiers with the same name, such as `$or` or `#not`.
+    if Regex::new(r"(?:^|[^\w$#@!])(or|not)\b")
+        .expect("the operator regex should be valid")
+        .is_match(&condition)
+    {
+        warnings.push(format!(
+            "{name}: the condition uses 'or' or 'not', which can't be expressed by a pattern, so the rule was skipped"
+        ));
+        return Ok(None);
+    }
+
+    for term in split_conjunction(&condition) {
+        if let Err(reason) = apply_term(&mut pattern, term, &strings) {
+            warnings.push(format!(
+                "{name}: the term '{term}' {reason}, and was skipped"
+            ));
+        }
+    }
+
+    let data = &pattern.data;
+    if data.sequences.is_empty()
+        && data.masked_sequences.is_empty()
+        && data.ranged_sequences.is_empty()
+        && data.regexes.is_empty()
+    {
+        warnings.push(format!(
+            "{name}: no part of the condition could be converted, so the rule was skipped"
+        ));
+        return Ok(None);
+    }
+
+    pattern
+        .data
+        .sequences
+        .sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+    pattern
+        .data
+        .masked_sequences
+        .sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+    pattern.compute_attributes();
+
+    Ok(Some(pattern))
+}
+
+/// Add the attributes described by a single term of a condition to a pattern.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+/// * `term` - The term.
+/// * `strings` - The strings of the rule, or the reason that they couldn't be converted.
+///
+/// # Returns
+///
+/// The reason that the term couldn't be converted, if applicable.
+fn apply_term(
+    pattern: &mut Pattern,
+    term: &str,
+    strings: &HashMap<String, Result<YaraString, ()>>,
+) -> Result<(), String> {
+    const NUMBER: &str = r"(0x[0-9A-Fa-f]+|\d+)";
+    let at = Regex::new(&format!(r"^\$(\w*) at {NUMBER}$")).expect("the regex should be valid");
+    let within = Regex::new(&format!(r"^\$(\w*) in \( ?{NUMBER} ?\.\. ?{NUMBER} ?\)$"))
+        .expect("the regex should be valid");
+    let bare = Regex::new(r"^\$(\w*)$").expect("the regex should be valid");
+    let integer = Regex::new(&format!(
+        r"^uint(8|16|32)(be)? ?\( ?{NUMBER} ?\) ?== ?{NUMBER}$"
+    ))
+    .expect("the regex should be valid");
+
+    let lookup = |id: &str| match strings.get(id) {
+        Some(Ok(string)) => Ok(string),
+        Some(Err(())) => Err("refers to a string that couldn't be converted".to_string()),
+        None => Err(format!("refers to the undefined string ${id}")),
+    };
+
+    if let Some(c) = at.captures(term) {
+        let position = parse_number(&c[2])?;
+        match lookup(&c[1])? {
+            YaraString::Text {
+                bytes,
+                nocase: false,
+            } => pattern
+                .data
+                .sequences
+                .push((position, interner::intern_bytes(bytes))),
+            YaraString::Text {
+                bytes,
+                nocase: true,
+            } => pattern
+                .data
+                .regexes
+                .push((position, format!("(?i-u){}", escape_regex(bytes)))),
+            YaraString::Hex(bytes) if bytes.iter().all(Option::is_some) => {
+                let bytes: Vec<u8> = bytes.iter().flatten().copied().collect();
+                pattern
+                    .data
+                    .sequences
+                    .push((position, interner::intern_bytes(&bytes)));
+            }
+            YaraString::Hex(bytes) => pattern
+                .data
+                .masked_sequences
+                .push((position, bytes.clone())),
+            YaraString::Regex(regex) => {
+                CompiledRegex::new(position, regex)
+                    .map_err(|_| "uses a regex that isn't supported".to_string())?;
+                pattern.data.regexes.push((position, regex.clone()));
+            }
+        }
+    } else if let Some(c) = within.captures(term) {
+        let (min, max) = (parse_number(&c[2])?, parse_number(&c[3])?);
+        if min > max {
+            return Err("has an empty range".to_string());
+        }
+        let bytes = exact_bytes(lookup(&c[1])?)?;
+        pattern.data.ranged_sequences.push((min, max, bytes));
+    } else if let Some(c) = bare.captures(term) {
+        let bytes = exact_bytes(lookup(&c[1])?)?;
+        pattern
+            .data
+            .ranged_sequences
+            .push((0, FILE_CHUNK_SIZE.saturating_sub(bytes.len()), bytes));
+    } else if let Some(c) = integer.captures(term) {
+        let width = parse_number(&c[1])? / 8;
+        let position = parse_number(&c[3])?;
+        let value = u64::try_from(parse_number(&c[4])?).unwrap_or(u64::MAX);
+        if width < 8 && value >> (width * 8) != 0 {
+            return Err("compares against a value that is too large".to_string());
+        }
+
+        let mut bytes = value.to_le_bytes()[..width].to_vec();
+        if c.get(2).is_some() {
+            bytes.reverse();
+        }
+        pattern
+            .data
+            .sequences
+            .push((position, interner::intern_bytes(&bytes)));
+    } else if term == "all of them" {
+        let mut ids: Vec<&String> = strings.keys().collect();
+        ids.sort_unstable();
+        for id in ids {
+            // The strings are applied individually, so that one that can't be converted doesn't affect the others.
+            let _ = apply_term(pattern, &format!("${id}"), strings);
+        }
+    } else {
+        return Err("isn't supported".to_string());
+    }
+
+    Ok(())
+}
+
+/// Get the bytes of a string that has no wildcards and is matched exactly.
+///
+/// # Arguments
+///
+/// * `string` - The string.
+fn exact_bytes(string: &YaraString) -> Result<Vec<u8>, String> {
+    match string {
+        YaraString::Text {
+            bytes,
+            nocase: false,
+        } => Ok(bytes.clone()),
+        YaraString::Hex(bytes) if bytes.iter().all(Option::is_some) => {
+            Ok(bytes.iter().flatten().copied().collect())
+        }
+        _ => Err("can only be converted if the string is given a fixed position".to_string()),
+    }
+}
+
+/// Split a condition into the terms that are joined by `and`, removing any grouping parentheses.
+///
+/// # Arguments
+///
+/// * `condition` - The condition, with its whitespace normalized.
+fn split_conjunction(condition: &str) -> Vec<&str> {
+    let condition = condition.trim();
+    if condition.is_empty() {
+        return Vec::new();
+    }
+
+    let mut terms = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in condition.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ if depth == 0 && condition[i..].starts_with(" and ") => {
+                terms.extend(split_conjunction(&condition[start..i]));
+                start = i + " and ".len();
+            }
+            _ => {}
+        }
+    }
+
+    let last = condition[start..].trim();
+    if start == 0 {
+        // A term that is wholly within parentheses is a group that may itself contain several terms.
+        match last.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            Some(inner) if find_closing_paren(last) == Some(last.len() - 1) => {
+                terms.extend(split_conjunction(inner));
+            }
+            _ => terms.push(last),
+        }
+    } else {
+        terms.extend(split_conjunction(last));
+    }
+
+    terms
+}
+
+/// Find the parenthesis that closes the opening parenthesis at the start of a string.
+///
+/// # Arguments
+///
+/// * `text` - The text, starting with an opening parenthesis.
+fn find_closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Split the body of a rule into its sections, keyed by the section label.
+///
+/// # Arguments
+///
+/// * `body` - The body of the rule.
+fn split_sections(body: &str) -> HashMap<&str, &str> {
+    let labels = Regex::new(r"\b(meta|strings|condition)\s*:").expect("the regex should be valid");
+    let quoted = quoted_ranges(body);
+
+    let found: Vec<_> = labels
+        .captures_iter(body)
+        .filter_map(|c| {
+            let (all, label) = (c.get(0)?, c.get(1)?);
+            (!quoted.iter().any(|r| r.contains(&all.start()))).then_some((
+                label.as_str(),
+                all.start(),
+                all.end(),
+            ))
+        })
+        .collect();
+
+    found
+        .iter()
+        .enumerate()
+        .map(|(i, (label, _, end))| {
+            let next = found.get(i + 1).map_or(body.len(), |f| f.1);
+            (*label, &body[*end..next])
+        })
+        .collect()
+}
+
+/// Parse the meta section of a rule.
+///
+/// # Arguments
+///
+/// * `meta` - The contents of the meta section.
+fn parse_meta(meta: &str) -> HashMap<String, String> {
+    let entry = Regex::new(r#"(?m)^\s*([A-Za-z_]\w*)\s*=\s*("(?:[^"\\]|\\.)*"|\S+)"#)
+        .expect("the regex should be valid");
+
+    entry
+        .captures_iter(meta)
+        .map(|c| {
+            let value = match c[2].strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(text) => String::from_utf8_lossy(&unescape_text(text)).into_owned(),
+                None => c[2].to_string(),
+            };
+            (c[1].to_lowercase(), value)
+        })
+        .collect()
+}
+
+/// The identifier of a string, and the string or the reason it couldn't be converted.
+type StringDefinition = (String, Result<YaraString, String>);
+
+/// Parse the strings section of a rule.
+///
+/// # Arguments
+///
+/// * `section` - The contents of the strings section.
+///
+/// # Returns
+///
+/// The identifier of each string along with the string or the reason that it couldn't be converted,
+/// or an error if the section is malformed.
+fn parse_strings(section: &str) -> Result<Vec<StringDefinition>, String> {
+    let mut definitions = Vec::new();
+    let mut rest = section.trim_start();
+
+    while !rest.is_empty() {
+        let Some(after) = rest.strip_prefix('$') else {
+            return Err(format!(
+                "expected a string definition at '{}'",
+                preview(rest)
+            ));
+        };
+        let id_length = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        let id = after[..id_length].to_string();
+        let Some(after) = after[id_length..].trim_start().strip_prefix('=') else {
+            return Err(format!("expected '=' after the string ${id}"));
+        };
+        let after = after.trim_start();
+
+        let (mut definition, after) = match after.chars().next() {
+            Some('"') => {
+                let end = find_unescaped(&after[1..], '"')
+                    .ok_or_else(|| format!("the string ${id} is unterminated"))?;
+                let bytes = unescape_text(&after[1..=end]);
+                (
+                    Ok(YaraString::Text {
+                        bytes,
+                        nocase: false,
+                    }),
+                    &after[end + 2..],
+                )
+            }
+            Some('{') => {
+                let end = after
+                    .find('}')
+                    .ok_or_else(|| format!("the string ${id} is unterminated"))?;
+                (parse_hex(&after[1..end]), &after[end + 1..])
+            }
+            Some('/') => {
+                let end = find_unescaped(&after[1..], '/')
+                    .ok_or_else(|| format!("the string ${id} is unterminated"))?;
+                let flags_length = after[end + 2..]
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(after.len() - end - 2);
+                let flags = &after[end + 2..end + 2 + flags_length];
+                let regex = if flags.is_empty() {
+                    format!("(?-u){}", &after[1..=end])
+                } else {
+                    format!("(?{flags}-u){}", &after[1..=end])
+                };
+                let definition = if flags.chars().all(|f| f == 'i' || f == 's') {
+                    Ok(YaraString::Regex(regex))
+                } else {
+                    Err(format!("the regex flags '{flags}'"))
+                };
+                (definition, &after[end + 2 + flags_length..])
+            }
+            _ => return Err(format!("the string ${id} has no value")),
+        };
+
+        // Any modifiers follow the value, up to the next definition.
+        let end = after.find('$').unwrap_or(after.len());
+        let modifiers = &after[..end];
+        rest = after[end..].trim_start();
+
+        for modifier in modifiers.split_whitespace() {
+            let name = modifier.split('(').next().unwrap_or_default();
+            definition = match definition {
+                Ok(YaraString::Text { bytes, .. }) if name == "nocase" => Ok(YaraString::Text {
+                    bytes,
+                    nocase: true,
+                }),
+                Ok(YaraString::Regex(regex)) if name == "nocase" => {
+                    Ok(YaraString::Regex(format!("(?i){regex}")))
+                }
+                Ok(string) if IGNORED_MODIFIERS.contains(&name) => Ok(string),
+                Ok(_) => Err(format!("the '{name}' modifier")),
+                Err(e) => Err(e),
+            };
+        }
+
+        definitions.push((id, definition));
+    }
+
+    Ok(definitions)
+}
+
+/// Parse the contents of a hex string.
+///
+/// # Arguments
+///
+/// * `hex` - The contents of the hex string, without the enclosing braces.
+fn parse_hex(hex: &str) -> Result<YaraString, String> {
+    let digits: String = hex.split_whitespace().collect();
+    if digits.contains('[') {
+        return Err("jumps".to_string());
+    } else if digits.contains('(') {
+        return Err("alternatives".to_string());
+    } else if digits.contains('~') {
+        return Err("negated bytes".to_string());
+    } else if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return Err("an invalid hex string".to_string());
+    }
+
+    let bytes = (0..digits.len())
+        .step_by(2)
+        .map(|i| match &digits[i..i + 2] {
+            "??" => Ok(None),
+            pair if pair.contains('?') => Err("nibble wildcards".to_string()),
+            pair => u8::from_str_radix(pair, 16)
+                .map(Some)
+                .map_err(|_| "an invalid hex string".to_string()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(YaraString::Hex(bytes))
+}
+
+/// Decode the escape sequences within a YARA text string.
+///
+/// # Arguments
+///
+/// * `text` - The text, without the enclosing quotes.
+fn unescape_text(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                bytes.push(u8::from_str_radix(&hex, 16).unwrap_or_default());
+            }
+            Some(c) => bytes.push(c as u8),
+            None => bytes.push(b'\\'),
+        }
+    }
+
+    bytes
+}
+
+/// Escape a sequence of bytes so that a regex matches them literally.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes.
+fn escape_regex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 4);
+    for b in bytes {
+        if b.is_ascii_alphanumeric() {
+            out.push(*b as char);
+        } else {
+            let _ = write!(out, "\\x{b:02X}");
+        }
+    }
+
+    out
+}
+
+/// Remove the comments from YARA source, leaving the contents of text strings intact.
+///
+/// # Arguments
+///
+/// * `source` - The YARA source.
+fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if c == '\\' {
+                out.extend(chars.next());
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push(c);
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                out.push(' ');
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Find the brace that closes a rule body, skipping over any quoted text.
+///
+/// # Arguments
+///
+/// * `source` - The YARA source.
+/// * `start` - The position immediately after the opening brace.
+fn find_closing_brace(source: &str, start: usize) -> Option<usize> {
+    let quoted = quoted_ranges(&source[start..]);
+    let mut depth = 1;
+    for (i, c) in source[start..].char_indices() {
+        if quoted.iter().any(|r| r.contains(&i)) {
+            continue;
+        }
+
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Get the ranges of the text that are within quotes.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+fn quoted_ranges(text: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find('"') {
+        let start = offset + start;
+        let end = find_unescaped(&text[start + 1..], '"').map_or(text.len(), |e| start + 1 + e);
+        ranges.push(start..end + 1);
+        offset = (end + 1).min(text.len());
+    }
+
+    ranges
+}
+
+/// Find the first instance of a character that isn't escaped with a backslash.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+/// * `target` - The character.
+fn find_unescaped(text: &str, target: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == target {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+/// Parse a decimal or hexadecimal number.
+///
+/// # Arguments
+///
+/// * `number` - The number.
+fn parse_number(number: &str) -> Result<usize, String> {
+    match number.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => number.parse(),
+    }
+    .map_err(|_| format!("has the invalid number '{number}'"))
+}
+
+/// Get the start of some text, for use within an error message.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+fn preview(text: &str) -> String {
+    text.chars().take(16).collect()
+}
+
+/// Create the error returned for rules that can't be parsed.
+///
+/// # Arguments
+///
+/// * `message` - The description of the problem.
+fn invalid(message: String) -> ItfError {
+    ItfError::InvalidPattern(format!("invalid YARA rule: {message}"))
+}
+
+#[cfg(test)]
+mod tests_yara {
+    use crate::{
+        file_processor::FILE_CHUNK_SIZE,
+        interner::{intern_all, intern_sequences},
+    };
+
+    use super::from_yara;
+
+    const RULES: &str = r#"
+import "pe"
+
+// A rule that can be converted completely.
+rule Test_Image : image
+{
+    meta:
+        description = "A \"test\" image"
+        extension = "tst,test"
+        mimetype = "image/x-test"
+        author = "Someone"
+        license = "MIT"
+    strings:
+        $magic = { 89 54 45 53 54 0D 0A }
+        $masked = { 48 ?? 44 }
+        $name = "Chunk" nocase
+        $tail = "END"
+        $data = /DAT[A-Z]/
+    condition:
+        $magic at 0 and ($masked at 12 and $name at 20) and
+        $tail in (100..200) and uint16be(8) == 0x0102 and $data at 30
+}
+
+/* A rule with parts that can't be converted. */
+rule Partial {
+    strings:
+        $a = "MZ"
+        $b = { 4D 5A [2-4] 50 45 }
+        $c = "wide" wide
+    condition:
+        $a at 0 and $b and $c and filesize < 1MB and all of them
+}
+
+// String identifiers may share the names of the operators.
+rule Identifiers {
+    strings:
+        $or = "OR"
+        $not = "NOT"
+    condition:
+        $or at 0 and $not at 4
+}
+
+rule Unsupported {
+    strings:
+        $a = "A"
+        $b = "B"
+    condition:
+        $a at 0 or $b at 0
+}
+"#;
+
+    #[test]
+    fn test_from_yara() {
+        let import = from_yara(RULES).expect("failed to convert the YARA rules");
+        assert_eq!(import.patterns.len(), 3);
+
+        let pattern = &import.patterns[0];
+        assert_eq!(pattern.type_data.name, "Test_Image");
+        assert_eq!(pattern.type_data.description, "A \"test\" image");
+        assert_eq!(
+            pattern.type_data.known_extensions,
+            intern_all(&["TST", "TEST"])
+        );
+        assert_eq!(
+            pattern.type_data.known_mimetypes,
+            intern_all(&["image/x-test"])
+        );
+        assert_eq!(pattern.submitter_data.scanned_by, "Someone");
+        assert_eq!(pattern.other_data.source, "YARA");
+        assert_eq!(pattern.other_data.license, "MIT");
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(8, vec![1, 2]), (0, b"\x89TEST\r\n".to_vec())])
+        );
+        assert_eq!(
+            pattern.data.masked_sequences,
+            vec![(12, vec![Some(b'H'), None, Some(b'D')])]
+        );
+        assert_eq!(
+            pattern.data.ranged_sequences,
+            vec![(100, 200, b"END".to_vec())]
+        );
+        assert_eq!(
+            pattern.data.regexes,
+            vec![
+                (20, "(?i-u)Chunk".to_string()),
+                (30, "(?-u)DAT[A-Z]".to_string())
+            ]
+        );
+        assert!(pattern.max_points > 0);
+
+        // The string with a jump, the wide string and the file size are skipped.
+        let pattern = &import.patterns[1];
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(0, b"MZ".to_vec())])
+        );
+        assert_eq!(
+            pattern.data.ranged_sequences,
+            vec![(0, FILE_CHUNK_SIZE - 2, b"MZ".to_vec())]
+        );
+
+        let pattern = &import.patterns[2];
+        assert_eq!(pattern.type_data.name, "Identifiers");
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(4, b"NOT".to_vec()), (0, b"OR".to_vec())])
+        );
+
+        let warnings: Vec<&str> = import
+            .warnings
+            .iter()
+            .map(|w| w.split(':').next().unwrap())
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "Partial",
+                "Partial",
+                "Partial",
+                "Partial",
+                "Partial",
+                "Unsupported"
+            ]
+        );
+        assert!(import.warnings[0].contains("jumps"));
+        assert!(import.warnings[1].contains("'wide'"));
+    }
+
+    #[test]
+    fn test_from_yara_invalid() {
+        assert!(from_yara("rule Broken { condition: true").is_err());
+        assert!(from_yara("rule Broken { strings: a = \"x\" condition: $a }").is_err());
+        assert!(from_yara("").unwrap().patterns.is_empty());
+    }
+}
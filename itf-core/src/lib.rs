@@ -1,37 +1,52 @@
 #![crate_name = "itf_core"]
 
+pub mod embedded_rule;
+pub mod error;
+pub mod evaluation;
 pub mod file_point_calculator;
 pub mod file_processor;
+pub mod fuzzy;
+pub mod identifier;
+pub mod interner;
+pub mod interop;
 pub mod pattern;
+pub mod pattern_archive;
+pub mod pattern_authoring;
+#[cfg(feature = "msgpack")]
+pub mod pattern_bundle;
+pub mod pattern_conflicts;
+pub mod pattern_diff;
+pub mod pattern_editor;
+#[cfg(feature = "fetch")]
+pub mod pattern_fetch;
 pub mod pattern_handler;
+pub mod pattern_index;
+pub mod pattern_lint;
+pub mod pattern_migration;
+pub mod pattern_report;
+pub mod pattern_selector;
+pub mod pattern_signing;
+pub mod result_template;
+pub mod string_blocklist;
 #[cfg(test)]
 mod test_utils;
+pub mod text_analyzer;
 pub mod utils;
 
 #[cfg(test)]
 mod tests_pattern {
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
     use std::{env, fs, path::PathBuf};
 
     use crate::{
-        file_point_calculator::FilePointCalculator, file_processor, pattern::Pattern,
-        pattern_handler::PatternHandler, test_utils, utils,
+        file_point_calculator::{MagicOnlyScoring, MatchReport, ScoringStrategy},
+        file_processor::{self, FILE_CHUNK_SIZE},
+        identifier::{ExtensionFilter, Identifier, MatchOutcome, MatchResult, RankBy},
+        interner::intern_sequences,
+        pattern::{BuildOptions, Pattern, PatternCompression},
+        pattern_handler::PatternHandler,
+        test_utils, utils,
     };
 
-    struct PatternMatch<'a> {
-        pub uuid: &'a str,
-        pub percentage: f32,
-    }
-
-    impl<'a> PatternMatch<'a> {
-        pub fn new(uuid: &'a str, points: usize, max_points: usize) -> Self {
-            Self {
-                uuid,
-                percentage: utils::round_to_dp(points as f32 / max_points as f32 * 100.0, 1),
-            }
-        }
-    }
-
     /// Automatically delete a file after a test has been completed.
     /// Use with care! Files go bye-bye!
     struct FileDropper {
@@ -48,7 +63,7 @@ mod tests_pattern {
         #[allow(unused)]
         path: FileDropper,
         pub new_pattern: Pattern,
-        pub pattern_handler: PatternHandler,
+        pub identifier: Identifier,
         pub test_dir: String,
     }
 
@@ -71,7 +86,7 @@ mod tests_pattern {
             Self {
                 path,
                 new_pattern: pattern,
-                pattern_handler,
+                identifier: Identifier::new(pattern_handler),
                 test_dir,
             }
         }
@@ -80,10 +95,14 @@ mod tests_pattern {
             let test_dir = test_utils::test_path_builder(test_type, test_id);
 
             let mut pattern = Pattern::new("valid", "test", vec!["test".to_string()], vec![]);
-            pattern.build_patterns_from_data(&test_dir, "test", true, true, true);
+            pattern
+                .build_patterns_from_data(&test_dir, "test", &BuildOptions::default())
+                .expect("failed to build test pattern");
 
             // Write the pattern file.
-            let path = pattern.write(&test_dir).expect("failed to write test file");
+            let path = pattern
+                .write(&test_dir, PatternCompression::None)
+                .expect("failed to write test file");
 
             (FileDropper { path }, pattern)
         }
@@ -103,7 +122,7 @@ mod tests_pattern {
         let test = TestEntry::new(1);
 
         // There should only ever be a single result.
-        let results = match_patterns(&test.pattern_handler, &test.get_test_file(0));
+        let results = match_patterns(&test.identifier, &test.get_test_file(0));
         assert_eq!(results.len(), 1);
 
         let first_result = results.first().unwrap();
@@ -118,7 +137,7 @@ mod tests_pattern {
         let test = TestEntry::new(2);
 
         // There should only ever be a single result.
-        let results = match_patterns(&test.pattern_handler, &test.get_test_file(0));
+        let results = match_patterns(&test.identifier, &test.get_test_file(0));
         assert_eq!(results.len(), 1);
 
         let top_match = results.first().unwrap();
@@ -134,7 +153,7 @@ mod tests_pattern {
 
         // There should be two results, the created pattern being the top one.
         // This is because the existing pattern is a "less perfect" match.
-        let results = match_patterns(&test.pattern_handler, &test.get_test_file(0));
+        let results = match_patterns(&test.identifier, &test.get_test_file(0));
         assert_eq!(results.len(), 2);
 
         let top_match = results.first().unwrap();
@@ -148,10 +167,371 @@ mod tests_pattern {
 
         // There should be no matches for the target file since it is
         // fundamentally different than the defined pattern file.
-        let results = match_patterns(&test.pattern_handler, &test.get_test_file_of_type(0, "abc"));
+        let results = match_patterns(&test.identifier, &test.get_test_file_of_type(0, "abc"));
         assert_eq!(results.len(), 0);
     }
 
+    #[test]
+    fn test_matching_reader() {
+        let test = TestEntry::new(5);
+        let path = test.get_test_file(0);
+
+        // Identifying from a reader with a file name hint should be identical to
+        // identifying the file directly.
+        let file = fs::File::open(&path).expect("failed to open test file");
+        let results = test
+            .identifier
+            .identify_reader(file, Some(&path))
+            .expect("failed to read test file");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].uuid, test.new_pattern.type_data.uuid);
+        assert_eq!(results[0].percentage, 100.0);
+
+        // Without a file name hint, no extension points can be awarded.
+        let bytes = fs::read(&path).expect("failed to read test file");
+        let results = test.identifier.identify_bytes(&bytes, None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].uuid, test.new_pattern.type_data.uuid);
+        assert!(results[0].percentage < 100.0);
+    }
+
+    #[test]
+    fn test_matching_ranged_sequence() {
+        let mut pattern = Pattern::new("ranged", "test", vec![], vec![]);
+        pattern.data.ranged_sequences = vec![(2, 6, b"MAGIC".to_vec())];
+        pattern.other_data.total_scanned_files = 1;
+        pattern.compute_attributes();
+
+        let mut pattern_handler = PatternHandler::default();
+        pattern_handler.patterns.push(pattern);
+        let identifier = Identifier::new(pattern_handler);
+
+        // The sequence may start at any position within the window.
+        for data in [&b"..MAGIC..."[..], b"......MAGIC"] {
+            let results = identifier.identify_bytes(data, None);
+            assert_eq!(results.len(), 1);
+        }
+
+        // But not before or after it.
+        for data in [&b".MAGIC....."[..], b".......MAGIC"] {
+            let results = identifier.identify_bytes(data, None);
+            assert!(results.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_matching_min_confidence() {
+        let mut pattern = Pattern::new("confidence", "test", vec!["test".to_string()], vec![]);
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        pattern.other_data.total_scanned_files = 1;
+        pattern.compute_attributes();
+
+        let mut pattern_handler = PatternHandler::default();
+        pattern_handler.patterns.push(pattern);
+
+        // Without a file name, the extension points can't be awarded.
+        let identifier = Identifier::new(pattern_handler).with_min_confidence(100.0);
+        let results = identifier.identify_bytes(b"MAGIC...", None);
+        assert!(MatchOutcome::from(results).is_unknown());
+
+        // With a file name, the match is perfect and should be retained.
+        let results = identifier.identify_bytes(b"MAGIC...", Some("file.test"));
+        let outcome = MatchOutcome::from(results);
+        assert_eq!(outcome.top().map(|r| r.percentage), Some(100.0));
+    }
+
+    #[test]
+    fn test_matching_report() {
+        let mut pattern = Pattern::new("report", "test", vec!["test".to_string()], vec![]);
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        pattern.other_data.total_scanned_files = 1;
+        pattern.compute_attributes();
+
+        let mut pattern_handler = PatternHandler::default();
+        pattern_handler.patterns.push(pattern);
+        let identifier = Identifier::new(pattern_handler);
+
+        let results = identifier.identify_bytes(b"MAGIC...", Some("file.test"));
+        assert_eq!(results.len(), 1);
+
+        // The points should be broken down by the criterion that awarded them.
+        let report = &results[0].report;
+        assert_eq!(report.sequence_points, 5.0);
+        assert_eq!(report.string_points, 0.0);
+        assert_eq!(report.extension_points, 5.0);
+        assert_eq!(report.total(), results[0].points);
+
+        // The serialized form of a result is relied upon by external tools.
+        let json = serde_json::to_value(&results[0]).unwrap();
+        let mut keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "extension",
+                "max_points",
+                "mime",
+                "name",
+                "percentage",
+                "points",
+                "uuid"
+            ]
+        );
+        assert_eq!(json["extension"], "TEST");
+        assert!(!MatchOutcome::from(results).is_ambiguous());
+    }
+
+    #[test]
+    fn test_matching_priority() {
+        let mut pattern_handler = PatternHandler::default();
+        for (name, priority) in [("generic", 0), ("specific", 10), ("other", -10)] {
+            let mut pattern = Pattern::new(name, "test", vec![], vec![]);
+            pattern.type_data.priority = priority;
+            pattern.data.sequences = intern_sequences(vec![(0, b"PK".to_vec())]);
+            pattern.other_data.total_scanned_files = 1;
+            pattern.compute_attributes();
+
+            pattern_handler.patterns.push(pattern);
+        }
+        let identifier = Identifier::new(pattern_handler);
+
+        // All of the patterns match perfectly, so the priority should determine the order.
+        let results = identifier.identify_bytes(b"PK...", None);
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["specific", "generic", "other"]);
+        assert!(MatchOutcome::from(results).is_ambiguous());
+    }
+
+    #[test]
+    fn test_matching_rank_by() {
+        let mut pattern_handler = PatternHandler::default();
+        for (name, extension, sequence) in [
+            ("tiny", "test", &b"PK"[..]),
+            (
+                "detailed",
+                "other",
+                b"PK\x03\x04\x14\x00\x06\x00\x08\x00\x00\x00!\x00\x00\x00",
+            ),
+        ] {
+            let mut pattern = Pattern::new(name, "test", vec![extension.to_string()], vec![]);
+            pattern.data.sequences = intern_sequences(vec![(0, sequence.to_vec())]);
+            pattern.other_data.total_scanned_files = 1;
+            pattern.compute_attributes();
+
+            pattern_handler.patterns.push(pattern);
+        }
+        let data = b"PK\x03\x04\x14\x00\x06\x00\x08\x00\x00\x00!\x00\x00\x00....";
+
+        // The tiny pattern is a perfect match, and so has the higher percentage.
+        let identifier = Identifier::new(pattern_handler);
+        let results = identifier.identify_bytes(data, Some("file.test"));
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["tiny", "detailed"]);
+        assert_eq!(results[0].percentage, 100.0);
+
+        // But the detailed pattern is more specific.
+        let identifier = identifier.with_rank_by(RankBy::Specificity);
+        let results = identifier.identify_bytes(data, Some("file.test"));
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["detailed", "tiny"]);
+        assert!(results[0].specificity() > results[1].specificity());
+    }
+
+    /// A [`ScoringStrategy`] whose maximum is always 0 points, and so can't produce a percentage.
+    struct ZeroMaximumScoring;
+
+    impl ScoringStrategy for ZeroMaximumScoring {
+        fn score(&self, _pattern: &Pattern, report: &MatchReport) -> usize {
+            report.total()
+        }
+
+        fn max_points(&self, _pattern: &Pattern) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn test_matching_scoring_strategy() {
+        let mut magic = Pattern::new("magic", "test", vec!["test".to_string()], vec![]);
+        magic.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        magic.data.strings.insert("IDENTIFYING".to_string());
+        let mut strings = Pattern::new("strings", "test", vec!["test".to_string()], vec![]);
+        strings.data.strings.insert("IDENTIFYING".to_string());
+
+        let mut pattern_handler = PatternHandler::default();
+        for mut pattern in [magic, strings] {
+            pattern.other_data.total_scanned_files = 1;
+            pattern.compute_attributes();
+            pattern_handler.patterns.push(pattern);
+        }
+        let data = b"MAGIC\x00IDENTIFYING\x00";
+
+        // By default, every criterion is scored, so the missing extension reduces the percentage.
+        let identifier = Identifier::new(pattern_handler);
+        let results = identifier.identify_bytes(data, Some("file.other"));
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["magic", "strings"]);
+        assert!(results[0].percentage < 100.0);
+
+        // Only the byte sequences are scored by the strict strategy, so patterns without any won't match.
+        let identifier = identifier.with_scoring_strategy(MagicOnlyScoring);
+        let results = identifier.identify_bytes(data, Some("file.other"));
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["magic"]);
+        assert!(results[0].report.string_points > 0.0);
+        assert_eq!(results[0].points, results[0].report.positional_total());
+        assert_eq!(results[0].percentage, 100.0);
+
+        // A maximum of 0 points is treated as no match, rather than giving an undefined percentage.
+        let identifier = identifier
+            .with_scoring_strategy(ZeroMaximumScoring)
+            .with_min_confidence(0.0);
+        assert!(identifier
+            .identify_bytes(data, Some("file.other"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_matching_extension_filter() {
+        let mut pattern_handler = PatternHandler::default();
+        for (name, extension) in [("zip", "zip"), ("docx", "docx")] {
+            let mut pattern = Pattern::new(name, "test", vec![extension.to_string()], vec![]);
+            pattern.data.sequences = intern_sequences(vec![(0, b"PK\x03\x04".to_vec())]);
+            pattern.other_data.total_scanned_files = 1;
+            pattern.compute_attributes();
+
+            pattern_handler.patterns.push(pattern);
+        }
+        let data = b"PK\x03\x04....";
+        let names = |identifier: &Identifier, file_name: Option<&str>| {
+            let mut names: Vec<String> = identifier
+                .identify_bytes(data, file_name)
+                .into_iter()
+                .map(|r| r.name)
+                .collect();
+            names.sort_unstable();
+            names
+        };
+
+        // Only the patterns listing the extension are tested, unless none of them match.
+        let identifier =
+            Identifier::new(pattern_handler).with_extension_filter(ExtensionFilter::Prefer);
+        assert_eq!(names(&identifier, Some("file.docx")), ["docx"]);
+        assert_eq!(names(&identifier, Some("file.bin")), ["docx", "zip"]);
+
+        // The strict filter never falls back, but has no effect without an extension.
+        let identifier = identifier.with_extension_filter(ExtensionFilter::Strict);
+        assert_eq!(names(&identifier, Some("FILE.ZIP")), ["zip"]);
+        assert!(names(&identifier, Some("file.bin")).is_empty());
+        assert_eq!(names(&identifier, Some("file")), ["docx", "zip"]);
+        assert_eq!(names(&identifier, None), ["docx", "zip"]);
+    }
+
+    #[test]
+    fn test_matching_families() {
+        let mut matroska = Pattern::new("Matroska", "test", vec!["mkv".to_string()], vec![]);
+        matroska.data.sequences = intern_sequences(vec![(0, b"\x1A\x45\xDF\xA3".to_vec())]);
+        let mut webm = Pattern::new("WebM", "test", vec!["webm".to_string()], vec![]);
+        webm.data.sequences = matroska.data.sequences.clone();
+        webm.data.strings.insert("WEBMDOCTYPE".to_string());
+        webm.type_data.parent_uuid = Some(matroska.type_data.uuid.clone());
+        let mut other = Pattern::new("Other", "test", vec!["other".to_string()], vec![]);
+        other.data.sequences = intern_sequences(vec![(0, b"\x1A\x45".to_vec())]);
+
+        let mut pattern_handler = PatternHandler::default();
+        for mut pattern in [matroska, webm, other] {
+            pattern.other_data.total_scanned_files = 1;
+            pattern.compute_attributes();
+            pattern_handler.patterns.push(pattern);
+        }
+        let identifier = Identifier::new(pattern_handler);
+
+        let names = |data: &[u8]| -> Vec<(String, Vec<String>)> {
+            let results = identifier.identify_bytes(data, None);
+            identifier
+                .group_by_family(&results)
+                .into_iter()
+                .map(|g| {
+                    let related = g.related.into_iter().map(|r| r.name).collect();
+                    (g.primary.name, related)
+                })
+                .collect()
+        };
+
+        // The more specific pattern is the primary result when it matches as well as its parent.
+        assert_eq!(
+            names(b"\x1A\x45\xDF\xA3\0\0\0\0webmdoctype\0\0\0\0"),
+            [
+                ("WebM".to_string(), vec!["Matroska".to_string()]),
+                ("Other".to_string(), vec![])
+            ]
+        );
+
+        // But not when it's a much weaker match.
+        assert_eq!(
+            names(b"\x1A\x45\xDF\xA3\0\0\0\0matroska\0\0\0\0"),
+            [
+                ("Matroska".to_string(), vec!["WebM".to_string()]),
+                ("Other".to_string(), vec![])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matching_text_fallback() {
+        let test = TestEntry::new(6);
+        let path = test.get_test_file_of_type(0, "txt");
+
+        // The text file doesn't match the pattern, and the fallback is disabled by default.
+        let results = match_patterns(&test.identifier, &path);
+        assert!(results.is_empty());
+
+        let identifier =
+            Identifier::new(built_pattern_handler(&test.test_dir, "")).with_text_fallback(true);
+        let results = match_patterns(&identifier, &path);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "Plain text (UTF-8, CRLF)");
+        assert_eq!(results[0].mimetype.as_deref(), Some("text/plain"));
+        assert!(results[0].is_text_fallback());
+    }
+
+    #[test]
+    fn test_matching_middle_sample() {
+        // The string lies beyond the header chunk, but before the tail chunk.
+        let mut data = vec![0; FILE_CHUNK_SIZE + 1024 * 1024];
+        data[FILE_CHUNK_SIZE + 16..FILE_CHUNK_SIZE + 28].copy_from_slice(b"MIDDLESTRING");
+
+        let path = env::temp_dir().join(format!("itf-middle-{}.test", utils::make_uuid()));
+        fs::write(&path, &data).expect("failed to write test file");
+        let _dropper = FileDropper { path: path.clone() };
+        let path = path.to_string_lossy().to_string();
+
+        let sample = file_processor::read_file_sample(&path).expect("failed to read test file");
+        assert!(sample.middle.is_some());
+        assert_eq!(
+            file_processor::FileSample::from_file_bytes(&data).middle,
+            sample.middle
+        );
+
+        let mut pattern = Pattern::new("middle", "test", vec![], vec![]);
+        pattern.data.strings.insert("MIDDLESTRING".to_string());
+        pattern.other_data.total_scanned_files = 1;
+        pattern.compute_attributes();
+
+        let mut pattern_handler = PatternHandler::default();
+        pattern_handler.patterns.push(pattern);
+        let identifier = Identifier::new(pattern_handler);
+
+        let results = match_patterns(&identifier, &path);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].report.string_points, 12.0);
+    }
+
     fn built_pattern_handler(source_directory: &str, target_pattern: &str) -> PatternHandler {
         let mut pattern_handler = PatternHandler::default();
 
@@ -175,39 +555,17 @@ mod tests_pattern {
             return pattern_handler;
         }
 
-        pattern_handler.read(pattern_source, target_pattern);
+        pattern_handler
+            .read(pattern_source, target_pattern)
+            .expect("failed to read the test patterns");
 
         pattern_handler
     }
 
     #[inline]
-    fn match_patterns<'a>(
-        pattern_handler: &'a PatternHandler,
-        path: &str,
-    ) -> Vec<PatternMatch<'a>> {
-        let chunk =
-            file_processor::read_file_header_chunk(path).expect("failed to read sample file");
-
-        let mut point_store: Vec<PatternMatch> = pattern_handler
-            .patterns
-            .par_iter()
-            .filter_map(|pattern| {
-                let points = FilePointCalculator::compute(pattern, &chunk, path, true);
-                if points > 0 {
-                    Some(PatternMatch::new(
-                        &pattern.type_data.uuid,
-                        points,
-                        pattern.max_points,
-                    ))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        // Sort the results by percentage match score, descending.
-        point_store.sort_unstable_by(|a, b| b.percentage.partial_cmp(&a.percentage).unwrap());
-
-        point_store
+    fn match_patterns(identifier: &Identifier, path: &str) -> Vec<MatchResult> {
+        identifier
+            .identify_path(path)
+            .expect("failed to read sample file")
     }
 }
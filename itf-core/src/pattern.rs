@@ -1,15 +1,57 @@
 use chrono;
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
+use rayon::prelude::*;
+use regex::bytes::Regex;
 use serde_derive::{Deserialize, Serialize};
-use std::{fs::File, io::Write, path::PathBuf};
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Cow,
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
-    file_point_calculator::{CONFIDENCE_SCALE_FACTOR, FILE_EXTENSION_POINTS, MAX_ENTROPY_POINTS},
-    file_processor, utils,
+    error::ItfError,
+    file_point_calculator::{
+        FilePointCalculator, MatchReport, FILE_EXTENSION_POINTS, MAX_BIGRAM_POINTS,
+        MAX_CHI_SQUARE_POINTS, MAX_ENTROPY_POINTS, MAX_ENTROPY_WINDOW_POINTS,
+        MAX_FUZZY_HASH_POINTS, MIN_SEQUENCE_SUPPORT_WEIGHT,
+    },
+    file_processor::{self, FileSample},
+    fuzzy, interner, pattern_authoring,
+    pattern_migration::{self, PATTERN_FORMAT_VERSION},
+    pattern_signing::{self, PatternSignature},
+    string_blocklist::StringBlocklist,
+    utils,
 };
 
-#[derive(Clone, Default, Serialize, Deserialize)]
+/// The fraction of the common byte sequences that a sample file must remove to be considered an outlier.
+const OUTLIER_SEQUENCE_LOSS_RATIO: f32 = 0.75;
+/// The fraction of the greatest sample weight below which a sample file is considered to be an edge case.
+pub const EDGE_CASE_WEIGHT_RATIO: f32 = 0.5;
+/// The number of sample files that must have refined the common byte sequences before outliers are detected.
+/// The first samples naturally remove most of the bytes, and so are assumed to be representative.
+const MIN_OUTLIER_SAMPLE_INDEX: usize = 2;
+/// The minimum number of anchored bytes and sample files required for a pattern to be graded as strong.
+const STRONG_PATTERN_THRESHOLDS: (usize, usize) = (8, 5);
+/// The minimum number of anchored bytes and sample files required for a pattern to be graded as moderate.
+/// A pattern without enough anchored bytes may still be graded as moderate if it has enough strings.
+const MODERATE_PATTERN_THRESHOLDS: (usize, usize) = (4, 2);
+/// The minimum number of strings that can stand in for the anchored bytes of a moderate pattern.
+const MODERATE_PATTERN_MIN_STRINGS: usize = 4;
+/// The default minimum number of sample files from which a pattern may be built.
+pub const DEFAULT_MIN_SAMPLES: usize = 1;
+/// The number of sample files below which a pattern is unlikely to generalize to other files of the same type.
+pub const RECOMMENDED_MIN_SAMPLES: usize = 3;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Pattern {
+    /// The version of the format in which the pattern was written.
+    #[serde(default = "default_format_version")]
+    pub format_version: u32,
     /// The basic pattern information.
     #[serde(rename = "td")]
     pub type_data: PatternTypeData,
@@ -22,12 +64,49 @@ pub struct Pattern {
     /// The submitter information, if specified.
     #[serde(rename = "sd")]
     pub submitter_data: PatternSubmitterData,
+    /// The signature of the pattern, if it has been signed by its publisher.
+    #[serde(rename = "sig", default = "default_signature")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<PatternSignature>,
+    /// The SHA-256 digest of the canonical contents of the pattern, as hex. This is updated whenever the pattern is written.
+    #[serde(default = "default_checksum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
     // The maximum number of points that can be given by a match against this pattern.
     #[serde(skip)]
     pub max_points: usize,
     /// The confidence factor, used in match point calculations.
     #[serde(skip)]
     pub confidence_factor: f32,
+    /// The compiled regexes of the pattern data.
+    #[serde(skip)]
+    pub(crate) compiled_regexes: Vec<CompiledRegex>,
+    /// The weight given to the points of each of the positional byte sequences, in the same order.
+    #[serde(skip)]
+    pub(crate) sequence_weights: Vec<f32>,
+    /// A description of each change that was needed to upgrade the pattern from an older format, if it was read
+    /// from a document written by an older version of the library.
+    #[serde(skip)]
+    pub migrations: Vec<String>,
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Self {
+            format_version: PATTERN_FORMAT_VERSION,
+            type_data: Default::default(),
+            data: Default::default(),
+            other_data: Default::default(),
+            submitter_data: Default::default(),
+            signature: None,
+            checksum: None,
+            max_points: 0,
+            confidence_factor: 0.0,
+            compiled_regexes: vec![],
+            sequence_weights: vec![],
+            migrations: vec![],
+        }
+    }
 }
 
 impl Pattern {
@@ -38,21 +117,42 @@ impl Pattern {
         known_mimetypes: Vec<String>,
     ) -> Self {
         Self {
+            format_version: PATTERN_FORMAT_VERSION,
             type_data: PatternTypeData {
                 name: name.to_string(),
                 description: description.to_string(),
-                known_extensions: known_extensions.iter().map(|s| s.to_uppercase()).collect(),
-                known_mimetypes,
+                known_extensions: known_extensions
+                    .iter()
+                    .map(|s| interner::intern(&s.to_uppercase()))
+                    .collect(),
+                known_mimetypes: interner::intern_all(&known_mimetypes),
+                categories: default_categories(),
                 uuid: utils::make_uuid(),
+                priority: default_priority(),
+                parent_uuid: default_parent_uuid(),
             },
             data: PatternData::default(),
             other_data: PatternOtherData::default(),
             submitter_data: PatternSubmitterData::default(),
+            signature: None,
+            checksum: None,
             max_points: 0,
             confidence_factor: 0.0,
+            compiled_regexes: vec![],
+            sequence_weights: vec![],
+            migrations: vec![],
         }
     }
 
+    /// Does this pattern belong to a specific category?
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - The category. This is compared without regard to case.
+    pub fn in_category(&self, category: &str) -> bool {
+        self.type_data.in_category(category)
+    }
+
     /// Add the relevant submitter data to the [`Pattern`].
     ///
     /// # Arguments
@@ -74,73 +174,2002 @@ impl Pattern {
     /// # Arguments
     ///
     /// * `source_directory` - The target directory containing the sample files.
-    /// * `target_extension` - The target extension for the sample files.
-    /// * `scan_strings` - Should the sample files be scanned for viable strings? This can be performance intensive with a large number of files.
-    /// * `scan_strings` - Should the sample files be scanned for matching byte sequences?
-    /// * `scan_byte_distribution` - Should the sample files have their byte distribution scanned?
+    /// * `target_extension` - The target extension for the sample files. Multiple extensions may be given as a comma-separated list.
+    /// * `options` - The [`BuildOptions`] controlling which attributes of the sample files are scanned.
+    ///
+    /// # Returns
+    ///
+    /// A [`BuildReport`] describing the sample files, or an error if any of the sample files could not be read.
     pub fn build_patterns_from_data(
         &mut self,
         source_directory: &str,
         target_extension: &str,
-        scan_strings: bool,
-        scan_byte_sequences: bool,
-        scan_byte_distribution: bool,
-    ) {
-        let mut first_byte_sequence_pass = true;
+        options: &BuildOptions,
+    ) -> Result<BuildReport, ItfError> {
+        self.build_patterns_from_weighted_data(
+            source_directory,
+            target_extension,
+            options,
+            &SampleWeights::Uniform,
+            &mut (),
+        )
+    }
+
+    /// Build a [`Pattern`] from a target directory and for a specific file type, where some sample files
+    /// have more influence over the pattern than others.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_directory` - The target directory containing the sample files.
+    /// * `target_extension` - The target extension for the sample files. Multiple extensions may be given as a comma-separated list.
+    /// * `options` - The [`BuildOptions`] controlling which attributes of the sample files are scanned.
+    /// * `weights` - The [`SampleWeights`] to be given to the sample files.
+    /// * `progress` - The [`ProgressSink`] to be notified as each sample file is processed.
+    ///
+    /// # Returns
+    ///
+    /// A [`BuildReport`] describing the sample files, or an error if any of the sample files could not be read.
+    ///
+    /// # Notes
+    ///
+    /// Sample files with less than [`EDGE_CASE_WEIGHT_RATIO`] of the greatest weight are treated as edge cases.
+    /// Any bytes in which they differ from the common sequences will be masked out, rather than splitting the sequences.
+    /// Entropy ranges are only widened toward a sample file in proportion to its weight.
+    pub fn build_patterns_from_weighted_data(
+        &mut self,
+        source_directory: &str,
+        target_extension: &str,
+        options: &BuildOptions,
+        weights: &SampleWeights,
+        progress: &mut dyn ProgressSink,
+    ) -> Result<BuildReport, ItfError> {
+        let files = utils::list_files_of_types(
+            source_directory,
+            &utils::split_extensions(target_extension),
+        );
 
-        let files = utils::list_files_of_type(source_directory, target_extension);
+        self.build_patterns_from_files(&files, options, weights, progress)
+    }
 
-        let mut common_byte_sequences = Vec::<(usize, Vec<u8>)>::new();
-        let mut all_strings = Vec::with_capacity(files.len());
-        let mut byte_distribution: [usize; 256] = [0; 256];
+    /// Build a [`Pattern`] from a set of sample files, such as those selected by a glob.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The paths to the sample files.
+    /// * `options` - The [`BuildOptions`] controlling which attributes of the sample files are scanned.
+    /// * `weights` - The [`SampleWeights`] to be given to the sample files.
+    /// * `progress` - The [`ProgressSink`] to be notified as each sample file is processed.
+    ///
+    /// # Returns
+    ///
+    /// A [`BuildReport`] describing the sample files, or an error if any of the sample files could not be read.
+    pub fn build_patterns_from_files(
+        &mut self,
+        files: &[String],
+        options: &BuildOptions,
+        weights: &SampleWeights,
+        progress: &mut dyn ProgressSink,
+    ) -> Result<BuildReport, ItfError> {
+        let weights = weights.compute(files)?;
+
+        PatternBuilder::from_files(files, &weights, options)?.finalize(self, progress)
+    }
 
-        #[cfg(debug_assertions)]
-        let mut no_strings = vec![];
+    /// Refine a [`Pattern`] using an additional set of sample files. Only the attributes
+    /// common to both the existing pattern and the new sample files will be retained.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_directory` - The target directory containing the new sample files.
+    /// * `target_extension` - The target extension for the sample files. Multiple extensions may be given as a comma-separated list.
+    /// * `refined_by` - The name of the person that performed the refinement. May be empty.
+    /// * `refined_by_email` - The email of the person that performed the refinement. May be empty.
+    ///
+    /// # Returns
+    ///
+    /// An error if any of the sample files could not be read, or if no sample files were found.
+    pub fn refine_from_data(
+        &mut self,
+        source_directory: &str,
+        target_extension: &str,
+        refined_by: &str,
+        refined_by_email: &str,
+    ) -> Result<(), ItfError> {
+        // Only the attributes already present in the pattern are worth scanning for.
+        let options = BuildOptions::from_pattern_data(&self.data);
+
+        let mut refinement = Pattern::default();
+        refinement.build_patterns_from_data(source_directory, target_extension, &options)?;
+        if refinement.other_data.total_scanned_files == 0 {
+            return Err(ItfError::InvalidPattern(format!(
+                "no sample files with the extension(s) '{target_extension}' were found"
+            )));
+        }
+
+        self.intersect_data(&refinement);
+
+        self.submitter_data.refined_by.push(refined_by.to_string());
+        self.submitter_data
+            .refined_by_email
+            .push(refined_by_email.to_string());
+
+        self.compute_attributes();
+
+        Ok(())
+    }
+
+    /// Add a positional byte sequence to the [`Pattern`], such as a known magic number.
+    /// Any existing sequences that overlap it will be trimmed, so that the added sequence takes precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position of the sequence.
+    /// * `sequence` - The byte sequence.
+    pub fn add_sequence(&mut self, position: usize, sequence: Vec<u8>) {
+        if sequence.is_empty() {
+            return;
+        }
+
+        let end = position + sequence.len();
+        let overlaps = |start: usize, len: usize| start < end && position < start + len;
+
+        let data = &mut self.data;
+        let old_support = std::mem::take(&mut data.sequence_support);
+        let support_of = |start: usize| old_support.iter().find(|(p, _)| *p == start);
+
+        // Any retained parts of an existing sequence keep its support. The added sequence didn't
+        // come from the sample files, so it has none.
+        let mut sequences = Vec::with_capacity(data.sequences.len() + 1);
+        let mut support = Vec::with_capacity(old_support.len());
+        for (start, bytes) in std::mem::take(&mut data.sequences) {
+            let s = support_of(start);
+            if !overlaps(start, bytes.len()) {
+                sequences.push((start, bytes));
+                support.extend(s.copied());
+                continue;
+            }
+
+            // Retain any parts of the existing sequence that lie either side of the added sequence.
+            if start < position {
+                sequences.push((start, interner::intern_bytes(&bytes[..position - start])));
+                support.extend(s.copied());
+            }
+            if start + bytes.len() > end {
+                sequences.push((end, interner::intern_bytes(&bytes[end - start..])));
+                support.extend(s.map(|(_, s)| (end, *s)));
+            }
+        }
+        sequences.push((position, interner::intern_bytes(&sequence)));
+        sequences.sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+        support.sort_unstable();
+        data.sequences = sequences;
+        data.sequence_support = support;
+
+        data.masked_sequences
+            .retain(|(start, s)| !overlaps(*start, s.len()));
+        file_processor::strip_covered_anchored_strings(
+            &mut data.anchored_strings,
+            &data.sequences,
+            &data.masked_sequences,
+        );
+    }
+
+    /// Add a byte regex to the [`Pattern`], which must match at a fixed position.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position at which the regex must match.
+    /// * `regex` - The regex.
+    ///
+    /// # Returns
+    ///
+    /// The number of points that a match will award, before any scaling, or an error if the regex was not valid.
+    pub fn add_regex(&mut self, position: usize, regex: &str) -> Result<usize, ItfError> {
+        let compiled = CompiledRegex::new(position, regex)?;
+        let points = compiled.points;
+
+        let entry = (position, regex.to_string());
+        if !self.data.regexes.contains(&entry) {
+            self.data.regexes.push(entry);
+            self.compiled_regexes.push(compiled);
+        }
+
+        Ok(points)
+    }
+
+    /// Merge another [`Pattern`] for the same file type into this one, such as one created from a different
+    /// set of sample files. Only the attributes common to both patterns will be retained, while the known
+    /// extensions, mimetypes, categories and submitters will be combined.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The pattern to be merged into this one.
+    pub fn merge(&mut self, other: &Pattern) {
+        self.intersect_data(other);
+
+        let type_data = &mut self.type_data;
+        for extension in &other.type_data.known_extensions {
+            if !type_data.known_extensions.contains(extension) {
+                type_data.known_extensions.push(extension.clone());
+            }
+        }
+        for mimetype in &other.type_data.known_mimetypes {
+            if !type_data.known_mimetypes.contains(mimetype) {
+                type_data.known_mimetypes.push(mimetype.clone());
+            }
+        }
+        for category in &other.type_data.categories {
+            if !type_data.categories.contains(category) {
+                type_data.categories.push(category.clone());
+            }
+        }
+        if type_data.description.is_empty() {
+            type_data.description = other.type_data.description.clone();
+        }
+        let (other_data, other_other_data) = (&mut self.other_data, &other.other_data);
+        for (field, other_field) in [
+            (
+                &mut other_data.file_format_url,
+                &other_other_data.file_format_url,
+            ),
+            (&mut other_data.source, &other_other_data.source),
+            (&mut other_data.license, &other_other_data.license),
+            (&mut other_data.origin_url, &other_other_data.origin_url),
+        ] {
+            if field.is_empty() {
+                field.clone_from(other_field);
+            }
+        }
+
+        // The submitter of the other pattern, and anyone that refined it, are treated as refiners of this one.
+        let other_submitter = &other.submitter_data;
+        let submitters = std::iter::once((
+            &other_submitter.scanned_by,
+            &other_submitter.scanned_by_email,
+        ))
+        .chain(
+            other_submitter
+                .refined_by
+                .iter()
+                .zip(&other_submitter.refined_by_email),
+        );
+
+        let submitter_data = &mut self.submitter_data;
+        for (name, email) in submitters {
+            if name.is_empty() && email.is_empty() {
+                continue;
+            }
+
+            let exists = submitter_data
+                .refined_by
+                .iter()
+                .zip(&submitter_data.refined_by_email)
+                .any(|(n, e)| n == name && e == email)
+                || (&submitter_data.scanned_by == name
+                    && &submitter_data.scanned_by_email == email);
+            if !exists {
+                submitter_data.refined_by.push(name.clone());
+                submitter_data.refined_by_email.push(email.clone());
+            }
+        }
+
+        self.compute_attributes();
+    }
+
+    /// Validate a [`Pattern`] against a set of files that it should not match.
+    ///
+    /// Any strings found within those files are dropped, since they would inflate the score of unrelated files.
+    /// Byte sequences must all be present for a match to occur, so removing them can't prevent a false positive.
+    /// Any file that still matches is instead reported, so that the pattern can be adjusted by hand.
+    ///
+    /// # Arguments
+    ///
+    /// * `negative_directory` - The directory containing the files that should not match, regardless of their extension.
+    ///
+    /// # Returns
+    ///
+    /// A [`NegativeValidation`] describing the changes made and any remaining false positives, or an error if any of the files could not be read.
+    pub fn validate_against_negative(
+        &mut self,
+        negative_directory: &str,
+    ) -> Result<NegativeValidation, ItfError> {
+        let files = utils::list_files(negative_directory);
+
+        let mut samples = Vec::with_capacity(files.len());
+        let mut validation = NegativeValidation {
+            total_scanned_files: files.len(),
+            ..Default::default()
+        };
+
+        for file_path in files {
+            let sample = file_processor::read_file_sample(&file_path)?;
+
+            let mut strings = file_processor::extract_file_strings(&sample.head);
+            if let Some(middle) = &sample.middle {
+                strings.extend(file_processor::extract_file_strings(middle));
+            }
 
-        for file_path in &files {
-            let chunk =
-                file_processor::read_file_header_chunk(file_path).expect("failed to read file");
+            let data = &mut self.data;
+            let total_strings = data.strings.len() + data.anchored_strings.len();
+            data.strings.retain(|s| !strings.contains(s));
+            data.anchored_strings
+                .retain(|(i, s)| !file_processor::has_string_at(&sample.head, *i, s));
+            validation.dropped_strings +=
+                total_strings - data.strings.len() - data.anchored_strings.len();
+
+            samples.push((file_path, sample));
+        }
+
+        // The maximum number of points may have changed, so this must be done before checking for false positives.
+        self.compute_attributes();
+
+        for (file_path, sample) in &samples {
+            let Some(report) = FilePointCalculator::compute_report(
+                self,
+                sample,
+                Some(file_path),
+                utils::DEFAULT_COMPOUND_EXTENSIONS,
+                false,
+            ) else {
+                continue;
+            };
+
+            let points = report.total();
+            if points > 0 {
+                let percentage =
+                    utils::round_to_dp(points as f32 / self.max_points as f32 * 100.0, 1);
+                validation
+                    .false_positives
+                    .push((file_path.clone(), percentage));
+            }
+        }
+
+        validation
+            .false_positives
+            .sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+
+        Ok(validation)
+    }
+
+    /// Estimate how well a [`Pattern`] built from a set of sample files generalizes to files that it
+    /// hasn't seen, using k-fold cross-validation.
+    ///
+    /// The sample files are split into a number of folds. For each fold, a pattern is built from the
+    /// remaining folds and used to score the sample files within the held-out fold.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_directory` - The target directory containing the sample files.
+    /// * `target_extension` - The target extension for the sample files. Multiple extensions may be given as a comma-separated list.
+    /// * `options` - The [`BuildOptions`] controlling which attributes of the sample files are scanned.
+    /// * `folds` - The number of folds. This must be at least 2, and no more than the number of sample files.
+    ///
+    /// # Returns
+    ///
+    /// A [`CrossValidation`] describing the scores of the held-out sample files, or an error if any of the
+    /// sample files could not be read or there were too few of them.
+    ///
+    /// # Notes
+    ///
+    /// Only the type data of this [`Pattern`] is used. Its scan data is left untouched.
+    pub fn cross_validate(
+        &self,
+        source_directory: &str,
+        target_extension: &str,
+        options: &BuildOptions,
+        folds: usize,
+    ) -> Result<CrossValidation, ItfError> {
+        let files = utils::list_files_of_types(
+            source_directory,
+            &utils::split_extensions(target_extension),
+        );
+
+        self.cross_validate_files(&files, options, folds)
+    }
+
+    /// Estimate how well a [`Pattern`] built from a set of sample files generalizes to files that it
+    /// hasn't seen, using k-fold cross-validation. See [`Pattern::cross_validate`] for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The paths to the sample files.
+    /// * `options` - The [`BuildOptions`] controlling which attributes of the sample files are scanned.
+    /// * `folds` - The number of folds. This must be at least 2, and no more than the number of sample files.
+    ///
+    /// # Returns
+    ///
+    /// A [`CrossValidation`] describing the scores of the held-out sample files, or an error if any of the
+    /// sample files could not be read or there were too few of them.
+    pub fn cross_validate_files(
+        &self,
+        files: &[String],
+        options: &BuildOptions,
+        folds: usize,
+    ) -> Result<CrossValidation, ItfError> {
+        if folds < 2 || files.len() < folds {
+            return Err(ItfError::InsufficientSamples {
+                required: folds.max(2),
+                found: files.len(),
+            });
+        }
+
+        let samples = PatternBuilder::from_files(files, &vec![1.0; files.len()], options)?.samples;
+
+        // Each fold is built from a subset of the sample files, so the minimum can't be applied to it.
+        let options = BuildOptions {
+            min_samples: DEFAULT_MIN_SAMPLES,
+            ..options.clone()
+        };
+
+        // The sample files are assigned to the folds in turn, so that each fold is roughly the same size.
+        let fold_scores = utils::in_thread_pool(|| {
+            (0..folds)
+                .into_par_iter()
+                .map(|fold| {
+                    let mut pattern = self.clone();
+                    pattern.data = PatternData::default();
+
+                    PatternBuilder {
+                        options: options.clone(),
+                        samples: samples
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, _)| i % folds != fold)
+                            .map(|(_, s)| s.clone())
+                            .collect(),
+                    }
+                    .finalize(&mut pattern, &mut ())?;
+                    pattern.compute_attributes();
+
+                    Ok(samples
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| i % folds == fold)
+                        .map(|(_, s)| (s.name.clone(), pattern.score(&s.analysis.sample, &s.name)))
+                        .collect::<Vec<_>>())
+                })
+                .collect::<Result<Vec<_>, ItfError>>()
+        })?;
+
+        let mut scores: Vec<(String, f32)> = fold_scores.into_iter().flatten().collect();
+        scores.sort_unstable_by(|a, b| a.1.total_cmp(&b.1));
+
+        Ok(CrossValidation {
+            folds,
+            min_percentage: scores.first().map_or(0.0, |(_, p)| *p),
+            average_percentage: utils::round_to_dp(
+                scores.iter().map(|(_, p)| p).sum::<f32>() / scores.len() as f32,
+                1,
+            ),
+            scores,
+        })
+    }
+
+    /// Compute the match percentage of a sample against this [`Pattern`], as it would be reported when identifying it.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - The [`FileSample`] to be scored.
+    /// * `path` - The path to the sample, used for extension matching.
+    ///
+    /// # Returns
+    ///
+    /// The match percentage, which will be zero if a required criterion was not met.
+    fn score(&self, sample: &FileSample, path: &str) -> f32 {
+        if self.max_points == 0 {
+            return 0.0;
+        }
+
+        FilePointCalculator::compute_report(
+            self,
+            sample,
+            Some(path),
+            utils::DEFAULT_COMPOUND_EXTENSIONS,
+            true,
+        )
+        .map_or(0.0, |r| {
+            utils::round_to_dp(r.total() as f32 / self.max_points as f32 * 100.0, 1)
+        })
+    }
+
+    /// Intersect the scan data of this [`Pattern`] with that of another pattern for the same
+    /// file type, such that the result describes the sample files used to build both.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other pattern.
+    fn intersect_data(&mut self, other: &Pattern) {
+        let (data, other_data) = (&mut self.data, &other.data);
+        let (files, other_files) = (
+            self.other_data.total_scanned_files as f32,
+            other.other_data.total_scanned_files as f32,
+        );
+
+        // Header sequences, both unmasked and masked.
+        let to_masked = |data: &PatternData| -> Vec<(usize, Vec<Option<u8>>)> {
+            data.sequences
+                .iter()
+                .map(|(i, s)| (*i, s.iter().map(|b| Some(*b)).collect()))
+                .chain(data.masked_sequences.iter().cloned())
+                .collect()
+        };
+        let (mut sequences, mut masked_sequences) =
+            file_processor::intersect_sequences(&to_masked(data), &to_masked(other_data));
+        file_processor::strip_unwanted_sequences(&mut sequences);
+        file_processor::merge_adjacent_sequences(&mut sequences);
+        sequences.sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+        masked_sequences.sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+
+        // Every sample file of either pattern matches one of the combined alternatives. A group that only one of the
+        // patterns has is retained only if the sample files of the other pattern all share one of its alternatives.
+        let same_position = |a: &[(usize, Vec<u8>)], b: &[(usize, Vec<u8>)]| {
+            a.first().map(|(p, _)| p) == b.first().map(|(p, _)| p)
+        };
+        let shares_alternative = |data: &PatternData, group: &[(usize, Vec<u8>)]| {
+            data.sequences.iter().any(|(start, sequence)| {
+                group
+                    .iter()
+                    .any(|(p, a)| p == start && sequence.starts_with(a))
+            })
+        };
+        let mut alternative_sequences = Vec::new();
+        for group in &data.alternative_sequences {
+            match other_data
+                .alternative_sequences
+                .iter()
+                .find(|g| same_position(g, group))
+            {
+                Some(other_group) => {
+                    let mut combined = group.clone();
+                    for alternative in other_group {
+                        if !combined.contains(alternative) {
+                            combined.push(alternative.clone());
+                        }
+                    }
+                    combined.sort_unstable();
+                    alternative_sequences.push(combined);
+                }
+                None if shares_alternative(other_data, group) => {
+                    alternative_sequences.push(group.clone());
+                }
+                None => {}
+            }
+        }
+        for group in &other_data.alternative_sequences {
+            if !data
+                .alternative_sequences
+                .iter()
+                .any(|g| same_position(g, group))
+                && shares_alternative(data, group)
+            {
+                alternative_sequences.push(group.clone());
+            }
+        }
+        data.alternative_sequences = alternative_sequences;
+
+        // Any sequence without a recorded support is assumed to have been present in every sample file.
+        let mut support: Vec<(usize, usize)> = sequences
+            .iter()
+            .map(|(start, _)| {
+                let a = data.sequence_support_at(*start).unwrap_or(files as usize);
+                let b = other_data
+                    .sequence_support_at(*start)
+                    .unwrap_or(other_files as usize);
+                (*start, a + b)
+            })
+            .collect();
+        support.sort_unstable();
+        data.sequence_support = support;
+        data.sequences = interner::intern_sequences(sequences);
+        data.masked_sequences = masked_sequences;
+
+        // Trailer sequences are converted into positions within a tail chunk so they can be intersected.
+        let to_tail_positions = |data: &PatternData| -> Vec<(usize, Vec<Option<u8>>)> {
+            data.trailer_sequences
+                .iter()
+                .map(|(i, s)| {
+                    let start = file_processor::TAIL_CHUNK_SIZE.saturating_sub(*i);
+                    (start, s.iter().map(|b| Some(*b)).collect())
+                })
+                .collect()
+        };
+        let (mut trailer_sequences, _) = file_processor::intersect_sequences(
+            &to_tail_positions(data),
+            &to_tail_positions(other_data),
+        );
+        for (i, _) in &mut trailer_sequences {
+            *i = file_processor::TAIL_CHUNK_SIZE - *i;
+        }
+        file_processor::strip_unwanted_sequences(&mut trailer_sequences);
+        trailer_sequences.sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+        data.trailer_sequences = trailer_sequences;
+
+        // Strings.
+        data.strings = data
+            .strings
+            .intersection(&other_data.strings)
+            .cloned()
+            .collect();
+        data.anchored_strings
+            .retain(|s| other_data.anchored_strings.contains(s));
+
+        // Regexes.
+        data.regexes.retain(|r| other_data.regexes.contains(r));
+
+        // Composition. Averages are weighted by the number of files used to build each pattern,
+        // while ranges are widened to cover both patterns.
+        let weighted = |a: f32, b: f32| {
+            if files + other_files == 0.0 {
+                a
+            } else {
+                (a * files + b * other_files) / (files + other_files)
+            }
+        };
+        if data.byte_distribution.len() == 256 && other_data.byte_distribution.len() == 256 {
+            // The combined byte distribution gives the exact entropy of both sets of sample files.
+            let mut frequencies = [0; 256];
+            for ((f, a), b) in frequencies
+                .iter_mut()
+                .zip(&mut data.byte_distribution)
+                .zip(&other_data.byte_distribution)
+            {
+                *a += b;
+                *f = *a as usize;
+            }
+            data.average_entropy = utils::calculate_shannon_entropy(&frequencies);
+        } else {
+            // Otherwise the averages can only be approximated, and the byte distribution no longer describes both sets.
+            if data.average_entropy != 0.0 && other_data.average_entropy != 0.0 {
+                data.average_entropy = weighted(data.average_entropy, other_data.average_entropy);
+            }
+            data.byte_distribution.clear();
+        }
+
+        for (window, min, max) in &other_data.entropy_windows {
+            match data
+                .entropy_windows
+                .iter_mut()
+                .find(|(w, _, _)| w == window)
+            {
+                Some((_, a, b)) => {
+                    *a = a.min(*min);
+                    *b = b.max(*max);
+                }
+                None => data.entropy_windows.push((*window, *min, *max)),
+            }
+        }
+        data.entropy_windows.sort_unstable_by_key(|(w, _, _)| *w);
+
+        data.chi_square = match (data.chi_square, other_data.chi_square) {
+            (Some((a, b)), Some((c, d))) => Some((a.min(c), b.max(d))),
+            (a, b) => a.or(b),
+        };
+
+        if !other_data.bigram_profile.is_empty() {
+            if data.bigram_profile.is_empty() {
+                data.bigram_profile = other_data.bigram_profile.clone();
+            } else {
+                for (a, b) in data
+                    .bigram_profile
+                    .iter_mut()
+                    .zip(&other_data.bigram_profile)
+                {
+                    *a = utils::round_to_dp(weighted(*a, *b), 4);
+                }
+            }
+        }
+
+        if data.fuzzy_hash.is_none() {
+            data.fuzzy_hash = other_data.fuzzy_hash.clone();
+        }
+
+        self.other_data.total_scanned_files += other.other_data.total_scanned_files;
+    }
+
+    /// Compute the fuzzy hash that best represents a set of sample files.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunks` - The header chunks of the sample files. This must not be empty.
+    ///
+    /// # Returns
+    ///
+    /// The block size and digest of the representative fuzzy hash.
+    fn build_fuzzy_hash(chunks: &[Vec<u8>]) -> (usize, String) {
+        // Every digest must be computed using the same block size, so that they can be compared.
+        let mut chunk_lengths: Vec<usize> = chunks.iter().map(Vec::len).collect();
+        chunk_lengths.sort_unstable();
+        let block_size = fuzzy::block_size_for(chunk_lengths[chunk_lengths.len() / 2]);
+
+        let digests: Vec<String> = chunks
+            .iter()
+            .map(|chunk| fuzzy::hash(chunk, block_size))
+            .collect();
+
+        let digest = fuzzy::representative(&digests).cloned().unwrap_or_default();
+
+        (block_size, digest)
+    }
+
+    /// Replace any masked sequences whose masked bytes only take a few values across the sample files
+    /// with equivalent regexes.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunks` - The header chunks of the sample files.
+    /// * `masked_sequences` - A mutable reference to the vector of masked sequences.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples giving the position and the regex.
+    fn build_regexes(
+        chunks: &[Vec<u8>],
+        masked_sequences: &mut Vec<(usize, Vec<Option<u8>>)>,
+    ) -> Vec<(usize, String)> {
+        // The values observed at each masked position, or None once a position has too many values to be listed.
+        let mut observed: Vec<Vec<Option<Vec<u8>>>> = masked_sequences
+            .iter()
+            .map(|(_, s)| vec![Some(vec![]); s.len()])
+            .collect();
+
+        for chunk in chunks {
+            for ((start, sequence), observed) in masked_sequences.iter().zip(&mut observed) {
+                for (i, values) in observed.iter_mut().enumerate() {
+                    let Some(set) = values.as_mut().filter(|_| sequence[i].is_none()) else {
+                        continue;
+                    };
+
+                    match chunk.get(start + i) {
+                        Some(b) if set.contains(b) => {}
+                        Some(b) if set.len() < file_processor::MAX_REGEX_CLASS_SIZE => set.push(*b),
+                        _ => *values = None,
+                    }
+                }
+            }
+        }
+
+        let mut regexes = vec![];
+        let mut remaining = vec![];
+        for ((start, sequence), observed) in
+            std::mem::take(masked_sequences).into_iter().zip(observed)
+        {
+            match file_processor::synthesize_regex(&sequence, &observed) {
+                Some(regex) => regexes.push((start, regex)),
+                None => remaining.push((start, sequence)),
+            }
+        }
+
+        *masked_sequences = remaining;
+
+        regexes
+    }
+
+    /// Assess the quality of the [`Pattern`], so that weak patterns can be rejected before publication.
+    ///
+    /// # Returns
+    ///
+    /// A [`PatternQuality`] report.
+    pub fn quality(&self) -> PatternQuality {
+        let data = &self.data;
+
+        // Only the bytes that must be present at a known position are counted as anchored.
+        let anchored_bytes = data
+            .sequences
+            .iter()
+            .map(|(_, s)| s.len())
+            .chain(data.trailer_sequences.iter().map(|(_, s)| s.len()))
+            .chain(data.ranged_sequences.iter().map(|(_, _, s)| s.len()))
+            .chain(
+                data.alternative_sequences
+                    .iter()
+                    .map(|g| PatternData::alternative_points(g)),
+            )
+            .chain(
+                data.masked_sequences
+                    .iter()
+                    .map(|(_, s)| s.iter().flatten().count()),
+            )
+            .chain(
+                data.regexes
+                    .iter()
+                    .filter_map(|(p, r)| CompiledRegex::new(*p, r).ok())
+                    .map(|r| r.points),
+            )
+            .sum();
+        let string_count = data.strings.len() + data.anchored_strings.len();
+        let entropy_spread = data
+            .entropy_windows
+            .iter()
+            .map(|(_, min, max)| max - min)
+            .reduce(f32::max);
+        let total_samples = self.other_data.total_scanned_files;
+
+        let meets = |(min_bytes, min_samples): (usize, usize)| {
+            anchored_bytes >= min_bytes && total_samples >= min_samples
+        };
+        let grade = if meets(STRONG_PATTERN_THRESHOLDS) {
+            QualityGrade::Strong
+        } else if meets(MODERATE_PATTERN_THRESHOLDS)
+            || (string_count >= MODERATE_PATTERN_MIN_STRINGS
+                && total_samples >= MODERATE_PATTERN_THRESHOLDS.1)
+        {
+            QualityGrade::Moderate
+        } else {
+            QualityGrade::Weak
+        };
+
+        PatternQuality {
+            anchored_bytes,
+            total_samples,
+            string_count,
+            entropy_spread,
+            grade,
+        }
+    }
+
+    /// Compute various attributes once the main object data has been initialized.
+    pub fn compute_attributes(&mut self) {
+        self.compile_regexes();
+        self.compute_confidence_factor();
+        self.compute_sequence_weights();
+        self.compute_max_points();
+    }
+
+    /// Compile the regexes of the pattern data. Any invalid regexes will be ignored.
+    fn compile_regexes(&mut self) {
+        self.compiled_regexes = self
+            .data
+            .regexes
+            .iter()
+            .filter_map(|(position, pattern)| CompiledRegex::new(*position, pattern).ok())
+            .collect();
+    }
+
+    /// Compute the confidence scale factor based on the number of files scanned to build this pattern.
+    ///
+    /// # Notes
+    ///
+    /// This is the probability that the next file of this type will also exhibit the attributes of the
+    /// pattern, given that every one of the `n` scanned files did, as estimated by Laplace's rule of succession: `(n + 1) / (n + 2)`.
+    /// As it is bounded, a large sample set can't drown out the file extension points.
+    fn compute_confidence_factor(&mut self) {
+        let n = self.other_data.total_scanned_files as f32;
+        self.confidence_factor = (n + 1.0) / (n + 2.0);
+    }
+
+    /// Compute the weight given to the points of each of the positional byte sequences, based on their support.
+    ///
+    /// # Notes
+    ///
+    /// A sequence that every sample file contained has a weight of 1, falling linearly to
+    /// [`MIN_SEQUENCE_SUPPORT_WEIGHT`] for a sequence that none did. Sequences without a recorded support have a weight of 1.
+    fn compute_sequence_weights(&mut self) {
+        let total = self.other_data.total_scanned_files;
+        self.sequence_weights = self
+            .data
+            .sequences
+            .iter()
+            .map(|(start, _)| match self.data.sequence_support_at(*start) {
+                Some(support) if total > 0 => {
+                    let ratio = support.min(total) as f32 / total as f32;
+                    MIN_SEQUENCE_SUPPORT_WEIGHT + (1.0 - MIN_SEQUENCE_SUPPORT_WEIGHT) * ratio
+                }
+                _ => 1.0,
+            })
+            .collect();
+    }
+
+    /// Get the weight given to the points of a positional byte sequence.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the sequence within the pattern data.
+    #[inline(always)]
+    pub(crate) fn sequence_weight(&self, index: usize) -> f32 {
+        self.sequence_weights.get(index).copied().unwrap_or(1.0)
+    }
+
+    /// Computer the maximum number of points that can be awarded for a perfect match against this pattern.
+    /// The more detailed the pattern, the higher the total points available.
+    fn compute_max_points(&mut self) {
+        // This must be rounded in the same way as the points awarded to a match, so that a perfect match scores 100%.
+        self.max_points = self.max_report().total();
+    }
+
+    /// Get the per-criterion breakdown of the points that would be awarded for a perfect match against
+    /// this pattern.
+    pub fn max_report(&self) -> MatchReport {
+        let mut report = MatchReport {
+            // Scale the relevant points by the confidence factor derived from the total files scanned.
+            confidence_factor: self.confidence_factor,
+            // The file extension is considered a separate factor and doesn't scale with the number
+            // of scanned files.
+            extension_points: FILE_EXTENSION_POINTS,
+            ..Default::default()
+        };
+
+        if self.data.should_scan_sequences() {
+            for (i, (_, sequence)) in self.data.sequences.iter().enumerate() {
+                report.sequence_points += sequence.len() as f32 * self.sequence_weight(i);
+            }
+
+            for (_, _, sequence) in &self.data.ranged_sequences {
+                report.sequence_points += sequence.len() as f32;
+            }
+
+            for group in &self.data.alternative_sequences {
+                report.sequence_points += PatternData::alternative_points(group) as f32;
+            }
+        }
+
+        if self.data.should_scan_trailer_sequences() {
+            for (_, sequence) in &self.data.trailer_sequences {
+                report.trailer_sequence_points += sequence.len() as f32;
+            }
+        }
+
+        if self.data.should_scan_masked_sequences() {
+            for (_, sequence) in &self.data.masked_sequences {
+                report.masked_sequence_points += sequence.iter().flatten().count() as f32;
+            }
+        }
+
+        if self.data.should_scan_regexes() {
+            for regex in &self.compiled_regexes {
+                report.regex_points += regex.points as f32;
+            }
+        }
+
+        if self.data.should_scan_strings() {
+            for string in &self.data.strings {
+                report.string_points += string.len() as f32;
+            }
+        }
+
+        if self.data.should_scan_anchored_strings() {
+            for (_, string) in &self.data.anchored_strings {
+                report.string_points += string.len() as f32;
+            }
+        }
+
+        if self.data.should_scan_composition() {
+            report.entropy_points += MAX_ENTROPY_POINTS;
+        }
+
+        if self.data.should_scan_entropy_windows() {
+            report.entropy_points +=
+                MAX_ENTROPY_WINDOW_POINTS * self.data.entropy_windows.len() as f32;
+        }
+
+        if self.data.chi_square.is_some() {
+            report.composition_points += MAX_CHI_SQUARE_POINTS;
+        }
+
+        if !self.data.bigram_profile.is_empty() {
+            report.composition_points += MAX_BIGRAM_POINTS;
+        }
+
+        if self.data.should_scan_fuzzy_hash() {
+            report.fuzzy_hash_points += MAX_FUZZY_HASH_POINTS;
+        }
+
+        report
+    }
+
+    /// Attempt to build a [`Pattern`] from a JSON string.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input JSON string.
+    ///
+    /// # Returns
+    ///
+    /// An error if the deserialization failed, otherwise the build [`Patten`] will be returned.
+    ///
+    /// # Notes
+    ///
+    /// Documents written in an older format will be upgraded to the current format, with the changes that
+    /// were needed being recorded within [`Pattern::migrations`].
+    pub fn from_json_str(input: &str) -> Result<Pattern, ItfError> {
+        let mut document: serde_json::Value = serde_json::from_str(input)?;
+        let migrations = pattern_migration::migrate(&mut document)?;
+
+        let mut p: Pattern = serde_json::from_value(document)?;
+        p.migrations = migrations;
+        Ok(p)
+    }
+
+    /// Attempt to build a [`Pattern`] from a JSON string, using SIMD.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input JSON string.
+    ///
+    /// # Returns
+    ///
+    /// An error if the deserialization failed, otherwise the build [`Patten`] will be returned.
+    ///
+    /// # Notes
+    ///
+    /// Documents that aren't in the current format will be parsed by [`Pattern::from_json_str`], so that they can be upgraded.
+    pub fn from_simd_json_str(input: &str) -> Result<Pattern, ItfError> {
+        let mut json_bytes = input.as_bytes().to_vec();
+        match simd_json::from_slice::<Pattern>(&mut json_bytes[..]) {
+            Ok(p) if p.format_version == PATTERN_FORMAT_VERSION => Ok(p),
+            _ => Pattern::from_json_str(input),
+        }
+    }
+
+    /// Attempt to build a [`Pattern`] from a hand-authored TOML string.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input TOML string.
+    ///
+    /// # Returns
+    ///
+    /// An error if the deserialization failed, otherwise the build [`Patten`] will be returned.
+    ///
+    /// # Notes
+    ///
+    /// The document has the same layout as a JSON pattern, but byte sequences may also be written as hex strings.
+    pub fn from_toml_str(input: &str) -> Result<Pattern, ItfError> {
+        let document: serde_json::Value =
+            toml::from_str(input).map_err(|e| ItfError::JsonParse(e.to_string()))?;
+        Pattern::from_authored_document(document)
+    }
+
+    /// Attempt to build a [`Pattern`] from a hand-authored YAML string.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input YAML string.
+    ///
+    /// # Returns
+    ///
+    /// An error if the deserialization failed, otherwise the build [`Patten`] will be returned.
+    ///
+    /// # Notes
+    ///
+    /// The document has the same layout as a JSON pattern, but byte sequences may also be written as hex strings.
+    pub fn from_yaml_str(input: &str) -> Result<Pattern, ItfError> {
+        let document: serde_json::Value =
+            serde_yaml::from_str(input).map_err(|e| ItfError::JsonParse(e.to_string()))?;
+        Pattern::from_authored_document(document)
+    }
+
+    /// Build a [`Pattern`] from a hand-authored document, expanding any hex strings and upgrading the document
+    /// to the current format.
+    fn from_authored_document(mut document: serde_json::Value) -> Result<Pattern, ItfError> {
+        pattern_authoring::expand_hex_sequences(&mut document)?;
+        let migrations = pattern_migration::migrate(&mut document)?;
+
+        let mut p: Pattern = serde_json::from_value(document)?;
+        p.migrations = migrations;
+        Ok(p)
+    }
+
+    /// Attempt to build a [`Pattern`] from its MessagePack encoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The MessagePack encoded pattern.
+    ///
+    /// # Returns
+    ///
+    /// An error if the deserialization failed, otherwise the build [`Patten`] will be returned.
+    ///
+    /// # Notes
+    ///
+    /// Patterns that aren't in the current format will be upgraded, as with [`Pattern::from_json_str`].
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(input: &[u8]) -> Result<Pattern, ItfError> {
+        match rmp_serde::from_slice::<Pattern>(input) {
+            Ok(p) if p.format_version == PATTERN_FORMAT_VERSION => Ok(p),
+            _ => {
+                let mut document: serde_json::Value =
+                    rmp_serde::from_slice(input).map_err(|e| ItfError::JsonParse(e.to_string()))?;
+                let migrations = pattern_migration::migrate(&mut document)?;
+
+                let mut p: Pattern = serde_json::from_value(document)?;
+                p.migrations = migrations;
+                Ok(p)
+            }
+        }
+    }
+
+    /// Encode the [`Pattern`] as MessagePack, which is more compact than JSON for patterns
+    /// containing many byte sequences.
+    ///
+    /// # Returns
+    ///
+    /// An error if the pattern could not be serialized, otherwise the encoded pattern.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, ItfError> {
+        Ok(rmp_serde::to_vec_named(self).map_err(io::Error::other)?)
+    }
+
+    /// Attempt to read a [`Pattern`] from a pattern file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the pattern file.
+    ///
+    /// # Returns
+    ///
+    /// An error if the file could not be read or the deserialization failed, otherwise the [`Pattern`] will be returned.
+    ///
+    /// # Notes
+    ///
+    /// Files with a `.gz` or `.zst` extension will be decompressed before being parsed. Files with a `.toml`,
+    /// `.yaml` or `.yml` extension will be parsed as hand-authored patterns, files with a `.msgpack` extension
+    /// as MessagePack (when the `msgpack` feature is enabled), and any other files as JSON.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Pattern, ItfError> {
+        Pattern::from_reader(&path, File::open(&path)?)
+    }
+
+    /// Attempt to read a [`Pattern`] from a reader, such as an entry within an archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path or name of the pattern file, which determines its compression and format as with
+    ///   [`Pattern::from_file`].
+    /// * `reader` - The source of the contents of the pattern file.
+    ///
+    /// # Returns
+    ///
+    /// An error if the contents could not be read or the deserialization failed, otherwise the [`Pattern`] will be returned.
+    pub fn from_reader<P: AsRef<Path>, R: Read>(path: P, reader: R) -> Result<Pattern, ItfError> {
+        let contents = Pattern::read_contents(&path, reader)?;
+
+        let text = |bytes: Vec<u8>| {
+            String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        };
+        let mut pattern = match PatternFormat::from_path(&path) {
+            PatternFormat::Json => Pattern::from_simd_json_str(&text(contents)?)?,
+            PatternFormat::Toml => Pattern::from_toml_str(&text(contents)?)?,
+            PatternFormat::Yaml => Pattern::from_yaml_str(&text(contents)?)?,
+            #[cfg(feature = "msgpack")]
+            PatternFormat::MessagePack => Pattern::from_msgpack(&contents)?,
+        };
+        pattern.compute_attributes();
+
+        Ok(pattern)
+    }
+
+    /// Read the contents of a pattern file, decompressing them if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the pattern file.
+    ///
+    /// # Returns
+    ///
+    /// An error if the file could not be read or decompressed, otherwise the contents of the file.
+    pub(crate) fn read_file_contents<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, ItfError> {
+        Pattern::read_contents(&path, File::open(&path)?)
+    }
+
+    /// Read the contents of a pattern file from a reader, decompressing them if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path or name of the pattern file, which determines its compression.
+    /// * `reader` - The source of the contents of the pattern file.
+    fn read_contents<P: AsRef<Path>, R: Read>(path: P, mut reader: R) -> Result<Vec<u8>, ItfError> {
+        let mut contents = Vec::new();
+        match PatternCompression::from_path(&path) {
+            PatternCompression::None => reader.read_to_end(&mut contents)?,
+            PatternCompression::Gzip => {
+                flate2::read::GzDecoder::new(reader).read_to_end(&mut contents)?
+            }
+            PatternCompression::Zstd => {
+                zstd::stream::read::Decoder::new(reader)?.read_to_end(&mut contents)?
+            }
+        };
+
+        Ok(contents)
+    }
+
+    /// Compute the checksum of the pattern, which covers everything other than the signature and the checksum itself.
+    ///
+    /// # Returns
+    ///
+    /// An error if the pattern could not be serialized, otherwise the SHA-256 digest of its canonical contents, as hex.
+    pub fn compute_checksum(&self) -> Result<String, ItfError> {
+        let digest = Sha256::digest(pattern_signing::canonical_bytes(self)?);
+
+        Ok(utils::encode_hex(&digest))
+    }
+
+    /// Verify that the contents of the pattern match its checksum.
+    ///
+    /// # Returns
+    ///
+    /// An error if the pattern has a checksum that doesn't match its contents. A pattern without a checksum,
+    /// such as one written by an older version of the library, is always accepted.
+    pub fn verify_checksum(&self) -> Result<(), ItfError> {
+        match &self.checksum {
+            Some(checksum) if *checksum != self.compute_checksum()? => {
+                Err(ItfError::CorruptPattern(format!(
+                    "the contents of '{}' don't match its checksum",
+                    self.type_data.name
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the pattern with an up to date checksum, ready to be written.
+    ///
+    /// # Returns
+    ///
+    /// An error if the pattern could not be serialized, otherwise this pattern if its checksum is already up to
+    /// date, or a copy of it with an updated checksum.
+    pub(crate) fn checksummed(&self) -> Result<Cow<'_, Pattern>, ItfError> {
+        let checksum = self.compute_checksum()?;
+        if self.checksum.as_ref() == Some(&checksum) {
+            return Ok(Cow::Borrowed(self));
+        }
+
+        let mut pattern = self.clone();
+        pattern.checksum = Some(checksum);
+        Ok(Cow::Owned(pattern))
+    }
+
+    /// Derive the name of a pattern based on the stored pattern data.
+    fn get_pattern_file_name(&self) -> String {
+        let file_name = utils::sanitize_file_name(&self.type_data.name);
+        file_name.replace(" ", "-") + ".json"
+    }
+
+    /// Attempt to write a JSON file for the data contained within the pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The input JSON string.
+    /// * `compression` - The compression to be applied to the file. The extension of the compression format will be
+    ///   appended to the file name.
+    ///
+    /// # Returns
+    ///
+    /// An error if the writing failed, otherwise a [`PathBuf`] to the written file will be returned.
+    pub fn write(&self, path: &str, compression: PatternCompression) -> Result<PathBuf, ItfError> {
+        let mut path = PathBuf::from(path);
+        path.push(self.get_pattern_file_name() + compression.extension());
+
+        self.write_file(&path)?;
+
+        Ok(path)
+    }
+
+    /// Write the [`Pattern`] to a specific file, replacing the file if it already exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - The path to the file.
+    ///
+    /// # Returns
+    ///
+    /// An error if the pattern could not be serialized or the file could not be written.
+    ///
+    /// # Notes
+    ///
+    /// Files with a `.gz` or `.zst` extension will be compressed with the corresponding format. Files with a
+    /// `.toml`, `.yaml`, `.yml` or `.msgpack` extension will be written in that format, and any other files as JSON.
+    pub fn write_file<P: AsRef<Path>>(&self, file_path: P) -> Result<(), ItfError> {
+        let pattern = self.checksummed()?;
+        let serialized = match PatternFormat::from_path(&file_path) {
+            PatternFormat::Json => serde_json::to_vec(&pattern)?,
+            PatternFormat::Toml => toml::to_string(&pattern)
+                .map_err(|e| ItfError::InvalidPattern(e.to_string()))?
+                .into_bytes(),
+            PatternFormat::Yaml => serde_yaml::to_string(&pattern)
+                .map_err(|e| ItfError::InvalidPattern(e.to_string()))?
+                .into_bytes(),
+            #[cfg(feature = "msgpack")]
+            PatternFormat::MessagePack => pattern.to_msgpack()?,
+        };
+
+        let output = File::create(&file_path)?;
+        match PatternCompression::from_path(&file_path) {
+            PatternCompression::None => {
+                let mut output = output;
+                output.write_all(&serialized)?;
+            }
+            PatternCompression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(output, flate2::Compression::default());
+                encoder.write_all(&serialized)?;
+                encoder.finish()?;
+            }
+            PatternCompression::Zstd => {
+                let mut encoder = zstd::stream::write::Encoder::new(output, 0)?;
+                encoder.write_all(&serialized)?;
+                encoder.finish()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The compression applied to a pattern file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatternCompression {
+    /// The pattern file is plain JSON.
+    #[default]
+    None,
+    /// The pattern file is compressed with gzip.
+    Gzip,
+    /// The pattern file is compressed with Zstandard.
+    Zstd,
+}
+
+impl PatternCompression {
+    /// The extensions of the pattern files that can be read, including those of the compressed files
+    /// and the hand-authored files.
+    #[cfg(not(feature = "msgpack"))]
+    pub const PATTERN_FILE_EXTENSIONS: &'static [&'static str] =
+        &[".json", ".json.gz", ".json.zst", ".toml", ".yaml", ".yml"];
+    /// The extensions of the pattern files that can be read, including those of the compressed files,
+    /// the hand-authored files and the MessagePack files.
+    #[cfg(feature = "msgpack")]
+    pub const PATTERN_FILE_EXTENSIONS: &'static [&'static str] = &[
+        ".json",
+        ".json.gz",
+        ".json.zst",
+        ".toml",
+        ".yaml",
+        ".yml",
+        ".msgpack",
+        ".msgpack.gz",
+        ".msgpack.zst",
+    ];
+
+    /// The extension appended to the name of a pattern file compressed with this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PatternCompression::None => "",
+            PatternCompression::Gzip => ".gz",
+            PatternCompression::Zstd => ".zst",
+        }
+    }
+
+    /// Get the compression of a pattern file from its extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the pattern file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match utils::get_file_extension(path.as_ref()).as_str() {
+            "GZ" => PatternCompression::Gzip,
+            "ZST" => PatternCompression::Zstd,
+            _ => PatternCompression::None,
+        }
+    }
+}
+
+/// The format of a pattern file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatternFormat {
+    /// The canonical JSON format.
+    #[default]
+    Json,
+    /// A hand-authored TOML file.
+    Toml,
+    /// A hand-authored YAML file.
+    Yaml,
+    /// A MessagePack file.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl PatternFormat {
+    /// Get the format of a pattern file from its extension, ignoring any compression extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the pattern file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let name = path.as_ref().to_string_lossy().to_lowercase();
+        let name = name
+            .strip_suffix(PatternCompression::from_path(path.as_ref()).extension())
+            .unwrap_or(&name);
+
+        if name.ends_with(".toml") {
+            return PatternFormat::Toml;
+        }
+        if name.ends_with(".yaml") || name.ends_with(".yml") {
+            return PatternFormat::Yaml;
+        }
+        #[cfg(feature = "msgpack")]
+        if name.ends_with(".msgpack") {
+            return PatternFormat::MessagePack;
+        }
+
+        PatternFormat::Json
+    }
+}
+
+#[derive(Clone)]
+pub struct BuildOptions {
+    /// Should the sample files be scanned for viable strings? This can be performance intensive with a large number of files.
+    pub scan_strings: bool,
+    /// Should the sample files be scanned for matching byte sequences?
+    pub scan_byte_sequences: bool,
+    /// Should the sample files have their byte distribution scanned?
+    pub scan_byte_distribution: bool,
+    /// Should byte sequences separated by a few differing bytes be merged into masked sequences?
+    pub mask_sequences: bool,
+    /// Should the chi-square statistic and byte bigram profile be computed along with the byte distribution?
+    pub scan_extended_composition: bool,
+    /// Should a fuzzy hash be computed from the sample files? This requires the sample files to be read twice.
+    pub fuzzy_hash: bool,
+    /// Should sample files that remove most of the common byte sequences be excluded from the pattern?
+    pub exclude_outliers: bool,
+    /// The strings that shouldn't be added to the pattern, as they are common to many unrelated file types.
+    pub string_blocklist: StringBlocklist,
+    /// Should masked sequences whose masked bytes only take a few values be replaced by regexes?
+    /// This requires the sample files to be read twice.
+    pub synthesize_regexes: bool,
+    /// The minimum number of sample files from which a pattern may be built.
+    pub min_samples: usize,
+}
+
+impl BuildOptions {
+    /// Create a set of [`BuildOptions`] that will scan for the attributes already present in a pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The [`PatternData`] of the pattern.
+    pub fn from_pattern_data(data: &PatternData) -> Self {
+        Self {
+            scan_strings: data.should_scan_strings() || data.should_scan_anchored_strings(),
+            scan_byte_sequences: !data.sequences.is_empty()
+                || data.should_scan_masked_sequences()
+                || data.should_scan_trailer_sequences(),
+            scan_byte_distribution: data.should_scan_composition()
+                || data.should_scan_entropy_windows(),
+            mask_sequences: data.should_scan_masked_sequences(),
+            scan_extended_composition: data.should_scan_extended_composition(),
+            fuzzy_hash: false,
+            exclude_outliers: false,
+            string_blocklist: StringBlocklist::default(),
+            synthesize_regexes: data.should_scan_regexes(),
+            min_samples: DEFAULT_MIN_SAMPLES,
+        }
+    }
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            scan_strings: true,
+            scan_byte_sequences: true,
+            scan_byte_distribution: true,
+            mask_sequences: true,
+            scan_extended_composition: true,
+            fuzzy_hash: false,
+            exclude_outliers: false,
+            string_blocklist: StringBlocklist::default(),
+            synthesize_regexes: false,
+            min_samples: DEFAULT_MIN_SAMPLES,
+        }
+    }
+}
+
+/// A regex from the pattern data, compiled so that it can be matched.
+#[derive(Clone, Debug)]
+pub(crate) struct CompiledRegex {
+    /// The position at which the regex must match.
+    pub position: usize,
+    /// The compiled regex, anchored to the start of the data being matched.
+    pub regex: Regex,
+    /// The number of points to be awarded for a match.
+    pub points: usize,
+}
+
+impl CompiledRegex {
+    /// Compile a regex from the pattern data.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position at which the regex must match.
+    /// * `pattern` - The regex.
+    ///
+    /// # Returns
+    ///
+    /// An error if the regex was not valid.
+    pub fn new(position: usize, pattern: &str) -> Result<Self, ItfError> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            ItfError::InvalidPattern(format!("invalid regex '{pattern}': {e}"))
+        };
+
+        // Byte regexes may match invalid UTF-8, so the same must be permitted when parsing.
+        let hir = regex_syntax::ParserBuilder::new()
+            .utf8(false)
+            .build()
+            .parse(pattern)
+            .map_err(|e| invalid(&e))?;
+        let regex = Regex::new(&format!("^(?:{pattern})")).map_err(|e| invalid(&e))?;
+
+        Ok(Self {
+            position,
+            regex,
+            points: hir.properties().minimum_len().unwrap_or(0),
+        })
+    }
+}
+
+/// The weights to be given to the sample files when building a [`Pattern`].
+#[derive(Clone, Debug, Default)]
+pub enum SampleWeights {
+    /// Every sample file is given the same weight.
+    #[default]
+    Uniform,
+    /// More recently modified sample files are given a greater weight.
+    Recent,
+    /// Explicit weights, keyed by file name. Any sample file that isn't listed is given a weight of 1.
+    Explicit(HashMap<String, f32>),
+}
+
+impl SampleWeights {
+    /// Compute the weight of each of a set of sample files.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The paths to the sample files.
+    ///
+    /// # Returns
+    ///
+    /// A vector giving the weight of each file, in the same order, or an error if the modification time of a file could not be read.
+    pub fn compute(&self, files: &[String]) -> Result<Vec<f32>, ItfError> {
+        match self {
+            SampleWeights::Uniform => Ok(vec![1.0; files.len()]),
+            SampleWeights::Recent => {
+                let mut modified = Vec::with_capacity(files.len());
+                for file_path in files {
+                    modified.push(std::fs::metadata(file_path)?.modified()?);
+                }
+
+                // The weight increases linearly with the rank of the file, with the newest being given a weight of 1.
+                let mut order: Vec<usize> = (0..files.len()).collect();
+                order.sort_by_key(|i| modified[*i]);
+
+                let mut weights = vec![0.0; files.len()];
+                for (rank, i) in order.into_iter().enumerate() {
+                    weights[i] = (rank + 1) as f32 / files.len() as f32;
+                }
+
+                Ok(weights)
+            }
+            SampleWeights::Explicit(weights) => Ok(files
+                .iter()
+                .map(|f| {
+                    Path::new(f)
+                        .file_name()
+                        .and_then(|n| weights.get(n.to_string_lossy().as_ref()))
+                        .copied()
+                        .unwrap_or(1.0)
+                })
+                .collect()),
+        }
+    }
+}
+
+/// The progress of a [`Pattern`] build.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BuildProgress {
+    /// The number of sample files that have been processed.
+    pub files_processed: usize,
+    /// The total number of sample files.
+    pub total_files: usize,
+    /// The number of common byte sequences that remain.
+    pub sequence_count: usize,
+    /// The number of strings that have been extracted from the sample files.
+    pub string_count: usize,
+}
+
+/// Receives progress updates while a [`Pattern`] is being built.
+pub trait ProgressSink {
+    /// Called before each sample file is processed, and once more after the last.
+    ///
+    /// # Arguments
+    ///
+    /// * `progress` - The [`BuildProgress`] so far.
+    fn update(&mut self, progress: &BuildProgress);
+
+    /// Called once every sample file has been processed.
+    fn finish(&mut self) {}
+}
+
+/// A [`ProgressSink`] that discards every update.
+impl ProgressSink for () {
+    fn update(&mut self, _progress: &BuildProgress) {}
+}
+
+/// Builds a [`Pattern`] from a set of samples that are supplied one at a time, such as those read from
+/// a network stream or an archive, rather than from a directory of sample files.
+pub struct PatternBuilder {
+    /// The options controlling which attributes of the samples are scanned.
+    options: BuildOptions,
+    /// The samples that have been added so far.
+    samples: Vec<WeightedSample>,
+}
+
+impl PatternBuilder {
+    /// Create a new [`PatternBuilder`].
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The [`BuildOptions`] controlling which attributes of the samples are scanned.
+    pub fn new(options: BuildOptions) -> Self {
+        Self {
+            options,
+            samples: vec![],
+        }
+    }
+
+    /// Create a [`PatternBuilder`] from a set of sample files, which will be analysed in parallel.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The paths to the sample files.
+    /// * `weights` - The weight of each of the sample files, in the same order.
+    /// * `options` - The [`BuildOptions`] controlling which attributes of the sample files are scanned.
+    ///
+    /// # Returns
+    ///
+    /// The [`PatternBuilder`], or an error if any of the sample files could not be read.
+    fn from_files(
+        files: &[String],
+        weights: &[f32],
+        options: &BuildOptions,
+    ) -> Result<Self, ItfError> {
+        let samples = utils::in_thread_pool(|| {
+            files
+                .par_iter()
+                .zip(weights)
+                .map(|(file_path, weight)| {
+                    Ok(WeightedSample {
+                        name: file_path.clone(),
+                        weight: *weight,
+                        analysis: SampleAnalysis::new(file_path, options)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, ItfError>>()
+        })?;
+
+        Ok(Self {
+            options: options.clone(),
+            samples,
+        })
+    }
+
+    /// Add a sample to the [`PatternBuilder`].
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The entire contents of the sample.
+    pub fn add_sample(&mut self, bytes: &[u8]) {
+        let name = format!("sample {}", self.samples.len() + 1);
+        self.add_weighted_sample(&name, bytes, 1.0);
+    }
+
+    /// Add a named sample with a given weight to the [`PatternBuilder`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the sample. This is used to identify the sample within the [`BuildReport`].
+    /// * `bytes` - The entire contents of the sample.
+    /// * `weight` - The weight of the sample. See [`Pattern::build_patterns_from_weighted_data`] for details.
+    pub fn add_weighted_sample(&mut self, name: &str, bytes: &[u8], weight: f32) {
+        self.samples.push(WeightedSample {
+            name: name.to_string(),
+            weight,
+            analysis: SampleAnalysis::from_sample(
+                FileSample::from_file_bytes(bytes),
+                bytes.len(),
+                &self.options,
+            ),
+        });
+    }
+
+    /// The number of samples that have been added to the [`PatternBuilder`].
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Have any samples been added to the [`PatternBuilder`]?
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Build the [`Pattern`] from the samples that have been added.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The [`Pattern`] into which the computed data will be written.
+    /// * `progress` - The [`ProgressSink`] to be notified as each sample is processed.
+    ///
+    /// # Returns
+    ///
+    /// A [`BuildReport`] describing the samples.
+    pub fn finalize(
+        self,
+        pattern: &mut Pattern,
+        progress: &mut dyn ProgressSink,
+    ) -> Result<BuildReport, ItfError> {
+        let BuildOptions {
+            scan_strings,
+            scan_byte_sequences,
+            scan_byte_distribution,
+            mask_sequences,
+            scan_extended_composition,
+            fuzzy_hash,
+            exclude_outliers,
+            ref string_blocklist,
+            synthesize_regexes,
+            min_samples,
+        } = self.options;
+
+        if self.samples.len() < min_samples {
+            return Err(ItfError::InsufficientSamples {
+                required: min_samples,
+                found: self.samples.len(),
+            });
+        }
+
+        let scan_extended_composition = scan_byte_distribution && scan_extended_composition;
+
+        let mut first_byte_sequence_pass = true;
+
+        // The samples with the greatest weight come first, so that the common sequences are established
+        // before any edge cases are considered.
+        let mut samples = self.samples;
+        samples.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+        let max_weight = samples.first().map_or(1.0, |s| s.weight);
+        let total_samples = samples.len();
+
+        let mut common_byte_sequences = Vec::<(usize, Vec<u8>)>::new();
+        let mut common_trailer_sequences = Vec::<(usize, Vec<u8>)>::new();
+        let mut common_masked_sequences = Vec::<(usize, Vec<Option<u8>>)>::new();
+        let mut regexes = Vec::new();
+        let mut alternative_sequences = Vec::new();
+        let mut common_file_size = None;
+        let mut all_strings = Vec::with_capacity(total_samples);
+        let mut common_anchored_strings: Option<HashSet<(usize, String)>> = None;
+        let mut byte_distribution: [usize; 256] = [0; 256];
+        let mut window_entropies: Vec<Vec<(f32, f32)>> =
+            vec![vec![]; file_processor::ENTROPY_WINDOWS.len()];
+        let mut edge_case_masks = HashSet::new();
+        let mut chi_square_range: Option<(f32, f32)> = None;
+        let mut bigram_counts = [0; file_processor::BIGRAM_PROFILE_SIZE];
+        let mut retained_chunks = Vec::with_capacity(total_samples);
+        let mut sequence_pass_index = 0;
+        let mut report = BuildReport::default();
+        let mut string_count = 0;
+
+        #[cfg(debug_assertions)]
+        let mut no_strings = vec![];
+
+        // The sequence refinement depends on the order of the samples, and so must be run serially.
+        for (i, sample) in samples.into_iter().enumerate() {
+            progress.update(&BuildProgress {
+                files_processed: i,
+                total_files: total_samples,
+                sequence_count: common_byte_sequences.len(),
+                string_count,
+            });
+
+            let WeightedSample {
+                name,
+                weight,
+                analysis,
+            } = sample;
+            let SampleAnalysis {
+                sample:
+                    FileSample {
+                        head: chunk,
+                        mut tail,
+                        ..
+                    },
+                file_size,
+                strings,
+                anchored_strings,
+                byte_frequencies,
+                window_entropies: entropies,
+                chi_square,
+                bigram_counts: file_bigram_counts,
+            } = analysis;
+
+            if scan_byte_sequences {
+                // Trailer sequences are anchored to the end of the file. The tail chunk is reversed
+                // so that the same refinement logic can be applied, and will be reversed back later.
+                tail.reverse();
+
+                if first_byte_sequence_pass {
+                    // On the first pass, we simply set the matching sequence as the entire byte block.
+                    // This will get trimmed down and split into sections over future loop iterations.
+                    common_byte_sequences.push((0, chunk.clone()));
+                    common_trailer_sequences.push((0, tail));
+                    common_file_size = Some(file_size);
+                    first_byte_sequence_pass = false;
+                } else if mask_sequences && weight < max_weight * EDGE_CASE_WEIGHT_RATIO {
+                    // Edge cases can't remove any of the common sequences, but the bytes in which they differ will be masked.
+                    file_processor::find_differing_bytes(
+                        &chunk,
+                        &common_byte_sequences,
+                        &mut edge_case_masks,
+                    );
+                    file_processor::refine_common_byte_sequences_v2(
+                        &tail,
+                        &mut common_trailer_sequences,
+                    );
+
+                    if common_file_size != Some(file_size) {
+                        common_file_size = None;
+                    }
+                } else {
+                    let previous = exclude_outliers.then(|| {
+                        (
+                            common_byte_sequences.clone(),
+                            common_trailer_sequences.clone(),
+                        )
+                    });
+                    let common_before = common_sequence_length(&common_byte_sequences);
+
+                    file_processor::refine_common_byte_sequences_v2(
+                        &chunk,
+                        &mut common_byte_sequences,
+                    );
+                    file_processor::refine_common_byte_sequences_v2(
+                        &tail,
+                        &mut common_trailer_sequences,
+                    );
+
+                    // Once the samples have settled on a set of common sequences, a sample that removes most of them
+                    // is likely to have been mislabeled.
+                    let common_after = common_sequence_length(&common_byte_sequences);
+                    let is_outlier = sequence_pass_index >= MIN_OUTLIER_SAMPLE_INDEX
+                        && common_before > 0
+                        && (common_before - common_after) as f32
+                            > common_before as f32 * OUTLIER_SEQUENCE_LOSS_RATIO;
+                    sequence_pass_index += 1;
+
+                    if is_outlier {
+                        report.outliers.push(name.clone());
+
+                        if let Some((sequences, trailer_sequences)) = previous {
+                            common_byte_sequences = sequences;
+                            common_trailer_sequences = trailer_sequences;
+                            continue;
+                        }
+                    }
+
+                    if common_file_size != Some(file_size) {
+                        common_file_size = None;
+                    }
+                }
+            }
+
+            #[cfg(debug_assertions)]
+            {
+                if scan_strings && strings.is_empty() {
+                    no_strings.push(name);
+                }
+            }
 
             if scan_byte_distribution {
-                file_processor::count_byte_frequencies(&chunk, &mut byte_distribution);
+                for (total, count) in byte_distribution.iter_mut().zip(byte_frequencies) {
+                    *total += count;
+                }
+
+                // Record the entropy of each window that this file extends into.
+                for (samples, entropy) in window_entropies.iter_mut().zip(entropies) {
+                    if let Some(e) = entropy {
+                        samples.push((e, weight));
+                    }
+                }
             }
 
-            if scan_strings {
-                let strings = file_processor::extract_file_strings(&chunk);
+            if let Some(c) = chi_square {
+                chi_square_range =
+                    Some(chi_square_range.map_or((c, c), |(min, max)| (min.min(c), max.max(c))));
 
-                #[cfg(debug_assertions)]
-                {
-                    if strings.is_empty() {
-                        no_strings.push(file_path);
-                    }
+                for (total, count) in bigram_counts.iter_mut().zip(file_bigram_counts) {
+                    *total += count;
                 }
+            }
 
+            if scan_strings {
+                string_count += strings.len();
                 all_strings.push(strings);
-            }
 
-            // On the first pass, we simply set the matching sequence as the entire byte block.
-            // This will get trimmed down and split into sections over future loop iterations.
-            if scan_byte_sequences && first_byte_sequence_pass {
-                common_byte_sequences.push((0, chunk));
-                first_byte_sequence_pass = false;
-                continue;
+                // Strings that appear at the same position in every file can be anchored to it.
+                common_anchored_strings = Some(match common_anchored_strings {
+                    Some(common) => common.intersection(&anchored_strings).cloned().collect(),
+                    None => anchored_strings,
+                });
             }
 
-            if scan_byte_sequences {
-                file_processor::refine_common_byte_sequences_v2(&chunk, &mut common_byte_sequences);
-            }
+            retained_chunks.push(chunk);
         }
 
+        progress.update(&BuildProgress {
+            files_processed: total_samples,
+            total_files: total_samples,
+            sequence_count: common_byte_sequences.len(),
+            string_count,
+        });
+        progress.finish();
+
         if scan_byte_sequences {
             file_processor::strip_unwanted_sequences(&mut common_byte_sequences);
 
+            // Sequences separated by a small number of differing bytes can be combined
+            // into a single sequence, with the differing bytes masked out.
+            if mask_sequences {
+                let mut edge_case_sequences = file_processor::mask_sequence_positions(
+                    &mut common_byte_sequences,
+                    &edge_case_masks,
+                );
+                common_masked_sequences =
+                    file_processor::extract_masked_sequences(&mut common_byte_sequences);
+                common_masked_sequences.append(&mut edge_case_sequences);
+
+                // Masked bytes that only take a few values can be more precisely described by a regex.
+                if synthesize_regexes && !common_masked_sequences.is_empty() {
+                    regexes =
+                        Pattern::build_regexes(&retained_chunks, &mut common_masked_sequences);
+                }
+
+                common_masked_sequences.sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+            }
+
             /*
              * Sort by the start position of the sequence, descending first.
              * This is done because the testing loop will bail if the start index is
              * beyond the bounds of the array. This could be an asset when testing
              * lots of smaller files.
              */
+            file_processor::merge_adjacent_sequences(&mut common_byte_sequences);
             common_byte_sequences.sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+
+            // The trailer sequences must be merged before they are reversed, while they are still ordered
+            // by their position within the reversed tail chunks.
+            file_processor::strip_unwanted_sequences(&mut common_trailer_sequences);
+            file_processor::merge_adjacent_sequences(&mut common_trailer_sequences);
+            file_processor::reverse_trailer_sequences(&mut common_trailer_sequences);
+
+            // If every sample file has the same size then the trailer sequences will duplicate
+            // the header sequences, and so those need to be removed.
+            if let Some(file_size) = common_file_size {
+                file_processor::strip_covered_trailer_sequences(
+                    &mut common_trailer_sequences,
+                    &common_byte_sequences,
+                    &common_masked_sequences,
+                    file_size,
+                );
+            }
+
+            // As above, the sequence furthest from the end of the file comes first.
+            common_trailer_sequences.sort_unstable_by_key(|b| std::cmp::Reverse(b.0));
+
+            // Formats with several distinct headers would otherwise have nothing anchored at the start of the file.
+            // The variants must stop short of the first byte that is already covered.
+            let first_covered = common_byte_sequences
+                .iter()
+                .map(|(start, _)| *start)
+                .chain(common_masked_sequences.iter().map(|(start, _)| *start))
+                .chain(regexes.iter().map(|(position, _)| *position))
+                .min()
+                .unwrap_or(usize::MAX);
+            let variants = file_processor::detect_header_variants(&retained_chunks, first_covered);
+            if !variants.is_empty() {
+                alternative_sequences.push(variants);
+            }
         }
 
         // Sieve the strings to retain only the common ones.
@@ -152,10 +2181,44 @@ impl Pattern {
             }
 
             common_strings = file_processor::common_string_sieve(&mut all_string_sets);
+            common_strings.retain(|s| !string_blocklist.is_blocked(s));
+            file_processor::strip_sequence_covered_strings(
+                &mut common_strings,
+                &common_byte_sequences,
+                &common_masked_sequences,
+            );
         }
 
+        let mut anchored_strings: Vec<(usize, String)> = common_anchored_strings
+            .map(|s| s.into_iter().collect())
+            .unwrap_or_default();
+        anchored_strings.retain(|(_, s)| !string_blocklist.is_blocked(s));
+        file_processor::strip_covered_anchored_strings(
+            &mut anchored_strings,
+            &common_byte_sequences,
+            &common_masked_sequences,
+        );
+        anchored_strings.sort_unstable();
+
         if scan_byte_distribution {
-            self.data.average_entropy = utils::calculate_shannon_entropy(&byte_distribution);
+            pattern.data.average_entropy = utils::calculate_shannon_entropy(&byte_distribution);
+            pattern.data.byte_distribution = byte_distribution.iter().map(|c| *c as u64).collect();
+            pattern.data.entropy_windows = window_entropies
+                .iter()
+                .enumerate()
+                .filter_map(|(i, samples)| {
+                    weighted_range(samples, max_weight).map(|(min, max)| (i, min, max))
+                })
+                .collect();
+        }
+
+        if scan_extended_composition {
+            pattern.data.chi_square = chi_square_range;
+            pattern.data.bigram_profile = file_processor::compute_bigram_profile(&bigram_counts);
+        }
+
+        if fuzzy_hash && !retained_chunks.is_empty() {
+            pattern.data.fuzzy_hash = Some(Pattern::build_fuzzy_hash(&retained_chunks));
         }
 
         #[cfg(debug_assertions)]
@@ -166,111 +2229,190 @@ impl Pattern {
         }
 
         // Add the computed information into the struct.
-        self.data.strings = HashSet::from_iter(common_strings);
-        self.data.sequences = common_byte_sequences;
-
-        self.other_data.total_scanned_files = files.len();
+        pattern.data.strings = HashSet::from_iter(common_strings);
+        pattern.data.anchored_strings = anchored_strings;
+        pattern.data.sequence_support = common_byte_sequences
+            .iter()
+            .rev()
+            .map(|(start, sequence)| {
+                let support = retained_chunks
+                    .iter()
+                    .filter(|c| c.get(*start..start + sequence.len()) == Some(&sequence[..]))
+                    .count();
+                (*start, support)
+            })
+            .collect();
+        pattern.data.sequences = interner::intern_sequences(common_byte_sequences);
+        pattern.data.alternative_sequences = alternative_sequences;
+        pattern.data.trailer_sequences = common_trailer_sequences;
+        pattern.data.masked_sequences = common_masked_sequences;
+        pattern.data.regexes = regexes;
+
+        pattern.other_data.total_scanned_files = retained_chunks.len();
+
+        Ok(report)
     }
+}
 
-    /// Compute various attributes once the main object data has been initialized.
-    pub fn compute_attributes(&mut self) {
-        self.compute_confidence_factor();
-        self.compute_max_points();
-    }
+/// A sample that has been analysed, along with its name and weight.
+#[derive(Clone)]
+struct WeightedSample {
+    /// The name of the sample, usually the path to the sample file.
+    name: String,
+    /// The weight of the sample.
+    weight: f32,
+    /// The analysis of the sample.
+    analysis: SampleAnalysis,
+}
 
-    /// Compute the confidence scale factor based on the number of files scanned to build this pattern.
-    fn compute_confidence_factor(&mut self) {
-        self.confidence_factor =
-            (self.other_data.total_scanned_files as f32).powf(CONFIDENCE_SCALE_FACTOR);
+/// The information extracted from a single sample file, prior to it being combined with the other sample files.
+#[derive(Clone)]
+struct SampleAnalysis {
+    /// The sampled regions of the file.
+    sample: FileSample,
+    /// The size of the file, in bytes.
+    file_size: usize,
+    /// The strings found within the header chunk and the middle of the file.
+    strings: HashSet<String>,
+    /// The strings found within the header chunk, along with their positions.
+    anchored_strings: HashSet<(usize, String)>,
+    /// The byte frequencies of the header chunk.
+    byte_frequencies: [usize; 256],
+    /// The entropy of each of the entropy windows that the header chunk extends into.
+    window_entropies: Vec<Option<f32>>,
+    /// The chi-square statistic of the header chunk, if the extended composition was computed.
+    chi_square: Option<f32>,
+    /// The bigram counts of the header chunk.
+    bigram_counts: [usize; file_processor::BIGRAM_PROFILE_SIZE],
+}
+
+impl SampleAnalysis {
+    /// Analyse a sample file, computing only the attributes required by the build options.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - The path to the sample file.
+    /// * `options` - The options used to build the pattern.
+    fn new(file_path: &str, options: &BuildOptions) -> Result<Self, ItfError> {
+        let sample = file_processor::read_file_sample(file_path)?;
+        let file_size = std::fs::metadata(file_path)?.len() as usize;
+
+        Ok(Self::from_sample(sample, file_size, options))
     }
 
-    /// Computer the maximum number of points that can be awarded for a perfect match against this pattern.
-    /// The more detailed the pattern, the higher the total points available.
-    fn compute_max_points(&mut self) {
-        let mut points = 0.0;
+    /// Analyse a sample that has already been read, computing only the attributes required by the build options.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - The [`FileSample`] of the sample.
+    /// * `file_size` - The total size of the sample, in bytes.
+    /// * `options` - The options used to build the pattern.
+    fn from_sample(sample: FileSample, file_size: usize, options: &BuildOptions) -> Self {
+        let mut analysis = Self {
+            sample,
+            file_size,
+            strings: HashSet::new(),
+            anchored_strings: HashSet::new(),
+            byte_frequencies: [0; 256],
+            window_entropies: vec![],
+            chi_square: None,
+            bigram_counts: [0; file_processor::BIGRAM_PROFILE_SIZE],
+        };
+        let chunk = &analysis.sample.head;
 
-        if self.data.should_scan_sequences() {
-            for (_, sequence) in &self.data.sequences {
-                points += sequence.len() as f32;
+        if options.scan_byte_distribution {
+            file_processor::count_byte_frequencies(chunk, &mut analysis.byte_frequencies);
+            analysis.window_entropies = file_processor::compute_window_entropies(chunk);
+
+            if options.scan_extended_composition {
+                analysis.chi_square = Some(utils::calculate_chi_square(&analysis.byte_frequencies));
+                file_processor::count_bigrams(chunk, &mut analysis.bigram_counts);
             }
         }
 
-        if self.data.should_scan_strings() {
-            for string in &self.data.strings {
-                points += string.len() as f32;
+        if options.scan_strings {
+            // Strings may also be found within the middle of larger files.
+            analysis.strings = file_processor::extract_file_strings(chunk);
+            if let Some(middle) = &analysis.sample.middle {
+                analysis
+                    .strings
+                    .extend(file_processor::extract_file_strings(middle));
             }
-        }
 
-        if self.data.should_scan_composition() {
-            points += MAX_ENTROPY_POINTS;
+            analysis.anchored_strings = file_processor::extract_anchored_strings(chunk);
         }
 
-        // Scale the relevant points by the confidence factor derived from the total files scanned.
-        points *= self.confidence_factor;
-
-        // The file extension is considered a separate factor and doesn't scale with the number
-        // of scanned files.
-        points += FILE_EXTENSION_POINTS;
-
-        self.max_points = points.ceil() as usize;
+        analysis
     }
+}
 
-    /// Attempt to build a [`Pattern`] from a JSON string.
-    ///
-    /// # Arguments
-    ///
-    /// * `input` - The input JSON string.
-    ///
-    /// # Returns
-    ///
-    /// An error if the deserialization failed, otherwise the build [`Patten`] will be returned.
-    pub fn from_json_str(input: &str) -> Result<Pattern, Box<dyn std::error::Error>> {
-        let json_bytes = input.as_bytes().to_vec();
-        let p: Pattern = serde_json::from_slice(&json_bytes[..])?;
-        Ok(p)
-    }
+/// A summary of the sample files used to build a [`Pattern`].
+#[derive(Clone, Debug, Default)]
+pub struct BuildReport {
+    /// The sample files that removed most of the common byte sequences, and so may have been mislabeled.
+    /// These will have been excluded from the pattern if [`BuildOptions::exclude_outliers`] was set.
+    pub outliers: Vec<String>,
+}
 
-    /// Attempt to build a [`Pattern`] from a JSON string, using SIMD.
-    ///
-    /// # Arguments
-    ///
-    /// * `input` - The input JSON string.
-    ///
-    /// # Returns
-    ///
-    /// An error if the deserialization failed, otherwise the build [`Patten`] will be returned.
-    pub fn from_simd_json_str(input: &str) -> Result<Pattern, Box<dyn std::error::Error>> {
-        let mut json_bytes = input.as_bytes().to_vec();
-        let p: Pattern = simd_json::from_slice(&mut json_bytes[..])?;
-        Ok(p)
-    }
+/// The outcome of validating a [`Pattern`] against a set of files that it should not match.
+#[derive(Clone, Debug, Default)]
+pub struct NegativeValidation {
+    /// The number of files that were scanned.
+    pub total_scanned_files: usize,
+    /// The number of strings, including anchored strings, that were dropped from the pattern.
+    pub dropped_strings: usize,
+    /// The files that still match the pattern, along with their match percentage, in descending order.
+    pub false_positives: Vec<(String, f32)>,
+}
 
-    /// Derive the name of a pattern based on the stored pattern data.
-    fn get_pattern_file_name(&self) -> String {
-        let file_name = utils::sanitize_file_name(&self.type_data.name);
-        file_name.replace(" ", "-") + ".json"
-    }
+/// The outcome of cross-validating a [`Pattern`] against its own sample files.
+#[derive(Clone, Debug, Default)]
+pub struct CrossValidation {
+    /// The number of folds into which the sample files were split.
+    pub folds: usize,
+    /// The lowest match percentage of any held-out sample file.
+    pub min_percentage: f32,
+    /// The average match percentage of the held-out sample files.
+    pub average_percentage: f32,
+    /// Each sample file along with its match percentage when held out, in ascending order.
+    pub scores: Vec<(String, f32)>,
+}
 
-    /// Attempt to write a JSON file for the data contained within the pattern.
-    ///
-    /// # Arguments
-    ///
-    /// * `path` - The input JSON string.
-    ///
-    /// # Returns
-    ///
-    /// An error if the writing failed, otherwise a [`PathBuf`] to the written file will be returned.
-    pub fn write(&self, path: &str) -> std::io::Result<PathBuf> {
-        let serialized = serde_json::to_string(self).unwrap();
+/// An assessment of how reliably a [`Pattern`] is likely to identify its file type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatternQuality {
+    /// The total number of bytes that must be present at a known position, across all of the sequences and regexes.
+    pub anchored_bytes: usize,
+    /// The number of sample files from which the pattern was built.
+    pub total_samples: usize,
+    /// The number of strings, including anchored strings.
+    pub string_count: usize,
+    /// The widest range of entropy observed within any of the entropy windows, if the byte distribution was scanned.
+    pub entropy_spread: Option<f32>,
+    /// The overall grade of the pattern.
+    pub grade: QualityGrade,
+}
 
-        let mut path = PathBuf::from(path);
-        path.push(self.get_pattern_file_name());
+/// The overall grade of a [`Pattern`]. Grades are ordered from weakest to strongest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum QualityGrade {
+    /// The pattern is likely to produce false positives, or was built from too few sample files.
+    Weak,
+    /// The pattern is usable, but would benefit from more sample files or anchored bytes.
+    Moderate,
+    /// The pattern has plenty of anchored bytes and was built from a reasonable number of sample files.
+    Strong,
+}
 
-        let mut output = File::create(&path)?;
-        match write!(output, "{serialized}") {
-            Ok(_) => Ok(path),
-            Err(e) => Err(e),
-        }
+impl fmt::Display for QualityGrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let grade = match self {
+            QualityGrade::Weak => "Weak",
+            QualityGrade::Moderate => "Moderate",
+            QualityGrade::Strong => "Strong",
+        };
+
+        write!(f, "{grade}")
     }
 }
 
@@ -284,14 +2426,46 @@ pub struct PatternTypeData {
     pub description: String,
     /// Any known extensions for this file type.
     #[serde(rename = "extensions", default = "default_extensions")]
+    #[serde(deserialize_with = "interner::deserialize_interned")]
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub known_extensions: Vec<String>,
+    pub known_extensions: Vec<Arc<str>>,
     /// Any known mimetypes for this file type.
     #[serde(rename = "mimetypes", default = "default_mimetypes")]
+    #[serde(deserialize_with = "interner::deserialize_interned")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub known_mimetypes: Vec<Arc<str>>,
+    /// The categories to which this file type belongs (e.g. "video", "archive" or "executable"),
+    /// used to restrict matching to the patterns of interest.
+    #[serde(default = "default_categories")]
+    #[serde(deserialize_with = "interner::deserialize_interned")]
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub known_mimetypes: Vec<String>,
+    pub categories: Vec<Arc<str>>,
     /// The UUID of the pattern file.
     pub uuid: String,
+    /// The priority of this pattern, used to order results that have the same match percentage.
+    /// Patterns for more specific file types (such as DOCX, which is a ZIP container)
+    /// should be given a higher priority than the generic type on which they are based.
+    #[serde(default = "default_priority")]
+    #[serde(skip_serializing_if = "is_default_priority")]
+    pub priority: i32,
+    /// The UUID of the pattern for the more general format of which this is a member, if any
+    /// (e.g. WebM is a member of the Matroska family). Used to group the results of related patterns.
+    #[serde(default = "default_parent_uuid")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_uuid: Option<String>,
+}
+
+impl PatternTypeData {
+    /// Does this file type belong to a specific category?
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - The category. This is compared without regard to case.
+    pub fn in_category(&self, category: &str) -> bool {
+        self.categories
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(category))
+    }
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -302,7 +2476,59 @@ pub struct PatternData {
     /// # Notes
     /// Byte sequence matches are -not- optional - a missing sequence will result in an immediate no-match.
     #[serde(default = "default_sequences")]
-    pub sequences: Vec<(usize, Vec<u8>)>,
+    #[serde(deserialize_with = "interner::deserialize_interned_sequences")]
+    pub sequences: Vec<(usize, Arc<[u8]>)>,
+    /// The number of sample files that contained each of the positional byte sequences, given as the
+    /// start position of the sequence followed by its support.
+    ///
+    /// # Notes
+    /// Sequences without an entry, such as those added by hand, are assumed to have been present in every sample file.
+    #[serde(default = "default_sequence_support")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sequence_support: Vec<(usize, usize)>,
+    /// Any byte sequences that may start anywhere within a window of positions, given as the
+    /// minimum and maximum (inclusive) start positions followed by the byte sequence.
+    /// These can't be derived from sample files and must be specified by the pattern author.
+    ///
+    /// # Notes
+    /// As with the positional byte sequences, ranged sequence matches are -not- optional.
+    #[serde(default = "default_ranged_sequences")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ranged_sequences: Vec<(usize, usize, Vec<u8>)>,
+    /// Groups of alternative byte sequences, given as the position of each alternative followed by the byte sequence.
+    /// These describe formats with several distinct headers, such as the little and big endian variants of TIFF.
+    ///
+    /// # Notes
+    /// Any one alternative of a group satisfies the group, but a group without a matching alternative will result in an immediate no-match.
+    #[serde(default = "default_alternative_sequences")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alternative_sequences: Vec<Vec<(usize, Vec<u8>)>>,
+    /// Any byte sequences that are anchored to the end of the file, giving the distance from
+    /// the start of the sequence to the end of the file.
+    /// This field will be empty if byte sequence scanning was disabled.
+    ///
+    /// # Notes
+    /// As with the positional byte sequences, trailer sequence matches are -not- optional.
+    #[serde(default = "default_sequences")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trailer_sequences: Vec<(usize, Vec<u8>)>,
+    /// Any positional byte sequences that contain one or more masked (wildcard) bytes.
+    /// A masked byte is represented by a `None` value and will match any byte.
+    /// This field will be empty if byte sequence scanning or masking was disabled.
+    ///
+    /// # Notes
+    /// As with the positional byte sequences, masked sequence matches are -not- optional.
+    #[serde(default = "default_masked_sequences")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub masked_sequences: Vec<(usize, Vec<Option<u8>>)>,
+    /// Any byte regexes that must match at a fixed position, given as the position followed by the regex.
+    /// Points are awarded based on the minimum length of a match.
+    ///
+    /// # Notes
+    /// As with the positional byte sequences, regex matches are -not- optional.
+    #[serde(default = "default_regexes")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub regexes: Vec<(usize, String)>,
     /// Any strings that may be associated with this file type.
     /// This field will be empty if string scanning was disabled.
     ///
@@ -310,6 +2536,15 @@ pub struct PatternData {
     /// String matches are optional and a missing string will not render the match void.
     #[serde(default = "default_strings")]
     pub strings: HashSet<String>,
+    /// Any strings that appear at a fixed position, given as the position followed by the string.
+    /// This field will be empty if string scanning was disabled.
+    ///
+    /// # Notes
+    /// As with strings, anchored string matches are optional. Strings that are covered
+    /// by a byte sequence will be omitted.
+    #[serde(default = "default_anchored_strings")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub anchored_strings: Vec<(usize, String)>,
     /// The average entropy for this file type.
     /// This will be zero if byte distribution scanning was disabled.
     ///
@@ -317,6 +2552,44 @@ pub struct PatternData {
     /// Entropy will be evaluated based by its percentage of deviation from the stored average.
     #[serde(default = "default_average_entropy")]
     pub average_entropy: f32,
+    /// The total number of times that each byte value appeared within the header chunks of the sample files.
+    /// This field will be empty if byte distribution scanning was disabled.
+    ///
+    /// # Notes
+    /// This isn't used when matching, but allows the average entropy to be accurately recomputed when the pattern is refined.
+    #[serde(default = "default_byte_distribution")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub byte_distribution: Vec<u64>,
+    /// The minimum and maximum entropy observed within each of the entropy windows, given as the
+    /// window index followed by the range. Windows that no sample file extended into are omitted.
+    /// This field will be empty if byte distribution scanning was disabled.
+    ///
+    /// # Notes
+    /// Entropy within a window will be evaluated based on its distance from the stored range.
+    #[serde(default = "default_entropy_windows")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entropy_windows: Vec<(usize, f32, f32)>,
+    /// The minimum and maximum chi-square statistic of the byte distribution, relative to a uniform distribution.
+    /// This will be `None` if extended composition scanning was disabled.
+    ///
+    /// # Notes
+    /// This can help to separate data with similar entropy, such as compressed and encrypted data.
+    #[serde(default = "default_chi_square")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chi_square: Option<(f32, f32)>,
+    /// The proportion of byte bigrams that fall within each bucket, with each bucket representing a pair of high nibbles.
+    /// This field will be empty if extended composition scanning was disabled.
+    #[serde(default = "default_bigram_profile")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bigram_profile: Vec<f32>,
+    /// The block size and digest of a fuzzy hash representing the sample files.
+    /// This will be `None` if fuzzy hashing was disabled.
+    ///
+    /// # Notes
+    /// Fuzzy hash matches are optional, and points are awarded based on the similarity of the digests.
+    #[serde(default = "default_fuzzy_hash")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fuzzy_hash: Option<(usize, String)>,
 }
 
 impl PatternData {
@@ -326,10 +2599,63 @@ impl PatternData {
         !self.strings.is_empty()
     }
 
+    /// Should we scan for anchored strings when using this pattern?
+    #[inline(always)]
+    pub fn should_scan_anchored_strings(&self) -> bool {
+        !self.anchored_strings.is_empty()
+    }
+
+    /// Get the recorded support of the positional byte sequence that covers a position.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position.
+    ///
+    /// # Returns
+    ///
+    /// The number of sample files that contained the sequence, or `None` if no sequence covers
+    /// the position or its support wasn't recorded.
+    pub fn sequence_support_at(&self, position: usize) -> Option<usize> {
+        let (start, _) = self
+            .sequences
+            .iter()
+            .find(|(start, s)| (*start..start + s.len()).contains(&position))?;
+
+        self.sequence_support
+            .iter()
+            .find(|(p, _)| p == start)
+            .map(|(_, support)| *support)
+    }
+
     /// Should we scan for byte sequences when using this pattern?
     #[inline(always)]
     pub fn should_scan_sequences(&self) -> bool {
         !self.sequences.is_empty()
+            || !self.ranged_sequences.is_empty()
+            || !self.alternative_sequences.is_empty()
+    }
+
+    /// Get the number of points awarded for a match against a group of alternative byte sequences.
+    /// This is the length of the shortest alternative, so that a match against any of them is a perfect match.
+    ///
+    /// # Arguments
+    ///
+    /// * `group` - The group of alternatives.
+    #[inline(always)]
+    pub fn alternative_points(group: &[(usize, Vec<u8>)]) -> usize {
+        group.iter().map(|(_, s)| s.len()).min().unwrap_or(0)
+    }
+
+    /// Should we scan for trailer byte sequences when using this pattern?
+    #[inline(always)]
+    pub fn should_scan_trailer_sequences(&self) -> bool {
+        !self.trailer_sequences.is_empty()
+    }
+
+    /// Should we scan for masked byte sequences when using this pattern?
+    #[inline(always)]
+    pub fn should_scan_masked_sequences(&self) -> bool {
+        !self.masked_sequences.is_empty()
     }
 
     /// Should we scan the file's composition when using this pattern?
@@ -337,6 +2663,30 @@ impl PatternData {
     pub fn should_scan_composition(&self) -> bool {
         self.average_entropy != 0.0
     }
+
+    /// Should we scan the file's extended composition metrics when using this pattern?
+    #[inline(always)]
+    pub fn should_scan_extended_composition(&self) -> bool {
+        self.chi_square.is_some() || !self.bigram_profile.is_empty()
+    }
+
+    /// Should we scan for regexes when using this pattern?
+    #[inline(always)]
+    pub fn should_scan_regexes(&self) -> bool {
+        !self.regexes.is_empty()
+    }
+
+    /// Should we compare the file's fuzzy hash when using this pattern?
+    #[inline(always)]
+    pub fn should_scan_fuzzy_hash(&self) -> bool {
+        self.fuzzy_hash.is_some()
+    }
+
+    /// Should we scan the file's windowed entropy when using this pattern?
+    #[inline(always)]
+    pub fn should_scan_entropy_windows(&self) -> bool {
+        !self.entropy_windows.is_empty()
+    }
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -348,6 +2698,19 @@ pub struct PatternOtherData {
     #[serde(default = "default_file_format_url")]
     #[serde(skip_serializing_if = "String::is_empty")]
     pub file_format_url: String,
+    /// The tool or project from which the pattern was imported (e.g. "TrID"). This will be empty if the
+    /// pattern was built from sample files.
+    #[serde(default = "default_source")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub source: String,
+    /// The license under which the pattern may be redistributed, ideally as an SPDX identifier (e.g. "MIT").
+    #[serde(default = "default_license")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub license: String,
+    /// The URL from which the pattern, or the definition from which it was imported, was obtained.
+    #[serde(default = "default_origin_url")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub origin_url: String,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -380,23 +2743,106 @@ impl Default for PatternSubmitterData {
     }
 }
 
+/// Compute the range of a set of weighted values. The range is only widened toward each value
+/// in proportion to its weight, relative to the greatest weight.
+///
+/// # Arguments
+///
+/// * `samples` - The values and their weights.
+/// * `max_weight` - The greatest weight.
+///
+/// # Returns
+///
+/// The minimum and maximum of the range, or `None` if there are no values.
+fn weighted_range(samples: &[(f32, f32)], max_weight: f32) -> Option<(f32, f32)> {
+    let total_weight: f32 = samples.iter().map(|(_, w)| w).sum();
+    if samples.is_empty() || total_weight <= 0.0 {
+        return None;
+    }
+
+    let center = samples.iter().map(|(v, w)| v * w).sum::<f32>() / total_weight;
+
+    samples
+        .iter()
+        .map(|(v, w)| {
+            if *w >= max_weight {
+                *v
+            } else {
+                center + (v - center) * (w / max_weight)
+            }
+        })
+        .fold(None, |range, v| {
+            Some(range.map_or((v, v), |(min, max): (f32, f32)| (min.min(v), max.max(v))))
+        })
+}
+
+/// Get the total length of a set of byte sequences.
+fn common_sequence_length(sequences: &[(usize, Vec<u8>)]) -> usize {
+    sequences.iter().map(|(_, s)| s.len()).sum()
+}
+
+fn default_format_version() -> u32 {
+    // Documents written before the version was recorded are in the sectioned layout, if they can be read at all.
+    1
+}
+
 fn default_description() -> String {
     String::new()
 }
 
-fn default_extensions() -> Vec<String> {
+fn default_extensions() -> Vec<Arc<str>> {
+    vec![]
+}
+
+fn default_mimetypes() -> Vec<Arc<str>> {
     vec![]
 }
 
-fn default_mimetypes() -> Vec<String> {
+fn default_categories() -> Vec<Arc<str>> {
     vec![]
 }
 
+fn default_priority() -> i32 {
+    0
+}
+
+fn is_default_priority(priority: &i32) -> bool {
+    *priority == default_priority()
+}
+
+fn default_parent_uuid() -> Option<String> {
+    None
+}
+
 fn default_strings() -> HashSet<String> {
     HashSet::new()
 }
 
-fn default_sequences() -> Vec<(usize, Vec<u8>)> {
+fn default_anchored_strings() -> Vec<(usize, String)> {
+    vec![]
+}
+
+fn default_sequences<B>() -> Vec<(usize, B)> {
+    vec![]
+}
+
+fn default_sequence_support() -> Vec<(usize, usize)> {
+    vec![]
+}
+
+fn default_alternative_sequences() -> Vec<Vec<(usize, Vec<u8>)>> {
+    vec![]
+}
+
+fn default_ranged_sequences() -> Vec<(usize, usize, Vec<u8>)> {
+    vec![]
+}
+
+fn default_masked_sequences() -> Vec<(usize, Vec<Option<u8>>)> {
+    vec![]
+}
+
+fn default_regexes() -> Vec<(usize, String)> {
     vec![]
 }
 
@@ -404,10 +2850,50 @@ fn default_average_entropy() -> f32 {
     0.0
 }
 
+fn default_byte_distribution() -> Vec<u64> {
+    vec![]
+}
+
+fn default_chi_square() -> Option<(f32, f32)> {
+    None
+}
+
+fn default_bigram_profile() -> Vec<f32> {
+    vec![]
+}
+
+fn default_fuzzy_hash() -> Option<(usize, String)> {
+    None
+}
+
+fn default_entropy_windows() -> Vec<(usize, f32, f32)> {
+    vec![]
+}
+
+fn default_signature() -> Option<PatternSignature> {
+    None
+}
+
+fn default_checksum() -> Option<String> {
+    None
+}
+
 fn default_file_format_url() -> String {
     String::new()
 }
 
+fn default_source() -> String {
+    String::new()
+}
+
+fn default_license() -> String {
+    String::new()
+}
+
+fn default_origin_url() -> String {
+    String::new()
+}
+
 fn default_refined_by() -> Vec<String> {
     vec![]
 }
@@ -419,12 +2905,44 @@ fn default_refined_by_email() -> Vec<String> {
 #[cfg(test)]
 mod tests_pattern {
     use core::str;
+    use std::{fs, path::Path};
+
+    use hashbrown::{HashMap, HashSet};
+    use rayon::ThreadPoolBuilder;
+
+    use crate::{
+        file_point_calculator::{
+            FilePointCalculator, FILE_EXTENSION_POINTS, MIN_SEQUENCE_SUPPORT_WEIGHT,
+        },
+        file_processor::FileSample,
+        fuzzy,
+        interner::{intern_all, intern_sequences},
+        string_blocklist::StringBlocklist,
+        test_utils, utils,
+    };
+
+    use super::{
+        weighted_range, BuildOptions, BuildProgress, Pattern, PatternBuilder, ProgressSink,
+        QualityGrade, SampleWeights, DEFAULT_MIN_SAMPLES,
+    };
+
+    /// A [`ProgressSink`] that records every callback, in the order in which they were made.
+    #[derive(Default)]
+    struct RecordingSink {
+        updates: Vec<BuildProgress>,
+        /// The number of updates that had been made when each call to finish was made.
+        finished_after: Vec<usize>,
+    }
 
-    use hashbrown::HashSet;
-
-    use crate::{test_utils, utils};
+    impl ProgressSink for RecordingSink {
+        fn update(&mut self, progress: &BuildProgress) {
+            self.updates.push(*progress);
+        }
 
-    use super::Pattern;
+        fn finish(&mut self) {
+            self.finished_after.push(self.updates.len());
+        }
+    }
 
     #[test]
     fn test_string_1() {
@@ -436,6 +2954,23 @@ mod tests_pattern {
         assert_eq!(pattern.data.strings, set);
     }
 
+    #[test]
+    fn test_string_blocklist() {
+        // As above, but the only common string contains a blocked entry.
+        let mut string_blocklist = StringBlocklist::empty();
+        string_blocklist.add("defg");
+
+        let options = BuildOptions {
+            scan_byte_sequences: false,
+            scan_byte_distribution: false,
+            string_blocklist,
+            ..Default::default()
+        };
+        let pattern = build_test_with_options("strings", "1", &options);
+
+        assert!(pattern.data.strings.is_empty());
+    }
+
     #[test]
     fn test_string_2() {
         // Simple non-match, two files and none are matching.
@@ -511,7 +3046,7 @@ mod tests_pattern {
         // Basic match, two files both completely matching.
         let pattern = build_test("byte_sequences", "1", false, true, false);
 
-        let expected_set = vec![(0, (*b"abcdefghijk").to_vec())];
+        let expected_set = intern_sequences(vec![(0, (*b"abcdefghijk").to_vec())]);
 
         assert_eq!(pattern.data.sequences, expected_set);
     }
@@ -521,7 +3056,7 @@ mod tests_pattern {
         // Simple non-match, two files and none are matching.
         let pattern = build_test("byte_sequences", "2", false, true, false);
 
-        assert_eq!(pattern.data.sequences, vec![]);
+        assert!(pattern.data.sequences.is_empty());
     }
 
     #[test]
@@ -529,7 +3064,8 @@ mod tests_pattern {
         // Simple match, two sub-sequences matching.
         let pattern = build_test("byte_sequences", "3", false, true, false);
 
-        let expected_set = vec![(6, (*b"ghijk").to_vec()), (0, (*b"abcde").to_vec())];
+        let expected_set =
+            intern_sequences(vec![(6, (*b"ghijk").to_vec()), (0, (*b"abcde").to_vec())]);
 
         assert_eq!(pattern.data.sequences, expected_set);
     }
@@ -539,7 +3075,7 @@ mod tests_pattern {
         // Single match, the end of the sequence is offset and so won't match.
         let pattern = build_test("byte_sequences", "4", false, true, false);
 
-        let expected_set = vec![(0, (*b"abcde").to_vec())];
+        let expected_set = intern_sequences(vec![(0, (*b"abcde").to_vec())]);
 
         assert_eq!(pattern.data.sequences, expected_set);
     }
@@ -549,7 +3085,7 @@ mod tests_pattern {
         // No matches.
         let pattern = build_test("byte_sequences", "5", false, true, false);
 
-        assert_eq!(pattern.data.sequences, vec![]);
+        assert!(pattern.data.sequences.is_empty());
     }
 
     #[test]
@@ -558,10 +3094,10 @@ mod tests_pattern {
         // exceed the maximum then it will get split into two segments.
         let pattern = build_test("byte_sequences", "6", false, true, false);
 
-        let expected_set = vec![
+        let expected_set = intern_sequences(vec![
             (16, "123456".as_bytes().to_vec()),
             (0, "abcdefghijkŠaŠ".as_bytes().to_vec()),
-        ];
+        ]);
 
         assert_eq!(pattern.data.sequences, expected_set);
     }
@@ -571,10 +3107,10 @@ mod tests_pattern {
         // Split match, two substrings will be returned.
         let pattern = build_test("byte_sequences", "7", false, true, false);
 
-        let expected_set = vec![
+        let expected_set = intern_sequences(vec![
             (16, "123456".as_bytes().to_vec()),
             (13, "a".as_bytes().to_vec()),
-        ];
+        ]);
 
         assert_eq!(pattern.data.sequences, expected_set);
     }
@@ -584,7 +3120,7 @@ mod tests_pattern {
         // Single match at the very end.
         let pattern = build_test("byte_sequences", "8", false, true, false);
 
-        let expected_set = vec![(10, "k".as_bytes().to_vec())];
+        let expected_set = intern_sequences(vec![(10, "k".as_bytes().to_vec())]);
 
         assert_eq!(pattern.data.sequences, expected_set);
     }
@@ -594,11 +3130,65 @@ mod tests_pattern {
         // Single match at the beginning. The null byte sequence should be stripped.
         let pattern = build_test("byte_sequences", "9", false, true, false);
 
-        let expected_set = vec![(0, "abcdefghijk".as_bytes().to_vec())];
+        let expected_set = intern_sequences(vec![(0, "abcdefghijk".as_bytes().to_vec())]);
 
         assert_eq!(pattern.data.sequences, expected_set);
     }
 
+    #[test]
+    fn test_masked_sequence_1() {
+        // Two sub-sequences separated by a single differing byte, which will be masked.
+        let options = BuildOptions {
+            scan_strings: false,
+            scan_byte_distribution: false,
+            ..Default::default()
+        };
+        let pattern = build_test_with_options("byte_sequences", "3", &options);
+
+        let mut expected_sequence: Vec<_> = b"abcde ghijk".iter().map(|b| Some(*b)).collect();
+        expected_sequence[5] = None;
+
+        assert!(pattern.data.sequences.is_empty());
+        assert_eq!(pattern.data.masked_sequences, vec![(0, expected_sequence)]);
+    }
+
+    #[test]
+    fn test_masked_sequence_2() {
+        // Two sub-sequences separated by two differing bytes, which will be masked.
+        let options = BuildOptions {
+            scan_strings: false,
+            scan_byte_distribution: false,
+            ..Default::default()
+        };
+        let pattern = build_test_with_options("byte_sequences", "7", &options);
+
+        let mut expected_sequence: Vec<_> = b"a..123456".iter().map(|b| Some(*b)).collect();
+        expected_sequence[1] = None;
+        expected_sequence[2] = None;
+
+        assert!(pattern.data.sequences.is_empty());
+        assert_eq!(pattern.data.masked_sequences, vec![(13, expected_sequence)]);
+    }
+
+    #[test]
+    fn test_trailer_sequence_1() {
+        // Files of different sizes with a common ending.
+        let pattern = build_test("trailer_sequences", "1", false, true, false);
+
+        let expected_set = vec![(6, "-IEND#".as_bytes().to_vec())];
+
+        assert!(pattern.data.sequences.is_empty());
+        assert_eq!(pattern.data.trailer_sequences, expected_set);
+    }
+
+    #[test]
+    fn test_trailer_sequence_2() {
+        // Files of the same size, the trailer sequences would duplicate the header sequences.
+        let pattern = build_test("byte_sequences", "1", false, true, false);
+
+        assert!(pattern.data.trailer_sequences.is_empty());
+    }
+
     #[test]
     fn test_entropy_1() {
         let pattern = build_test("entropy", "1", false, false, true);
@@ -664,6 +3254,726 @@ mod tests_pattern {
         }
     }
 
+    #[test]
+    fn test_anchored_string() {
+        // One string is at the same position in both files, and the other isn't.
+        let pattern = build_test("strings", "9", true, false, false);
+
+        assert_eq!(
+            pattern.data.anchored_strings,
+            vec![(9, "HELLO WORLD".to_string())]
+        );
+        assert!(pattern.data.strings.contains("FLOATING"));
+    }
+
+    #[test]
+    fn test_anchored_string_covered() {
+        // The anchored string is also a byte sequence, and so shouldn't be scored twice.
+        let pattern = build_test("strings", "9", true, true, false);
+
+        assert!(pattern.data.anchored_strings.is_empty());
+    }
+
+    #[test]
+    fn test_refine() {
+        let mut pattern = build_test("refine", "1", true, true, false);
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![
+                (16, b"\x01TAIL".to_vec()),
+                (0, b"MAGIC1abcdefghij".to_vec())
+            ])
+        );
+
+        // The new sample file differs by a single byte, which must be removed from the sequence.
+        // What remains of the first sequence is then adjacent to the second, and so they are merged.
+        let test_dir = test_utils::test_path_builder("refine", "2");
+        pattern
+            .refine_from_data(&test_dir, "test", "tester", "")
+            .expect("failed to refine test pattern");
+
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![
+                (6, b"abcdefghij\x01TAIL".to_vec()),
+                (0, b"MAGIC".to_vec())
+            ])
+        );
+        assert_eq!(pattern.other_data.total_scanned_files, 3);
+        assert_eq!(
+            pattern.submitter_data.refined_by,
+            vec!["tester".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_add_sequence() {
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        pattern.data.sequences =
+            intern_sequences(vec![(20, b"TAIL".to_vec()), (0, b"ABCDEFGH".to_vec())]);
+        pattern.data.masked_sequences = vec![(4, vec![Some(b'E'), None, Some(b'G')])];
+        pattern.data.anchored_strings = vec![(2, "XYZAB".to_string())];
+
+        // The added sequence overrides the middle of the existing one.
+        pattern.add_sequence(3, b"XYZ".to_vec());
+
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![
+                (20, b"TAIL".to_vec()),
+                (6, b"GH".to_vec()),
+                (3, b"XYZ".to_vec()),
+                (0, b"ABC".to_vec())
+            ])
+        );
+        assert!(pattern.data.masked_sequences.is_empty());
+        assert_eq!(pattern.data.anchored_strings.len(), 1);
+    }
+
+    #[test]
+    fn test_sequence_support() {
+        // Every retained sample file contains the sequences of a built pattern.
+        let pattern = build_test("refine", "1", true, true, false);
+        let total = pattern.other_data.total_scanned_files;
+        assert!(!pattern.data.sequences.is_empty());
+        for (start, _) in &pattern.data.sequences {
+            assert_eq!(pattern.data.sequence_support_at(*start), Some(total));
+        }
+
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        pattern.data.sequences =
+            intern_sequences(vec![(20, b"TAIL".to_vec()), (0, b"ABCDEFGH".to_vec())]);
+        pattern.data.sequence_support = vec![(0, 1), (20, 4)];
+        pattern.other_data.total_scanned_files = 4;
+
+        // The retained parts of a trimmed sequence keep its support, while an added sequence has none.
+        pattern.add_sequence(3, b"XYZ".to_vec());
+        assert_eq!(pattern.data.sequence_support, vec![(0, 1), (6, 1), (20, 4)]);
+        assert_eq!(pattern.data.sequence_support_at(7), Some(1));
+        assert_eq!(pattern.data.sequence_support_at(4), None);
+        assert_eq!(pattern.data.sequence_support_at(10), None);
+
+        // A sequence with a low support is worth fewer points.
+        pattern.compute_attributes();
+        let report = FilePointCalculator::compute_report(
+            &pattern,
+            &FileSample::from_bytes(b"ABCXYZGH............TAIL"),
+            None,
+            utils::DEFAULT_COMPOUND_EXTENSIONS,
+            false,
+        )
+        .unwrap();
+        let low = (1.0 - MIN_SEQUENCE_SUPPORT_WEIGHT) / 4.0 + MIN_SEQUENCE_SUPPORT_WEIGHT;
+        assert_eq!(report.sequence_points, 4.0 + 3.0 + 5.0 * low);
+    }
+
+    #[test]
+    fn test_add_regex() {
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+
+        // Points are awarded based on the minimum length of a match.
+        assert_eq!(pattern.add_regex(2, "AB[CD]{2,}E?").ok(), Some(4));
+        assert!(pattern.add_regex(0, "AB(").is_err());
+        assert_eq!(pattern.data.regexes, vec![(2, "AB[CD]{2,}E?".to_string())]);
+
+        pattern.other_data.total_scanned_files = 1;
+        pattern.compute_attributes();
+        let matches = |bytes: &[u8]| {
+            FilePointCalculator::compute_report(
+                &pattern,
+                &FileSample::from_bytes(bytes),
+                None,
+                utils::DEFAULT_COMPOUND_EXTENSIONS,
+                false,
+            )
+            .map(|r| r.regex_points)
+        };
+        assert_eq!(matches(b"..ABCDDE"), Some(4.0));
+        assert_eq!(matches(b"ABCD"), None);
+    }
+
+    #[test]
+    fn test_merge_byte_distribution() {
+        let mut pattern = Pattern::new("test", "test", vec![], vec![]);
+        pattern.data.byte_distribution = vec![0; 256];
+        pattern.data.byte_distribution[0] = 10;
+        pattern.other_data.total_scanned_files = 1;
+
+        let mut other = pattern.clone();
+        other.data.byte_distribution.swap(0, 1);
+
+        // Each pattern has zero entropy on its own, but together the two byte values are equally likely.
+        let mut merged = pattern.clone();
+        merged.merge(&other);
+        assert_eq!(merged.data.average_entropy, 1.0);
+        assert_eq!(merged.data.byte_distribution[..2], [10, 10]);
+
+        // Without a byte distribution, only an approximation can be made.
+        other.data.byte_distribution.clear();
+        pattern.merge(&other);
+        assert_eq!(pattern.data.average_entropy, 0.0);
+        assert!(pattern.data.byte_distribution.is_empty());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut pattern = build_test("refine", "1", true, true, false);
+        pattern.type_data.known_mimetypes = vec!["application/x-test".into()];
+        pattern.add_submitter_data("first", "first@example.com");
+
+        let mut other = build_test("refine", "2", true, true, false);
+        other.type_data.known_extensions = vec!["TEST".into(), "TST".into()];
+        other.add_submitter_data("second", "second@example.com");
+
+        pattern.merge(&other);
+
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![
+                (6, b"abcdefghij\x01TAIL".to_vec()),
+                (0, b"MAGIC".to_vec())
+            ])
+        );
+        assert_eq!(
+            pattern.type_data.known_extensions,
+            intern_all(&["TEST", "TST"])
+        );
+        assert_eq!(
+            pattern.type_data.known_mimetypes,
+            intern_all(&["application/x-test"])
+        );
+        assert_eq!(pattern.submitter_data.refined_by, vec!["second"]);
+        assert_eq!(pattern.other_data.total_scanned_files, 3);
+    }
+
+    #[test]
+    fn test_outliers() {
+        // The final sample file has nothing in common with the others.
+        let mut options = BuildOptions {
+            scan_strings: false,
+            scan_byte_distribution: false,
+            mask_sequences: false,
+            ..Default::default()
+        };
+
+        let test_dir = test_utils::test_path_builder("outliers", "1");
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        let report = pattern
+            .build_patterns_from_data(&test_dir, "test", &options)
+            .expect("failed to build test pattern");
+        assert_eq!(report.outliers.len(), 1);
+        assert!(report.outliers[0].ends_with("5.test"));
+        assert!(pattern.data.sequences.is_empty());
+
+        // Once excluded, the common sequences of the remaining files are retained.
+        options.exclude_outliers = true;
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        let report = pattern
+            .build_patterns_from_data(&test_dir, "test", &options)
+            .expect("failed to build test pattern");
+        assert_eq!(report.outliers.len(), 1);
+        assert!(pattern
+            .data
+            .sequences
+            .iter()
+            .any(|(p, s)| *p == 0 && **s == *b"SIGNATURE\x01HEADER"));
+        assert_eq!(pattern.other_data.total_scanned_files, 4);
+    }
+
+    #[test]
+    fn test_regex_synthesis() {
+        let options = BuildOptions {
+            scan_strings: false,
+            scan_byte_distribution: false,
+            synthesize_regexes: true,
+            ..Default::default()
+        };
+        let mut pattern = build_test_with_options("regex", "1", &options);
+        pattern.compute_attributes();
+
+        // The masked byte only takes two values, so the masked sequence is replaced by a regex.
+        assert!(pattern.data.masked_sequences.is_empty());
+        assert_eq!(
+            pattern.data.regexes,
+            vec![(0, "(?-u)GIF8[79]a\\x01DATA".to_string())]
+        );
+        assert_eq!(pattern.compiled_regexes[0].points, 11);
+
+        let matches = |bytes: &[u8]| {
+            FilePointCalculator::compute_report(
+                &pattern,
+                &FileSample::from_bytes(bytes),
+                None,
+                utils::DEFAULT_COMPOUND_EXTENSIONS,
+                false,
+            )
+            .is_some()
+        };
+        assert!(matches(b"GIF89a\x01DATA"));
+        assert!(!matches(b"GIF88a\x01DATA"));
+        assert!(!matches(b"GIF8"));
+    }
+
+    #[test]
+    fn test_weighted_sequences() {
+        let options = BuildOptions {
+            scan_strings: false,
+            scan_byte_distribution: false,
+            ..Default::default()
+        };
+        let test_dir = test_utils::test_path_builder("weights", "1");
+
+        // When every sample has the same weight, the differing bytes split the sequence.
+        let pattern = build_test_with_options("weights", "1", &options);
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(9, b"-ABCDEF".to_vec()), (0, b"SIG".to_vec())])
+        );
+        assert!(pattern.data.masked_sequences.is_empty());
+
+        // But when they only differ within an edge case, they are masked instead.
+        let weights = SampleWeights::Explicit(HashMap::from_iter([("3.test".to_string(), 0.1)]));
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        pattern
+            .build_patterns_from_weighted_data(&test_dir, "test", &options, &weights, &mut ())
+            .expect("failed to build test pattern");
+
+        let mut expected_sequence: Vec<_> = b"SIGNATURE-ABCDEF".iter().map(|b| Some(*b)).collect();
+        expected_sequence[3..9].fill(None);
+        assert!(pattern.data.sequences.is_empty());
+        assert_eq!(pattern.data.masked_sequences, vec![(0, expected_sequence)]);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let options = BuildOptions {
+            mask_sequences: false,
+            ..Default::default()
+        };
+
+        // The differing bytes split the sequences, leaving pieces that can be merged across the 16 byte boundary.
+        let mut builder = PatternBuilder::new(options);
+        for i in 1..=3u8 {
+            let mut sample = b"MAGIC".to_vec();
+            sample.push(i);
+            sample.extend_from_slice(b"abcdefghijklmn");
+            sample.push(i);
+            sample.extend_from_slice(b"pqrstuvwxyz0");
+            builder.add_sample(&sample);
+        }
+
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        builder
+            .finalize(&mut pattern, &mut ())
+            .expect("failed to build test pattern");
+
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![
+                (21, b"pqrstuvwxyz0".to_vec()),
+                (6, b"abcdefghijklmn".to_vec()),
+                (0, b"MAGIC".to_vec())
+            ])
+        );
+
+        // Every string is contained within a sequence, and so would be scored twice.
+        assert!(pattern.data.strings.is_empty());
+    }
+
+    #[test]
+    fn test_header_variants() {
+        let build = |headers: &[&[u8]]| {
+            let mut builder = PatternBuilder::new(BuildOptions::default());
+            for header in headers {
+                let mut sample = header.to_vec();
+                sample.extend_from_slice(b"COMMONDATA");
+                builder.add_sample(&sample);
+            }
+
+            let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+            builder
+                .finalize(&mut pattern, &mut ())
+                .expect("failed to build test pattern");
+            pattern.compute_attributes();
+            pattern
+        };
+        let (le, be): (&[u8], &[u8]) = (b"II*+\x08ABC", b"MM+*CBA\x08");
+
+        // The little and big endian headers have nothing in common, but each is shared by several sample files.
+        let pattern = build(&[le, be, le, be]);
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(8, b"COMMONDATA".to_vec())])
+        );
+        assert_eq!(
+            pattern.data.alternative_sequences,
+            vec![vec![(0, le.to_vec()), (0, be.to_vec())]]
+        );
+
+        // Either alternative is a perfect match for the group.
+        let score = |bytes: &[u8]| {
+            FilePointCalculator::compute_report(
+                &pattern,
+                &FileSample::from_bytes(bytes),
+                None,
+                utils::DEFAULT_COMPOUND_EXTENSIONS,
+                false,
+            )
+            .map(|r| r.sequence_points)
+        };
+        assert_eq!(score(b"MM+*CBA\x08COMMONDATA"), Some(18.0));
+        assert_eq!(score(b"II*+\x08ABCCOMMONDATA"), Some(18.0));
+        assert_eq!(score(b"XX+*CBA\x08COMMONDATA"), None);
+
+        // A header seen in a single sample file may simply differ between files.
+        let single = build(&[le, be, le]);
+        assert!(single.data.alternative_sequences.is_empty());
+
+        // Merging with a pattern whose sample files all share one of the alternatives retains the group.
+        let mut merged = build(&[le, le]);
+        assert!(merged.data.alternative_sequences.is_empty());
+        merged.merge(&pattern);
+        assert_eq!(
+            merged.data.alternative_sequences,
+            pattern.data.alternative_sequences
+        );
+    }
+
+    #[test]
+    fn test_parallel_build() {
+        let options = BuildOptions {
+            synthesize_regexes: true,
+            fuzzy_hash: true,
+            ..Default::default()
+        };
+        let tests = [
+            ("byte_sequences", "3"),
+            ("regex", "1"),
+            ("strings", "1"),
+            ("entropy", "1"),
+            ("trailer_sequences", "1"),
+        ];
+        let build_all = || -> Vec<String> {
+            tests
+                .iter()
+                .map(|(test_type, test_id)| {
+                    let pattern = build_test_with_options(test_type, test_id, &options);
+                    serde_json::to_string(&pattern.data).expect("failed to serialize pattern data")
+                })
+                .collect()
+        };
+
+        // The sample files are analysed in parallel, which mustn't change the patterns that are built.
+        // Local thread pools are used, as the limit set by `utils::set_thread_limit` is shared with other tests.
+        let pool = |threads| {
+            ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("failed to build thread pool")
+        };
+        let serial = pool(1).install(build_all);
+        let parallel = pool(4).install(build_all);
+
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn test_pattern_builder() {
+        let options = BuildOptions {
+            synthesize_regexes: true,
+            ..Default::default()
+        };
+        let test_dir = test_utils::test_path_builder("regex", "1");
+        let expected = build_test_with_options("regex", "1", &options);
+
+        // Building from the bytes held in memory should give the same result as building from the directory.
+        let mut builder = PatternBuilder::new(options);
+        for file_path in utils::list_files_of_type(&test_dir, "test") {
+            builder.add_sample(&fs::read(file_path).expect("failed to read test file"));
+        }
+        assert_eq!(builder.len(), expected.other_data.total_scanned_files);
+
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        builder
+            .finalize(&mut pattern, &mut ())
+            .expect("failed to build test pattern");
+
+        assert_eq!(pattern.data.sequences, expected.data.sequences);
+        assert_eq!(
+            pattern.data.masked_sequences,
+            expected.data.masked_sequences
+        );
+        assert_eq!(pattern.data.regexes, expected.data.regexes);
+        assert_eq!(pattern.data.strings, expected.data.strings);
+        assert_eq!(pattern.data.fuzzy_hash, expected.data.fuzzy_hash);
+        assert_eq!(pattern.data.average_entropy, expected.data.average_entropy);
+    }
+
+    #[test]
+    fn test_progress_sink() {
+        let mut builder = PatternBuilder::new(BuildOptions::default());
+        for sample in [
+            &b"MAGIC\x00FIRST"[..],
+            b"MAGIC\x00SECOND",
+            b"MAGIC\x00THIRD",
+        ] {
+            builder.add_sample(sample);
+        }
+
+        let mut sink = RecordingSink::default();
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        builder
+            .finalize(&mut pattern, &mut sink)
+            .expect("failed to build test pattern");
+
+        // One update before each sample, one more after the last, and then a single call to finish.
+        let processed: Vec<_> = sink.updates.iter().map(|p| p.files_processed).collect();
+        assert_eq!(processed, vec![0, 1, 2, 3]);
+        assert!(sink.updates.iter().all(|p| p.total_files == 3));
+        assert_eq!(sink.finished_after, vec![4]);
+
+        // Nothing is known of the samples until the first has been processed.
+        assert_eq!(
+            sink.updates[0],
+            BuildProgress {
+                total_files: 3,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_quality() {
+        let mut pattern = Pattern::new("quality", "test", vec![], vec![]);
+        let quality = pattern.quality();
+        assert_eq!(quality.anchored_bytes, 0);
+        assert_eq!(quality.entropy_spread, None);
+        assert_eq!(quality.grade, QualityGrade::Weak);
+
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        pattern.data.masked_sequences = vec![(8, vec![Some(b'A'), None, Some(b'B')])];
+        pattern.data.regexes = vec![(16, "GIF8[79]a".to_string())];
+        pattern.data.entropy_windows = vec![(0, 2.0, 3.0), (1, 4.0, 6.5)];
+        pattern.other_data.total_scanned_files = 2;
+
+        // Masked bytes aren't anchored, but the minimum length of a regex match is.
+        let quality = pattern.quality();
+        assert_eq!(quality.anchored_bytes, 13);
+        assert_eq!(quality.entropy_spread, Some(2.5));
+        assert_eq!(quality.grade, QualityGrade::Moderate);
+
+        // Only with enough sample files can the pattern be considered strong.
+        pattern.other_data.total_scanned_files = 5;
+        assert_eq!(pattern.quality().grade, QualityGrade::Strong);
+    }
+
+    #[test]
+    fn test_cross_validation() {
+        let options = BuildOptions {
+            scan_strings: false,
+            scan_byte_distribution: false,
+            mask_sequences: false,
+            exclude_outliers: true,
+            ..Default::default()
+        };
+
+        let test_dir = test_utils::test_path_builder("outliers", "1");
+        let pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        let validation = pattern
+            .cross_validate(&test_dir, "test", &options, 5)
+            .expect("failed to cross-validate test pattern");
+
+        // Only the mislabeled sample file should fail to match once held out.
+        assert_eq!(validation.scores.len(), 5);
+        assert!(validation.scores[0].0.ends_with("5.test"));
+        assert_eq!(validation.min_percentage, 0.0);
+        assert!(validation.scores[1..].iter().all(|(_, p)| *p == 100.0));
+        assert_eq!(validation.average_percentage, 80.0);
+
+        // There can't be more folds than sample files.
+        assert!(pattern
+            .cross_validate(&test_dir, "test", &options, 6)
+            .is_err());
+    }
+
+    #[test]
+    fn test_min_samples() {
+        let test_dir = test_utils::test_path_builder("matching", "1");
+        let options = BuildOptions {
+            min_samples: 3,
+            ..Default::default()
+        };
+
+        // There are only two sample files, and so the pattern can't be built.
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        assert!(pattern
+            .build_patterns_from_data(&test_dir, "test", &options)
+            .is_err());
+
+        // The confidence factor rises toward one as more sample files are scanned.
+        let mut pattern = build_test_with_options("matching", "1", &BuildOptions::default());
+        pattern.compute_attributes();
+        assert_eq!(pattern.other_data.total_scanned_files, 2);
+        assert_eq!(pattern.confidence_factor, 0.75);
+    }
+
+    #[test]
+    fn test_multiple_extensions() {
+        let test_dir = test_utils::test_path_builder("negative", "1");
+        assert_eq!(
+            utils::split_extensions(" .bin,DAT,,bin"),
+            vec!["BIN".to_string(), "DAT".to_string()]
+        );
+
+        // Sample files with any of the extensions should be used to build the pattern.
+        let mut pattern = Pattern::new("test", "test", vec![], vec![]);
+        pattern
+            .build_patterns_from_data(&test_dir, "bin,.dat", &BuildOptions::default())
+            .expect("failed to build test pattern");
+        assert_eq!(pattern.other_data.total_scanned_files, 2);
+    }
+
+    #[test]
+    fn test_glob_selection() {
+        let test_dir = test_utils::test_path_builder("outliers", "1");
+
+        // The mislabeled sample file can be excluded by a glob.
+        let files = utils::list_files_matching(&test_dir, &["*.test", "!5.*"])
+            .expect("failed to list test files");
+        assert_eq!(files.len(), 4);
+        assert!(files.iter().all(|f| !f.ends_with("5.test")));
+
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        pattern
+            .build_patterns_from_files(
+                &files,
+                &BuildOptions::default(),
+                &SampleWeights::Uniform,
+                &mut (),
+            )
+            .expect("failed to build test pattern");
+        assert!(pattern
+            .data
+            .sequences
+            .iter()
+            .any(|(p, s)| *p == 0 && **s == *b"SIGNATURE\x01HEADER"));
+
+        // An excluding glob on its own selects every other file, and a single star doesn't cross directories.
+        assert_eq!(
+            utils::list_files_matching(&test_dir, &["!5.*"])
+                .unwrap()
+                .len(),
+            4
+        );
+        let parent = Path::new(&test_dir).parent().unwrap();
+        assert!(utils::list_files_matching(parent, &["*.test"])
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            utils::list_files_matching(parent, &["**/*.test"])
+                .unwrap()
+                .len(),
+            5
+        );
+
+        assert!(utils::list_files_matching(&test_dir, &["[a"]).is_err());
+    }
+
+    #[test]
+    fn test_weighted_range() {
+        assert_eq!(
+            weighted_range(&[(2.0, 1.0), (8.0, 1.0)], 1.0),
+            Some((2.0, 8.0))
+        );
+        assert_eq!(weighted_range(&[], 1.0), None);
+
+        // The edge case only widens the range by a quarter of its distance from the weighted mean.
+        let (min, max) = weighted_range(&[(2.0, 1.0), (4.0, 1.0), (8.0, 0.25)], 1.0).unwrap();
+        assert!(approx_equal(min, 2.0, 2));
+        assert!(approx_equal(max, 4.67, 2));
+    }
+
+    #[test]
+    fn test_negative_validation() {
+        let mut pattern = Pattern::new("Negative", "", vec!["TEST".to_string()], vec![]);
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        pattern.data.strings =
+            HashSet::from_iter(["HELLO WORLD", "UNIQUE STRING"].map(String::from));
+        pattern.other_data.total_scanned_files = 1;
+
+        let test_dir = test_utils::test_path_builder("negative", "1");
+        let validation = pattern
+            .validate_against_negative(&test_dir)
+            .expect("failed to validate test pattern");
+
+        // The common string is dropped, but only the file with the same header remains a false positive.
+        assert_eq!(validation.total_scanned_files, 2);
+        assert_eq!(validation.dropped_strings, 1);
+        assert_eq!(
+            pattern.data.strings,
+            HashSet::from_iter(["UNIQUE STRING".to_string()])
+        );
+        assert_eq!(validation.false_positives.len(), 1);
+        assert!(validation.false_positives[0].0.ends_with("a.bin"));
+    }
+
+    #[test]
+    fn test_extended_composition() {
+        let pattern = build_test("entropy", "4", false, false, true);
+
+        // Half of each file is zeroes, so the distribution is far from uniform.
+        let (min, max) = pattern
+            .data
+            .chi_square
+            .expect("expected a chi-square range");
+        assert!(min > 256.0 && approx_equal(min, max, 1));
+
+        // The profile is normalized, and the zero-filled header dominates the first bucket.
+        let profile = &pattern.data.bigram_profile;
+        assert_eq!(profile.len(), 256);
+        assert!(approx_equal(profile.iter().sum(), 1.0, 2));
+        assert!(profile[0] > 0.5);
+    }
+
+    #[test]
+    fn test_fuzzy_hash() {
+        let options = BuildOptions {
+            scan_strings: false,
+            scan_byte_sequences: false,
+            scan_byte_distribution: false,
+            fuzzy_hash: true,
+            ..Default::default()
+        };
+        let pattern = build_test_with_options("entropy", "4", &options);
+
+        // Both of the files are identical, so the representative digest will be the digest of either.
+        let test_dir = test_utils::test_path_builder("entropy", "4");
+        let chunk =
+            fs::read(Path::new(&test_dir).join("1.test")).expect("failed to read test file");
+        let block_size = fuzzy::block_size_for(chunk.len());
+        assert_eq!(
+            pattern.data.fuzzy_hash,
+            Some((block_size, fuzzy::hash(&chunk, block_size)))
+        );
+    }
+
+    #[test]
+    fn test_entropy_windows() {
+        // The files have a zero-filled header followed by a uniformly distributed body.
+        let pattern = build_test("entropy", "4", false, false, true);
+
+        let windows = &pattern.data.entropy_windows;
+        assert_eq!(
+            windows.len(),
+            2,
+            "expected only the windows that the files extend into"
+        );
+        assert_eq!(windows[0].0, 0);
+        assert!(approx_equal(windows[0].1, 0.0, 1) && approx_equal(windows[0].2, 0.0, 1));
+        assert_eq!(windows[1].0, 1);
+        assert!(approx_equal(windows[1].1, 8.0, 1) && approx_equal(windows[1].2, 8.0, 1));
+    }
+
     fn approx_equal(a: f32, b: f32, decimal_places: usize) -> bool {
         utils::round_to_dp(a, decimal_places) == utils::round_to_dp(b, decimal_places)
     }
@@ -675,11 +3985,81 @@ mod tests_pattern {
         bytes: bool,
         entropy: bool,
     ) -> Pattern {
+        let options = BuildOptions {
+            scan_strings: strings,
+            scan_byte_sequences: bytes,
+            scan_byte_distribution: entropy,
+            mask_sequences: false,
+            scan_extended_composition: entropy,
+            fuzzy_hash: false,
+            exclude_outliers: false,
+            string_blocklist: StringBlocklist::default(),
+            synthesize_regexes: false,
+            min_samples: DEFAULT_MIN_SAMPLES,
+        };
+
+        build_test_with_options(test_type, test_id, &options)
+    }
+
+    fn build_test_with_options(test_type: &str, test_id: &str, options: &BuildOptions) -> Pattern {
         let test_dir = test_utils::test_path_builder(test_type, test_id);
 
         let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
-        pattern.build_patterns_from_data(&test_dir, "test", strings, bytes, entropy);
+        pattern
+            .build_patterns_from_data(&test_dir, "test", options)
+            .expect("failed to build test pattern");
 
         pattern
     }
+
+    #[test]
+    fn test_compound_extension() {
+        let mut pattern = Pattern::new("test", "test", vec!["tar.gz".to_string()], vec![]);
+        pattern.other_data.total_scanned_files = 1;
+        pattern.compute_attributes();
+
+        let extension_points = |pattern: &Pattern, path: &str, compound_extensions: &[&str]| {
+            FilePointCalculator::compute_report(
+                pattern,
+                &FileSample::from_bytes(b"DATA"),
+                Some(path),
+                compound_extensions,
+                false,
+            )
+            .map(|r| r.extension_points)
+        };
+        assert_eq!(
+            extension_points(
+                &pattern,
+                "archive.tar.gz",
+                utils::DEFAULT_COMPOUND_EXTENSIONS
+            ),
+            Some(FILE_EXTENSION_POINTS)
+        );
+        assert_eq!(extension_points(&pattern, "archive.tar.gz", &[]), Some(0.0));
+        assert_eq!(
+            extension_points(&pattern, "archive.gz", utils::DEFAULT_COMPOUND_EXTENSIONS),
+            Some(0.0)
+        );
+
+        // A file with a compound extension still matches patterns that list its last extension.
+        pattern.type_data.known_extensions = vec!["GZ".into()];
+        assert_eq!(
+            extension_points(
+                &pattern,
+                "ARCHIVE.TAR.GZ",
+                utils::DEFAULT_COMPOUND_EXTENSIONS
+            ),
+            Some(FILE_EXTENSION_POINTS)
+        );
+
+        assert_eq!(
+            utils::get_compound_file_extension("a/b.tar.xz", utils::DEFAULT_COMPOUND_EXTENSIONS),
+            "TAR.XZ"
+        );
+        assert_eq!(
+            utils::get_compound_file_extension("a/.tar.gz", utils::DEFAULT_COMPOUND_EXTENSIONS),
+            "GZ"
+        );
+    }
 }
@@ -0,0 +1,221 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{error::ItfError, pattern::PatternCompression, pattern_index::INDEX_FILE_NAME};
+
+/// The kinds of archive from which pattern files can be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// A zip archive.
+    Zip,
+    /// An uncompressed tar archive.
+    Tar,
+    /// A tar archive compressed with gzip.
+    TarGzip,
+    /// A tar archive compressed with Zstandard.
+    TarZstd,
+}
+
+impl ArchiveKind {
+    /// Get the kind of an archive from its extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the archive.
+    ///
+    /// # Returns
+    ///
+    /// None if the file doesn't have the extension of a supported archive, otherwise the kind of the archive.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let name = path.as_ref().to_string_lossy().to_lowercase();
+        let has_extension = |extensions: &[&str]| extensions.iter().any(|e| name.ends_with(e));
+
+        if has_extension(&[".zip"]) {
+            Some(ArchiveKind::Zip)
+        } else if has_extension(&[".tar"]) {
+            Some(ArchiveKind::Tar)
+        } else if has_extension(&[".tar.gz", ".tgz"]) {
+            Some(ArchiveKind::TarGzip)
+        } else if has_extension(&[".tar.zst", ".tzst"]) {
+            Some(ArchiveKind::TarZstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Visit each of the pattern files within an archive, without unpacking the archive.
+///
+/// # Arguments
+///
+/// * `path` - The path to the archive.
+/// * `visit` - The function called for each pattern file, with the path of the file within the archive and a
+///   reader providing its contents.
+///
+/// # Returns
+///
+/// An error if the archive couldn't be read, or if the visiting function returned an error.
+///
+/// # Notes
+///
+/// The pattern files are those with the extension of a pattern file, as with
+/// [`PatternHandler::list_pattern_files`](crate::pattern_handler::PatternHandler::list_pattern_files),
+/// and are visited in the order in which they appear within the archive.
+pub fn for_each_pattern_file<P, F>(path: P, mut visit: F) -> Result<(), ItfError>
+where
+    P: AsRef<Path>,
+    F: FnMut(&str, &mut dyn Read) -> Result<(), ItfError>,
+{
+    let path = path.as_ref();
+    let Some(kind) = ArchiveKind::from_path(path) else {
+        return Err(ItfError::InvalidArchive(format!(
+            "'{}' isn't a zip or tar archive",
+            path.display()
+        )));
+    };
+
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = match kind {
+        ArchiveKind::Zip => return for_each_zip_pattern_file(file, visit),
+        ArchiveKind::Tar => Box::new(file),
+        ArchiveKind::TarGzip => Box::new(flate2::read::GzDecoder::new(file)),
+        ArchiveKind::TarZstd => Box::new(zstd::stream::read::Decoder::new(file)?),
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry.path()?.to_string_lossy().to_string();
+        if is_pattern_file(&name) {
+            visit(&name, &mut entry)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Visit each of the pattern files within a zip archive.
+///
+/// # Arguments
+///
+/// * `file` - The zip archive.
+/// * `visit` - The function called for each pattern file.
+fn for_each_zip_pattern_file<F>(file: File, mut visit: F) -> Result<(), ItfError>
+where
+    F: FnMut(&str, &mut dyn Read) -> Result<(), ItfError>,
+{
+    let invalid = |e: zip::result::ZipError| match e {
+        zip::result::ZipError::Io(e) => ItfError::Io(e),
+        e => ItfError::InvalidArchive(e.to_string()),
+    };
+
+    let mut archive = zip::ZipArchive::new(io::BufReader::new(file)).map_err(invalid)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(invalid)?;
+        if !entry.is_file() || !is_pattern_file(entry.name()) {
+            continue;
+        }
+
+        let name = entry.name().to_string();
+        visit(&name, &mut entry)?;
+    }
+
+    Ok(())
+}
+
+/// Is an entry within an archive a pattern file?
+///
+/// # Arguments
+///
+/// * `name` - The path of the entry within the archive.
+fn is_pattern_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    PatternCompression::PATTERN_FILE_EXTENSIONS
+        .iter()
+        .any(|e| name.ends_with(e))
+        && !name.ends_with(INDEX_FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests_pattern_archive {
+    use std::{env, fs, io::Write};
+
+    use crate::{interner::intern_sequences, pattern::Pattern, pattern_handler::PatternHandler};
+
+    use super::ArchiveKind;
+
+    #[test]
+    fn test_read_archive() {
+        let test_dir = env::temp_dir().join(format!("itf-test-archive-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        let mut entries = Vec::new();
+        for name in ["first", "second"] {
+            let mut pattern = Pattern::new(name, "test", vec![], vec![]);
+            pattern.data.sequences = intern_sequences(vec![(0, name.as_bytes().to_vec())]);
+            pattern.checksum = Some(pattern.compute_checksum().unwrap());
+            entries.push((
+                format!("patterns/{name}.json"),
+                serde_json::to_vec(&pattern).unwrap(),
+            ));
+        }
+        entries.push(("patterns/broken.json".to_string(), b"{".to_vec()));
+        entries.push(("README.md".to_string(), b"Not a pattern.".to_vec()));
+
+        let zip_path = test_dir.join("patterns.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        for (name, contents) in &entries {
+            zip.start_file(name.as_str(), zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let tar_path = test_dir.join("patterns.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&tar_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(encoder);
+        for (name, contents) in &entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, contents.as_slice())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let mut zip_handler = PatternHandler::default();
+        let zip_report = zip_handler.read(&zip_path, "");
+        let mut tar_handler = PatternHandler::default();
+        let tar_report = tar_handler.read(&tar_path, "second");
+        _ = fs::remove_dir_all(&test_dir);
+
+        let zip_report = zip_report.expect("failed to read zip archive");
+        assert_eq!(zip_handler.patterns.len(), 2);
+        assert_eq!(zip_report.failed.len(), 1);
+        assert!(zip_report.failed[0].0.ends_with("broken.json"));
+
+        let tar_report = tar_report.expect("failed to read tar archive");
+        assert_eq!(tar_handler.patterns.len(), 1);
+        assert_eq!(tar_handler.patterns[0].type_data.name, "second");
+        assert_eq!(tar_report.skipped.len(), 2);
+
+        assert_eq!(ArchiveKind::from_path("a.TGZ"), Some(ArchiveKind::TarGzip));
+        assert_eq!(
+            ArchiveKind::from_path("a.tar.zst"),
+            Some(ArchiveKind::TarZstd)
+        );
+        assert_eq!(ArchiveKind::from_path("a.json"), None);
+    }
+}
@@ -0,0 +1,156 @@
+use serde_json::Value;
+
+use crate::error::ItfError;
+
+/// The byte sequence fields of the pattern data section, given as the field name and the index of the
+/// byte sequence within each entry, along with whether the sequence may contain masked bytes.
+const SEQUENCE_FIELDS: &[(&str, usize, bool)] = &[
+    ("sequences", 1, false),
+    ("trailer_sequences", 1, false),
+    ("ranged_sequences", 2, false),
+    ("masked_sequences", 1, true),
+];
+
+/// Expand the byte sequences of a hand-authored pattern document that are written as hex strings,
+/// such as `"89 50 4E 47"`, into the arrays of bytes used by the canonical JSON format.
+///
+/// # Arguments
+///
+/// * `document` - The pattern document.
+///
+/// # Returns
+///
+/// An error if a hex string isn't valid.
+///
+/// # Notes
+///
+/// Whitespace within a hex string is ignored. Masked sequences may use `??` to denote a masked byte.
+pub fn expand_hex_sequences(document: &mut Value) -> Result<(), ItfError> {
+    let Some(data) = document.get_mut("pd") else {
+        return Ok(());
+    };
+
+    for (field, index, allow_masked) in SEQUENCE_FIELDS {
+        if let Some(entries) = data.get_mut(*field).and_then(Value::as_array_mut) {
+            for entry in entries {
+                expand_entry(entry, *index, *allow_masked)?;
+            }
+        }
+    }
+
+    if let Some(groups) = data
+        .get_mut("alternative_sequences")
+        .and_then(Value::as_array_mut)
+    {
+        for entry in groups.iter_mut().filter_map(Value::as_array_mut).flatten() {
+            expand_entry(entry, 1, false)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand the byte sequence within a single entry, if it's written as a hex string.
+///
+/// # Arguments
+///
+/// * `entry` - The entry, which is an array containing the byte sequence.
+/// * `index` - The index of the byte sequence within the entry.
+/// * `allow_masked` - Whether the sequence may contain masked bytes.
+fn expand_entry(entry: &mut Value, index: usize, allow_masked: bool) -> Result<(), ItfError> {
+    let Some(value) = entry.get_mut(index) else {
+        return Ok(());
+    };
+    let Some(hex) = value.as_str() else {
+        return Ok(());
+    };
+
+    let bytes = parse_hex_sequence(hex)
+        .filter(|b| allow_masked || b.iter().all(Option::is_some))
+        .ok_or_else(|| ItfError::InvalidPattern(format!("invalid hex sequence '{hex}'")))?;
+    *value = Value::Array(
+        bytes
+            .into_iter()
+            .map(|b| b.map_or(Value::Null, Value::from))
+            .collect(),
+    );
+
+    Ok(())
+}
+
+/// Parse a hex string, in which `??` denotes a masked byte.
+///
+/// # Arguments
+///
+/// * `hex` - The hex string. Any whitespace is ignored.
+///
+/// # Returns
+///
+/// None if the string isn't valid, otherwise the bytes, with a masked byte represented by `None`.
+pub(crate) fn parse_hex_sequence(hex: &str) -> Option<Vec<Option<u8>>> {
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| match pair {
+            b"??" => Some(None),
+            _ => u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16)
+                .ok()
+                .map(Some),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests_pattern_authoring {
+    use serde_json::json;
+
+    use super::{expand_hex_sequences, parse_hex_sequence};
+
+    #[test]
+    fn test_parse_hex_sequence() {
+        assert_eq!(
+            parse_hex_sequence("89 50 4e 47"),
+            Some(vec![Some(0x89), Some(0x50), Some(0x4E), Some(0x47)])
+        );
+        assert_eq!(
+            parse_hex_sequence("FF??D8"),
+            Some(vec![Some(0xFF), None, Some(0xD8)])
+        );
+        assert_eq!(parse_hex_sequence("FFD"), None);
+        assert_eq!(parse_hex_sequence("ZZ"), None);
+        assert_eq!(parse_hex_sequence(""), None);
+    }
+
+    #[test]
+    fn test_expand_hex_sequences() {
+        let mut document = json!({
+            "pd": {
+                "sequences": [[0, "4D 5A"], [4, [1, 2]]],
+                "masked_sequences": [[0, "FF ?? D8"]],
+                "alternative_sequences": [[[0, "49 49"], [0, "4D 4D"]]]
+            }
+        });
+        expand_hex_sequences(&mut document).unwrap();
+
+        assert_eq!(
+            document["pd"]["sequences"],
+            json!([[0, [0x4D, 0x5A]], [4, [1, 2]]])
+        );
+        assert_eq!(
+            document["pd"]["masked_sequences"],
+            json!([[0, [0xFF, null, 0xD8]]])
+        );
+        assert_eq!(
+            document["pd"]["alternative_sequences"][0][1],
+            json!([0, [0x4D, 0x4D]])
+        );
+
+        // Masked bytes are only permitted within masked sequences.
+        let mut invalid = json!({ "pd": { "sequences": [[0, "4D ??"]] } });
+        assert!(expand_hex_sequences(&mut invalid).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+use crate::{error::ItfError, pattern::Pattern};
+
+/// The bytes at the start of every pattern bundle.
+pub const BUNDLE_MAGIC: &[u8; 8] = b"ITFBUNDL";
+/// The version of the bundle format written by this version of the library.
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+/// The extension conventionally given to pattern bundles.
+pub const BUNDLE_EXTENSION: &str = "itfb";
+/// The zstd compression level applied to the patterns within a bundle.
+const BUNDLE_COMPRESSION_LEVEL: i32 = 9;
+/// The length of the fixed prefix of a bundle: the magic, the format version and the length of the header.
+const PREFIX_LENGTH: usize = BUNDLE_MAGIC.len() + 4 + 4;
+
+/// An entry within the index of a [`PatternBundle`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundleEntry {
+    /// The name of the pattern.
+    pub name: String,
+    /// The UUID of the pattern.
+    pub uuid: String,
+    /// The offset of the compressed pattern, relative to the end of the header.
+    pub offset: u64,
+    /// The length of the compressed pattern.
+    pub length: u64,
+}
+
+/// The header of a [`PatternBundle`], which indexes the patterns that it contains.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundleHeader {
+    /// The date and time at which the bundle was created.
+    pub created_on: String,
+    /// The index of the patterns within the bundle, in the order in which they were added.
+    pub entries: Vec<BundleEntry>,
+}
+
+/// A single file containing many patterns, along with an index of those patterns.
+///
+/// # Notes
+///
+/// A bundle is laid out as follows, with integers stored in little endian order:
+///
+/// * The [`BUNDLE_MAGIC`] bytes.
+/// * The bundle format version, as a 32-bit integer.
+/// * The length of the header, as a 32-bit integer.
+/// * The [`BundleHeader`], encoded as MessagePack.
+/// * The patterns, each stored as zstd compressed JSON so that a single pattern can be read without decompressing the others.
+///
+/// The patterns are stored as JSON so that they will be upgraded in the same way as pattern files when the
+/// pattern format changes.
+pub struct PatternBundle {
+    /// The header of the bundle.
+    pub header: BundleHeader,
+    /// The compressed patterns that follow the header.
+    data: Vec<u8>,
+}
+
+impl PatternBundle {
+    /// Create a bundle containing a set of patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `patterns` - The patterns to be added to the bundle.
+    ///
+    /// # Returns
+    ///
+    /// An error if a pattern could not be serialized or compressed, otherwise the bundle.
+    pub fn new(patterns: &[Pattern]) -> Result<Self, ItfError> {
+        let mut entries = Vec::with_capacity(patterns.len());
+        let mut data = Vec::new();
+
+        for pattern in patterns {
+            let json = serde_json::to_vec(&pattern.checksummed()?)?;
+            let compressed = zstd::stream::encode_all(&json[..], BUNDLE_COMPRESSION_LEVEL)?;
+
+            entries.push(BundleEntry {
+                name: pattern.type_data.name.clone(),
+                uuid: pattern.type_data.uuid.clone(),
+                offset: data.len() as u64,
+                length: compressed.len() as u64,
+            });
+            data.extend_from_slice(&compressed);
+        }
+
+        Ok(Self {
+            header: BundleHeader {
+                created_on: chrono::offset::Utc::now().to_string(),
+                entries,
+            },
+            data,
+        })
+    }
+
+    /// Parse a bundle from its encoded bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded bundle.
+    ///
+    /// # Returns
+    ///
+    /// An error if the bytes aren't a valid bundle, or the bundle was written by a newer version of the library.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ItfError> {
+        if bytes.len() < PREFIX_LENGTH || !bytes.starts_with(BUNDLE_MAGIC) {
+            return Err(ItfError::InvalidBundle(
+                "the data isn't a pattern bundle".to_string(),
+            ));
+        }
+
+        let read_u32 = |at: usize| {
+            let mut buffer = [0; 4];
+            buffer.copy_from_slice(&bytes[at..at + 4]);
+            u32::from_le_bytes(buffer)
+        };
+
+        let version = read_u32(BUNDLE_MAGIC.len());
+        if version > BUNDLE_FORMAT_VERSION {
+            return Err(ItfError::InvalidBundle(format!(
+                "bundle format version {version} is newer than the latest supported version ({BUNDLE_FORMAT_VERSION})"
+            )));
+        }
+
+        let header_end = PREFIX_LENGTH + read_u32(BUNDLE_MAGIC.len() + 4) as usize;
+        let header_bytes = bytes
+            .get(PREFIX_LENGTH..header_end)
+            .ok_or_else(|| ItfError::InvalidBundle("the header is truncated".to_string()))?;
+        let header: BundleHeader = rmp_serde::from_slice(header_bytes)
+            .map_err(|e| ItfError::InvalidBundle(format!("the header is invalid: {e}")))?;
+
+        let data = bytes[header_end..].to_vec();
+        for entry in &header.entries {
+            if entry.offset.saturating_add(entry.length) > data.len() as u64 {
+                return Err(ItfError::InvalidBundle(format!(
+                    "the pattern '{}' lies outside of the bundle",
+                    entry.name
+                )));
+            }
+        }
+
+        Ok(Self { header, data })
+    }
+
+    /// Read a bundle from a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the bundle file.
+    ///
+    /// # Returns
+    ///
+    /// An error if the file could not be read or isn't a valid bundle, otherwise the bundle.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ItfError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Encode the bundle into bytes.
+    ///
+    /// # Returns
+    ///
+    /// An error if the header could not be encoded, otherwise the encoded bundle.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ItfError> {
+        let header = rmp_serde::to_vec_named(&self.header).map_err(io::Error::other)?;
+        let header_length = u32::try_from(header.len())
+            .map_err(|_| ItfError::InvalidBundle("the header is too large".to_string()))?;
+
+        let mut bytes = Vec::with_capacity(PREFIX_LENGTH + header.len() + self.data.len());
+        bytes.extend_from_slice(BUNDLE_MAGIC);
+        bytes.extend_from_slice(&BUNDLE_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&header_length.to_le_bytes());
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(&self.data);
+
+        Ok(bytes)
+    }
+
+    /// Write the bundle to a file, replacing the file if it already exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the bundle file.
+    ///
+    /// # Returns
+    ///
+    /// An error if the bundle could not be encoded or the file could not be written.
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ItfError> {
+        fs::write(path, self.to_bytes()?)?;
+
+        Ok(())
+    }
+
+    /// Read a single pattern from the bundle.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The index entry of the pattern.
+    ///
+    /// # Returns
+    ///
+    /// An error if the pattern could not be decompressed or parsed, otherwise the [`Pattern`].
+    pub fn read_pattern(&self, entry: &BundleEntry) -> Result<Pattern, ItfError> {
+        let start = entry.offset as usize;
+        let compressed = self
+            .data
+            .get(start..start.saturating_add(entry.length as usize))
+            .ok_or_else(|| {
+                ItfError::InvalidBundle(format!(
+                    "the pattern '{}' lies outside of the bundle",
+                    entry.name
+                ))
+            })?;
+        let json = zstd::stream::decode_all(compressed)?;
+
+        let mut pattern = Pattern::from_simd_json_str(&String::from_utf8_lossy(&json))?;
+        pattern.compute_attributes();
+
+        Ok(pattern)
+    }
+
+    /// Read every pattern from the bundle.
+    ///
+    /// # Returns
+    ///
+    /// An error if any of the patterns could not be read, otherwise the patterns in the order of the index.
+    pub fn read_patterns(&self) -> Result<Vec<Pattern>, ItfError> {
+        self.header
+            .entries
+            .iter()
+            .map(|entry| self.read_pattern(entry))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests_pattern_bundle {
+    use crate::{error::ItfError, interner::intern_sequences, pattern::Pattern};
+
+    use super::{PatternBundle, BUNDLE_FORMAT_VERSION, BUNDLE_MAGIC};
+
+    fn make_patterns() -> Vec<Pattern> {
+        ["first", "second"]
+            .iter()
+            .map(|name| {
+                let mut pattern = Pattern::new(name, "test", vec!["TEST".to_string()], vec![]);
+                pattern.data.sequences = intern_sequences(vec![(0, name.as_bytes().to_vec())]);
+                pattern
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let patterns = make_patterns();
+        let bytes = PatternBundle::new(&patterns).unwrap().to_bytes().unwrap();
+        assert!(bytes.starts_with(BUNDLE_MAGIC));
+
+        let bundle = PatternBundle::from_bytes(&bytes).expect("failed to parse bundle");
+        let names: Vec<_> = bundle
+            .header
+            .entries
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["first", "second"]);
+
+        // A single pattern can be read through the index.
+        let second = bundle.read_pattern(&bundle.header.entries[1]).unwrap();
+        assert_eq!(second.type_data.uuid, patterns[1].type_data.uuid);
+        assert_eq!(
+            second.data.sequences,
+            intern_sequences(vec![(0, b"second".to_vec())])
+        );
+        assert!(second.max_points > 0);
+
+        assert_eq!(bundle.read_patterns().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_bundle_invalid() {
+        let bytes = PatternBundle::new(&make_patterns())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+
+        assert!(matches!(
+            PatternBundle::from_bytes(b"{\"td\": {}}"),
+            Err(ItfError::InvalidBundle(_))
+        ));
+        assert!(matches!(
+            PatternBundle::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ItfError::InvalidBundle(_))
+        ));
+
+        let mut newer = bytes.clone();
+        newer[BUNDLE_MAGIC.len()..BUNDLE_MAGIC.len() + 4]
+            .copy_from_slice(&(BUNDLE_FORMAT_VERSION + 1).to_le_bytes());
+        assert!(PatternBundle::from_bytes(&newer).is_err());
+    }
+}
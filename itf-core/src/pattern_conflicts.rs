@@ -0,0 +1,170 @@
+use std::fmt;
+
+use serde_derive::Serialize;
+
+use crate::{pattern::Pattern, pattern_handler::PatternHandler};
+
+/// The way in which the byte sequences of two patterns overlap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictKind {
+    /// The patterns require the same byte sequences.
+    Identical,
+    /// Every byte sequence required by the general pattern is also required by the specific pattern,
+    /// which requires others besides.
+    Subset,
+}
+
+/// A pair of patterns that will match every file that the more specific of them matches, found by
+/// [`find_conflicts`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PatternConflict {
+    /// The way in which the patterns overlap.
+    pub kind: ConflictKind,
+    /// The name of the pattern whose byte sequences are all required by the other pattern.
+    pub general_name: String,
+    /// The UUID of the pattern whose byte sequences are all required by the other pattern.
+    pub general_uuid: String,
+    /// The name of the pattern that requires all of the byte sequences of the other pattern.
+    pub specific_name: String,
+    /// The UUID of the pattern that requires all of the byte sequences of the other pattern.
+    pub specific_uuid: String,
+}
+
+impl fmt::Display for PatternConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ConflictKind::Identical => write!(
+                f,
+                "'{}' ({}) and '{}' ({}) require identical byte sequences",
+                self.general_name, self.general_uuid, self.specific_name, self.specific_uuid
+            ),
+            ConflictKind::Subset => write!(
+                f,
+                "'{}' ({}) requires every byte sequence of '{}' ({})",
+                self.specific_name, self.specific_uuid, self.general_name, self.general_uuid
+            ),
+        }
+    }
+}
+
+/// Compare every loaded pattern with every other, finding the pairs whose required byte sequences are
+/// identical, or where those of one pattern are a strict subset of those of the other. Any file matching
+/// the sequences of one pattern of such a pair will also match those of the other.
+///
+/// # Arguments
+///
+/// * `pattern_handler` - The [`PatternHandler`] containing the loaded patterns.
+///
+/// # Returns
+///
+/// A vector of the conflicting pairs, in the order in which the patterns were loaded.
+///
+/// # Notes
+///
+/// A byte sequence is considered to be required by a pattern if the pattern has a sequence that contains
+/// the same bytes at the same offset. Patterns that don't require any byte sequences are ignored, as are
+/// pairs of patterns within the same format family, since these are expected to overlap.
+pub fn find_conflicts(pattern_handler: &PatternHandler) -> Vec<PatternConflict> {
+    let patterns: Vec<&Pattern> = pattern_handler
+        .iter()
+        .filter(|p| !p.data.sequences.is_empty())
+        .collect();
+    let lineages: Vec<Vec<&str>> = patterns
+        .iter()
+        .map(|p| pattern_handler.lineage(&p.type_data.uuid))
+        .collect();
+
+    let mut conflicts = Vec::new();
+    for (i, first) in patterns.iter().enumerate() {
+        for (j, second) in patterns.iter().enumerate().skip(i + 1) {
+            if lineages[i].iter().any(|uuid| lineages[j].contains(uuid)) {
+                continue;
+            }
+
+            let (kind, general, specific) =
+                match (requires_all(first, second), requires_all(second, first)) {
+                    (true, true) => (ConflictKind::Identical, first, second),
+                    (true, false) => (ConflictKind::Subset, second, first),
+                    (false, true) => (ConflictKind::Subset, first, second),
+                    (false, false) => continue,
+                };
+
+            conflicts.push(PatternConflict {
+                kind,
+                general_name: general.type_data.name.clone(),
+                general_uuid: general.type_data.uuid.clone(),
+                specific_name: specific.type_data.name.clone(),
+                specific_uuid: specific.type_data.uuid.clone(),
+            });
+        }
+    }
+
+    conflicts
+}
+
+/// Does a pattern require every byte sequence of another pattern?
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+/// * `other` - The other pattern.
+fn requires_all(pattern: &Pattern, other: &Pattern) -> bool {
+    other.data.sequences.iter().all(|(offset, bytes)| {
+        pattern.data.sequences.iter().any(|(outer_offset, outer)| {
+            offset
+                .checked_sub(*outer_offset)
+                .and_then(|start| outer.get(start..start + bytes.len()))
+                == Some(&bytes[..])
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests_pattern_conflicts {
+    use crate::{interner::intern_sequences, pattern::Pattern, pattern_handler::PatternHandler};
+
+    use super::{find_conflicts, ConflictKind};
+
+    fn make_pattern(name: &str, sequences: Vec<(usize, &[u8])>) -> Pattern {
+        let mut pattern = Pattern::new(name, "test", vec![], vec![]);
+        pattern.data.sequences = intern_sequences(sequences);
+        pattern
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let zip = make_pattern("zip", vec![(0, b"PK\x03\x04")]);
+        let copy = make_pattern("copy", vec![(0, b"PK\x03\x04")]);
+        let short = make_pattern("short", vec![(1, b"K\x03")]);
+        let mut docx = make_pattern("docx", vec![(0, b"PK\x03\x04"), (30, b"[Content_Types]")]);
+        docx.type_data.parent_uuid = Some(zip.type_data.uuid.clone());
+        let gif = make_pattern("gif", vec![(0, b"GIF8")]);
+        let empty = make_pattern("empty", vec![]);
+
+        let mut pattern_handler = PatternHandler::default();
+        pattern_handler.patterns = vec![zip, copy, short, docx, gif, empty];
+
+        let conflicts: Vec<(ConflictKind, String, String)> = find_conflicts(&pattern_handler)
+            .into_iter()
+            .map(|c| (c.kind, c.general_name, c.specific_name))
+            .collect();
+
+        // The DOCX pattern belongs to the ZIP family, and so isn't reported as conflicting with it.
+        let expected = [
+            (ConflictKind::Identical, "zip", "copy"),
+            (ConflictKind::Subset, "short", "zip"),
+            (ConflictKind::Subset, "short", "copy"),
+            (ConflictKind::Subset, "copy", "docx"),
+            (ConflictKind::Subset, "short", "docx"),
+        ];
+        assert_eq!(
+            conflicts,
+            expected.map(|(kind, general, specific)| (
+                kind,
+                general.to_string(),
+                specific.to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use std::{fmt, sync::Arc};
+
+use serde_derive::Serialize;
+
+use crate::pattern::Pattern;
+
+/// The changes between two sets of byte sequences, each given as the position followed by the bytes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SequenceChanges<T> {
+    /// The sequences that are only present within the new pattern.
+    pub added: Vec<(usize, T)>,
+    /// The sequences that are only present within the old pattern.
+    pub removed: Vec<(usize, T)>,
+    /// The sequences whose bytes are unchanged but whose position has changed, given as the old position,
+    /// the new position and the bytes.
+    pub moved: Vec<(usize, usize, T)>,
+    /// The sequences whose position is unchanged but whose bytes have changed, given as the position,
+    /// the old bytes and the new bytes.
+    pub changed: Vec<(usize, T, T)>,
+}
+
+impl<T: Clone + PartialEq> SequenceChanges<T> {
+    /// Compute the changes between two sets of byte sequences.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The old sequences.
+    /// * `new` - The new sequences.
+    fn between(old: &[(usize, T)], new: &[(usize, T)]) -> Self {
+        let mut removed: Vec<_> = old.iter().filter(|s| !new.contains(s)).cloned().collect();
+        let mut added: Vec<_> = new.iter().filter(|s| !old.contains(s)).cloned().collect();
+
+        // A sequence whose bytes are found at a different position has been moved.
+        let mut moved = Vec::new();
+        removed.retain(
+            |(old_position, bytes)| match added.iter().position(|(_, b)| b == bytes) {
+                Some(i) => {
+                    let (new_position, _) = added.remove(i);
+                    moved.push((*old_position, new_position, bytes.clone()));
+                    false
+                }
+                None => true,
+            },
+        );
+
+        // A sequence whose position is found with different bytes has been changed.
+        let mut changed = Vec::new();
+        removed.retain(|(position, old_bytes)| {
+            match added.iter().position(|(p, _)| p == position) {
+                Some(i) => {
+                    let (_, new_bytes) = added.remove(i);
+                    changed.push((*position, old_bytes.clone(), new_bytes));
+                    false
+                }
+                None => true,
+            }
+        });
+
+        Self {
+            added,
+            removed,
+            moved,
+            changed,
+        }
+    }
+
+    /// Are the two sets of sequences identical?
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// The change to the entropy range of a single entropy window.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EntropyWindowChange {
+    /// The index of the window.
+    pub window: usize,
+    /// The range of the window within the old pattern, if it had one.
+    pub old: Option<(f32, f32)>,
+    /// The range of the window within the new pattern, if it has one.
+    pub new: Option<(f32, f32)>,
+}
+
+/// A structured description of the differences between two versions of a [`Pattern`], such as before
+/// and after a refinement.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PatternDiff {
+    /// The changes to the positional byte sequences.
+    pub sequences: SequenceChanges<Arc<[u8]>>,
+    /// The changes to the trailer byte sequences, whose positions are the distance to the end of the file.
+    pub trailer_sequences: SequenceChanges<Vec<u8>>,
+    /// The changes to the masked byte sequences.
+    pub masked_sequences: SequenceChanges<Vec<Option<u8>>>,
+    /// The strings that are only present within the new pattern, in sorted order.
+    pub strings_gained: Vec<String>,
+    /// The strings that are only present within the old pattern, in sorted order.
+    pub strings_lost: Vec<String>,
+    /// The old and new average entropy.
+    pub average_entropy: (f32, f32),
+    /// The entropy windows whose range has changed, in window order.
+    pub entropy_windows: Vec<EntropyWindowChange>,
+    /// The old and new maximum number of points that can be given by a match.
+    pub max_points: (usize, usize),
+}
+
+impl PatternDiff {
+    /// Compute the differences between two versions of a pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The old version of the pattern.
+    /// * `new` - The new version of the pattern.
+    ///
+    /// # Notes
+    ///
+    /// The maximum number of points is only meaningful if the attributes of both patterns have been computed,
+    /// as is the case for patterns read from a file.
+    pub fn between(old: &Pattern, new: &Pattern) -> Self {
+        let mut strings_gained: Vec<_> = new
+            .data
+            .strings
+            .difference(&old.data.strings)
+            .cloned()
+            .collect();
+        strings_gained.sort_unstable();
+        let mut strings_lost: Vec<_> = old
+            .data
+            .strings
+            .difference(&new.data.strings)
+            .cloned()
+            .collect();
+        strings_lost.sort_unstable();
+
+        let window_range = |pattern: &Pattern, window: usize| {
+            pattern
+                .data
+                .entropy_windows
+                .iter()
+                .find(|(w, _, _)| *w == window)
+                .map(|(_, min, max)| (*min, *max))
+        };
+        let mut windows: Vec<_> = old
+            .data
+            .entropy_windows
+            .iter()
+            .chain(&new.data.entropy_windows)
+            .map(|(w, _, _)| *w)
+            .collect();
+        windows.sort_unstable();
+        windows.dedup();
+        let entropy_windows = windows
+            .into_iter()
+            .map(|window| EntropyWindowChange {
+                window,
+                old: window_range(old, window),
+                new: window_range(new, window),
+            })
+            .filter(|c| c.old != c.new)
+            .collect();
+
+        Self {
+            sequences: SequenceChanges::between(&old.data.sequences, &new.data.sequences),
+            trailer_sequences: SequenceChanges::between(
+                &old.data.trailer_sequences,
+                &new.data.trailer_sequences,
+            ),
+            masked_sequences: SequenceChanges::between(
+                &old.data.masked_sequences,
+                &new.data.masked_sequences,
+            ),
+            strings_gained,
+            strings_lost,
+            average_entropy: (old.data.average_entropy, new.data.average_entropy),
+            entropy_windows,
+            max_points: (old.max_points, new.max_points),
+        }
+    }
+
+    /// Are the two versions of the pattern identical, in terms of the attributes covered by the diff?
+    pub fn is_empty(&self) -> bool {
+        self.sequences.is_empty()
+            && self.trailer_sequences.is_empty()
+            && self.masked_sequences.is_empty()
+            && self.strings_gained.is_empty()
+            && self.strings_lost.is_empty()
+            && self.average_entropy.0 == self.average_entropy.1
+            && self.entropy_windows.is_empty()
+            && self.max_points.0 == self.max_points.1
+    }
+}
+
+impl fmt::Display for PatternDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "The patterns are identical.");
+        }
+
+        write_sequence_changes(f, "sequences", &self.sequences)?;
+        write_sequence_changes(f, "trailer sequences", &self.trailer_sequences)?;
+        write_sequence_changes(f, "masked sequences", &self.masked_sequences)?;
+
+        if !self.strings_gained.is_empty() || !self.strings_lost.is_empty() {
+            writeln!(f, "strings:")?;
+            for string in &self.strings_gained {
+                writeln!(f, "  + {string:?}")?;
+            }
+            for string in &self.strings_lost {
+                writeln!(f, "  - {string:?}")?;
+            }
+        }
+
+        let (old_entropy, new_entropy) = self.average_entropy;
+        if old_entropy != new_entropy {
+            writeln!(f, "average entropy: {old_entropy:.3} -> {new_entropy:.3}")?;
+        }
+
+        let range = |r: Option<(f32, f32)>| {
+            r.map_or("none".to_string(), |(min, max)| {
+                format!("{min:.3}..{max:.3}")
+            })
+        };
+        for change in &self.entropy_windows {
+            writeln!(
+                f,
+                "entropy window {}: {} -> {}",
+                change.window,
+                range(change.old),
+                range(change.new)
+            )?;
+        }
+
+        let (old_points, new_points) = self.max_points;
+        if old_points != new_points {
+            writeln!(
+                f,
+                "max points: {old_points} -> {new_points} ({:+})",
+                new_points as i64 - old_points as i64
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Byte sequences that can be displayed as hex.
+trait HexBytes {
+    /// Format the bytes as space separated hex, with a masked byte given as `??`.
+    fn to_hex(&self) -> String;
+}
+
+impl HexBytes for [u8] {
+    fn to_hex(&self) -> String {
+        self.iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl HexBytes for Vec<u8> {
+    fn to_hex(&self) -> String {
+        self.as_slice().to_hex()
+    }
+}
+
+impl HexBytes for Arc<[u8]> {
+    fn to_hex(&self) -> String {
+        (**self).to_hex()
+    }
+}
+
+impl HexBytes for Vec<Option<u8>> {
+    fn to_hex(&self) -> String {
+        self.iter()
+            .map(|b| b.map_or("??".to_string(), |b| format!("{b:02X}")))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Write the changes to a set of byte sequences, if there are any.
+///
+/// # Arguments
+///
+/// * `f` - The formatter.
+/// * `title` - The title of the set of sequences.
+/// * `changes` - The changes.
+fn write_sequence_changes<T: HexBytes + Clone + PartialEq>(
+    f: &mut fmt::Formatter<'_>,
+    title: &str,
+    changes: &SequenceChanges<T>,
+) -> fmt::Result {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(f, "{title}:")?;
+    for (position, bytes) in &changes.added {
+        writeln!(f, "  + {position}: {}", bytes.to_hex())?;
+    }
+    for (position, bytes) in &changes.removed {
+        writeln!(f, "  - {position}: {}", bytes.to_hex())?;
+    }
+    for (old_position, new_position, bytes) in &changes.moved {
+        writeln!(
+            f,
+            "  > {old_position} -> {new_position}: {}",
+            bytes.to_hex()
+        )?;
+    }
+    for (position, old_bytes, new_bytes) in &changes.changed {
+        writeln!(
+            f,
+            "  ~ {position}: {} -> {}",
+            old_bytes.to_hex(),
+            new_bytes.to_hex()
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_pattern_diff {
+    use crate::{
+        interner::{intern_bytes, intern_sequences},
+        pattern::Pattern,
+    };
+
+    use super::PatternDiff;
+
+    #[test]
+    fn test_pattern_diff() {
+        let mut old = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        old.data.sequences = intern_sequences(vec![
+            (0, b"MAGIC".to_vec()),
+            (8, vec![1, 2]),
+            (16, vec![3, 4]),
+        ]);
+        old.data.strings.insert("KEPT".to_string());
+        old.data.strings.insert("LOST".to_string());
+        old.data.entropy_windows = vec![(0, 3.0, 4.0), (1, 5.0, 6.0)];
+        old.compute_attributes();
+
+        let mut new = old.clone();
+        assert!(PatternDiff::between(&old, &new).is_empty());
+
+        new.data.sequences = intern_sequences(vec![
+            (0, b"MAGIC".to_vec()),
+            (10, vec![1, 2]),
+            (16, vec![3, 5]),
+            (20, vec![6]),
+        ]);
+        new.data.strings.remove("LOST");
+        new.data.strings.insert("GAINED".to_string());
+        new.data.entropy_windows = vec![(0, 3.0, 4.0), (1, 4.5, 6.0)];
+        new.compute_attributes();
+
+        let diff = PatternDiff::between(&old, &new);
+        assert_eq!(diff.sequences.added, intern_sequences(vec![(20, vec![6])]));
+        assert!(diff.sequences.removed.is_empty());
+        assert_eq!(diff.sequences.moved, vec![(8, 10, intern_bytes(&[1, 2]))]);
+        assert_eq!(
+            diff.sequences.changed,
+            vec![(16, intern_bytes(&[3, 4]), intern_bytes(&[3, 5]))]
+        );
+        assert_eq!(diff.strings_gained, vec!["GAINED"]);
+        assert_eq!(diff.strings_lost, vec!["LOST"]);
+        assert_eq!(diff.entropy_windows.len(), 1);
+        assert_eq!(diff.entropy_windows[0].old, Some((5.0, 6.0)));
+        assert!(diff.max_points.1 > diff.max_points.0);
+
+        let text = diff.to_string();
+        assert!(text.contains("  > 8 -> 10: 01 02"));
+        assert!(text.contains("  ~ 16: 03 04 -> 03 05"));
+        assert!(text.contains("  + \"GAINED\""));
+    }
+}
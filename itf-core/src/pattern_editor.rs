@@ -0,0 +1,656 @@
+use std::fmt::Write;
+
+use crate::{error::ItfError, interner, pattern::Pattern, pattern_authoring};
+
+/// The maximum entropy of a sequence of bytes, in bits per byte.
+const MAX_ENTROPY: f32 = 8.0;
+
+/// A summary of the edits understood by [`PatternEdit::parse`], suitable for display to the user.
+pub const EDIT_COMMANDS: &str = "\
+set name|description <text>                  Set the name or description of the pattern.
+set extensions|mimetypes|categories <a,b>    Set a comma separated list, which may be empty.
+set priority <number>                        Set the priority of the pattern.
+set parent <uuid>                            Set the UUID of the parent pattern of the format family, if any.
+add sequence|trailer|masked <position> <hex> Add a byte sequence, e.g. 'add sequence 0 89 50 4E 47'.
+                                             Masked sequences may use '??' to denote a masked byte.
+set sequence|trailer|masked <index> <position> <hex>
+                                             Replace the byte sequence with the given index.
+remove sequence|trailer|masked <index>       Remove the byte sequence with the given index.
+add string <text>                            Add a string.
+remove string <index>                        Remove the string with the given index.
+set entropy <average>                        Set the average entropy, or 0 to disable entropy scanning.
+set window <window> <min> <max>              Set the entropy bounds of an entropy window.
+remove window <window>                       Remove the entropy bounds of an entropy window.";
+
+/// The kinds of byte sequence that can be edited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SequenceKind {
+    /// A sequence at a fixed position from the start of the file.
+    Positional,
+    /// A sequence at a fixed distance from the end of the file.
+    Trailer,
+    /// A sequence at a fixed position from the start of the file, which contains masked bytes.
+    Masked,
+}
+
+impl SequenceKind {
+    /// Get the sequence kind with a given name, as used by the edit commands.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sequence" => Some(Self::Positional),
+            "trailer" => Some(Self::Trailer),
+            "masked" => Some(Self::Masked),
+            _ => None,
+        }
+    }
+
+    /// Get the number of sequences of this kind within a pattern.
+    fn count(&self, pattern: &Pattern) -> usize {
+        match self {
+            Self::Positional => pattern.data.sequences.len(),
+            Self::Trailer => pattern.data.trailer_sequences.len(),
+            Self::Masked => pattern.data.masked_sequences.len(),
+        }
+    }
+}
+
+/// A single validated change to a [`Pattern`], such as those entered within the interactive pattern editor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatternEdit {
+    /// Set the name of the pattern.
+    SetName(String),
+    /// Set the description of the pattern.
+    SetDescription(String),
+    /// Set the known extensions of the pattern.
+    SetExtensions(Vec<String>),
+    /// Set the known MIME types of the pattern.
+    SetMimetypes(Vec<String>),
+    /// Set the categories of the pattern.
+    SetCategories(Vec<String>),
+    /// Set the priority of the pattern.
+    SetPriority(i32),
+    /// Set the UUID of the parent pattern of the format family, or remove it.
+    SetParent(Option<String>),
+    /// Add a byte sequence, given as the position (or the distance to the end of the file) and the bytes.
+    AddSequence(SequenceKind, usize, Vec<Option<u8>>),
+    /// Replace the byte sequence with a given index by a new position and bytes.
+    ReplaceSequence(SequenceKind, usize, usize, Vec<Option<u8>>),
+    /// Remove the byte sequence with a given index.
+    RemoveSequence(SequenceKind, usize),
+    /// Add a string.
+    AddString(String),
+    /// Remove the string with a given index, in sorted order.
+    RemoveString(usize),
+    /// Set the average entropy.
+    SetAverageEntropy(f32),
+    /// Set the minimum and maximum entropy of an entropy window.
+    SetEntropyWindow(usize, f32, f32),
+    /// Remove the entropy bounds of an entropy window.
+    RemoveEntropyWindow(usize),
+}
+
+impl PatternEdit {
+    /// Parse an edit command, such as `add sequence 0 89 50 4E 47`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command. See [`EDIT_COMMANDS`] for the supported commands.
+    ///
+    /// # Returns
+    ///
+    /// The edit, or an error describing why the command isn't valid.
+    pub fn parse(command: &str) -> Result<Self, ItfError> {
+        let (action, rest) = split_word(command.trim());
+        let (target, rest) = split_word(rest);
+
+        let list = |value: &str| -> Vec<String> {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+
+        let edit = match (action, target) {
+            ("set", "name") if rest.is_empty() => {
+                return Err(ItfError::InvalidEdit("the name can't be empty".to_string()))
+            }
+            ("set", "name") => Self::SetName(rest.to_string()),
+            ("set", "description") => Self::SetDescription(rest.to_string()),
+            ("set", "extensions") => Self::SetExtensions(
+                list(rest)
+                    .into_iter()
+                    .map(|e| e.trim_start_matches('.').to_uppercase())
+                    .collect(),
+            ),
+            ("set", "mimetypes") => Self::SetMimetypes(list(rest)),
+            ("set", "categories") => {
+                Self::SetCategories(list(rest).iter().map(|c| c.to_lowercase()).collect())
+            }
+            ("set", "priority") => Self::SetPriority(parse_number(rest, "priority")?),
+            ("set", "parent") => Self::SetParent(Some(rest.to_string()).filter(|p| !p.is_empty())),
+            ("add", "string") if rest.is_empty() => {
+                return Err(ItfError::InvalidEdit(
+                    "the string can't be empty".to_string(),
+                ))
+            }
+            ("add", "string") => Self::AddString(rest.to_string()),
+            ("remove", "string") => Self::RemoveString(parse_number(rest, "index")?),
+            ("set", "entropy") => {
+                Self::SetAverageEntropy(parse_entropy(rest, "the average entropy")?)
+            }
+            ("set", "window") => {
+                let (window, rest) = split_word(rest);
+                let (min, max) = split_word(rest);
+                let window = parse_number(window, "window")?;
+                let min = parse_entropy(min, "the minimum entropy")?;
+                let max = parse_entropy(max, "the maximum entropy")?;
+                if min > max {
+                    return Err(ItfError::InvalidEdit(format!(
+                        "the minimum entropy ({min}) is greater than the maximum entropy ({max})"
+                    )));
+                }
+                Self::SetEntropyWindow(window, min, max)
+            }
+            ("remove", "window") => Self::RemoveEntropyWindow(parse_number(rest, "window")?),
+            (action, target) => {
+                let Some(kind) = SequenceKind::from_name(target) else {
+                    return Err(ItfError::InvalidEdit(format!(
+                        "unknown command '{}', use 'help' to list the commands",
+                        command.trim()
+                    )));
+                };
+
+                match action {
+                    "add" => {
+                        let (position, hex) = split_word(rest);
+                        let position = parse_number(position, "position")?;
+                        Self::AddSequence(kind, position, parse_sequence(kind, hex)?)
+                    }
+                    "set" => {
+                        let (index, rest) = split_word(rest);
+                        let (position, hex) = split_word(rest);
+                        let index = parse_number(index, "index")?;
+                        let position = parse_number(position, "position")?;
+                        Self::ReplaceSequence(kind, index, position, parse_sequence(kind, hex)?)
+                    }
+                    "remove" => Self::RemoveSequence(kind, parse_number(rest, "index")?),
+                    _ => {
+                        return Err(ItfError::InvalidEdit(format!(
+                            "unknown command '{}', use 'help' to list the commands",
+                            command.trim()
+                        )))
+                    }
+                }
+            }
+        };
+
+        Ok(edit)
+    }
+
+    /// Apply the edit to a pattern, recomputing the attributes of the pattern afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern.
+    ///
+    /// # Returns
+    ///
+    /// An error if the edit isn't valid for the pattern, in which case the pattern is left unchanged.
+    ///
+    /// # Notes
+    ///
+    /// Any signature of the pattern is removed, as it no longer covers the contents of the pattern.
+    pub fn apply(&self, pattern: &mut Pattern) -> Result<(), ItfError> {
+        let data = &mut pattern.data;
+        match self {
+            Self::SetName(name) => pattern.type_data.name = name.clone(),
+            Self::SetDescription(description) => {
+                pattern.type_data.description = description.clone()
+            }
+            Self::SetExtensions(extensions) => {
+                pattern.type_data.known_extensions = interner::intern_all(extensions)
+            }
+            Self::SetMimetypes(mimetypes) => {
+                pattern.type_data.known_mimetypes = interner::intern_all(mimetypes)
+            }
+            Self::SetCategories(categories) => {
+                pattern.type_data.categories = interner::intern_all(categories)
+            }
+            Self::SetPriority(priority) => pattern.type_data.priority = *priority,
+            Self::SetParent(Some(parent)) if *parent == pattern.type_data.uuid => {
+                return Err(ItfError::InvalidEdit(
+                    "a pattern can't be its own parent".to_string(),
+                ))
+            }
+            Self::SetParent(parent) => pattern.type_data.parent_uuid = parent.clone(),
+            Self::AddSequence(kind, position, bytes) => {
+                check_sequence(pattern, *kind, None, *position, bytes)?;
+                insert_sequence(pattern, *kind, *position, bytes);
+            }
+            Self::ReplaceSequence(kind, index, position, bytes) => {
+                check_index(*index, kind.count(pattern), "sequence")?;
+                check_sequence(pattern, *kind, Some(*index), *position, bytes)?;
+                remove_sequence(pattern, *kind, *index);
+                insert_sequence(pattern, *kind, *position, bytes);
+            }
+            Self::RemoveSequence(kind, index) => {
+                check_index(*index, kind.count(pattern), "sequence")?;
+                remove_sequence(pattern, *kind, *index);
+            }
+            Self::AddString(string) => {
+                if !data.strings.insert(string.clone()) {
+                    return Err(ItfError::InvalidEdit(format!(
+                        "the string {string:?} is already present"
+                    )));
+                }
+            }
+            Self::RemoveString(index) => {
+                check_index(*index, data.strings.len(), "string")?;
+                let string = sorted_strings(pattern)[*index].clone();
+                pattern.data.strings.remove(&string);
+            }
+            Self::SetAverageEntropy(entropy) => data.average_entropy = *entropy,
+            Self::SetEntropyWindow(window, min, max) => {
+                data.entropy_windows.retain(|(w, _, _)| w != window);
+                data.entropy_windows.push((*window, *min, *max));
+                data.entropy_windows.sort_unstable_by_key(|(w, _, _)| *w);
+            }
+            Self::RemoveEntropyWindow(window) => {
+                let count = data.entropy_windows.len();
+                data.entropy_windows.retain(|(w, _, _)| w != window);
+                if data.entropy_windows.len() == count {
+                    return Err(ItfError::InvalidEdit(format!(
+                        "entropy window {window} has no bounds"
+                    )));
+                }
+            }
+        }
+
+        pattern.signature = None;
+        pattern.compute_attributes();
+
+        Ok(())
+    }
+}
+
+/// Describe the editable contents of a pattern, with each entry numbered by the index used to edit it.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+pub fn describe(pattern: &Pattern) -> String {
+    let mut output = String::new();
+    let type_data = &pattern.type_data;
+    let data = &pattern.data;
+
+    let _ = writeln!(output, "name: {}", type_data.name);
+    let _ = writeln!(output, "description: {}", type_data.description);
+    let _ = writeln!(
+        output,
+        "extensions: {}",
+        type_data.known_extensions.join(", ")
+    );
+    let _ = writeln!(
+        output,
+        "mimetypes: {}",
+        type_data.known_mimetypes.join(", ")
+    );
+    let _ = writeln!(output, "categories: {}", type_data.categories.join(", "));
+    let _ = writeln!(output, "priority: {}", type_data.priority);
+    let _ = writeln!(
+        output,
+        "parent: {}",
+        type_data.parent_uuid.as_deref().unwrap_or("")
+    );
+
+    let hex = |bytes: &[Option<u8>]| {
+        bytes
+            .iter()
+            .map(|b| b.map_or("??".to_string(), |b| format!("{b:02X}")))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut write_sequences = |title: &str, sequences: Vec<(usize, Vec<Option<u8>>)>| {
+        let _ = writeln!(output, "{title}:");
+        for (i, (position, bytes)) in sequences.iter().enumerate() {
+            let _ = writeln!(output, "  [{i}] {position}: {}", hex(bytes));
+        }
+    };
+    fn unmasked<B: AsRef<[u8]>>(sequences: &[(usize, B)]) -> Vec<(usize, Vec<Option<u8>>)> {
+        sequences
+            .iter()
+            .map(|(p, s)| (*p, s.as_ref().iter().copied().map(Some).collect()))
+            .collect()
+    }
+    write_sequences("sequences", unmasked(&data.sequences));
+    write_sequences("trailer sequences", unmasked(&data.trailer_sequences));
+    write_sequences("masked sequences", data.masked_sequences.clone());
+
+    let _ = writeln!(output, "strings:");
+    for (i, string) in sorted_strings(pattern).iter().enumerate() {
+        let _ = writeln!(output, "  [{i}] {string:?}");
+    }
+
+    let _ = writeln!(output, "average entropy: {:.3}", data.average_entropy);
+    let _ = writeln!(output, "entropy windows:");
+    for (window, min, max) in &data.entropy_windows {
+        let _ = writeln!(output, "  window {window}: {min:.3}..{max:.3}");
+    }
+    let _ = writeln!(output, "max points: {}", pattern.max_points);
+
+    output
+}
+
+/// Get the strings of a pattern in sorted order, which is the order in which they are numbered.
+fn sorted_strings(pattern: &Pattern) -> Vec<String> {
+    let mut strings: Vec<_> = pattern.data.strings.iter().cloned().collect();
+    strings.sort_unstable();
+    strings
+}
+
+/// Split the first whitespace delimited word from a string.
+///
+/// # Arguments
+///
+/// * `input` - The string.
+///
+/// # Returns
+///
+/// The first word and the remainder of the string, with any leading whitespace removed.
+fn split_word(input: &str) -> (&str, &str) {
+    match input.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim_start()),
+        None => (input, ""),
+    }
+}
+
+/// Parse a number that forms part of an edit command.
+///
+/// # Arguments
+///
+/// * `value` - The value.
+/// * `name` - The name of the value, used within the error message.
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, ItfError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ItfError::InvalidEdit(format!("'{value}' isn't a valid {name}")))
+}
+
+/// Parse an entropy value, which must be between zero and [`MAX_ENTROPY`].
+///
+/// # Arguments
+///
+/// * `value` - The value.
+/// * `name` - The name of the value, used within the error message.
+fn parse_entropy(value: &str, name: &str) -> Result<f32, ItfError> {
+    match value.trim().parse::<f32>() {
+        Ok(entropy) if (0.0..=MAX_ENTROPY).contains(&entropy) => Ok(entropy),
+        _ => Err(ItfError::InvalidEdit(format!(
+            "'{value}' isn't valid for {name}, which must be between 0 and {MAX_ENTROPY}"
+        ))),
+    }
+}
+
+/// Parse the hex bytes of a sequence.
+///
+/// # Arguments
+///
+/// * `kind` - The kind of sequence. Only masked sequences may contain masked bytes, and they must contain at least one.
+/// * `hex` - The hex string.
+fn parse_sequence(kind: SequenceKind, hex: &str) -> Result<Vec<Option<u8>>, ItfError> {
+    let bytes = pattern_authoring::parse_hex_sequence(hex)
+        .ok_or_else(|| ItfError::InvalidEdit(format!("invalid hex sequence '{hex}'")))?;
+
+    let masked = bytes.iter().any(Option::is_none);
+    match kind {
+        SequenceKind::Masked if !masked => Err(ItfError::InvalidEdit(
+            "a masked sequence must contain at least one masked byte".to_string(),
+        )),
+        SequenceKind::Masked if bytes.iter().all(Option::is_none) => Err(ItfError::InvalidEdit(
+            "a masked sequence must contain at least one unmasked byte".to_string(),
+        )),
+        SequenceKind::Positional | SequenceKind::Trailer if masked => Err(ItfError::InvalidEdit(
+            "only masked sequences may contain masked bytes".to_string(),
+        )),
+        _ => Ok(bytes),
+    }
+}
+
+/// Check that an index refers to an existing entry.
+///
+/// # Arguments
+///
+/// * `index` - The index.
+/// * `count` - The number of entries.
+/// * `name` - The name of the entries, used within the error message.
+fn check_index(index: usize, count: usize, name: &str) -> Result<(), ItfError> {
+    if index >= count {
+        return Err(ItfError::InvalidEdit(format!(
+            "there is no {name} with index {index}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check that a sequence can be added to a pattern without conflicting with its other sequences.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+/// * `kind` - The kind of sequence.
+/// * `replacing` - The index of the sequence being replaced, if any, which is ignored.
+/// * `position` - The position of the sequence.
+/// * `bytes` - The bytes of the sequence.
+///
+/// # Notes
+///
+/// A positional sequence that overlaps an existing positional sequence takes precedence over it, so only
+/// trailer and masked sequences are checked for overlaps.
+fn check_sequence(
+    pattern: &Pattern,
+    kind: SequenceKind,
+    replacing: Option<usize>,
+    position: usize,
+    bytes: &[Option<u8>],
+) -> Result<(), ItfError> {
+    let data = &pattern.data;
+
+    // Trailer positions are distances from the end of the file, so the range of a trailer sequence
+    // is given in terms of the distance to the end of the file.
+    let (start, end, others): (usize, usize, Vec<(usize, usize)>) = match kind {
+        SequenceKind::Positional => return Ok(()),
+        SequenceKind::Trailer => {
+            if bytes.len() > position {
+                return Err(ItfError::InvalidEdit(format!(
+                    "a trailer sequence of {} bytes can't start {position} bytes from the end of the file",
+                    bytes.len()
+                )));
+            }
+
+            let others = data
+                .trailer_sequences
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| Some(*i) != replacing)
+                .map(|(_, (p, s))| (p.saturating_sub(s.len()), *p))
+                .collect();
+            (position - bytes.len(), position, others)
+        }
+        SequenceKind::Masked => {
+            let others = data
+                .sequences
+                .iter()
+                .map(|(p, s)| (*p, p + s.len()))
+                .chain(
+                    data.masked_sequences
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| Some(*i) != replacing)
+                        .map(|(_, (p, s))| (*p, p + s.len())),
+                )
+                .collect();
+            (position, position + bytes.len(), others)
+        }
+    };
+
+    if others.iter().any(|(s, e)| *s < end && start < *e) {
+        return Err(ItfError::InvalidEdit(format!(
+            "the sequence at {position} overlaps an existing sequence"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Insert a validated sequence into a pattern.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+/// * `kind` - The kind of sequence.
+/// * `position` - The position of the sequence.
+/// * `bytes` - The bytes of the sequence, which may only be masked for a masked sequence.
+fn insert_sequence(
+    pattern: &mut Pattern,
+    kind: SequenceKind,
+    position: usize,
+    bytes: &[Option<u8>],
+) {
+    let unmasked = || bytes.iter().flatten().copied().collect::<Vec<_>>();
+    match kind {
+        SequenceKind::Positional => pattern.add_sequence(position, unmasked()),
+        SequenceKind::Trailer => {
+            let sequences = &mut pattern.data.trailer_sequences;
+            sequences.push((position, unmasked()));
+            sequences.sort_unstable_by_key(|(p, _)| std::cmp::Reverse(*p));
+        }
+        SequenceKind::Masked => {
+            let sequences = &mut pattern.data.masked_sequences;
+            sequences.push((position, bytes.to_vec()));
+            sequences.sort_unstable_by_key(|(p, _)| *p);
+        }
+    }
+}
+
+/// Remove a sequence from a pattern, along with its recorded support.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+/// * `kind` - The kind of sequence.
+/// * `index` - The index of the sequence, which must exist.
+fn remove_sequence(pattern: &mut Pattern, kind: SequenceKind, index: usize) {
+    let data = &mut pattern.data;
+    match kind {
+        SequenceKind::Positional => {
+            let (position, _) = data.sequences.remove(index);
+            data.sequence_support.retain(|(p, _)| *p != position);
+        }
+        SequenceKind::Trailer => {
+            data.trailer_sequences.remove(index);
+        }
+        SequenceKind::Masked => {
+            data.masked_sequences.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_pattern_editor {
+    use crate::{interner::intern_sequences, pattern::Pattern};
+
+    use super::{describe, PatternEdit, SequenceKind};
+
+    #[test]
+    fn test_parse_edit() {
+        assert_eq!(
+            PatternEdit::parse("add sequence 4 89 50 4e 47").unwrap(),
+            PatternEdit::AddSequence(
+                SequenceKind::Positional,
+                4,
+                vec![Some(0x89), Some(0x50), Some(0x4E), Some(0x47)]
+            )
+        );
+        assert_eq!(
+            PatternEdit::parse("set extensions .png, apng").unwrap(),
+            PatternEdit::SetExtensions(vec!["PNG".to_string(), "APNG".to_string()])
+        );
+        assert_eq!(
+            PatternEdit::parse("  add string hello world ").unwrap(),
+            PatternEdit::AddString("hello world".to_string())
+        );
+        assert_eq!(
+            PatternEdit::parse("set window 2 3.5 7").unwrap(),
+            PatternEdit::SetEntropyWindow(2, 3.5, 7.0)
+        );
+
+        assert!(PatternEdit::parse("add sequence 0 89 ??").is_err());
+        assert!(PatternEdit::parse("add masked 0 89 50").is_err());
+        assert!(PatternEdit::parse("add sequence x 89").is_err());
+        assert!(PatternEdit::parse("set window 0 5 4").is_err());
+        assert!(PatternEdit::parse("set entropy 9").is_err());
+        assert!(PatternEdit::parse("set name").is_err());
+        assert!(PatternEdit::parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let mut pattern = Pattern::new("Test", "test", vec!["tst".to_string()], vec![]);
+        pattern.data.sequence_support = vec![(0, 3)];
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        pattern.compute_attributes();
+        let max_points = pattern.max_points;
+
+        let apply = |pattern: &mut Pattern, command: &str| {
+            PatternEdit::parse(command).unwrap().apply(pattern)
+        };
+
+        apply(&mut pattern, "add sequence 8 01 02").unwrap();
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(8, vec![1, 2]), (0, b"MAGIC".to_vec())])
+        );
+        assert!(pattern.max_points > max_points);
+
+        // Replacing a sequence discards the support that was recorded for it.
+        apply(&mut pattern, "set sequence 1 0 4D 5A").unwrap();
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(8, vec![1, 2]), (0, vec![0x4D, 0x5A])])
+        );
+        assert!(pattern.data.sequence_support.is_empty());
+
+        apply(&mut pattern, "add masked 16 FF ?? D8").unwrap();
+        assert!(apply(&mut pattern, "add masked 7 FF ?? D8").is_err());
+        apply(&mut pattern, "remove masked 0").unwrap();
+        assert!(pattern.data.masked_sequences.is_empty());
+        assert!(apply(&mut pattern, "remove masked 0").is_err());
+
+        apply(&mut pattern, "add string BETA").unwrap();
+        apply(&mut pattern, "add string ALPHA").unwrap();
+        assert!(apply(&mut pattern, "add string ALPHA").is_err());
+        apply(&mut pattern, "remove string 1").unwrap();
+        assert!(pattern.data.strings.contains("ALPHA"));
+        assert!(!pattern.data.strings.contains("BETA"));
+
+        apply(&mut pattern, "set window 1 3 4").unwrap();
+        apply(&mut pattern, "set window 0 1 2").unwrap();
+        apply(&mut pattern, "set window 1 3.5 4").unwrap();
+        assert_eq!(
+            pattern.data.entropy_windows,
+            vec![(0, 1.0, 2.0), (1, 3.5, 4.0)]
+        );
+        assert!(apply(&mut pattern, "remove window 5").is_err());
+
+        apply(&mut pattern, "set name Renamed").unwrap();
+        let description = describe(&pattern);
+        assert!(description.contains("name: Renamed"));
+        assert!(description.contains("  [1] 0: 4D 5A"));
+        assert!(description.contains("  [0] \"ALPHA\""));
+        assert!(description.contains("  window 1: 3.500..4.000"));
+    }
+}
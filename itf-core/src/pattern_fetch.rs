@@ -0,0 +1,792 @@
+use hashbrown::HashMap;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Component, Path},
+};
+
+use crate::{
+    error::ItfError,
+    pattern::{Pattern, PatternCompression},
+    pattern_bundle::{PatternBundle, BUNDLE_MAGIC},
+    pattern_index::{PatternIndex, INDEX_FILE_NAME},
+    pattern_signing,
+};
+
+/// The name of the file, within a pattern directory, that records the sources from which patterns were fetched.
+/// The name deliberately lacks a pattern file extension, so that the file isn't mistaken for a pattern.
+pub const SOURCES_FILE_NAME: &str = "patterns.sources";
+/// The name of the file, within a pattern directory, that holds the set of patterns replaced by the last update.
+pub const ROLLBACK_FILE_NAME: &str = "patterns.rollback";
+/// The largest response that will be accepted, in bytes.
+const MAX_RESPONSE_LENGTH: u64 = 256 * 1024 * 1024;
+/// The zstd compression level applied to the rollback file.
+const ROLLBACK_COMPRESSION_LEVEL: i32 = 3;
+
+/// A remote location from which patterns have been fetched.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PatternSource {
+    /// The URL of the pattern bundle or pattern index.
+    pub url: String,
+    /// The installed version of the patterns, which is incremented whenever fetching changes the patterns.
+    pub version: u32,
+    /// The date and time at which the patterns were last changed.
+    pub updated_on: String,
+    /// The paths to the pattern files provided by the source, relative to the pattern directory.
+    pub files: Vec<String>,
+    /// The entity tags returned by the server for each fetched URL, allowing unchanged files to be skipped.
+    etags: HashMap<String, String>,
+}
+
+/// The sources from which the patterns within a pattern directory have been fetched.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PatternSources {
+    /// The sources, in the order in which they were first fetched.
+    pub sources: Vec<PatternSource>,
+}
+
+impl PatternSources {
+    /// Load the sources recorded within a pattern directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the pattern directory.
+    ///
+    /// # Returns
+    ///
+    /// An error if the sources file could not be read or parsed, otherwise the sources. The sources
+    /// will be empty if no patterns have been fetched into the directory.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ItfError> {
+        match fs::read_to_string(path.as_ref().join(SOURCES_FILE_NAME)) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the sources into a pattern directory, replacing any existing sources file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the pattern directory.
+    fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ItfError> {
+        fs::write(
+            path.as_ref().join(SOURCES_FILE_NAME),
+            serde_json::to_string(self)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// The set of patterns that was replaced by an update, from which the update can be rolled back.
+#[derive(Serialize, Deserialize)]
+struct RollbackSet {
+    /// The sources as they were before the update.
+    sources: PatternSources,
+    /// The relative path and contents of each file provided by the sources before the update.
+    files: Vec<(String, Vec<u8>)>,
+}
+
+/// The outcome of fetching a set of patterns.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FetchSummary {
+    /// The number of pattern files that were written.
+    pub written: usize,
+    /// The number of remote files that were unchanged since they were last fetched.
+    pub unchanged: usize,
+    /// The number of pattern files that were removed, as the source no longer provides them.
+    pub removed: usize,
+}
+
+impl FetchSummary {
+    /// Did fetching change any of the pattern files?
+    pub fn has_changes(&self) -> bool {
+        self.written > 0 || self.removed > 0
+    }
+}
+
+/// The names of the pattern definitions that were changed by an update.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DefinitionChanges {
+    /// The definitions that were added.
+    pub added: Vec<String>,
+    /// The definitions that were removed.
+    pub removed: Vec<String>,
+    /// The definitions that were modified.
+    pub modified: Vec<String>,
+}
+
+impl DefinitionChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// The outcome of updating the patterns from a single source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateReport {
+    /// The URL of the source.
+    pub url: String,
+    /// The installed version of the patterns from the source, after the update.
+    pub version: u32,
+    /// A summary of the files that were fetched.
+    pub summary: FetchSummary,
+    /// The definitions that were changed.
+    pub changes: DefinitionChanges,
+}
+
+/// Download a set of patterns from a remote repository into a local pattern directory.
+///
+/// # Arguments
+///
+/// * `url` - The URL of a pattern bundle, a pattern index, or a directory containing a pattern index.
+/// * `directory` - The path to the local pattern directory, which will be created if it doesn't exist.
+/// * `allow_insecure` - Whether the patterns may be downloaded over plain HTTP.
+///
+/// # Returns
+///
+/// An error if the URL doesn't use HTTPS and insecure downloads aren't allowed, or if the patterns could not be
+/// downloaded or written, otherwise a summary of the files fetched.
+///
+/// # Notes
+///
+/// A bundle will have its patterns extracted into the directory. An index will have each of the pattern
+/// files that it lists downloaded, relative to the URL of the index. A URL ending with `/` refers to the
+/// index within that remote directory.
+///
+/// The source is recorded within the directory, along with the entity tag of each downloaded file, so
+/// that [`update`] can later fetch only the files that have changed.
+pub fn fetch<P: AsRef<Path>>(
+    url: &str,
+    directory: P,
+    allow_insecure: bool,
+) -> Result<FetchSummary, ItfError> {
+    check_scheme(url, allow_insecure)?;
+
+    let directory = directory.as_ref();
+    fs::create_dir_all(directory)?;
+
+    let url = if url.ends_with('/') {
+        format!("{url}{INDEX_FILE_NAME}")
+    } else {
+        url.to_string()
+    };
+
+    let mut sources = PatternSources::load(directory)?;
+    let position = match sources.sources.iter().position(|s| s.url == url) {
+        Some(i) => i,
+        None => {
+            sources.sources.push(PatternSource {
+                url,
+                ..Default::default()
+            });
+            sources.sources.len() - 1
+        }
+    };
+
+    let source = &mut sources.sources[position];
+    let summary = fetch_source(
+        &new_agent(allow_insecure),
+        directory,
+        source,
+        allow_insecure,
+    )?;
+    if summary.has_changes() {
+        source.version += 1;
+        source.updated_on = chrono::offset::Utc::now().to_string();
+    }
+
+    sources.write(directory)?;
+
+    Ok(summary)
+}
+
+/// Update the patterns within a directory from every source that they were fetched from.
+///
+/// # Arguments
+///
+/// * `directory` - The path to the local pattern directory.
+/// * `allow_insecure` - Whether the patterns may be downloaded over plain HTTP.
+///
+/// # Returns
+///
+/// An error if no patterns have been fetched into the directory, if a source doesn't use HTTPS and insecure
+/// downloads aren't allowed, or if the patterns could not be downloaded or written, otherwise a report for
+/// each source.
+///
+/// # Notes
+///
+/// Only the files that have changed since they were last fetched will be downloaded. If any of the
+/// pattern files are changed, the previous set is kept so that the update can be undone with [`rollback`].
+pub fn update<P: AsRef<Path>>(
+    directory: P,
+    allow_insecure: bool,
+) -> Result<Vec<UpdateReport>, ItfError> {
+    let directory = directory.as_ref();
+
+    let mut sources = PatternSources::load(directory)?;
+    if sources.sources.is_empty() {
+        return Err(ItfError::Fetch(
+            "no patterns have been fetched into the directory".to_string(),
+        ));
+    }
+
+    let previous = RollbackSet {
+        sources: sources.clone(),
+        files: sources
+            .sources
+            .iter()
+            .flat_map(|s| &s.files)
+            .filter_map(|f| fs::read(directory.join(f)).ok().map(|d| (f.clone(), d)))
+            .collect(),
+    };
+
+    let agent = new_agent(allow_insecure);
+    let mut reports = Vec::with_capacity(sources.sources.len());
+    let mut result = Ok(());
+    for source in &mut sources.sources {
+        let before = read_definitions(directory, &source.files);
+        let summary = match fetch_source(&agent, directory, source, allow_insecure) {
+            Ok(s) => s,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+
+        let changes = diff_definitions(&before, &read_definitions(directory, &source.files));
+        if summary.has_changes() {
+            source.version += 1;
+            source.updated_on = chrono::offset::Utc::now().to_string();
+        }
+
+        reports.push(UpdateReport {
+            url: source.url.clone(),
+            version: source.version,
+            summary,
+            changes,
+        });
+    }
+
+    // The previous set is kept whenever the files may have changed, including when the update failed part way.
+    if result.is_err() || reports.iter().any(|r| r.summary.has_changes()) {
+        let bytes = rmp_serde::to_vec_named(&previous).map_err(io::Error::other)?;
+        fs::write(
+            directory.join(ROLLBACK_FILE_NAME),
+            zstd::stream::encode_all(&bytes[..], ROLLBACK_COMPRESSION_LEVEL)?,
+        )?;
+    }
+
+    result?;
+    sources.write(directory)?;
+
+    Ok(reports)
+}
+
+/// Restore the set of patterns that was replaced by the last update.
+///
+/// # Arguments
+///
+/// * `directory` - The path to the local pattern directory.
+///
+/// # Returns
+///
+/// An error if there is no previous set of patterns, or the files could not be restored, otherwise
+/// the restored sources.
+///
+/// # Notes
+///
+/// Only a single update can be rolled back.
+pub fn rollback<P: AsRef<Path>>(directory: P) -> Result<PatternSources, ItfError> {
+    let directory = directory.as_ref();
+    let rollback_path = directory.join(ROLLBACK_FILE_NAME);
+
+    let compressed = match fs::read(&rollback_path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(ItfError::Fetch(
+                "there is no previous set of patterns to roll back to".to_string(),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let previous: RollbackSet = rmp_serde::from_slice(&zstd::stream::decode_all(&compressed[..])?)
+        .map_err(|e| ItfError::Fetch(format!("the previous set of patterns is invalid: {e}")))?;
+
+    for file in PatternSources::load(directory)?
+        .sources
+        .iter()
+        .flat_map(|s| &s.files)
+    {
+        remove_file_if_exists(&directory.join(safe_relative_path(file)?))?;
+    }
+
+    for (file, data) in &previous.files {
+        let path = directory.join(safe_relative_path(file)?);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)?;
+    }
+
+    previous.sources.write(directory)?;
+    fs::remove_file(rollback_path)?;
+
+    Ok(previous.sources)
+}
+
+/// Fetch the patterns from a single source, updating the record of the source.
+///
+/// # Arguments
+///
+/// * `agent` - The agent with which the requests should be made.
+/// * `directory` - The path to the local pattern directory.
+/// * `source` - The source.
+/// * `allow_insecure` - Whether the patterns may be downloaded over plain HTTP.
+///
+/// # Returns
+///
+/// An error if the URL of the source doesn't use HTTPS and insecure downloads aren't allowed, or if the
+/// patterns could not be downloaded or written, otherwise a summary of the files fetched.
+fn fetch_source(
+    agent: &ureq::Agent,
+    directory: &Path,
+    source: &mut PatternSource,
+    allow_insecure: bool,
+) -> Result<FetchSummary, ItfError> {
+    let url = source.url.clone();
+    check_scheme(&url, allow_insecure)?;
+    let mut summary = FetchSummary::default();
+
+    let Some(body) = get(agent, &url, &mut source.etags)? else {
+        summary.unchanged += 1;
+        return Ok(summary);
+    };
+
+    let mut files = Vec::new();
+    if body.starts_with(BUNDLE_MAGIC) {
+        for pattern in PatternBundle::from_bytes(&body)?.read_patterns()? {
+            let path = pattern.write(&directory.to_string_lossy(), PatternCompression::None)?;
+            files.push(
+                path.strip_prefix(directory)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+            );
+            summary.written += 1;
+        }
+    } else {
+        let index: PatternIndex = serde_json::from_slice(&body).map_err(|_| {
+            ItfError::Fetch(format!(
+                "'{url}' is neither a pattern bundle nor a pattern index"
+            ))
+        })?;
+
+        // The paths within the index are relative to the location of the index.
+        let base = &url[..=url.rfind('/').unwrap_or(url.len() - 1)];
+        for entry in &index.entries {
+            let local_path = directory.join(safe_relative_path(&entry.path)?);
+            let file_url = format!("{base}{}", entry.path);
+
+            // The cached entity tag is meaningless if the local copy has since been removed.
+            if !local_path.is_file() {
+                source.etags.remove(&file_url);
+            }
+
+            // Servers that don't provide entity tags will resend unchanged files.
+            match get(agent, &file_url, &mut source.etags)? {
+                Some(data) if fs::read(&local_path).is_ok_and(|d| d == data) => {
+                    summary.unchanged += 1
+                }
+                Some(data) => {
+                    if let Some(parent) = local_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&local_path, data)?;
+                    summary.written += 1;
+                }
+                None => summary.unchanged += 1,
+            }
+            files.push(entry.path.clone());
+        }
+
+        // The entity tags of files that are no longer listed are of no further use.
+        source
+            .etags
+            .retain(|u, _| *u == url || files.iter().any(|f| *u == format!("{base}{f}")));
+    }
+
+    // Any files that the source no longer provides are removed.
+    for file in source.files.iter().filter(|f| !files.contains(f)) {
+        if remove_file_if_exists(&directory.join(safe_relative_path(file)?))? {
+            summary.removed += 1;
+        }
+    }
+    source.files = files;
+
+    Ok(summary)
+}
+
+/// Create the agent with which the patterns will be downloaded.
+///
+/// # Arguments
+///
+/// * `allow_insecure` - Whether plain HTTP requests, including redirects from HTTPS to HTTP, should be allowed.
+fn new_agent(allow_insecure: bool) -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .https_only(!allow_insecure)
+        .build()
+}
+
+/// Check that a URL uses HTTPS, so that the patterns can't be tampered with in transit.
+///
+/// # Arguments
+///
+/// * `url` - The URL.
+/// * `allow_insecure` - Whether plain HTTP URLs should also be accepted.
+///
+/// # Returns
+///
+/// An error if the URL uses a scheme other than HTTPS, or plain HTTP when insecure downloads are allowed.
+fn check_scheme(url: &str, allow_insecure: bool) -> Result<(), ItfError> {
+    let has_scheme = |scheme: &str| {
+        url.get(..scheme.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(scheme))
+    };
+
+    if has_scheme("https://") || (allow_insecure && has_scheme("http://")) {
+        return Ok(());
+    }
+
+    if has_scheme("http://") {
+        return Err(ItfError::Fetch(format!(
+            "'{url}' doesn't use HTTPS, and insecure downloads haven't been allowed"
+        )));
+    }
+
+    Err(ItfError::Fetch(format!("'{url}' isn't an HTTPS URL")))
+}
+
+/// Download a file, unless it hasn't changed since it was last downloaded.
+///
+/// # Arguments
+///
+/// * `agent` - The agent with which the request should be made.
+/// * `url` - The URL of the file.
+/// * `etags` - The cached entity tags, which will be updated with the tag of the downloaded file.
+///
+/// # Returns
+///
+/// None if the file hasn't changed, otherwise the contents of the file.
+fn get(
+    agent: &ureq::Agent,
+    url: &str,
+    etags: &mut HashMap<String, String>,
+) -> Result<Option<Vec<u8>>, ItfError> {
+    let mut request = agent.get(url);
+    if let Some(etag) = etags.get(url) {
+        request = request.set("If-None-Match", etag);
+    }
+
+    let response = request
+        .call()
+        .map_err(|e| ItfError::Fetch(format!("failed to download '{url}': {e}")))?;
+    if response.status() == 304 {
+        return Ok(None);
+    }
+
+    match response.header("ETag") {
+        Some(etag) => etags.insert(url.to_string(), etag.to_string()),
+        None => etags.remove(url),
+    };
+
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_RESPONSE_LENGTH + 1)
+        .read_to_end(&mut body)?;
+    if body.len() as u64 > MAX_RESPONSE_LENGTH {
+        return Err(ItfError::Fetch(format!("'{url}' is too large")));
+    }
+
+    Ok(Some(body))
+}
+
+/// Read the definitions within a set of pattern files, keyed by their UUIDs.
+///
+/// # Arguments
+///
+/// * `directory` - The path to the local pattern directory.
+/// * `files` - The paths to the pattern files, relative to the directory.
+///
+/// # Returns
+///
+/// A map of the UUID of each definition to its name and canonical form. Files that can't be read are skipped.
+fn read_definitions(directory: &Path, files: &[String]) -> HashMap<String, (String, Vec<u8>)> {
+    files
+        .iter()
+        .filter_map(|f| Pattern::from_file(directory.join(f)).ok())
+        .filter_map(|p| {
+            let canonical = pattern_signing::canonical_bytes(&p).ok()?;
+            Some((p.type_data.uuid, (p.type_data.name, canonical)))
+        })
+        .collect()
+}
+
+/// Compare two sets of definitions.
+///
+/// # Arguments
+///
+/// * `before` - The definitions before the update.
+/// * `after` - The definitions after the update.
+fn diff_definitions(
+    before: &HashMap<String, (String, Vec<u8>)>,
+    after: &HashMap<String, (String, Vec<u8>)>,
+) -> DefinitionChanges {
+    let mut changes = DefinitionChanges::default();
+    for (uuid, (name, canonical)) in after {
+        match before.get(uuid) {
+            None => changes.added.push(name.clone()),
+            Some((_, previous)) if previous != canonical => changes.modified.push(name.clone()),
+            _ => {}
+        }
+    }
+    for (uuid, (name, _)) in before {
+        if !after.contains_key(uuid) {
+            changes.removed.push(name.clone());
+        }
+    }
+
+    changes.added.sort_unstable();
+    changes.removed.sort_unstable();
+    changes.modified.sort_unstable();
+
+    changes
+}
+
+/// Remove a file, if it exists.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+///
+/// # Returns
+///
+/// An error if the file exists but could not be removed, otherwise whether the file was removed.
+fn remove_file_if_exists(path: &Path) -> Result<bool, ItfError> {
+    match fs::remove_file(path) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Check that a path listed within a remote index refers to a pattern file inside the pattern directory.
+///
+/// # Arguments
+///
+/// * `path` - The path, as listed within the index.
+fn safe_relative_path(path: &str) -> Result<&Path, ItfError> {
+    let relative = Path::new(path);
+    let is_contained = relative.components().count() > 0
+        && relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    let is_pattern_file = PatternCompression::PATTERN_FILE_EXTENSIONS
+        .iter()
+        .any(|e| path.to_lowercase().ends_with(e));
+
+    if !is_contained || !is_pattern_file {
+        return Err(ItfError::Fetch(format!(
+            "the index lists the unsafe path '{path}'"
+        )));
+    }
+
+    Ok(relative)
+}
+
+#[cfg(test)]
+mod tests_pattern_fetch {
+    use std::{
+        env, fs,
+        hash::{DefaultHasher, Hash, Hasher},
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use crate::{
+        error::ItfError, interner::intern_sequences, pattern::Pattern,
+        pattern_bundle::PatternBundle, pattern_handler::PatternHandler,
+    };
+
+    use super::{
+        check_scheme, fetch, rollback, safe_relative_path, update, FetchSummary, PatternSources,
+    };
+
+    /// Serve a single file, which takes each of the given contents in turn, one per request.
+    /// The entity tag of each response is derived from its contents.
+    fn serve(bodies: Vec<Vec<u8>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for (stream, body) in listener.incoming().zip(bodies) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut hasher = DefaultHasher::new();
+                body.hash(&mut hasher);
+                let etag = format!("\"{:x}\"", hasher.finish());
+
+                let mut is_cached = false;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    is_cached |= line
+                        .trim()
+                        .eq_ignore_ascii_case(&format!("if-none-match: {etag}"));
+                    line.clear();
+                }
+
+                let status = if is_cached {
+                    "304 Not Modified"
+                } else {
+                    "200 OK"
+                };
+                let length = if is_cached { 0 } else { body.len() };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nETag: {etag}\r\nContent-Length: {length}\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+                if !is_cached {
+                    stream.write_all(&body).unwrap();
+                }
+            }
+        });
+
+        format!("http://{address}/patterns.itfb")
+    }
+
+    fn make_pattern(name: &str) -> Pattern {
+        let mut pattern = Pattern::new(name, "test", vec!["TEST".to_string()], vec![]);
+        pattern.data.sequences = intern_sequences(vec![(0, name.as_bytes().to_vec())]);
+        pattern
+    }
+
+    fn make_bundle(patterns: &[Pattern]) -> Vec<u8> {
+        PatternBundle::new(patterns).unwrap().to_bytes().unwrap()
+    }
+
+    fn list_names(test_dir: &std::path::Path) -> Vec<String> {
+        PatternHandler::list_pattern_files(test_dir)
+            .iter()
+            .map(|f| Pattern::from_file(f).unwrap().type_data.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_fetch_bundle() {
+        let test_dir = env::temp_dir().join(format!("itf-test-fetch-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+
+        let bundle = make_bundle(&[make_pattern("Fetched")]);
+        let url = serve(vec![bundle.clone(), bundle]);
+
+        // The test server doesn't use HTTPS, so it can only be fetched from once insecure downloads are allowed.
+        let refused = fetch(&url, &test_dir, false);
+        let first = fetch(&url, &test_dir, true);
+        let second = fetch(&url, &test_dir, true);
+        let files = PatternHandler::list_pattern_files(&test_dir);
+        let sources = PatternSources::load(&test_dir);
+        _ = fs::remove_dir_all(&test_dir);
+
+        assert!(matches!(refused, Err(ItfError::Fetch(_))));
+        assert_eq!(
+            first.unwrap(),
+            FetchSummary {
+                written: 1,
+                ..Default::default()
+            }
+        );
+
+        // The bundle is unchanged, so it isn't downloaded again.
+        assert_eq!(
+            second.unwrap(),
+            FetchSummary {
+                unchanged: 1,
+                ..Default::default()
+            }
+        );
+
+        // The record of the source isn't a pattern file.
+        assert_eq!(files.len(), 1);
+        let sources = sources.unwrap();
+        assert_eq!(sources.sources[0].url, url);
+        assert_eq!(sources.sources[0].version, 1);
+        assert_eq!(sources.sources[0].files, vec!["Fetched.json".to_string()]);
+    }
+
+    #[test]
+    fn test_update_and_rollback() {
+        let test_dir = env::temp_dir().join(format!("itf-test-update-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+
+        let first = make_pattern("First");
+        let mut modified = first.clone();
+        modified.data.sequences = intern_sequences(vec![(0, b"MODIFIED".to_vec())]);
+
+        let original = make_bundle(&[first, make_pattern("Second")]);
+        let updated = make_bundle(&[modified, make_pattern("Third")]);
+        let url = serve(vec![original, updated.clone(), updated]);
+
+        fetch(&url, &test_dir, true).unwrap();
+        let no_rollback = rollback(&test_dir);
+
+        let reports = update(&test_dir, true);
+        let updated_names = list_names(&test_dir);
+        let unchanged_reports = update(&test_dir, true);
+
+        let restored = rollback(&test_dir);
+        let restored_names = list_names(&test_dir);
+        _ = fs::remove_dir_all(&test_dir);
+
+        // Nothing has been updated, so there is nothing to roll back.
+        assert!(no_rollback.is_err());
+
+        let reports = reports.unwrap();
+        assert_eq!(reports[0].version, 2);
+        assert_eq!(reports[0].changes.added, vec!["Third".to_string()]);
+        assert_eq!(reports[0].changes.removed, vec!["Second".to_string()]);
+        assert_eq!(reports[0].changes.modified, vec!["First".to_string()]);
+        assert_eq!(updated_names, vec!["First", "Third"]);
+
+        let unchanged_reports = unchanged_reports.unwrap();
+        assert!(unchanged_reports[0].changes.is_empty());
+        assert!(!unchanged_reports[0].summary.has_changes());
+
+        assert_eq!(restored.unwrap().sources[0].version, 1);
+        assert_eq!(restored_names, vec!["First", "Second"]);
+    }
+
+    #[test]
+    fn test_check_scheme() {
+        assert!(check_scheme("https://example.com/patterns.itfb", false).is_ok());
+        assert!(check_scheme("HTTPS://example.com/", false).is_ok());
+        assert!(check_scheme("http://example.com/patterns.itfb", false).is_err());
+        assert!(check_scheme("http://example.com/patterns.itfb", true).is_ok());
+        assert!(check_scheme("ftp://example.com/patterns.itfb", true).is_err());
+        assert!(check_scheme("example.com/patterns.itfb", true).is_err());
+    }
+
+    #[test]
+    fn test_safe_relative_path() {
+        assert!(safe_relative_path("text/plain.json").is_ok());
+        assert!(safe_relative_path("plain.json.zst").is_ok());
+        assert!(safe_relative_path("../plain.json").is_err());
+        assert!(safe_relative_path("/etc/plain.json").is_err());
+        assert!(safe_relative_path("plain.exe").is_err());
+    }
+}
@@ -1,38 +1,3073 @@
-use std::{fs::File, io::Read, path::Path};
+use aho_corasick::AhoCorasick;
+use ed25519_dalek::VerifyingKey;
+use hashbrown::{HashMap, HashSet};
+use rayon::prelude::*;
+use serde_derive::Deserialize;
+#[cfg(feature = "msgpack")]
+use serde_derive::Serialize;
+use std::{
+    fmt, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use crate::{pattern::Pattern, utils};
+use crate::{
+    embedded_rule::{EmbeddedRule, EMBEDDED_RULES},
+    error::ItfError,
+    pattern::{Pattern, PatternCompression, PatternFormat, PatternTypeData},
+    pattern_archive::{self, ArchiveKind},
+    pattern_index::{PatternIndex, INDEX_FILE_NAME},
+    pattern_migration::PATTERN_FORMAT_VERSION,
+    pattern_selector::PatternSelector,
+    pattern_signing, utils,
+};
+
+#[cfg(feature = "msgpack")]
+use crate::pattern_bundle::PatternBundle;
+
+/// The number of bytes used as the key of the magic dispatch index.
+const MAGIC_LENGTH: usize = 4;
+/// The version of the pattern cache format. Changing this will invalidate any existing caches.
+#[cfg(feature = "msgpack")]
+const CACHE_FORMAT_VERSION: u32 = 1;
+/// The name of the file within a pattern directory that lists the patterns that shouldn't be loaded.
+pub const DISABLED_LIST_FILE_NAME: &str = "itf-disabled.txt";
 
 #[derive(Default)]
 pub struct PatternHandler {
     pub patterns: Vec<Pattern>,
+    /// The prefilter used to shortlist candidate patterns, if it has been built.
+    prefilter: Option<SequencePrefilter>,
+    /// The public keys of the trusted signers, if patterns must be signed to be loaded.
+    trusted_keys: Option<Vec<VerifyingKey>>,
+    /// The licenses under which a pattern must be distributed to be loaded, if any restriction applies.
+    allowed_licenses: Option<Vec<String>>,
+    /// The UUIDs or names of the patterns that shouldn't be loaded.
+    disabled_patterns: Vec<String>,
+    /// The combined report of every read of the patterns.
+    load_report: LoadReport,
+    /// The combined file counts and timings of every read of the patterns.
+    load_stats: LoadStats,
+    /// The patterns whose pattern data will only be parsed once they could match a file.
+    deferred: Vec<DeferredPattern>,
+    /// The steps by which the patterns were loaded, in order, which are repeated when the patterns are reloaded.
+    load_steps: Vec<LoadStep>,
+    /// How a pattern that has the same UUID as a loaded pattern should be handled.
+    duplicate_policy: DuplicatePolicy,
+    /// The source of each loaded pattern, along with the time at which its file was last modified (if known),
+    /// keyed by the UUID of the pattern. This is used to resolve duplicate UUIDs.
+    origins: HashMap<String, (String, Option<SystemTime>)>,
+}
+
+/// How a pattern that has the same UUID as a pattern that has already been loaded should be handled.
+/// Such a pattern, typically the result of copying a pattern file without changing its UUID, would
+/// otherwise make the results ambiguous.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Fail to load the patterns, returning [`ItfError::DuplicateUuid`].
+    Error,
+    /// Keep the pattern that was loaded first.
+    #[default]
+    KeepFirst,
+    /// Keep the pattern whose file was modified most recently. A pattern whose modification time isn't known,
+    /// such as one read from a bundle or archive, is considered to be older than one whose time is known.
+    /// If neither is known, the pattern that was loaded last is kept.
+    KeepNewest,
+}
+
+/// A step by which patterns were loaded into a [`PatternHandler`].
+#[derive(Clone)]
+enum LoadStep {
+    /// The patterns were read from a directory.
+    Directory {
+        path: PathBuf,
+        target_pattern: String,
+        extension_hint: Option<String>,
+    },
+    /// The patterns were read from a directory, deferring the parsing of their pattern data.
+    Lazy {
+        path: PathBuf,
+        target_pattern: String,
+    },
+    /// The patterns were read from an archive.
+    Archive {
+        path: PathBuf,
+        target_pattern: String,
+    },
+    /// The patterns were read from a bundle.
+    #[cfg(feature = "msgpack")]
+    Bundle {
+        path: PathBuf,
+        target_pattern: String,
+    },
+    /// The patterns were read from a bundle that was compiled into the program.
+    #[cfg(feature = "msgpack")]
+    Embedded {
+        bytes: &'static [u8],
+        target_pattern: String,
+    },
+    /// The patterns were read from a directory via a cache.
+    #[cfg(feature = "msgpack")]
+    Cached { path: PathBuf, cache_path: PathBuf },
+    /// The patterns were read from a set of layered sources.
+    Layered {
+        sources: Vec<PatternSource>,
+        target_pattern: String,
+    },
+    /// The patterns were compiled from a set of rules, which can't change.
+    Rules(Vec<Pattern>),
+    /// The patterns were restricted to a set of categories.
+    RetainCategories(Vec<String>),
+}
+
+/// A source of patterns, one of which may be layered over another with [`PatternHandler::read_layered`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatternSource {
+    /// A directory of pattern files, or a zip or tar archive of them.
+    Directory(PathBuf),
+    /// A pattern bundle.
+    #[cfg(feature = "msgpack")]
+    Bundle(PathBuf),
+    /// A pattern bundle that has been compiled into the program.
+    #[cfg(feature = "msgpack")]
+    Embedded(&'static [u8]),
+    /// The built-in rules of the library.
+    Builtin,
+}
+
+/// A report of the patterns that were loaded, skipped or couldn't be loaded when reading a set of patterns.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadReport {
+    /// The sources of the loaded patterns. These are the paths of the pattern files, or the names of the
+    /// patterns that were loaded from a bundle or cache.
+    pub loaded: Vec<String>,
+    /// The sources of the patterns that were deliberately skipped, along with the reason.
+    pub skipped: Vec<(String, String)>,
+    /// The pattern files that couldn't be loaded, such as those that couldn't be parsed or are corrupt, along
+    /// with the reason.
+    pub failed: Vec<(String, String)>,
+    /// The sources of the patterns that were discarded because they have the same UUID as another pattern,
+    /// along with the reason. See [`DuplicatePolicy`].
+    pub duplicates: Vec<(String, String)>,
+}
+
+impl LoadReport {
+    /// Add the entries of another report to this one.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other report.
+    fn append(&mut self, other: &LoadReport) {
+        self.loaded.extend_from_slice(&other.loaded);
+        self.skipped.extend_from_slice(&other.skipped);
+        self.failed.extend_from_slice(&other.failed);
+        self.duplicates.extend_from_slice(&other.duplicates);
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} patterns were loaded, {} were skipped, {} were duplicates and {} failed to load.",
+            self.loaded.len(),
+            self.skipped.len(),
+            self.duplicates.len(),
+            self.failed.len()
+        )?;
+        for source in &self.loaded {
+            writeln!(f, "  loaded: {source}")?;
+        }
+        for (source, reason) in &self.skipped {
+            writeln!(f, "  skipped: {source} ({reason})")?;
+        }
+        for (source, reason) in &self.failed {
+            writeln!(f, "  failed: {source} ({reason})")?;
+        }
+        for (source, reason) in &self.duplicates {
+            writeln!(f, "  duplicate: {source} ({reason})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics describing the loading of a set of patterns, used to diagnose slow loading.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadStats {
+    /// The number of pattern files that were found, including those that were skipped without being read.
+    pub files_scanned: usize,
+    /// The number of pattern files that were read and parsed, including those that couldn't be parsed.
+    /// Only the manifest of a deferred pattern is parsed (see [`PatternHandler::read_lazy`]).
+    pub files_parsed: usize,
+    /// The number of patterns that were loaded.
+    pub patterns_loaded: usize,
+    /// The number of patterns that were skipped, or discarded as duplicates.
+    pub patterns_skipped: usize,
+    /// The number of patterns that couldn't be loaded.
+    pub patterns_failed: usize,
+    /// The total number of byte sequences within the loaded patterns, excluding deferred patterns.
+    pub sequence_count: usize,
+    /// The total number of strings within the loaded patterns, excluding deferred patterns.
+    pub string_count: usize,
+    /// The time spent reading the patterns.
+    pub elapsed: Duration,
+}
+
+impl LoadStats {
+    /// Add the file counts and timings of another set of statistics to this one.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other statistics.
+    fn append(&mut self, other: &LoadStats) {
+        self.files_scanned += other.files_scanned;
+        self.files_parsed += other.files_parsed;
+        self.elapsed += other.elapsed;
+    }
+}
+
+impl fmt::Display for LoadStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} pattern files were scanned and {} were parsed in {:.3}s.",
+            self.files_scanned,
+            self.files_parsed,
+            self.elapsed.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "{} patterns were loaded ({} skipped, {} failed), with {} sequences and {} strings.",
+            self.patterns_loaded,
+            self.patterns_skipped,
+            self.patterns_failed,
+            self.sequence_count,
+            self.string_count
+        )
+    }
+}
+
+/// A prefilter used to quickly shortlist the patterns that could match a block of data,
+/// based on the byte sequences that are anchored to the start of the data.
+struct SequencePrefilter {
+    /// The index of patterns keyed by the first bytes of their anchored sequence.
+    /// Only patterns with an anchored sequence of at least [`MAGIC_LENGTH`] bytes are indexed here.
+    magic_index: HashMap<[u8; MAGIC_LENGTH], Vec<usize>>,
+    /// The automaton built from the anchored sequences that are too short to be indexed.
+    automaton: AhoCorasick,
+    /// The index of the pattern associated with each sequence within the automaton.
+    automaton_patterns: Vec<usize>,
+    /// The indices of the patterns without an anchored sequence. These must always be tested.
+    unanchored_patterns: Vec<usize>,
+    /// The length of the longest anchored sequence.
+    max_sequence_length: usize,
+    /// The number of patterns that were loaded when the prefilter was built.
+    pattern_count: usize,
+}
+
+/// A precompiled cache of the patterns loaded from a directory.
+#[cfg(feature = "msgpack")]
+#[derive(Deserialize)]
+struct PatternCache {
+    /// The version of the cache format.
+    version: u32,
+    /// The key derived from the pattern files, used to detect a stale cache.
+    key: u64,
+    /// The cached patterns.
+    patterns: Vec<Pattern>,
+}
+
+/// A borrowed variant of [`PatternCache`], used when writing the cache.
+#[cfg(feature = "msgpack")]
+#[derive(Serialize)]
+struct PatternCacheRef<'a> {
+    version: u32,
+    key: u64,
+    patterns: &'a [Pattern],
+}
+
+/// The parts of a pattern file that are parsed immediately by [`PatternHandler::read_lazy`]. Any other fields are
+/// skipped without being parsed.
+#[derive(Deserialize)]
+struct PatternManifest {
+    #[serde(default)]
+    format_version: u32,
+    #[serde(rename = "td")]
+    type_data: PatternTypeData,
+    #[serde(rename = "pd")]
+    data: ManifestData,
+    #[serde(rename = "od")]
+    other_data: ManifestOtherData,
+}
+
+/// The parts of the pattern data that are needed to decide whether a pattern could match a file.
+#[derive(Deserialize)]
+struct ManifestData {
+    #[serde(default)]
+    sequences: Vec<(usize, Vec<u8>)>,
+    #[serde(default)]
+    alternative_sequences: Vec<Vec<(usize, Vec<u8>)>>,
+    #[serde(default)]
+    masked_sequences: Vec<(usize, Vec<Option<u8>>)>,
+}
+
+/// The parts of the other data that are needed to decide whether a pattern should be loaded.
+#[derive(Deserialize)]
+struct ManifestOtherData {
+    #[serde(default)]
+    license: String,
+}
+
+/// A pattern whose pattern data is only parsed once the pattern could match a file.
+struct DeferredPattern {
+    /// The path to the pattern file.
+    path: String,
+    /// The type data of the pattern.
+    type_data: PatternTypeData,
+    /// The byte sequences, one of which must be at the very start of a file for the pattern to match. This will
+    /// be empty if the pattern has no byte sequence anchored to the start of a file.
+    anchors: Vec<Vec<u8>>,
+    /// The fully parsed pattern, once it has been needed. This will be none if the pattern couldn't be parsed.
+    pattern: OnceLock<Option<Pattern>>,
+}
+
+impl DeferredPattern {
+    /// Read the manifest of a pattern file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the pattern file.
+    ///
+    /// # Returns
+    ///
+    /// None if the pattern file isn't a JSON document in the current format, as it must then be parsed in full.
+    /// Otherwise, the deferred pattern and its license.
+    fn from_file(path: &str) -> Option<(Self, String)> {
+        if PatternFormat::from_path(path) != PatternFormat::Json {
+            return None;
+        }
+
+        let contents = Pattern::read_file_contents(path).ok()?;
+        let manifest: PatternManifest = serde_json::from_slice(&contents).ok()?;
+        if manifest.format_version != PATTERN_FORMAT_VERSION {
+            return None;
+        }
+
+        // These mirror the anchored sequences used by the prefilter, which the pattern would require if parsed.
+        let data = manifest.data;
+        let anchors = if let Some((_, sequence)) = data.sequences.into_iter().find(|(p, _)| *p == 0)
+        {
+            vec![sequence]
+        } else if let Some((_, sequence)) = data.masked_sequences.iter().find(|(p, _)| *p == 0) {
+            vec![sequence.iter().map_while(|b| *b).collect()]
+        } else {
+            data.alternative_sequences
+                .into_iter()
+                .find(|g| !g.is_empty() && g.iter().all(|(p, _)| *p == 0))
+                .map(|g| g.into_iter().map(|(_, sequence)| sequence).collect())
+                .unwrap_or_default()
+        };
+
+        let deferred = Self {
+            path: path.to_string(),
+            type_data: manifest.type_data,
+            anchors,
+            pattern: OnceLock::new(),
+        };
+        Some((deferred, manifest.other_data.license))
+    }
+
+    /// Could the pattern match data with a given header chunk?
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - The header chunk of the data.
+    fn could_match(&self, chunk: &[u8]) -> bool {
+        self.anchors.is_empty() || self.anchors.iter().any(|a| chunk.starts_with(a))
+    }
+
+    /// Get the fully parsed pattern, parsing it if this is the first time that it has been needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler holding the pattern, which provides the trusted keys.
+    fn pattern(&self, handler: &PatternHandler) -> Option<&Pattern> {
+        self.pattern
+            .get_or_init(|| handler.read_parse_pattern(&self.path).ok())
+            .as_ref()
+    }
+}
+
+/// Is an error confined to a single pattern, such that the other patterns can still be loaded?
+///
+/// # Arguments
+///
+/// * `error` - The error raised while loading the pattern.
+fn is_pattern_error(error: &ItfError) -> bool {
+    matches!(
+        error,
+        ItfError::JsonParse(_)
+            | ItfError::InvalidPattern(_)
+            | ItfError::CorruptPattern(_)
+            | ItfError::UnsupportedFormatVersion { .. }
+            | ItfError::InvalidSignature(_)
+    )
 }
 
 impl PatternHandler {
-    pub fn read<P: AsRef<Path>>(&mut self, path: P, target_pattern: &str) {
-        let files = utils::list_files_of_type(path, "json");
+    /// Read the pattern files contained within a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    /// * `target_pattern` - If not empty, only pattern files whose path contains this string will be loaded.
+    ///   Patterns may also be selected by UUID or name, as described by [`PatternSelector`].
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the pattern files that were loaded, skipped or couldn't be loaded. An error will be
+    /// returned if a pattern file could not be read, or if no patterns were loaded.
+    ///
+    /// # Notes
+    ///
+    /// Pattern files compressed with gzip (`.json.gz`) or Zstandard (`.json.zst`) will be decompressed transparently.
+    /// If the directory has an up to date index, only the targeted pattern files will be parsed.
+    ///
+    /// A zip or tar archive of pattern files may be given in place of the directory, in which case the patterns are
+    /// read by [`PatternHandler::read_archive`].
+    pub fn read<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        target_pattern: &str,
+    ) -> Result<LoadReport, ItfError> {
+        self.read_with_hint(path, target_pattern, None)
+    }
+
+    /// Read the pattern files contained within a directory, optionally limited to the patterns for a specific extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    /// * `target_pattern` - If not empty, only pattern files whose path contains this string will be loaded.
+    ///   Patterns may also be selected by UUID or name, as described by [`PatternSelector`].
+    /// * `extension_hint` - If specified, only patterns that list this extension, or that don't list any
+    ///   extensions, will be loaded.
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the pattern files that were loaded, skipped or couldn't be loaded. An error will be
+    /// returned if a pattern file could not be read, or if no patterns were loaded.
+    ///
+    /// # Notes
+    ///
+    /// The pattern files are parsed in parallel, within the thread pool given by [`utils::set_thread_limit`].
+    /// A pattern file that can't be parsed doesn't prevent the other patterns from being loaded, and is
+    /// instead listed as a failure within the report.
+    ///
+    /// If the directory has an up to date index (see [`PatternIndex`]) then only the selected pattern files will be parsed.
+    /// Otherwise every pattern file must be parsed before the extension hint can be applied.
+    ///
+    /// An error will be returned if the directory doesn't exist. The built-in rules are only loaded when
+    /// requested explicitly, with [`PatternHandler::read_builtin`].
+    pub fn read_with_hint<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        target_pattern: &str,
+        extension_hint: Option<&str>,
+    ) -> Result<LoadReport, ItfError> {
+        let path = path.as_ref();
+
+        // An archive of pattern files may be given in place of a directory.
+        if ArchiveKind::from_path(path).is_some() && utils::file_exists(path) {
+            return self.read_archive(path, target_pattern);
+        }
+
+        if !utils::directory_exists(path) {
+            return Err(ItfError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the pattern directory '{}' doesn't exist", path.display()),
+            )));
+        }
+
+        self.load_steps.push(LoadStep::Directory {
+            path: path.to_path_buf(),
+            target_pattern: target_pattern.to_string(),
+            extension_hint: extension_hint.map(str::to_string),
+        });
+
+        let start = Instant::now();
+        let first = self.patterns.len();
+        let listed = Self::read_disabled_list(path)?;
+        let (files, indexed) = match PatternIndex::load(path)? {
+            Some(index) => (index.select(path, target_pattern, extension_hint), true),
+            None => (Self::list_pattern_files(path), false),
+        };
+
+        // Load every pattern, or the specific patterns if a target has been specified.
+        let target = PatternSelector::parse(target_pattern);
+        let mut report = LoadReport::default();
+        self.load_stats.files_scanned += files.len();
+        let (targeted, untargeted): (Vec<_>, Vec<_>) =
+            files.into_iter().partition(|f| target.could_select(f));
+        self.load_stats.files_parsed += targeted.len();
+        for f in untargeted {
+            report
+                .skipped
+                .push((f, format!("it doesn't match the target '{target_pattern}'")));
+        }
+
+        // Each pattern file is independent, and so they can be parsed in parallel. The order of the files is retained.
+        let parsed: Vec<_> = utils::in_thread_pool(|| {
+            targeted
+                .into_par_iter()
+                .map(|f| {
+                    let result = self.read_parse_pattern(&f);
+                    (f, result)
+                })
+                .collect()
+        });
+
+        for (f, result) in parsed {
+            let pattern = match result {
+                Ok(p) => p,
+                // Files that aren't valid patterns are skipped, but reported.
+                Err(e) if is_pattern_error(&e) => {
+                    report.failed.push((f, e.to_string()));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // Any UUID or name selectors can only be applied once the pattern has been parsed.
+            if !target.selects(&f, &pattern.type_data.uuid, &pattern.type_data.name) {
+                report
+                    .skipped
+                    .push((f, format!("it doesn't match the target '{target_pattern}'")));
+                continue;
+            }
+
+            match extension_hint {
+                Some(extension)
+                    if !indexed
+                        && !PatternIndex::lists_extension(
+                            &pattern.type_data.known_extensions,
+                            extension,
+                        ) =>
+                {
+                    report
+                        .skipped
+                        .push((f, format!("it doesn't list the extension '{extension}'")));
+                }
+                _ => {
+                    self.patterns.push(pattern);
+                    report.loaded.push(f);
+                }
+            }
+        }
+        self.retain_allowed_licenses(first, &mut report);
+        self.retain_enabled(first, &mut report, &listed);
+        self.retain_unique(first, &mut report)?;
+        self.load_report.append(&report);
+        self.load_stats.elapsed += start.elapsed();
+
+        if self.patterns.is_empty() {
+            return Err(ItfError::NoPatternsFound);
+        }
+
+        Ok(report)
+    }
+
+    /// Read the pattern files contained within a directory, deferring the parsing of the pattern data of each
+    /// pattern until it could match a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    /// * `target_pattern` - If not empty, only pattern files whose path contains this string will be loaded.
+    ///   Patterns may also be selected by UUID or name, as described by [`PatternSelector`].
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the pattern files that were loaded, skipped or couldn't be loaded. An error will be
+    /// returned if a pattern file could not be read, or if no patterns were loaded.
+    ///
+    /// # Notes
+    ///
+    /// Only the type data, license and anchored byte sequences of each pattern are parsed immediately. Every other
+    /// pattern is parsed in full once [`PatternHandler::candidates`] finds that it could match a file, and so
+    /// will be absent from [`PatternHandler::patterns`] until then. This greatly reduces the time taken to load a
+    /// large directory of patterns when only a few files are to be identified.
+    ///
+    /// Pattern files that aren't JSON documents in the current format are parsed in full immediately, as are
+    /// all of the patterns if signed patterns are required, so that their signatures are verified before use.
+    /// A deferred pattern that turns out to be invalid when it's parsed, such as one whose checksum doesn't
+    /// match, will never be a candidate.
+    pub fn read_lazy<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        target_pattern: &str,
+    ) -> Result<LoadReport, ItfError> {
+        if self.trusted_keys.is_some() {
+            return self.read(path, target_pattern);
+        }
+        self.load_steps.push(LoadStep::Lazy {
+            path: path.as_ref().to_path_buf(),
+            target_pattern: target_pattern.to_string(),
+        });
+
+        let start = Instant::now();
+        let listed = Self::read_disabled_list(path.as_ref())?;
+        let target = PatternSelector::parse(target_pattern);
+        let mut report = LoadReport::default();
+        let mut uuids: HashSet<_> = self.type_data().map(|t| t.uuid.clone()).collect();
+        for f in Self::list_pattern_files(path) {
+            self.load_stats.files_scanned += 1;
+            if !target.could_select(&f) {
+                report
+                    .skipped
+                    .push((f, format!("it doesn't match the target '{target_pattern}'")));
+                continue;
+            }
+
+            self.load_stats.files_parsed += 1;
+            let (deferred, pattern, license) = match DeferredPattern::from_file(&f) {
+                Some((deferred, license)) => (Some(deferred), None, license),
+                None => match self.read_parse_pattern(&f) {
+                    Ok(p) => {
+                        let license = p.other_data.license.clone();
+                        (None, Some(p), license)
+                    }
+                    Err(e) if is_pattern_error(&e) => {
+                        report.failed.push((f, e.to_string()));
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                },
+            };
+
+            let type_data = deferred
+                .as_ref()
+                .map(|d| &d.type_data)
+                .or(pattern.as_ref().map(|p| &p.type_data));
+            if let Some(type_data) = type_data {
+                if !target.selects(&f, &type_data.uuid, &type_data.name) {
+                    report
+                        .skipped
+                        .push((f, format!("it doesn't match the target '{target_pattern}'")));
+                    continue;
+                }
+                if let Some(reason) = self.license_skip_reason(&license) {
+                    report.skipped.push((f, reason));
+                    continue;
+                }
+                if let Some(reason) = self.disabled_skip_reason(type_data, &listed) {
+                    report.skipped.push((f, reason));
+                    continue;
+                }
+                if !self.admit_unique(type_data, &f, &mut uuids, &mut report)? {
+                    continue;
+                }
+            }
+
+            self.deferred.extend(deferred);
+            self.patterns.extend(pattern);
+            report.loaded.push(f);
+        }
+        self.load_report.append(&report);
+        self.load_stats.elapsed += start.elapsed();
+
+        if self.is_empty() {
+            return Err(ItfError::NoPatternsFound);
+        }
+
+        Ok(report)
+    }
 
-        // Load every pattern, or the specific pattern if a target has been specified.
-        for f in &files {
-            if target_pattern.is_empty() || f.contains(target_pattern) {
-                self.read_parse_pattern(f);
+    /// Read the pattern files contained within a zip or tar archive, without unpacking the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the archive. See [`ArchiveKind`] for the supported archives.
+    /// * `target_pattern` - If not empty, only pattern files whose path within the archive contains this string
+    ///   will be loaded.
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the pattern files that were loaded, skipped or couldn't be parsed, each given as its
+    /// path within the archive, appended to the path of the archive. An error will be returned if the archive or
+    /// a pattern file could not be read, or if no patterns were loaded.
+    ///
+    /// # Notes
+    ///
+    /// As with [`PatternHandler::read`], pattern files that can't be parsed are listed as failures within the report.
+    /// Detached signatures aren't read from an archive, and so the patterns within it must have embedded
+    /// signatures if signed patterns are required.
+    pub fn read_archive<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        target_pattern: &str,
+    ) -> Result<LoadReport, ItfError> {
+        let path = path.as_ref();
+        self.load_steps.push(LoadStep::Archive {
+            path: path.to_path_buf(),
+            target_pattern: target_pattern.to_string(),
+        });
+
+        let start = Instant::now();
+        let first = self.patterns.len();
+        let target = PatternSelector::parse(target_pattern);
+        let mut report = LoadReport::default();
+        let mut patterns = Vec::new();
+        let mut stats = LoadStats::default();
+        pattern_archive::for_each_pattern_file(path, |name, reader| {
+            stats.files_scanned += 1;
+            let source = path.join(name).to_string_lossy().to_string();
+            let untargeted = || {
+                (
+                    source.clone(),
+                    format!("it doesn't match the target '{target_pattern}'"),
+                )
+            };
+            if !target.could_select(name) {
+                report.skipped.push(untargeted());
+                return Ok(());
+            }
+
+            stats.files_parsed += 1;
+            let result = Pattern::from_reader(name, reader).and_then(|pattern| {
+                pattern.verify_checksum()?;
+                self.check_signature(&pattern)?;
+                Ok(pattern)
+            });
+            match result {
+                Ok(pattern)
+                    if !target.selects(name, &pattern.type_data.uuid, &pattern.type_data.name) =>
+                {
+                    report.skipped.push(untargeted());
+                }
+                Ok(pattern) => {
+                    patterns.push(pattern);
+                    report.loaded.push(source);
+                }
+                // Files that aren't valid patterns are skipped, but reported.
+                Err(e) if is_pattern_error(&e) => report.failed.push((source, e.to_string())),
+                Err(e) => return Err(e),
             }
+
+            Ok(())
+        })?;
+
+        self.patterns.extend(patterns);
+        self.retain_allowed_licenses(first, &mut report);
+        self.retain_enabled(first, &mut report, &[]);
+        self.retain_unique(first, &mut report)?;
+        self.load_report.append(&report);
+        stats.elapsed = start.elapsed();
+        self.load_stats.append(&stats);
+
+        if self.patterns.is_empty() {
+            return Err(ItfError::NoPatternsFound);
+        }
+
+        Ok(report)
+    }
+
+    /// Load the rules that are built into the library for the most common file formats.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_pattern` - If not empty, only rules whose name contains this string will be loaded.
+    ///
+    /// # Returns
+    ///
+    /// An error if no patterns were loaded.
+    ///
+    /// # Notes
+    ///
+    /// The built-in rules are part of the library itself, and so aren't subject to any signature requirement.
+    pub fn read_builtin(&mut self, target_pattern: &str) -> Result<(), ItfError> {
+        self.read_rules(EMBEDDED_RULES, target_pattern)
+    }
+
+    /// Load a set of rules that have been compiled into the program, such as those generated by
+    /// [`compile_rules`](crate::embedded_rule::compile_rules).
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - The rules.
+    /// * `target_pattern` - If not empty, only rules whose name contains this string will be loaded.
+    ///
+    /// # Returns
+    ///
+    /// An error if no patterns were loaded.
+    ///
+    /// # Notes
+    ///
+    /// As with [`PatternHandler::read_builtin`], the rules aren't subject to any signature requirement.
+    pub fn read_rules(
+        &mut self,
+        rules: &[EmbeddedRule],
+        target_pattern: &str,
+    ) -> Result<(), ItfError> {
+        let first = self.patterns.len();
+        self.push_rules(rules, target_pattern);
+        self.load_steps
+            .push(LoadStep::Rules(self.patterns[first..].to_vec()));
+
+        if self.patterns.is_empty() {
+            return Err(ItfError::NoPatternsFound);
         }
+
+        Ok(())
+    }
+
+    /// Add a set of compiled rules to the loaded patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - The rules.
+    /// * `target_pattern` - If not empty, only rules whose name contains this string will be added.
+    fn push_rules(&mut self, rules: &[EmbeddedRule], target_pattern: &str) {
+        let target = PatternSelector::parse(target_pattern);
+        self.patterns.extend(
+            rules
+                .iter()
+                .filter(|r| target.selects(r.name, r.uuid, r.name))
+                .map(EmbeddedRule::to_pattern),
+        );
+    }
+
+    /// Read the patterns contained within a pattern bundle.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the bundle file.
+    /// * `target_pattern` - If not empty, only patterns whose name contains this string will be loaded.
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the patterns that were loaded or skipped, identified by name. An error will be returned
+    /// if the bundle or one of its patterns could not be read, or if no patterns were loaded.
+    #[cfg(feature = "msgpack")]
+    pub fn read_bundle<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        target_pattern: &str,
+    ) -> Result<LoadReport, ItfError> {
+        self.load_steps.push(LoadStep::Bundle {
+            path: path.as_ref().to_path_buf(),
+            target_pattern: target_pattern.to_string(),
+        });
+
+        let bundle = PatternBundle::from_file(path)?;
+        self.push_bundle(&bundle, target_pattern)
+    }
+
+    /// Read the patterns contained within a pattern bundle that has been compiled into the program, such as one
+    /// included with [`include_bytes!`]. This allows a default set of patterns to be used without any access to
+    /// the filesystem.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes of the bundle, as written by [`PatternBundle::to_bytes`].
+    /// * `target_pattern` - If not empty, only patterns whose name contains this string will be loaded.
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the patterns that were loaded or skipped, identified by name. An error will be returned
+    /// if the bundle or one of its patterns could not be read, or if no patterns were loaded.
+    ///
+    /// # Notes
+    ///
+    /// Unlike the built-in rules loaded by [`PatternHandler::read_builtin`], the patterns are subject to any
+    /// signature requirement.
+    #[cfg(feature = "msgpack")]
+    pub fn read_embedded(
+        &mut self,
+        bytes: &'static [u8],
+        target_pattern: &str,
+    ) -> Result<LoadReport, ItfError> {
+        self.load_steps.push(LoadStep::Embedded {
+            bytes,
+            target_pattern: target_pattern.to_string(),
+        });
+
+        let bundle = PatternBundle::from_bytes(bytes)?;
+        self.push_bundle(&bundle, target_pattern)
+    }
+
+    /// Add the patterns contained within a pattern bundle to the loaded patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle` - The bundle.
+    /// * `target_pattern` - If not empty, only patterns whose name contains this string will be added.
+    #[cfg(feature = "msgpack")]
+    fn push_bundle(
+        &mut self,
+        bundle: &PatternBundle,
+        target_pattern: &str,
+    ) -> Result<LoadReport, ItfError> {
+        let start = Instant::now();
+        let first = self.patterns.len();
+
+        // Only the targeted patterns need to be decompressed.
+        let target = PatternSelector::parse(target_pattern);
+        let mut report = LoadReport::default();
+        self.load_stats.files_scanned += bundle.header.entries.len();
+        for entry in &bundle.header.entries {
+            if target.selects(&entry.name, &entry.uuid, &entry.name) {
+                self.load_stats.files_parsed += 1;
+                let result = bundle.read_pattern(entry).and_then(|pattern| {
+                    pattern.verify_checksum()?;
+                    self.check_signature(&pattern)?;
+                    Ok(pattern)
+                });
+                match result {
+                    Ok(pattern) => {
+                        self.patterns.push(pattern);
+                        report.loaded.push(entry.name.clone());
+                    }
+                    // Patterns that aren't valid are skipped, but reported.
+                    Err(e) if is_pattern_error(&e) => {
+                        report.failed.push((entry.name.clone(), e.to_string()));
+                    }
+                    Err(e) => return Err(e),
+                }
+            } else {
+                report.skipped.push((
+                    entry.name.clone(),
+                    format!("it doesn't match the target '{target_pattern}'"),
+                ));
+            }
+        }
+        self.retain_allowed_licenses(first, &mut report);
+        self.retain_enabled(first, &mut report, &[]);
+        self.retain_unique(first, &mut report)?;
+        self.load_report.append(&report);
+        self.load_stats.elapsed += start.elapsed();
+
+        if self.patterns.is_empty() {
+            return Err(ItfError::NoPatternsFound);
+        }
+
+        Ok(report)
+    }
+
+    /// Read the pattern files contained within a directory, using a precompiled cache where possible.
+    /// The cache will be regenerated if any pattern file has been added, removed or modified since it was written.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    /// * `cache_path` - The path to the cache file.
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the patterns that were loaded, skipped or couldn't be parsed. The patterns loaded from
+    /// the cache are identified by name. An error will be returned if the patterns could not be read, if no patterns
+    /// were loaded, or if the cache could not be written.
+    #[cfg(feature = "msgpack")]
+    pub fn read_cached<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        path: P,
+        cache_path: Q,
+    ) -> Result<LoadReport, ItfError> {
+        self.load_steps.push(LoadStep::Cached {
+            path: path.as_ref().to_path_buf(),
+            cache_path: cache_path.as_ref().to_path_buf(),
+        });
+        let steps = self.load_steps.len();
+
+        let start = Instant::now();
+        let first = self.patterns.len();
+        let mut report = if self.load_cache(&path, &cache_path)? {
+            // The cache is read as a single file. Otherwise, the read that regenerates the cache records its
+            // own statistics.
+            self.load_stats.files_parsed += 1;
+            self.load_stats.elapsed += start.elapsed();
+            LoadReport {
+                loaded: self.patterns[first..]
+                    .iter()
+                    .map(|p| p.type_data.name.clone())
+                    .collect(),
+                ..Default::default()
+            }
+        } else {
+            // The cache must contain every pattern, regardless of the licenses that are currently allowed and the
+            // patterns that are currently disabled. Only the patterns disabled by the directory itself are excluded.
+            // The read records its own report, which is replaced by the report of the allowed patterns below.
+            let allowed_licenses = self.allowed_licenses.take();
+            let disabled_patterns = std::mem::take(&mut self.disabled_patterns);
+            let recorded = self.load_report.clone();
+            let result = self.read(&path, "").and_then(|report| {
+                self.write_cache(&path, &cache_path)?;
+                Ok(report)
+            });
+            self.allowed_licenses = allowed_licenses;
+            self.disabled_patterns = disabled_patterns;
+            // The read that regenerated the cache is part of this step.
+            self.load_steps.truncate(steps);
+
+            let report = result?;
+            self.load_report = recorded;
+            report
+        };
+
+        // A pattern loaded before this read may have been replaced by a newer duplicate.
+        let first = self.patterns.len() - report.loaded.len();
+        self.retain_allowed_licenses(first, &mut report);
+        self.retain_enabled(first, &mut report, &[]);
+        self.retain_unique(first, &mut report)?;
+        self.load_report.append(&report);
+        if self.patterns.is_empty() {
+            return Err(ItfError::NoPatternsFound);
+        }
+
+        Ok(report)
+    }
+
+    /// Read the patterns from a set of layered sources, such as a system-wide bundle followed by a user's own
+    /// pattern directory. A pattern from a later source replaces any pattern from an earlier source, or any
+    /// pattern that was already loaded, that has the same UUID. This allows a pattern to be overridden locally
+    /// without modifying the source that it came from.
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - The sources, in order of increasing precedence.
+    /// * `target_pattern` - If not empty, only patterns whose path or name contains this string will be loaded.
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the patterns that were loaded, skipped or couldn't be parsed. Each overridden pattern is
+    /// listed as having been skipped. An error will be returned if a source could not be read, or if no patterns
+    /// were loaded.
+    ///
+    /// # Notes
+    ///
+    /// A directory that doesn't exist, or a source that contains no applicable patterns, is ignored so that an
+    /// optional layer (such as a user directory that hasn't been created yet) doesn't prevent the others from being
+    /// read. Patterns with the same UUID within a single source are handled according to the duplicate policy
+    /// (see [`PatternHandler::set_duplicate_policy`]).
+    pub fn read_layered(
+        &mut self,
+        sources: &[PatternSource],
+        target_pattern: &str,
+    ) -> Result<LoadReport, ItfError> {
+        self.load_steps.push(LoadStep::Layered {
+            sources: sources.to_vec(),
+            target_pattern: target_pattern.to_string(),
+        });
+
+        let mut report = LoadReport::default();
+        for source in sources {
+            let mut layer = PatternHandler {
+                trusted_keys: self.trusted_keys.clone(),
+                allowed_licenses: self.allowed_licenses.clone(),
+                disabled_patterns: self.disabled_patterns.clone(),
+                duplicate_policy: self.duplicate_policy,
+                ..Default::default()
+            };
+
+            let result = match source {
+                PatternSource::Directory(path)
+                    if !utils::directory_exists(path) && !utils::file_exists(path) =>
+                {
+                    continue;
+                }
+                PatternSource::Directory(path) => layer.read(path, target_pattern),
+                #[cfg(feature = "msgpack")]
+                PatternSource::Bundle(path) => layer.read_bundle(path, target_pattern),
+                #[cfg(feature = "msgpack")]
+                PatternSource::Embedded(bytes) => layer.read_embedded(bytes, target_pattern),
+                PatternSource::Builtin => layer
+                    .read_builtin(target_pattern)
+                    .map(|_| LoadReport::default()),
+            };
+            match result {
+                Ok(_) | Err(ItfError::NoPatternsFound) => {}
+                Err(e) => return Err(e),
+            }
+
+            report.append(&layer.load_report);
+            self.load_stats.append(&layer.load_stats);
+            self.push_layer(layer, &mut report);
+        }
+        self.load_report.append(&report);
+
+        if self.is_empty() {
+            return Err(ItfError::NoPatternsFound);
+        }
+
+        Ok(report)
+    }
+
+    /// Add the patterns of a layer to the loaded patterns, replacing any loaded patterns that have the same UUID.
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The handler into which the patterns of the layer were read.
+    /// * `report` - The report of the layered read. The sources of any replaced patterns are moved to the
+    ///   skipped sources.
+    fn push_layer(&mut self, layer: PatternHandler, report: &mut LoadReport) {
+        let overrides: HashMap<_, _> = layer
+            .type_data()
+            .map(|t| {
+                let source = layer
+                    .origins
+                    .get(&t.uuid)
+                    .map_or(&t.name, |(source, _)| source);
+                (t.uuid.clone(), source.clone())
+            })
+            .collect();
+
+        let overridden: Vec<_> = self
+            .type_data()
+            .filter(|t| overrides.contains_key(&t.uuid))
+            .map(|t| {
+                let source = self
+                    .origins
+                    .get(&t.uuid)
+                    .map_or(&t.name, |(source, _)| source);
+                (t.uuid.clone(), source.clone())
+            })
+            .collect();
+        if !overridden.is_empty() {
+            self.patterns
+                .retain(|p| !overrides.contains_key(&p.type_data.uuid));
+            self.deferred
+                .retain(|d| !overrides.contains_key(&d.type_data.uuid));
+            self.prefilter = None;
+        }
+        for (uuid, source) in overridden {
+            for loaded in [&mut report.loaded, &mut self.load_report.loaded] {
+                loaded.retain(|s| *s != source);
+            }
+            report.skipped.push((
+                source,
+                format!("it's overridden by the pattern '{}'", overrides[&uuid]),
+            ));
+        }
+
+        self.patterns.extend(layer.patterns);
+        self.deferred.extend(layer.deferred);
+        self.origins.extend(layer.origins);
+    }
+
+    /// Reload the patterns, repeating each read by which they were loaded so that any pattern files that have been
+    /// added, modified or removed since then take effect.
+    ///
+    /// # Returns
+    ///
+    /// A [`LoadReport`] of the reloaded patterns. An error will be returned if the patterns could not be read, or
+    /// if no patterns were loaded, in which case the previously loaded patterns are retained.
+    ///
+    /// # Notes
+    ///
+    /// Any restriction to a set of categories is also repeated, and the prefilter is rebuilt if it had been built.
+    /// Patterns that were added directly to [`PatternHandler::patterns`] are discarded, as are the deferred patterns
+    /// that had already been parsed.
+    pub fn reload(&mut self) -> Result<LoadReport, ItfError> {
+        let mut reloaded = PatternHandler {
+            trusted_keys: self.trusted_keys.clone(),
+            allowed_licenses: self.allowed_licenses.clone(),
+            disabled_patterns: self.disabled_patterns.clone(),
+            duplicate_policy: self.duplicate_policy,
+            ..Default::default()
+        };
+
+        for step in &self.load_steps {
+            match step {
+                LoadStep::Directory {
+                    path,
+                    target_pattern,
+                    extension_hint,
+                } => {
+                    reloaded.read_with_hint(path, target_pattern, extension_hint.as_deref())?;
+                }
+                LoadStep::Lazy {
+                    path,
+                    target_pattern,
+                } => {
+                    reloaded.read_lazy(path, target_pattern)?;
+                }
+                LoadStep::Archive {
+                    path,
+                    target_pattern,
+                } => {
+                    reloaded.read_archive(path, target_pattern)?;
+                }
+                #[cfg(feature = "msgpack")]
+                LoadStep::Bundle {
+                    path,
+                    target_pattern,
+                } => {
+                    reloaded.read_bundle(path, target_pattern)?;
+                }
+                #[cfg(feature = "msgpack")]
+                LoadStep::Embedded {
+                    bytes,
+                    target_pattern,
+                } => {
+                    reloaded.read_embedded(bytes, target_pattern)?;
+                }
+                #[cfg(feature = "msgpack")]
+                LoadStep::Cached { path, cache_path } => {
+                    reloaded.read_cached(path, cache_path)?;
+                }
+                LoadStep::Layered {
+                    sources,
+                    target_pattern,
+                } => {
+                    reloaded.read_layered(sources, target_pattern)?;
+                }
+                LoadStep::Rules(patterns) => {
+                    reloaded.patterns.extend_from_slice(patterns);
+                    reloaded.load_steps.push(step.clone());
+                }
+                LoadStep::RetainCategories(categories) => reloaded.retain_categories(categories),
+            }
+        }
+
+        if reloaded.is_empty() {
+            return Err(ItfError::NoPatternsFound);
+        }
+
+        let rebuild_prefilter = self.prefilter.is_some();
+        *self = reloaded;
+        if rebuild_prefilter {
+            self.build_prefilter();
+        }
+
+        Ok(self.load_report.clone())
+    }
+
+    /// Attempt to load the patterns from a precompiled cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files from which the cache was built.
+    /// * `cache_path` - The path to the cache file.
+    ///
+    /// # Returns
+    ///
+    /// True if the patterns were loaded from the cache, false if the cache is missing or out of date.
+    /// An error will be returned if the pattern directory or cache file could not be read.
+    ///
+    /// # Notes
+    ///
+    /// Every cached pattern is loaded, even if its license isn't allowed by [`PatternHandler::allow_licenses`].
+    #[cfg(feature = "msgpack")]
+    pub fn load_cache<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        path: P,
+        cache_path: Q,
+    ) -> Result<bool, ItfError> {
+        let bytes = match fs::read(cache_path) {
+            Ok(b) => b,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+
+        // A cache that can't be decoded will simply be regenerated.
+        let cache: PatternCache = match rmp_serde::from_slice(&bytes) {
+            Ok(c) => c,
+            Err(_) => return Ok(false),
+        };
+
+        if cache.version != CACHE_FORMAT_VERSION || cache.key != Self::pattern_cache_key(path)? {
+            return Ok(false);
+        }
+
+        for mut pattern in cache.patterns {
+            self.check_signature(&pattern)?;
+            pattern.compute_attributes();
+            self.patterns.push(pattern);
+        }
+
+        Ok(true)
+    }
+
+    /// Write the currently loaded patterns into a precompiled cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files from which the patterns were loaded.
+    /// * `cache_path` - The path to the cache file.
+    ///
+    /// # Returns
+    ///
+    /// An error if the pattern directory could not be read, or if the cache could not be written.
+    #[cfg(feature = "msgpack")]
+    pub fn write_cache<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        path: P,
+        cache_path: Q,
+    ) -> Result<(), ItfError> {
+        let cache = PatternCacheRef {
+            version: CACHE_FORMAT_VERSION,
+            key: Self::pattern_cache_key(path)?,
+            patterns: &self.patterns,
+        };
+
+        let bytes = rmp_serde::to_vec_named(&cache).map_err(io::Error::other)?;
+        fs::write(cache_path, bytes)?;
+
+        Ok(())
+    }
+
+    /// Build the prefilter used to shortlist candidate patterns.
+    /// This should be called again whenever the loaded patterns are modified.
+    pub fn build_prefilter(&mut self) {
+        let mut magic_index: HashMap<[u8; MAGIC_LENGTH], Vec<usize>> = HashMap::new();
+        let mut needles = Vec::new();
+        let mut automaton_patterns = Vec::new();
+        let mut unanchored_patterns = Vec::new();
+
+        for (i, pattern) in self.patterns.iter().enumerate() {
+            if let Some(magic) = Self::get_magic(pattern) {
+                magic_index.entry(magic).or_default().push(i);
+                continue;
+            }
+
+            // A group of alternatives at the start of the data gives one needle per alternative.
+            let alternatives = pattern
+                .data
+                .alternative_sequences
+                .iter()
+                .find(|g| !g.is_empty() && g.iter().all(|(start, _)| *start == 0));
+            match pattern.data.sequences.iter().find(|(start, _)| *start == 0) {
+                Some((_, sequence)) => {
+                    needles.push(&sequence[..]);
+                    automaton_patterns.push(i);
+                }
+                None => match alternatives {
+                    Some(group) => {
+                        for (_, sequence) in group {
+                            needles.push(sequence.as_slice());
+                            automaton_patterns.push(i);
+                        }
+                    }
+                    None => unanchored_patterns.push(i),
+                },
+            }
+        }
+
+        let max_sequence_length = needles.iter().map(|n| n.len()).max().unwrap_or(0);
+
+        self.prefilter = AhoCorasick::new(&needles)
+            .ok()
+            .map(|automaton| SequencePrefilter {
+                magic_index,
+                automaton,
+                automaton_patterns,
+                unanchored_patterns,
+                max_sequence_length,
+                pattern_count: self.patterns.len(),
+            });
+    }
+
+    /// Get the patterns that could potentially match a block of data.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - The header chunk of the data.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the candidate patterns. If the prefilter hasn't been built, or is out of date, then every pattern will be returned.
+    ///
+    /// # Notes
+    ///
+    /// Any deferred patterns (see [`PatternHandler::read_lazy`]) whose anchored byte sequences match the start of
+    /// the data will be parsed, if they haven't been already.
+    pub fn candidates(&self, chunk: &[u8]) -> Vec<&Pattern> {
+        let mut candidates = self.parsed_candidates(chunk);
+        candidates.extend(
+            self.deferred
+                .iter()
+                .filter(|d| d.could_match(chunk))
+                .filter_map(|d| d.pattern(self)),
+        );
+
+        candidates
+    }
+
+    /// Get the parsed patterns that could potentially match a block of data, using the prefilter.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - The header chunk of the data.
+    fn parsed_candidates(&self, chunk: &[u8]) -> Vec<&Pattern> {
+        let prefilter = match &self.prefilter {
+            Some(p) if p.pattern_count == self.patterns.len() => p,
+            _ => return self.patterns.iter().collect(),
+        };
+
+        let mut indices = prefilter.unanchored_patterns.clone();
+
+        if let Some(magic) = chunk.first_chunk::<MAGIC_LENGTH>() {
+            if let Some(magic_patterns) = prefilter.magic_index.get(magic) {
+                indices.extend_from_slice(magic_patterns);
+            }
+        }
+
+        // Every anchored sequence that matches the start of the data, including those that overlap.
+        // Only the start of the data needs to be searched.
+        let haystack = &chunk[..chunk.len().min(prefilter.max_sequence_length)];
+        indices.extend(
+            prefilter
+                .automaton
+                .find_overlapping_iter(haystack)
+                .filter(|m| m.start() == 0)
+                .map(|m| prefilter.automaton_patterns[m.pattern().as_usize()]),
+        );
+
+        indices.sort_unstable();
+        indices.dedup();
+        indices.iter().map(|&i| &self.patterns[i]).collect()
+    }
+
+    /// Compute the cache key for a pattern directory, derived from the path, size and modification
+    /// time of every pattern file within the directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    pub(crate) fn compute_cache_key<P: AsRef<Path>>(path: P) -> Result<u64, ItfError> {
+        let mut files = Self::list_pattern_files(path);
+        files.sort_unstable();
+
+        let mut hasher = DefaultHasher::new();
+        for file in &files {
+            let metadata = fs::metadata(file)?;
+            let modified = metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+
+            file.hash(&mut hasher);
+            metadata.len().hash(&mut hasher);
+            modified.hash(&mut hasher);
+        }
+
+        Ok(hasher.finish())
+    }
+
+    /// Compute the key of the pattern cache for a pattern directory. As the cache excludes the patterns that are
+    /// disabled by the directory, this is derived from the directory's disabled list as well as its pattern files.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    #[cfg(feature = "msgpack")]
+    fn pattern_cache_key<P: AsRef<Path>>(path: P) -> Result<u64, ItfError> {
+        let mut hasher = DefaultHasher::new();
+        Self::compute_cache_key(&path)?.hash(&mut hasher);
+        Self::read_disabled_list(path.as_ref())?.hash(&mut hasher);
+
+        Ok(hasher.finish())
+    }
+
+    /// Get the magic bytes for a pattern, if it has an anchored sequence that is long enough.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern.
+    ///
+    /// # Returns
+    ///
+    /// An option - none if the pattern can't be indexed, or the first bytes of its anchored sequence.
+    fn get_magic(pattern: &Pattern) -> Option<[u8; MAGIC_LENGTH]> {
+        let sequence_magic = pattern
+            .data
+            .sequences
+            .iter()
+            .find(|(start, _)| *start == 0)
+            .and_then(|(_, sequence)| sequence.first_chunk::<MAGIC_LENGTH>().copied());
+        if sequence_magic.is_some() {
+            return sequence_magic;
+        }
+
+        // Masked sequences may also be used, provided that none of the magic bytes are masked.
+        let (_, sequence) = pattern
+            .data
+            .masked_sequences
+            .iter()
+            .find(|(start, _)| *start == 0)?;
+        let mut magic = [0; MAGIC_LENGTH];
+        for (m, b) in magic.iter_mut().zip(sequence.get(..MAGIC_LENGTH)?) {
+            *m = (*b)?;
+        }
+
+        Some(magic)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.patterns.is_empty()
+        self.patterns.is_empty() && self.deferred.is_empty()
+    }
+
+    /// Get the combined report of every read of the patterns from a directory, bundle or cache.
+    ///
+    /// # Notes
+    ///
+    /// This includes reads that failed because no patterns were loaded, which makes it possible to find
+    /// out why none were.
+    pub fn load_report(&self) -> &LoadReport {
+        &self.load_report
+    }
+
+    /// Get the combined statistics of every read of the patterns, along with the counts of the loaded patterns.
+    ///
+    /// # Notes
+    ///
+    /// The time spent writing a cache isn't included, nor is the time spent parsing deferred patterns once they
+    /// are needed.
+    pub fn load_stats(&self) -> LoadStats {
+        LoadStats {
+            patterns_loaded: self.patterns.len() + self.deferred.len(),
+            patterns_skipped: self.load_report.skipped.len() + self.load_report.duplicates.len(),
+            patterns_failed: self.load_report.failed.len(),
+            sequence_count: self
+                .patterns
+                .iter()
+                .map(|p| {
+                    let data = &p.data;
+                    data.sequences.len()
+                        + data.ranged_sequences.len()
+                        + data
+                            .alternative_sequences
+                            .iter()
+                            .map(Vec::len)
+                            .sum::<usize>()
+                        + data.trailer_sequences.len()
+                        + data.masked_sequences.len()
+                })
+                .sum(),
+            string_count: self.patterns.iter().map(|p| p.data.strings.len()).sum(),
+            ..self.load_stats.clone()
+        }
+    }
+
+    /// Get the loaded patterns that belong to a specific category.
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - The category. This is compared without regard to case.
+    pub fn patterns_in_category<'a>(
+        &'a self,
+        category: &'a str,
+    ) -> impl Iterator<Item = &'a Pattern> + 'a {
+        let deferred = self
+            .deferred
+            .iter()
+            .filter(move |d| d.type_data.in_category(category))
+            .filter_map(|d| d.pattern(self));
+
+        self.patterns
+            .iter()
+            .filter(move |p| p.in_category(category))
+            .chain(deferred)
+    }
+
+    /// Get a loaded pattern by its UUID, parsing it if it has been deferred.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the pattern.
+    pub fn pattern_by_uuid(&self, uuid: &str) -> Option<&Pattern> {
+        match self.patterns.iter().find(|p| p.type_data.uuid == uuid) {
+            Some(p) => Some(p),
+            None => self
+                .deferred
+                .iter()
+                .find(|d| d.type_data.uuid == uuid)
+                .and_then(|d| d.pattern(self)),
+        }
+    }
+
+    /// Get a loaded pattern by its name, parsing it if it has been deferred.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the pattern. This is compared without regard to case.
+    ///
+    /// # Notes
+    ///
+    /// If several patterns have the same name, the first that was loaded will be returned.
+    pub fn pattern_by_name(&self, name: &str) -> Option<&Pattern> {
+        match self
+            .patterns
+            .iter()
+            .find(|p| p.type_data.name.eq_ignore_ascii_case(name))
+        {
+            Some(p) => Some(p),
+            None => self
+                .deferred
+                .iter()
+                .find(|d| d.type_data.name.eq_ignore_ascii_case(name))
+                .and_then(|d| d.pattern(self)),
+        }
+    }
+
+    /// Get the loaded patterns that list a specific extension, parsing any that have been deferred.
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - The extension, with or without a leading period. This is compared without regard to case.
+    ///
+    /// # Notes
+    ///
+    /// Unlike the selection made by a [`PatternIndex`], patterns that don't list any extensions aren't included.
+    pub fn patterns_by_extension<'a>(
+        &'a self,
+        extension: &'a str,
+    ) -> impl Iterator<Item = &'a Pattern> + 'a {
+        let extension = extension.trim_start_matches('.');
+        let lists_extension = move |t: &PatternTypeData| {
+            t.known_extensions
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(extension))
+        };
+
+        let deferred = self
+            .deferred
+            .iter()
+            .filter(move |d| lists_extension(&d.type_data))
+            .filter_map(|d| d.pattern(self));
+
+        self.patterns
+            .iter()
+            .filter(move |p| lists_extension(&p.type_data))
+            .chain(deferred)
+    }
+
+    /// Iterate over every loaded pattern, parsing any that have been deferred.
+    ///
+    /// # Notes
+    ///
+    /// A deferred pattern that can't be parsed will be omitted.
+    pub fn iter(&self) -> impl Iterator<Item = &Pattern> {
+        self.patterns
+            .iter()
+            .chain(self.deferred.iter().filter_map(|d| d.pattern(self)))
     }
 
-    fn read_parse_pattern(&mut self, path: &str) {
-        let mut file = File::open(path).expect("failed to read file");
+    /// Get the type data of every loaded pattern, including those that have been deferred.
+    fn type_data(&self) -> impl Iterator<Item = &PatternTypeData> {
+        self.patterns
+            .iter()
+            .map(|p| &p.type_data)
+            .chain(self.deferred.iter().map(|d| &d.type_data))
+    }
+
+    /// Get the lineage of a pattern within its format family.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the pattern.
+    ///
+    /// # Returns
+    ///
+    /// A vector containing the UUID of the pattern followed by the UUIDs of its loaded ancestors, ending
+    /// with the root of the family. A parent that hasn't been loaded ends the lineage, as does a cycle.
+    pub fn lineage<'a>(&'a self, uuid: &'a str) -> Vec<&'a str> {
+        let pattern_of = |uuid: &str| self.type_data().find(|t| t.uuid == uuid);
+
+        let mut lineage = vec![uuid];
+        while let Some(parent) =
+            pattern_of(lineage[lineage.len() - 1]).and_then(|t| t.parent_uuid.as_deref())
+        {
+            if pattern_of(parent).is_none() || lineage.contains(&parent) {
+                break;
+            }
+            lineage.push(parent);
+        }
+
+        lineage
+    }
+
+    /// Discard any loaded patterns that don't belong to at least one of a set of categories.
+    ///
+    /// # Arguments
+    ///
+    /// * `categories` - The categories. These are compared without regard to case.
+    ///
+    /// # Notes
+    ///
+    /// The prefilter will need to be rebuilt once the patterns have been discarded.
+    pub fn retain_categories<S: AsRef<str>>(&mut self, categories: &[S]) {
+        self.patterns
+            .retain(|p| categories.iter().any(|c| p.in_category(c.as_ref())));
+        self.load_steps.push(LoadStep::RetainCategories(
+            categories.iter().map(|c| c.as_ref().to_string()).collect(),
+        ));
+        self.deferred.retain(|d| {
+            categories
+                .iter()
+                .any(|c| d.type_data.in_category(c.as_ref()))
+        });
+        self.prefilter = None;
+    }
+
+    /// List the pattern files within a directory, including any compressed pattern files.
+    /// The index of the directory isn't a pattern file, and so is excluded.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    pub fn list_pattern_files<P: AsRef<Path>>(path: P) -> Vec<String> {
+        let file_types: Vec<_> = PatternCompression::PATTERN_FILE_EXTENSIONS
+            .iter()
+            .filter_map(|e| e.rsplit('.').next())
+            .collect();
+
+        utils::list_files_of_types(path, &file_types)
+            .into_iter()
+            .filter(|f| {
+                let f = f.to_lowercase();
+                PatternCompression::PATTERN_FILE_EXTENSIONS
+                    .iter()
+                    .any(|e| f.ends_with(e))
+                    && !f.ends_with(INDEX_FILE_NAME)
+            })
+            .collect()
+    }
+
+    /// Require every pattern that is subsequently loaded to be signed by one of a set of trusted keys.
+    /// A pattern that isn't signed by a trusted key will be rejected, and listed among the failures of the load report.
+    ///
+    /// # Arguments
+    ///
+    /// * `trusted_keys` - The public keys of the trusted signers.
+    pub fn require_signed(&mut self, trusted_keys: Vec<VerifyingKey>) {
+        self.trusted_keys = Some(trusted_keys);
+    }
+
+    /// Only load patterns that are distributed under one of a set of licenses. Any other pattern, including one
+    /// without a license, will be excluded when the patterns are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `licenses` - The allowed licenses. These are compared without regard to case.
+    ///
+    /// # Notes
+    ///
+    /// The built-in rules are part of the library itself, and so are always loaded.
+    pub fn allow_licenses(&mut self, licenses: Vec<String>) {
+        self.allowed_licenses = Some(licenses);
+    }
+
+    /// Don't load any pattern whose UUID or name is within a list, such as a pattern that is known to produce
+    /// false positives. This applies in addition to the disabled list of each pattern directory that is read
+    /// (see [`DISABLED_LIST_FILE_NAME`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The UUIDs or names of the patterns. These are compared without regard to case.
+    ///
+    /// # Notes
+    ///
+    /// The built-in rules are part of the library itself, and so are always loaded.
+    pub fn disable_patterns(&mut self, entries: Vec<String>) {
+        self.disabled_patterns = entries;
+    }
+
+    /// Read the disabled list of a pattern directory, if it has one.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    ///
+    /// # Returns
+    ///
+    /// The UUIDs or names of the disabled patterns, one per line of the list. Blank lines and lines starting
+    /// with `#` are ignored. An error will be returned if the list exists but could not be read.
+    pub fn read_disabled_list(path: &Path) -> Result<Vec<String>, ItfError> {
+        let contents = match fs::read_to_string(path.join(DISABLED_LIST_FILE_NAME)) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Discard any newly loaded patterns that have been disabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The index of the first newly loaded pattern. Any patterns before this are retained.
+    /// * `report` - The report of the newly loaded patterns, whose loaded sources are in the same order as the
+    ///   patterns. The sources of any discarded patterns are moved to the skipped sources.
+    /// * `listed` - The entries of the disabled list of the source of the patterns, if any.
+    fn retain_enabled(&mut self, first: usize, report: &mut LoadReport, listed: &[String]) {
+        if self.disabled_patterns.is_empty() && listed.is_empty() {
+            return;
+        }
+
+        let sources = std::mem::take(&mut report.loaded);
+        for (source, pattern) in sources.into_iter().zip(self.patterns.split_off(first)) {
+            match self.disabled_skip_reason(&pattern.type_data, listed) {
+                Some(reason) => report.skipped.push((source, reason)),
+                None => {
+                    self.patterns.push(pattern);
+                    report.loaded.push(source);
+                }
+            }
+        }
+    }
+
+    /// Get the reason that a pattern should be skipped because it has been disabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `type_data` - The type data of the pattern.
+    /// * `listed` - The entries of the disabled list of the source of the pattern, if any.
+    ///
+    /// # Returns
+    ///
+    /// None if the pattern hasn't been disabled, otherwise the reason that the pattern should be skipped.
+    fn disabled_skip_reason(
+        &self,
+        type_data: &PatternTypeData,
+        listed: &[String],
+    ) -> Option<String> {
+        self.disabled_patterns
+            .iter()
+            .chain(listed)
+            .any(|e| {
+                e.eq_ignore_ascii_case(&type_data.uuid) || e.eq_ignore_ascii_case(&type_data.name)
+            })
+            .then(|| "it has been disabled".to_string())
+    }
+
+    /// Discard any newly loaded patterns whose license isn't allowed, if the allowed licenses are restricted.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The index of the first newly loaded pattern. Any patterns before this are retained.
+    /// * `report` - The report of the newly loaded patterns, whose loaded sources are in the same order as the
+    ///   patterns. The sources of any discarded patterns are moved to the skipped sources.
+    fn retain_allowed_licenses(&mut self, first: usize, report: &mut LoadReport) {
+        if self.allowed_licenses.is_none() {
+            return;
+        }
+
+        let sources = std::mem::take(&mut report.loaded);
+        for (source, pattern) in sources.into_iter().zip(self.patterns.split_off(first)) {
+            match self.license_skip_reason(&pattern.other_data.license) {
+                Some(reason) => report.skipped.push((source, reason)),
+                None => {
+                    self.patterns.push(pattern);
+                    report.loaded.push(source);
+                }
+            }
+        }
+    }
+
+    /// Get the reason that a pattern should be skipped because of its license, if the allowed licenses are restricted.
+    ///
+    /// # Arguments
+    ///
+    /// * `license` - The license of the pattern.
+    ///
+    /// # Returns
+    ///
+    /// None if the license is allowed, otherwise the reason that the pattern should be skipped.
+    fn license_skip_reason(&self, license: &str) -> Option<String> {
+        let licenses = self.allowed_licenses.as_ref()?;
+        if licenses.iter().any(|l| l.eq_ignore_ascii_case(license)) {
+            None
+        } else {
+            Some(format!("its license ('{license}') isn't allowed"))
+        }
+    }
+
+    /// Set how a pattern that has the same UUID as a pattern that has already been loaded should be handled.
+    /// By default, the pattern that was loaded first is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy.
+    ///
+    /// # Notes
+    ///
+    /// Every discarded pattern is listed within the duplicates of the [`LoadReport`]. Patterns that were added
+    /// directly to [`PatternHandler::patterns`] aren't checked.
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Discard any newly loaded patterns that have the same UUID as another pattern, according to the duplicate policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The index of the first newly loaded pattern. Any patterns before this are retained, unless
+    ///   replaced by a newer duplicate.
+    /// * `report` - The report of the newly loaded patterns, whose loaded sources are in the same order as the
+    ///   patterns. The sources of any discarded patterns are moved to the duplicate sources.
+    fn retain_unique(&mut self, first: usize, report: &mut LoadReport) -> Result<(), ItfError> {
+        let mut uuids: HashSet<_> = self.patterns[..first]
+            .iter()
+            .map(|p| &p.type_data)
+            .chain(self.deferred.iter().map(|d| &d.type_data))
+            .map(|t| t.uuid.clone())
+            .collect();
+
+        let sources = std::mem::take(&mut report.loaded);
+        for (source, pattern) in sources.into_iter().zip(self.patterns.split_off(first)) {
+            if self.admit_unique(&pattern.type_data, &source, &mut uuids, report)? {
+                self.patterns.push(pattern);
+                report.loaded.push(source);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Determine whether a newly loaded pattern should be kept, according to the duplicate policy. If it replaces
+    /// a loaded pattern with the same UUID, then that pattern is discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `type_data` - The type data of the new pattern.
+    /// * `source` - The source of the new pattern.
+    /// * `uuids` - The UUIDs of the loaded patterns, to which the UUID of the new pattern will be added.
+    /// * `report` - The report of the newly loaded patterns, to which any duplicates will be added.
+    ///
+    /// # Returns
+    ///
+    /// True if the new pattern should be kept. An error will be returned if the pattern is a duplicate and
+    /// duplicates aren't allowed.
+    fn admit_unique(
+        &mut self,
+        type_data: &PatternTypeData,
+        source: &str,
+        uuids: &mut HashSet<String>,
+        report: &mut LoadReport,
+    ) -> Result<bool, ItfError> {
+        let uuid = &type_data.uuid;
+        let modified = fs::metadata(source).and_then(|m| m.modified()).ok();
+        if uuids.insert(uuid.clone()) {
+            self.origins
+                .insert(uuid.clone(), (source.to_string(), modified));
+            return Ok(true);
+        }
+
+        // A pattern that wasn't loaded from a source, such as an embedded rule, is identified by its name.
+        let (existing, existing_modified) = match self.origins.get(uuid) {
+            Some(origin) => origin.clone(),
+            None => match self.type_data().find(|t| t.uuid == *uuid) {
+                Some(t) => (t.name.clone(), None),
+                None => (String::new(), None),
+            },
+        };
+
+        match self.duplicate_policy {
+            DuplicatePolicy::Error => Err(ItfError::DuplicateUuid {
+                uuid: uuid.clone(),
+                first: existing,
+                second: source.to_string(),
+            }),
+            DuplicatePolicy::KeepNewest if modified >= existing_modified => {
+                self.patterns.retain(|p| p.type_data.uuid != *uuid);
+                self.deferred.retain(|d| d.type_data.uuid != *uuid);
+                self.prefilter = None;
+
+                // The replaced pattern may have been loaded by this read, or by an earlier one.
+                for loaded in [&mut report.loaded, &mut self.load_report.loaded] {
+                    loaded.retain(|s| *s != existing);
+                }
+                report.duplicates.push((
+                    existing,
+                    format!("it has the same UUID as the newer pattern '{source}'"),
+                ));
+                self.origins
+                    .insert(uuid.clone(), (source.to_string(), modified));
+                Ok(true)
+            }
+            _ => {
+                report.duplicates.push((
+                    source.to_string(),
+                    format!("it has the same UUID as the pattern '{existing}'"),
+                ));
+                Ok(false)
+            }
+        }
+    }
+
+    /// Check that a pattern is signed by a trusted key, if signed patterns are required.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern.
+    fn check_signature(&self, pattern: &Pattern) -> Result<(), ItfError> {
+        match &self.trusted_keys {
+            Some(keys) => pattern_signing::verify(pattern, keys),
+            None => Ok(()),
+        }
+    }
+
+    fn read_parse_pattern(&self, path: &str) -> Result<Pattern, ItfError> {
+        let mut pattern = Pattern::from_file(path)?;
+        pattern.verify_checksum()?;
+
+        // An embedded signature takes precedence over a detached one.
+        if pattern.signature.is_none() {
+            pattern.signature = pattern_signing::read_detached_signature(path)?;
+        }
+        self.check_signature(&pattern)?;
+
+        Ok(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests_pattern_handler {
+    use std::{
+        env, fs,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use rayon::ThreadPoolBuilder;
+
+    use crate::{
+        error::ItfError,
+        interner::{intern_all, intern_sequences},
+        pattern::{Pattern, PatternCompression},
+        pattern_signing,
+    };
+
+    use super::{DuplicatePolicy, PatternHandler, PatternSource};
+
+    #[cfg(feature = "msgpack")]
+    use crate::{pattern_bundle::PatternBundle, test_utils};
+
+    #[cfg(feature = "msgpack")]
+    use super::DISABLED_LIST_FILE_NAME;
+
+    fn make_pattern(name: &str, sequences: Vec<(usize, Vec<u8>)>) -> Pattern {
+        let mut pattern = Pattern::new(name, "test", vec![], vec![]);
+        pattern.data.sequences = intern_sequences(sequences);
+        pattern
+    }
+
+    #[test]
+    fn test_prefilter_candidates() {
+        let mut handler = PatternHandler {
+            patterns: vec![
+                make_pattern("a", vec![(0, b"ABC".to_vec())]),
+                make_pattern("ab", vec![(0, b"AB".to_vec())]),
+                make_pattern("x", vec![(0, b"XYZ".to_vec())]),
+                make_pattern("unanchored", vec![(4, b"XYZ".to_vec())]),
+            ],
+            ..Default::default()
+        };
+        handler.build_prefilter();
+
+        let names: Vec<_> = handler
+            .candidates(b"ABCDEF")
+            .iter()
+            .map(|p| p.type_data.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "ab", "unanchored"]);
+
+        // Sequences must be anchored to the start of the data.
+        let names: Vec<_> = handler
+            .candidates(b"..XYZ")
+            .iter()
+            .map(|p| p.type_data.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["unanchored"]);
+    }
+
+    #[test]
+    fn test_prefilter_magic_index() {
+        let mut masked = make_pattern("masked", vec![]);
+        masked.data.masked_sequences = vec![(
+            0,
+            vec![
+                Some(b'R'),
+                Some(b'I'),
+                Some(b'F'),
+                Some(b'F'),
+                None,
+                Some(b'W'),
+            ],
+        )];
+
+        let mut handler = PatternHandler {
+            patterns: vec![
+                make_pattern("long", vec![(0, b"RIFFABCD".to_vec())]),
+                make_pattern("other", vec![(0, b"PK\x03\x04".to_vec())]),
+                masked,
+            ],
+            ..Default::default()
+        };
+        handler.build_prefilter();
+
+        let names: Vec<_> = handler
+            .candidates(b"RIFF.W..")
+            .iter()
+            .map(|p| p.type_data.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["long", "masked"]);
+
+        // Data shorter than the magic can't match any of the indexed patterns.
+        assert!(handler.candidates(b"RIF").is_empty());
+    }
+
+    #[test]
+    fn test_prefilter_out_of_date() {
+        let mut handler = PatternHandler {
+            patterns: vec![make_pattern("a", vec![(0, b"ABC".to_vec())])],
+            ..Default::default()
+        };
+        handler.build_prefilter();
+
+        // Modifying the patterns without rebuilding the prefilter will fall back to every pattern.
+        handler
+            .patterns
+            .push(make_pattern("x", vec![(0, b"XYZ".to_vec())]));
+        assert_eq!(handler.candidates(b"ABC").len(), 2);
+    }
 
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .expect("failed to read file");
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_pattern_cache() {
+        let test_dir = test_utils::test_path_builder("cache", "1");
+        let cache_path = env::temp_dir().join(format!("itf-test-{}.cache", std::process::id()));
+        _ = fs::remove_file(&cache_path);
 
-        if let Ok(mut p) = Pattern::from_simd_json_str(&contents) {
-            p.compute_attributes();
-            self.patterns.push(p);
+        // The first read will build the cache.
+        let mut handler = PatternHandler::default();
+        handler
+            .read_cached(&test_dir, &cache_path)
+            .expect("failed to read patterns");
+        assert!(cache_path.exists());
+
+        // The second read should be served from the cache.
+        let mut cached_handler = PatternHandler::default();
+        let loaded = cached_handler
+            .load_cache(&test_dir, &cache_path)
+            .expect("failed to read cache");
+        _ = fs::remove_file(&cache_path);
+
+        assert!(loaded);
+        assert_eq!(cached_handler.patterns.len(), handler.patterns.len());
+        assert_eq!(
+            cached_handler.patterns[0].type_data.uuid,
+            handler.patterns[0].type_data.uuid
+        );
+        assert_eq!(
+            cached_handler.patterns[0].max_points,
+            handler.patterns[0].max_points
+        );
+    }
+    #[test]
+    fn test_read_compressed() {
+        let test_dir = env::temp_dir().join(format!("itf-test-compressed-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+        let dir = test_dir.to_str().unwrap();
+
+        let mut paths = Vec::new();
+        for (name, compression) in [
+            ("plain", PatternCompression::None),
+            ("gzip", PatternCompression::Gzip),
+            ("zstd", PatternCompression::Zstd),
+        ] {
+            let pattern = make_pattern(name, vec![(0, name.as_bytes().to_vec())]);
+            paths.push(
+                pattern
+                    .write(dir, compression)
+                    .expect("failed to write pattern"),
+            );
         }
+        assert!(paths[1].to_string_lossy().ends_with(".json.gz"));
+        assert!(paths[2].to_string_lossy().ends_with(".json.zst"));
+
+        // Other compressed files aren't pattern files.
+        fs::write(test_dir.join("other.txt.gz"), b"not a pattern").unwrap();
+
+        let mut handler = PatternHandler::default();
+        let result = handler.read(&test_dir, "");
+        _ = fs::remove_dir_all(&test_dir);
+        result.expect("failed to read patterns");
+
+        let mut names: Vec<_> = handler
+            .patterns
+            .iter()
+            .map(|p| p.type_data.name.as_str())
+            .collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["gzip", "plain", "zstd"]);
+        assert!(handler.patterns.iter().all(|p| p.max_points > 0));
+    }
+
+    #[test]
+    fn test_read_authored() {
+        let test_dir = env::temp_dir().join(format!("itf-test-authored-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        let toml = r#"
+# Comments are permitted within hand-authored patterns.
+[td]
+name = "toml"
+uuid = "11111111-1111-1111-1111-111111111111"
+extensions = ["TOML"]
+
+[pd]
+sequences = [[0, "89 50 4E 47"]]
+masked_sequences = [[8, "FF ?? D8"]]
+strings = ["IHDR"]
+average_entropy = 0.0
+
+[od]
+total_scanned_files = 0
+
+[sd]
+scanned_by = ""
+scanned_by_email = ""
+scanned_on = ""
+"#;
+        let yaml = r#"
+td:
+  name: yaml
+  uuid: 22222222-2222-2222-2222-222222222222
+pd:
+  sequences: [[0, "4D 5A"]]
+  strings: []
+  average_entropy: 0.0
+od:
+  total_scanned_files: 0
+sd:
+  scanned_by: ""
+  scanned_by_email: ""
+  scanned_on: ""
+"#;
+        fs::write(test_dir.join("toml.toml"), toml).unwrap();
+        fs::write(test_dir.join("yaml.yml"), yaml).unwrap();
+
+        let mut handler = PatternHandler::default();
+        let result = handler.read(&test_dir, "");
+
+        // Patterns may also be written in the hand-authored formats.
+        let written = test_dir.join("written.yaml");
+        let round_trip = handler.patterns[0]
+            .write_file(&written)
+            .and_then(|_| Pattern::from_file(&written));
+        _ = fs::remove_dir_all(&test_dir);
+        result.expect("failed to read patterns");
+
+        let toml = &handler.patterns[0];
+        assert_eq!(toml.type_data.name, "toml");
+        assert_eq!(
+            toml.data.sequences,
+            intern_sequences(vec![(0, vec![0x89, 0x50, 0x4E, 0x47])])
+        );
+        assert_eq!(
+            toml.data.masked_sequences,
+            vec![(8, vec![Some(0xFF), None, Some(0xD8)])]
+        );
+        assert_eq!(
+            handler.patterns[1].data.sequences,
+            intern_sequences(vec![(0, vec![0x4D, 0x5A])])
+        );
+
+        let round_trip = round_trip.expect("failed to round trip pattern");
+        assert_eq!(round_trip.data.masked_sequences, toml.data.masked_sequences);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_read_msgpack() {
+        let test_dir = env::temp_dir().join(format!("itf-test-msgpack-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        let sequences = (0..64).map(|i| (i * 16, vec![0xAB; 16])).collect();
+        let pattern = make_pattern("msgpack", sequences);
+        let encoded = pattern.to_msgpack().expect("failed to encode pattern");
+        let json = serde_json::to_vec(&pattern).unwrap();
+
+        let path = test_dir.join("msgpack.msgpack.zst");
+        pattern.write_file(&path).expect("failed to write pattern");
+
+        let mut handler = PatternHandler::default();
+        let result = handler.read(&test_dir, "");
+        _ = fs::remove_dir_all(&test_dir);
+        result.expect("failed to read patterns");
+
+        // Byte sequences are more compact than within JSON.
+        assert!(encoded.len() < json.len());
+        let decoded = Pattern::from_msgpack(&encoded).unwrap();
+        assert_eq!(decoded.data.sequences, pattern.data.sequences);
+
+        assert_eq!(handler.patterns.len(), 1);
+        assert_eq!(handler.patterns[0].data.sequences, pattern.data.sequences);
+    }
+
+    #[test]
+    fn test_read_signed() {
+        let test_dir = env::temp_dir().join(format!("itf-test-signed-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+        let dir = test_dir.to_str().unwrap();
+        let key = pattern_signing::generate_key();
+
+        // One pattern has an embedded signature, and the other a detached signature.
+        let mut embedded = make_pattern("embedded", vec![(0, b"EMB".to_vec())]);
+        embedded.signature = Some(pattern_signing::sign(&embedded, &key).unwrap());
+        embedded.write(dir, PatternCompression::None).unwrap();
+
+        let detached = make_pattern("detached", vec![(0, b"DET".to_vec())]);
+        let path = detached.write(dir, PatternCompression::Gzip).unwrap();
+        let signature = pattern_signing::sign(&detached, &key).unwrap();
+        fs::write(
+            pattern_signing::detached_signature_path(&path),
+            serde_json::to_string(&signature).unwrap(),
+        )
+        .unwrap();
+
+        let mut handler = PatternHandler::default();
+        handler.require_signed(vec![key.verifying_key()]);
+        let signed_result = handler.read(&test_dir, "");
+
+        // An unsigned pattern is rejected, but the signed patterns are still loaded.
+        make_pattern("unsigned", vec![(0, b"UNS".to_vec())])
+            .write(dir, PatternCompression::None)
+            .unwrap();
+        let mut unsigned_handler = PatternHandler::default();
+        unsigned_handler.require_signed(vec![key.verifying_key()]);
+        let unsigned_result = unsigned_handler.read(&test_dir, "");
+
+        // Without requiring signatures, every pattern is loaded.
+        let mut unchecked_handler = PatternHandler::default();
+        let unchecked_result = unchecked_handler.read(&test_dir, "");
+        _ = fs::remove_dir_all(&test_dir);
+
+        signed_result.expect("failed to read signed patterns");
+        assert_eq!(handler.patterns.len(), 2);
+        let unsigned_report = unsigned_result.expect("failed to read signed patterns");
+        assert_eq!(unsigned_report.loaded.len(), 2);
+        assert_eq!(unsigned_report.failed.len(), 1);
+        assert!(unsigned_report.failed[0].0.ends_with("unsigned.json"));
+        assert!(unsigned_report.failed[0]
+            .1
+            .starts_with("signature verification failed"));
+        assert_eq!(unsigned_handler.patterns.len(), 2);
+        unchecked_result.expect("failed to read patterns");
+        assert_eq!(unchecked_handler.patterns.len(), 3);
+    }
+
+    #[test]
+    fn test_categories() {
+        let mut handler = PatternHandler::default();
+        for (name, categories) in [
+            ("mkv", vec!["video"]),
+            ("zip", vec!["archive"]),
+            ("raw", vec![]),
+        ] {
+            let mut pattern = make_pattern(name, vec![(0, name.as_bytes().to_vec())]);
+            pattern.type_data.categories = intern_all(&categories);
+            handler.patterns.push(pattern);
+        }
+
+        let names: Vec<_> = handler
+            .patterns_in_category("VIDEO")
+            .map(|p| p.type_data.name.as_str())
+            .collect();
+        assert_eq!(names, ["mkv"]);
+
+        handler.retain_categories(&["video", "archive"]);
+        assert_eq!(handler.patterns.len(), 2);
+
+        // The built-in rules are also categorized.
+        let mut embedded = PatternHandler::default();
+        embedded.read_builtin("").unwrap();
+        embedded.retain_categories(&["archive"]);
+        assert!(embedded.patterns.iter().any(|p| p.type_data.name == "ZIP"));
+        assert!(!embedded.patterns.iter().any(|p| p.type_data.name == "PNG"));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_allowed_licenses() {
+        let test_dir = env::temp_dir().join(format!("itf-test-licenses-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+        let dir = test_dir.to_str().unwrap();
+        let cache_path = test_dir.join("patterns.cache");
+
+        for (name, license) in [
+            ("mit", "MIT"),
+            ("proprietary", "LicenseRef-Proprietary"),
+            ("unlicensed", ""),
+        ] {
+            let mut pattern = make_pattern(name, vec![(0, name.as_bytes().to_vec())]);
+            pattern.other_data.license = license.to_string();
+            pattern.write(dir, PatternCompression::None).unwrap();
+        }
+
+        let mut handler = PatternHandler::default();
+        handler.allow_licenses(vec!["mit".to_string(), "Apache-2.0".to_string()]);
+        let read_result = handler.read(&test_dir, "");
+
+        // The cache must still contain every pattern, even though only the allowed patterns are loaded.
+        let mut cached_handler = PatternHandler::default();
+        cached_handler.allow_licenses(vec!["MIT".to_string()]);
+        let cached_result = cached_handler.read_cached(&test_dir, &cache_path);
+        let mut unrestricted_handler = PatternHandler::default();
+        let loaded = unrestricted_handler.load_cache(&test_dir, &cache_path);
+
+        // No patterns are loaded if none have an allowed license.
+        let mut empty_handler = PatternHandler::default();
+        empty_handler.allow_licenses(vec!["GPL-3.0-only".to_string()]);
+        let empty_result = empty_handler.read(&test_dir, "");
+        _ = fs::remove_dir_all(&test_dir);
+
+        read_result.expect("failed to read patterns");
+        assert_eq!(handler.patterns.len(), 1);
+        assert_eq!(handler.patterns[0].type_data.name, "mit");
+
+        cached_result.expect("failed to read patterns");
+        assert_eq!(cached_handler.patterns.len(), 1);
+        assert!(loaded.unwrap());
+        assert_eq!(unrestricted_handler.patterns.len(), 3);
+
+        assert!(matches!(empty_result, Err(ItfError::NoPatternsFound)));
+    }
+
+    #[test]
+    fn test_read_checksum() {
+        let test_dir = env::temp_dir().join(format!("itf-test-checksum-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        // The checksum must survive a round trip through each of the formats.
+        let mut pattern = make_pattern("checksum", vec![(0, b"SUM".to_vec())]);
+        pattern.data.strings.insert("FIRST".to_string());
+        pattern.data.strings.insert("SECOND".to_string());
+        pattern.data.average_entropy = 4.123_456_7;
+        for file in ["checksum.json", "checksum.toml", "checksum.yaml"] {
+            pattern.write_file(test_dir.join(file)).unwrap();
+        }
+        let mut handler = PatternHandler::default();
+        let read_result = handler.read(&test_dir, "");
+
+        // A hand-edited pattern no longer matches its checksum.
+        let json_path = test_dir.join("checksum.json");
+        let edited = fs::read_to_string(&json_path)
+            .unwrap()
+            .replace("[83,85,77]", "[83,85,78]");
+        fs::write(&json_path, edited).unwrap();
+        let corrupt_result = PatternHandler::default().read(&test_dir, "checksum.json");
+
+        // Rewriting the pattern updates its checksum.
+        let mut rewritten = Pattern::from_file(&json_path).unwrap();
+        rewritten.write_file(&json_path).unwrap();
+        let rewritten_result = PatternHandler::default().read(&test_dir, "checksum.json");
+        _ = fs::remove_dir_all(&test_dir);
+
+        // The files hold the same pattern, and so only the first is kept.
+        let report = read_result.expect("failed to read patterns");
+        assert_eq!(report.loaded.len(), 1);
+        assert_eq!(report.duplicates.len(), 2);
+        assert!(handler.patterns.iter().all(|p| p.checksum.is_some()));
+        assert!(matches!(corrupt_result, Err(ItfError::NoPatternsFound)));
+        assert!(rewritten_result.is_ok());
+        rewritten.checksum = None;
+        assert!(rewritten.verify_checksum().is_ok());
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let test_dir = env::temp_dir().join(format!("itf-test-invalid-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        make_pattern("first", vec![(0, b"FIRST".to_vec())])
+            .write_file(test_dir.join("first.json"))
+            .unwrap();
+        make_pattern("second", vec![(0, b"SECOND".to_vec())])
+            .write_file(test_dir.join("second.json"))
+            .unwrap();
+        make_pattern("corrupt", vec![(0, b"SUM".to_vec())])
+            .write_file(test_dir.join("corrupt.json"))
+            .unwrap();
+        let mut future = make_pattern("future", vec![(0, b"FUTURE".to_vec())]);
+        future.format_version = u32::MAX;
+        future.write_file(test_dir.join("future.json")).unwrap();
+
+        // A hand-edited pattern no longer matches its checksum.
+        let corrupt_path = test_dir.join("corrupt.json");
+        let edited = fs::read_to_string(&corrupt_path)
+            .unwrap()
+            .replace("[83,85,77]", "[83,85,78]");
+        fs::write(&corrupt_path, edited).unwrap();
+
+        let mut handler = PatternHandler::default();
+        let read_result = handler.read(&test_dir, "");
+        let mut lazy_handler = PatternHandler::default();
+        let lazy_result = lazy_handler.read_lazy(&test_dir, "");
+        _ = fs::remove_dir_all(&test_dir);
+
+        // The invalid patterns are reported, but don't prevent the others from being loaded.
+        let report = read_result.expect("failed to read patterns");
+        assert_eq!(report.loaded.len(), 2);
+        let failed: Vec<_> = report.failed.iter().map(|(f, e)| (f.as_str(), e)).collect();
+        assert_eq!(failed.len(), 2);
+        assert!(failed[0].0.ends_with("corrupt.json"));
+        assert!(failed[0].1.starts_with("corrupt pattern"));
+        assert!(failed[1].0.ends_with("future.json"));
+        assert!(failed[1].1.starts_with("pattern format version"));
+
+        // A deferred pattern isn't verified until it's first parsed, but the newer format can't be deferred.
+        let lazy_report = lazy_result.expect("failed to read patterns");
+        assert_eq!(lazy_report.loaded.len(), 3);
+        assert_eq!(lazy_report.failed.len(), 1);
+        assert!(lazy_report.failed[0].0.ends_with("future.json"));
+
+        let names: Vec<_> = handler.iter().map(|p| p.type_data.name.clone()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut zip = make_pattern("ZIP", vec![(0, b"PK".to_vec())]);
+        zip.type_data.known_extensions = vec!["ZIP".into(), "JAR".into()];
+        let mut jar = make_pattern("Java Archive", vec![(0, b"PK".to_vec())]);
+        jar.type_data.known_extensions = vec!["JAR".into()];
+        let handler = PatternHandler {
+            patterns: vec![zip.clone(), jar, make_pattern("unlisted", vec![])],
+            ..Default::default()
+        };
+
+        assert_eq!(handler.iter().count(), 3);
+        assert_eq!(
+            handler
+                .pattern_by_uuid(&zip.type_data.uuid)
+                .unwrap()
+                .type_data
+                .name,
+            "ZIP"
+        );
+        assert!(handler.pattern_by_uuid("missing").is_none());
+        assert_eq!(
+            handler
+                .pattern_by_name("java archive")
+                .unwrap()
+                .type_data
+                .name,
+            "Java Archive"
+        );
+        assert_eq!(handler.patterns_by_extension(".jar").count(), 2);
+        assert_eq!(handler.patterns_by_extension("zip").count(), 1);
+        assert_eq!(handler.patterns_by_extension("exe").count(), 0);
+    }
+
+    #[test]
+    fn test_reload() {
+        let test_dir = env::temp_dir().join(format!("itf-test-reload-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+        let dir = test_dir.to_str().unwrap();
+
+        let mut first = make_pattern("first", vec![(0, b"FIRST".to_vec())]);
+        first.type_data.categories = vec!["archive".into()];
+        first.write(dir, PatternCompression::None).unwrap();
+
+        let mut handler = PatternHandler::default();
+        handler
+            .read(&test_dir, "")
+            .expect("failed to read patterns");
+        handler.retain_categories(&["archive"]);
+        handler.build_prefilter();
+
+        // Added patterns take effect, subject to the same category restriction.
+        let mut second = make_pattern("second", vec![(0, b"SECOND".to_vec())]);
+        second.type_data.categories = vec!["archive".into()];
+        second.write(dir, PatternCompression::None).unwrap();
+        make_pattern("third", vec![(0, b"THIRD".to_vec())])
+            .write(dir, PatternCompression::None)
+            .unwrap();
+        let reloaded = handler.reload();
+        let candidates: Vec<_> = handler
+            .candidates(b"SECOND")
+            .iter()
+            .map(|p| p.type_data.name.clone())
+            .collect();
+
+        // The previous patterns are retained if the reload fails.
+        for file in PatternHandler::list_pattern_files(&test_dir) {
+            fs::remove_file(file).unwrap();
+        }
+        let failed = handler.reload();
+        _ = fs::remove_dir_all(&test_dir);
+
+        assert_eq!(reloaded.expect("failed to reload patterns").loaded.len(), 3);
+        assert_eq!(handler.patterns.len(), 2);
+        assert_eq!(candidates, vec!["second"]);
+        assert!(matches!(failed, Err(ItfError::NoPatternsFound)));
+        assert_eq!(handler.patterns.len(), 2);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_read_embedded() {
+        let patterns: Vec<_> = ["first", "second"]
+            .iter()
+            .map(|name| make_pattern(name, vec![(0, name.as_bytes().to_vec())]))
+            .collect();
+        let bytes = PatternBundle::new(&patterns).unwrap().to_bytes().unwrap();
+        let bytes: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+
+        let mut handler = PatternHandler::default();
+        let report = handler
+            .read_embedded(bytes, "second")
+            .expect("failed to read embedded bundle");
+        assert_eq!(report.loaded, vec!["second".to_string()]);
+        assert_eq!(report.skipped.len(), 1);
+
+        let reloaded = handler.reload().expect("failed to reload patterns");
+        assert_eq!(reloaded.loaded, vec!["second".to_string()]);
+        assert_eq!(handler.patterns.len(), 1);
+
+        assert!(matches!(
+            PatternHandler::default().read_embedded(b"not a bundle", ""),
+            Err(ItfError::InvalidBundle(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_uuids() {
+        let test_dir = env::temp_dir().join(format!("itf-test-duplicates-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        // The copy has the same UUID as the original, but was modified more recently.
+        let original = make_pattern("original", vec![(0, b"ORIGINAL".to_vec())]);
+        let mut copy = original.clone();
+        copy.type_data.name = "copy".to_string();
+        original.write_file(test_dir.join("original.json")).unwrap();
+        copy.write_file(test_dir.join("copy.json")).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(test_dir.join("original.json"))
+            .and_then(|f| f.set_modified(UNIX_EPOCH + Duration::from_secs(60)))
+            .unwrap();
+
+        let read_with = |policy, lazy| {
+            let mut handler = PatternHandler::default();
+            handler.set_duplicate_policy(policy);
+            let result = if lazy {
+                handler.read_lazy(&test_dir, "")
+            } else {
+                handler.read(&test_dir, "")
+            };
+            let names: Vec<_> = handler.iter().map(|p| p.type_data.name.clone()).collect();
+            (result, names)
+        };
+        let (first, first_names) = read_with(DuplicatePolicy::KeepFirst, false);
+        let (newest, newest_names) = read_with(DuplicatePolicy::KeepNewest, false);
+        let (_, lazy_names) = read_with(DuplicatePolicy::KeepNewest, true);
+        let (error, _) = read_with(DuplicatePolicy::Error, false);
+        _ = fs::remove_dir_all(&test_dir);
+
+        let first = first.expect("failed to read patterns");
+        assert_eq!(first.loaded.len(), 1);
+        assert_eq!(first.duplicates.len(), 1);
+        assert_eq!(first_names.len(), 1);
+
+        let newest = newest.expect("failed to read patterns");
+        assert_eq!(newest.loaded.len(), 1);
+        assert!(newest.loaded[0].ends_with("copy.json"));
+        assert!(newest.duplicates[0].0.ends_with("original.json"));
+        assert_eq!(newest_names, vec!["copy"]);
+        assert_eq!(lazy_names, vec!["copy"]);
+
+        assert!(matches!(error, Err(ItfError::DuplicateUuid { .. })));
+    }
+
+    #[test]
+    fn test_duplicate_uuids_parallel() {
+        let test_dir = env::temp_dir().join(format!(
+            "itf-test-duplicates-parallel-{}",
+            std::process::id()
+        ));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        // Every copy has the same UUID and modification time, so only the load order decides which is kept.
+        let original = make_pattern("copy-00", vec![(0, b"ORIGINAL".to_vec())]);
+        for i in 0..12 {
+            let mut copy = original.clone();
+            copy.type_data.name = format!("copy-{i:02}");
+            let path = test_dir.join(format!("copy-{i:02}.json"));
+            copy.write_file(&path).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|f| f.set_modified(UNIX_EPOCH + Duration::from_secs(60)))
+                .unwrap();
+        }
+        make_pattern("other", vec![(0, b"OTHER".to_vec())])
+            .write_file(test_dir.join("other.json"))
+            .unwrap();
+
+        let read_with = |policy| {
+            let mut handler = PatternHandler::default();
+            handler.set_duplicate_policy(policy);
+            let report = handler
+                .read(&test_dir, "")
+                .expect("failed to read patterns");
+            let names: Vec<_> = handler.iter().map(|p| p.type_data.name.clone()).collect();
+            (names, report.loaded, report.duplicates)
+        };
+
+        // Each pool size gets its own pool, since other tests running concurrently could change the global limit.
+        let mut results = Vec::new();
+        for threads in [1, 4] {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("failed to build thread pool");
+            for _ in 0..4 {
+                results.push(pool.install(|| {
+                    (
+                        read_with(DuplicatePolicy::KeepFirst),
+                        read_with(DuplicatePolicy::KeepNewest),
+                    )
+                }));
+            }
+        }
+        _ = fs::remove_dir_all(&test_dir);
+
+        // The same pattern is kept, and the same duplicates reported in the same order, however the files are loaded.
+        let ((first_names, _, first_duplicates), (newest_names, _, newest_duplicates)) =
+            &results[0];
+        assert!(results.iter().all(|r| r == &results[0]));
+        assert_eq!(first_names, &vec!["copy-00", "other"]);
+        assert_eq!(newest_names, &vec!["copy-11", "other"]);
+        assert_eq!(first_duplicates.len(), 11);
+        assert_eq!(newest_duplicates.len(), 11);
+    }
+
+    #[test]
+    fn test_read_layered() {
+        let test_dir = env::temp_dir().join(format!("itf-test-layered-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        let system_dir = test_dir.join("system");
+        let user_dir = test_dir.join("user");
+        fs::create_dir_all(&system_dir).expect("failed to create test directory");
+        fs::create_dir_all(&user_dir).expect("failed to create test directory");
+
+        // The user's copy of the shared pattern replaces the system's copy.
+        let shared = make_pattern("shared", vec![(0, b"SHARED".to_vec())]);
+        let mut local = shared.clone();
+        local.data.sequences = intern_sequences(vec![(0, b"LOCAL".to_vec())]);
+        shared.write_file(system_dir.join("shared.json")).unwrap();
+        make_pattern("system", vec![(0, b"SYSTEM".to_vec())])
+            .write_file(system_dir.join("system.json"))
+            .unwrap();
+        local.write_file(user_dir.join("shared.json")).unwrap();
+
+        let mut handler = PatternHandler::default();
+        let report = handler.read_layered(
+            &[
+                PatternSource::Directory(system_dir.clone()),
+                PatternSource::Directory(user_dir.clone()),
+                PatternSource::Directory(test_dir.join("missing")),
+            ],
+            "",
+        );
+        let reloaded = handler.reload().map(|r| r.loaded.len());
+        _ = fs::remove_dir_all(&test_dir);
+
+        let report = report.expect("failed to read patterns");
+        assert_eq!(report.loaded.len(), 2);
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.skipped[0].0.contains("system"));
+        assert!(report.skipped[0].1.contains("overridden"));
+        assert_eq!(handler.patterns.len(), 2);
+        let shared = handler.pattern_by_uuid(&local.type_data.uuid).unwrap();
+        assert_eq!(
+            shared.data.sequences,
+            intern_sequences(vec![(0, b"LOCAL".to_vec())])
+        );
+        assert_eq!(reloaded.expect("failed to reload patterns"), 2);
+    }
+
+    #[test]
+    fn test_read_missing_directory() {
+        let test_dir = env::temp_dir().join(format!("itf-test-missing-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+
+        // A missing directory is an error, rather than a silent fallback to the built-in rules.
+        let mut handler = PatternHandler::default();
+        let result = handler.read(&test_dir, "");
+        assert!(matches!(result, Err(ItfError::Io(_))));
+        assert!(handler.patterns.is_empty());
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_disabled_patterns() {
+        let test_dir = env::temp_dir().join(format!("itf-test-disabled-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+        let dir = test_dir.to_str().unwrap();
+        let cache_path = test_dir.join("patterns.cache");
+
+        let listed = make_pattern("listed", vec![(0, b"LISTED".to_vec())]);
+        for pattern in [
+            &listed,
+            &make_pattern("configured", vec![(0, b"CONFIGURED".to_vec())]),
+            &make_pattern("enabled", vec![(0, b"ENABLED".to_vec())]),
+        ] {
+            pattern.write(dir, PatternCompression::None).unwrap();
+        }
+        fs::write(
+            test_dir.join(DISABLED_LIST_FILE_NAME),
+            format!("# Known to be noisy.\n\n{}\n", listed.type_data.uuid),
+        )
+        .unwrap();
+
+        let read_with = |lazy| {
+            let mut handler = PatternHandler::default();
+            handler.disable_patterns(vec!["CONFIGURED".to_string()]);
+            let report = if lazy {
+                handler.read_lazy(&test_dir, "")
+            } else {
+                handler.read_cached(&test_dir, &cache_path)
+            };
+            let mut names: Vec<_> = handler.iter().map(|p| p.type_data.name.clone()).collect();
+            names.sort();
+            (report, names)
+        };
+        let (report, names) = read_with(false);
+        let (_, cached_names) = read_with(false);
+        let (_, lazy_names) = read_with(true);
+
+        // The cache is regenerated once the disabled list has been changed.
+        fs::write(test_dir.join(DISABLED_LIST_FILE_NAME), "").unwrap();
+        let (_, relisted_names) = read_with(false);
+        _ = fs::remove_dir_all(&test_dir);
+
+        let report = report.expect("failed to read patterns");
+        assert_eq!(report.skipped.len(), 2);
+        assert!(report.skipped.iter().all(|(_, r)| r.contains("disabled")));
+        assert_eq!(names, vec!["enabled"]);
+        assert_eq!(cached_names, vec!["enabled"]);
+        assert_eq!(lazy_names, vec!["enabled"]);
+        assert_eq!(relisted_names, vec!["enabled", "listed"]);
+    }
+
+    #[test]
+    fn test_target_selectors() {
+        let test_dir = env::temp_dir().join(format!("itf-test-selectors-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        // The file names don't reflect the patterns, as though they had been renamed.
+        let first = make_pattern("first", vec![(0, b"FIRST".to_vec())]);
+        let second = make_pattern("second", vec![(0, b"SECOND".to_vec())]);
+        first.write_file(test_dir.join("a.json")).unwrap();
+        second.write_file(test_dir.join("b.json")).unwrap();
+
+        let read_names = |target: &str, lazy: bool| {
+            let mut handler = PatternHandler::default();
+            let result = if lazy {
+                handler.read_lazy(&test_dir, target)
+            } else {
+                handler.read(&test_dir, target)
+            };
+            result.map(|_| {
+                let mut names: Vec<_> = handler.iter().map(|p| p.type_data.name.clone()).collect();
+                names.sort();
+                names
+            })
+        };
+        let by_uuid = read_names(&format!("uuid:{}", first.type_data.uuid), false);
+        let by_name = read_names("name:SECOND", true);
+        let by_both = read_names(&format!("b.json, uuid:{}", first.type_data.uuid), false);
+        let by_neither = read_names("name:third", false);
+        _ = fs::remove_dir_all(&test_dir);
+
+        assert_eq!(by_uuid.unwrap(), vec!["first"]);
+        assert_eq!(by_name.unwrap(), vec!["second"]);
+        assert_eq!(by_both.unwrap(), vec!["first", "second"]);
+        assert!(matches!(by_neither, Err(ItfError::NoPatternsFound)));
+    }
+
+    #[test]
+    fn test_read_lazy() {
+        let test_dir = env::temp_dir().join(format!("itf-test-lazy-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+
+        let mut masked = make_pattern("masked", vec![]);
+        masked.data.masked_sequences = vec![(0, vec![Some(b'M'), Some(b'K'), None, Some(b'X')])];
+        let mut child = make_pattern("child", vec![(0, b"SECOND".to_vec())]);
+        let parent = make_pattern("parent", vec![(8, b"UNANCHORED".to_vec())]);
+        child.type_data.parent_uuid = Some(parent.type_data.uuid.clone());
+        let first = make_pattern("first", vec![(0, b"FIRST".to_vec())]);
+        for (file, pattern) in [
+            ("first.json", &first),
+            ("child.json", &child),
+            ("parent.json", &parent),
+            ("masked.json", &masked),
+            (
+                "authored.yaml",
+                &make_pattern("authored", vec![(0, b"YAML".to_vec())]),
+            ),
+        ] {
+            pattern.write_file(test_dir.join(file)).unwrap();
+        }
+
+        let mut handler = PatternHandler::default();
+        let report = handler.read_lazy(&test_dir, "");
+
+        // Only the hand-authored pattern is parsed immediately.
+        assert_eq!(report.expect("failed to read patterns").loaded.len(), 5);
+        assert_eq!(handler.patterns.len(), 1);
+        assert_eq!(handler.deferred.len(), 4);
+        assert!(!handler.is_empty());
+        assert_eq!(
+            handler.lineage(&child.type_data.uuid),
+            vec![
+                child.type_data.uuid.as_str(),
+                parent.type_data.uuid.as_str()
+            ]
+        );
+
+        // Only the deferred patterns that could match are parsed.
+        handler.build_prefilter();
+        let mut names: Vec<_> = handler
+            .candidates(b"SECOND FILE")
+            .iter()
+            .map(|p| p.type_data.name.clone())
+            .collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["child", "parent"]);
+        let mut parsed: Vec<_> = handler
+            .deferred
+            .iter()
+            .filter(|d| d.pattern.get().is_some())
+            .map(|d| d.type_data.name.as_str())
+            .collect();
+        parsed.sort_unstable();
+        assert_eq!(parsed, vec!["child", "parent"]);
+
+        assert_eq!(handler.candidates(b"MKAX").len(), 2);
+        assert_eq!(handler.candidates(b"YAML").len(), 2);
+        assert!(handler.pattern_by_uuid(&first.type_data.uuid).is_some());
+        _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_load_stats() {
+        let test_dir = env::temp_dir().join(format!("itf-test-stats-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+        let dir = test_dir.to_str().unwrap();
+
+        let mut alpha = make_pattern("alpha", vec![(0, b"ALPHA".to_vec()), (8, b"A".to_vec())]);
+        alpha.data.strings.insert("ALPHABET".to_string());
+        alpha.write(dir, PatternCompression::None).unwrap();
+        make_pattern("beta", vec![(0, b"BETA".to_vec())])
+            .write(dir, PatternCompression::None)
+            .unwrap();
+        fs::write(test_dir.join("broken.json"), "{ \"type_data\": ").unwrap();
+
+        let mut handler = PatternHandler::default();
+        handler
+            .read(&test_dir, "")
+            .expect("failed to read patterns");
+        let mut targeted_handler = PatternHandler::default();
+        targeted_handler
+            .read(&test_dir, "alpha")
+            .expect("failed to read patterns");
+        _ = fs::remove_dir_all(&test_dir);
+
+        let stats = handler.load_stats();
+        assert_eq!(stats.files_scanned, 3);
+        assert_eq!(stats.files_parsed, 3);
+        assert_eq!(stats.patterns_loaded, 2);
+        assert_eq!(stats.patterns_failed, 1);
+        assert_eq!(stats.sequence_count, 3);
+        assert_eq!(stats.string_count, 1);
+
+        // Only the targeted pattern file is parsed.
+        let stats = targeted_handler.load_stats();
+        assert_eq!(stats.files_scanned, 3);
+        assert_eq!(stats.files_parsed, 1);
+        assert_eq!(stats.patterns_skipped, 2);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_load_report() {
+        let test_dir = env::temp_dir().join(format!("itf-test-report-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+        let dir = test_dir.to_str().unwrap();
+        let cache_path = test_dir.join("patterns.cache");
+
+        for (name, license) in [("mit", "MIT"), ("proprietary", "LicenseRef-Proprietary")] {
+            let mut pattern = make_pattern(name, vec![(0, name.as_bytes().to_vec())]);
+            pattern.other_data.license = license.to_string();
+            pattern.write(dir, PatternCompression::None).unwrap();
+        }
+        fs::write(test_dir.join("broken.json"), "{ \"type_data\": ").unwrap();
+
+        let mut handler = PatternHandler::default();
+        handler.allow_licenses(vec!["MIT".to_string()]);
+        let report = handler
+            .read(&test_dir, "")
+            .expect("failed to read patterns");
+
+        // The licensing is reported the same way when the patterns are read into the cache, and from it.
+        let mut cached_handler = PatternHandler::default();
+        cached_handler.allow_licenses(vec!["MIT".to_string()]);
+        let uncached_report = cached_handler.read_cached(&test_dir, &cache_path);
+        let mut cached_handler = PatternHandler::default();
+        cached_handler.allow_licenses(vec!["MIT".to_string()]);
+        let cached_report = cached_handler.read_cached(&test_dir, &cache_path);
+
+        // The report is retained even when no patterns were loaded.
+        let mut empty_handler = PatternHandler::default();
+        let empty_result = empty_handler.read(&test_dir, "broken");
+        _ = fs::remove_dir_all(&test_dir);
+
+        assert_eq!(report.loaded.len(), 1);
+        assert!(report.loaded[0].ends_with("mit.json"));
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.skipped[0].0.ends_with("proprietary.json"));
+        assert!(report.skipped[0].1.contains("license"));
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.failed[0].0.ends_with("broken.json"));
+        assert_eq!(handler.load_report(), &report);
+
+        let uncached_report = uncached_report.expect("failed to read patterns");
+        assert_eq!(uncached_report, report);
+        assert_eq!(cached_handler.load_report().loaded, vec!["mit"]);
+        assert_eq!(cached_report.unwrap().skipped[0].0, "proprietary");
+
+        assert!(matches!(empty_result, Err(ItfError::NoPatternsFound)));
+        assert_eq!(empty_handler.load_report().skipped.len(), 2);
+        assert_eq!(empty_handler.load_report().failed.len(), 1);
     }
 }
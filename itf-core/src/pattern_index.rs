@@ -0,0 +1,260 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::ItfError, pattern::Pattern, pattern_handler::PatternHandler,
+    pattern_selector::PatternSelector,
+};
+
+/// The name of the index file within a pattern directory.
+pub const INDEX_FILE_NAME: &str = "patterns.index.json";
+/// The version of the index format. Changing this will cause any existing indices to be ignored.
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+/// An entry within a [`PatternIndex`], describing a single pattern file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PatternIndexEntry {
+    /// The UUID of the pattern.
+    pub uuid: String,
+    /// The name of the pattern.
+    pub name: String,
+    /// The known extensions of the pattern.
+    pub extensions: Vec<String>,
+    /// The bytes that the pattern requires at the very start of a file. This will be empty if the
+    /// pattern has no byte sequence anchored to the start of a file.
+    pub magic: Vec<u8>,
+    /// The path to the pattern file, relative to the pattern directory.
+    pub path: String,
+}
+
+/// An index of the pattern files within a directory, allowing the files that are needed to be
+/// selected without parsing every pattern file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PatternIndex {
+    /// The version of the index format.
+    pub version: u32,
+    /// The key derived from the pattern files, used to detect a stale index.
+    pub key: u64,
+    /// The indexed pattern files.
+    pub entries: Vec<PatternIndexEntry>,
+}
+
+impl PatternIndex {
+    /// Build an index of the pattern files within a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    ///
+    /// # Returns
+    ///
+    /// An error if a pattern file could not be read, otherwise the index.
+    ///
+    /// # Notes
+    ///
+    /// As with [`PatternHandler::read`], files that aren't valid patterns are skipped.
+    pub fn build<P: AsRef<Path>>(path: P) -> Result<Self, ItfError> {
+        let path = path.as_ref();
+
+        let mut entries = Vec::new();
+        for file in PatternHandler::list_pattern_files(path) {
+            let pattern = match Pattern::from_file(&file) {
+                Ok(p) => p,
+                Err(ItfError::JsonParse(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            let relative = Path::new(&file)
+                .strip_prefix(path)
+                .unwrap_or(Path::new(&file));
+            entries.push(PatternIndexEntry {
+                uuid: pattern.type_data.uuid.clone(),
+                name: pattern.type_data.name.clone(),
+                extensions: pattern
+                    .type_data
+                    .known_extensions
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect(),
+                magic: Self::get_magic(&pattern),
+                path: relative.to_string_lossy().replace('\\', "/"),
+            });
+        }
+
+        Ok(Self {
+            version: INDEX_FORMAT_VERSION,
+            key: PatternHandler::compute_cache_key(path)?,
+            entries,
+        })
+    }
+
+    /// Write the index into a pattern directory, replacing any existing index.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    ///
+    /// # Returns
+    ///
+    /// An error if the index could not be serialized or written, otherwise the path to the index file.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, ItfError> {
+        let index_path = path.as_ref().join(INDEX_FILE_NAME);
+        fs::write(&index_path, serde_json::to_string(self)?)?;
+
+        Ok(index_path)
+    }
+
+    /// Load the index of a pattern directory, provided that it's up to date.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    ///
+    /// # Returns
+    ///
+    /// None if there is no index, or if the index is unreadable or out of date, otherwise the index.
+    /// An error will be returned if the pattern directory or index file could not be read.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Self>, ItfError> {
+        let path = path.as_ref();
+        let contents = match fs::read_to_string(path.join(INDEX_FILE_NAME)) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        // An index that can't be parsed will simply be ignored.
+        let index: PatternIndex = match serde_json::from_str(&contents) {
+            Ok(i) => i,
+            Err(_) => return Ok(None),
+        };
+
+        if index.version != INDEX_FORMAT_VERSION
+            || index.key != PatternHandler::compute_cache_key(path)?
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(index))
+    }
+
+    /// Select the pattern files that should be parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the directory containing the pattern files.
+    /// * `target_pattern` - If not empty, only the pattern files selected by this will be selected.
+    ///   See [`PatternSelector`] for the supported selectors.
+    /// * `extension_hint` - If specified, only patterns that list this extension, or that don't list any
+    ///   extensions, will be selected.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the paths to the selected pattern files.
+    pub fn select<P: AsRef<Path>>(
+        &self,
+        path: P,
+        target_pattern: &str,
+        extension_hint: Option<&str>,
+    ) -> Vec<String> {
+        let target = PatternSelector::parse(target_pattern);
+        self.entries
+            .iter()
+            .filter(|e| extension_hint.is_none_or(|ext| Self::lists_extension(&e.extensions, ext)))
+            .map(|e| (e, path.as_ref().join(&e.path).to_string_lossy().to_string()))
+            .filter(|(e, f)| target.selects(f, &e.uuid, &e.name))
+            .map(|(_, f)| f)
+            .collect()
+    }
+
+    /// Does a pattern list a specific extension, or list no extensions at all?
+    ///
+    /// # Arguments
+    ///
+    /// * `extensions` - The known extensions of the pattern.
+    /// * `extension` - The extension.
+    pub(crate) fn lists_extension<S: AsRef<str>>(extensions: &[S], extension: &str) -> bool {
+        let extension = extension.trim_start_matches('.');
+        extensions.is_empty()
+            || extensions
+                .iter()
+                .any(|e| e.as_ref().eq_ignore_ascii_case(extension))
+    }
+
+    /// Get the bytes that a pattern requires at the very start of a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern.
+    fn get_magic(pattern: &Pattern) -> Vec<u8> {
+        if let Some((_, sequence)) = pattern.data.sequences.iter().find(|(p, _)| *p == 0) {
+            return sequence.to_vec();
+        }
+
+        // Only the bytes before the first masked byte are certain.
+        pattern
+            .data
+            .masked_sequences
+            .iter()
+            .find(|(p, _)| *p == 0)
+            .map(|(_, sequence)| sequence.iter().map_while(|b| *b).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests_pattern_index {
+    use std::{env, fs};
+
+    use crate::{
+        interner::intern_sequences,
+        pattern::{Pattern, PatternCompression},
+        pattern_handler::PatternHandler,
+    };
+
+    use super::{PatternIndex, INDEX_FILE_NAME};
+
+    #[test]
+    fn test_pattern_index() {
+        let test_dir = env::temp_dir().join(format!("itf-test-index-{}", std::process::id()));
+        _ = fs::remove_dir_all(&test_dir);
+        fs::create_dir_all(&test_dir).expect("failed to create test directory");
+        let dir = test_dir.to_str().unwrap();
+
+        for (name, extension) in [("first", "ONE"), ("second", "TWO")] {
+            let mut pattern = Pattern::new(name, "test", vec![extension.to_string()], vec![]);
+            pattern.data.sequences = intern_sequences(vec![(0, name.as_bytes().to_vec())]);
+            pattern.write(dir, PatternCompression::None).unwrap();
+        }
+
+        let index = PatternIndex::build(&test_dir).expect("failed to build index");
+        index.write(&test_dir).expect("failed to write index");
+        let loaded = PatternIndex::load(&test_dir).unwrap();
+        assert_eq!(loaded.as_ref(), Some(&index));
+        assert_eq!(index.entries[0].path, "first.json");
+        assert_eq!(index.entries[1].magic, b"second".to_vec());
+
+        // The index file isn't a pattern file, and so isn't indexed itself.
+        assert_eq!(index.entries.len(), 2);
+        assert!(!PatternHandler::list_pattern_files(&test_dir)
+            .iter()
+            .any(|f| f.ends_with(INDEX_FILE_NAME)));
+
+        let mut handler = PatternHandler::default();
+        handler
+            .read_with_hint(&test_dir, "", Some("two"))
+            .expect("failed to read patterns");
+        assert_eq!(handler.patterns.len(), 1);
+        assert_eq!(handler.patterns[0].type_data.name, "second");
+
+        // Adding a pattern file makes the index stale.
+        Pattern::new("third", "test", vec![], vec![])
+            .write(dir, PatternCompression::None)
+            .unwrap();
+        let stale = PatternIndex::load(&test_dir).unwrap();
+        _ = fs::remove_dir_all(&test_dir);
+        assert!(stale.is_none());
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt;
+
+use regex_syntax::hir::{Hir, HirKind};
+use serde_derive::Serialize;
+
+use crate::pattern::{CompiledRegex, Pattern, RECOMMENDED_MIN_SAMPLES};
+
+/// The minimum number of characters that an unanchored string should have. Shorter strings
+/// are likely to be found within many unrelated files.
+pub const MIN_STRING_LENGTH: usize = 6;
+/// The bytes that are commonly used to pad files, and so are found within many unrelated files.
+const PADDING_BYTES: &[u8] = &[0x00, 0x20, 0xFF];
+
+/// The severity of a lint issue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    /// The pattern may cause false positives.
+    Warning,
+    /// The pattern will almost certainly cause false positives, or can't be used as intended.
+    Error,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintSeverity::Warning => write!(f, "warning"),
+            LintSeverity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// An issue found within a [`Pattern`] by [`lint`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LintIssue {
+    /// The severity of the issue.
+    pub severity: LintSeverity,
+    /// A short, stable identifier for the kind of issue (e.g. `short-string`).
+    pub code: &'static str,
+    /// A description of the issue.
+    pub message: String,
+}
+
+impl LintIssue {
+    fn warning(code: &'static str, message: String) -> Self {
+        Self {
+            severity: LintSeverity::Warning,
+            code,
+            message,
+        }
+    }
+
+    fn error(code: &'static str, message: String) -> Self {
+        Self {
+            severity: LintSeverity::Error,
+            code,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// Check a [`Pattern`] for definitions that are likely to cause false positives.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+///
+/// # Returns
+///
+/// A vector of the issues that were found, with the most severe first.
+pub fn lint(pattern: &Pattern) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let data = &pattern.data;
+
+    let has_positional = !data.sequences.is_empty()
+        || !data.ranged_sequences.is_empty()
+        || !data.alternative_sequences.is_empty()
+        || !data.trailer_sequences.is_empty()
+        || !data.masked_sequences.is_empty()
+        || !data.regexes.is_empty()
+        || !data.anchored_strings.is_empty();
+    let has_composition = data.should_scan_composition()
+        || !data.entropy_windows.is_empty()
+        || data.chi_square.is_some()
+        || !data.bigram_profile.is_empty()
+        || data.fuzzy_hash.is_some();
+    if !has_positional && data.strings.is_empty() {
+        issues.push(if has_composition {
+            LintIssue::error(
+                "composition-only",
+                "the pattern only describes the composition of a file, which is shared by many unrelated files"
+                    .to_string(),
+            )
+        } else {
+            LintIssue::error("empty", "the pattern doesn't describe any attributes".to_string())
+        });
+    }
+
+    if pattern.type_data.known_extensions.is_empty() {
+        issues.push(LintIssue::warning(
+            "missing-extensions",
+            "the pattern doesn't list any known extensions".to_string(),
+        ));
+    }
+
+    let mut strings: Vec<&String> = data.strings.iter().collect();
+    strings.sort_unstable();
+    for string in strings {
+        if string.chars().count() < MIN_STRING_LENGTH {
+            issues.push(LintIssue::warning(
+                "short-string",
+                format!("the string '{string}' is shorter than {MIN_STRING_LENGTH} characters"),
+            ));
+        }
+    }
+
+    let sequences = data
+        .sequences
+        .iter()
+        .map(|(position, sequence)| (format!("{position}"), &sequence[..]))
+        .chain(
+            data.trailer_sequences
+                .iter()
+                .map(|(position, sequence)| (format!("{position}"), &sequence[..])),
+        )
+        .chain(
+            data.ranged_sequences
+                .iter()
+                .map(|(min, max, sequence)| (format!("{min}-{max}"), &sequence[..])),
+        );
+    for (position, sequence) in sequences {
+        if sequence.iter().all(|b| PADDING_BYTES.contains(b)) {
+            issues.push(LintIssue::warning(
+                "padding-sequence",
+                format!("the byte sequence at {position} consists only of common padding bytes"),
+            ));
+        }
+    }
+
+    for (position, regex) in &data.regexes {
+        issues.extend(lint_regex(*position, regex));
+    }
+
+    let total = pattern.other_data.total_scanned_files;
+    if total < RECOMMENDED_MIN_SAMPLES {
+        issues.push(LintIssue::warning(
+            "few-samples",
+            format!(
+                "the pattern was built from {total} sample files, fewer than the recommended {RECOMMENDED_MIN_SAMPLES}"
+            ),
+        ));
+    } else {
+        for (position, support) in &data.sequence_support {
+            if *support < RECOMMENDED_MIN_SAMPLES {
+                issues.push(LintIssue::warning(
+                    "low-support",
+                    format!("the byte sequence at {position} was only present in {support} sample files"),
+                ));
+            }
+        }
+    }
+
+    issues.sort_by_key(|i| std::cmp::Reverse(i.severity));
+    issues
+}
+
+/// Check a regex for constructs that are likely to cause false positives or slow matching.
+///
+/// # Arguments
+///
+/// * `position` - The position at which the regex must match.
+/// * `regex` - The regex.
+fn lint_regex(position: usize, regex: &str) -> Option<LintIssue> {
+    if let Err(e) = CompiledRegex::new(position, regex) {
+        return Some(LintIssue::error("invalid-regex", e.to_string()));
+    }
+
+    let hir = regex_syntax::ParserBuilder::new()
+        .utf8(false)
+        .build()
+        .parse(regex)
+        .ok()?;
+
+    if hir.properties().minimum_len() == Some(0) {
+        Some(LintIssue::error(
+            "empty-regex",
+            format!("the regex '{regex}' at {position} can match an empty sequence, and so matches any file"),
+        ))
+    } else if has_nested_unbounded_repetition(&hir, false) {
+        Some(LintIssue::warning(
+            "catastrophic-regex",
+            format!(
+                "the regex '{regex}' at {position} nests unbounded repetitions, which backtracking regex engines can take exponential time to match"
+            ),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Does a regex contain an unbounded repetition within another unbounded repetition, such as `(a+)*`?
+///
+/// # Arguments
+///
+/// * `hir` - The parsed regex.
+/// * `within_unbounded` - Is the regex within an unbounded repetition?
+fn has_nested_unbounded_repetition(hir: &Hir, within_unbounded: bool) -> bool {
+    match hir.kind() {
+        HirKind::Repetition(rep) => {
+            let unbounded = rep.max.is_none();
+            (unbounded && within_unbounded)
+                || has_nested_unbounded_repetition(&rep.sub, unbounded || within_unbounded)
+        }
+        HirKind::Capture(capture) => {
+            has_nested_unbounded_repetition(&capture.sub, within_unbounded)
+        }
+        HirKind::Concat(subs) | HirKind::Alternation(subs) => subs
+            .iter()
+            .any(|sub| has_nested_unbounded_repetition(sub, within_unbounded)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests_pattern_lint {
+    use crate::{interner::intern_sequences, pattern::Pattern};
+
+    use super::{lint, LintSeverity};
+
+    fn codes(pattern: &Pattern) -> Vec<&'static str> {
+        lint(pattern).iter().map(|i| i.code).collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        pattern.other_data.total_scanned_files = 5;
+        assert_eq!(codes(&pattern), vec!["empty"]);
+
+        pattern.data.average_entropy = 4.5;
+        assert_eq!(codes(&pattern), vec!["composition-only"]);
+
+        pattern.data.sequences =
+            intern_sequences(vec![(8, vec![0, 0, 0, 0]), (0, b"MAGIC".to_vec())]);
+        pattern.data.sequence_support = vec![(0, 5), (8, 2)];
+        pattern.data.strings.insert("HEAD".to_string());
+        pattern.data.strings.insert("LONGER STRING".to_string());
+        assert_eq!(
+            codes(&pattern),
+            vec!["short-string", "padding-sequence", "low-support"]
+        );
+
+        pattern.type_data.known_extensions.clear();
+        pattern.other_data.total_scanned_files = 1;
+        pattern.data.sequences.remove(0);
+        pattern.data.strings.clear();
+        assert_eq!(codes(&pattern), vec!["missing-extensions", "few-samples"]);
+    }
+
+    #[test]
+    fn test_lint_regexes() {
+        let mut pattern = Pattern::new("test", "test", vec!["test".to_string()], vec![]);
+        pattern.other_data.total_scanned_files = 5;
+        pattern.data.regexes = vec![
+            (0, "GIF8[79]a".to_string()),
+            (0, "(AB+)*C".to_string()),
+            (0, "A?".to_string()),
+            (0, "AB(".to_string()),
+        ];
+
+        let issues = lint(&pattern);
+        let codes: Vec<_> = issues.iter().map(|i| i.code).collect();
+        assert_eq!(
+            codes,
+            vec!["empty-regex", "invalid-regex", "catastrophic-regex"]
+        );
+        assert_eq!(issues[0].severity, LintSeverity::Error);
+        assert_eq!(issues[2].severity, LintSeverity::Warning);
+    }
+}
@@ -0,0 +1,307 @@
+use serde_json::{Map, Value};
+
+use crate::{error::ItfError, utils};
+
+/// The version of the pattern format written by this version of the library.
+///
+/// # Notes
+///
+/// * Version 0 is the original flat layout, in which every field was stored at the top level of the document,
+///   alongside the `scan_*` flags that were used to build the pattern.
+/// * Version 1 is the sectioned layout (`td`, `pd`, `od` and `sd`), which didn't record its version.
+/// * Version 2 records its version.
+pub const PATTERN_FORMAT_VERSION: u32 = 2;
+
+/// The fields of the flat layout that belong to the type data section.
+const TYPE_DATA_FIELDS: &[&str] = &[
+    "name",
+    "description",
+    "extensions",
+    "known_extensions",
+    "mimetypes",
+    "uuid",
+    "priority",
+];
+/// The fields of the flat layout that belong to the other data section.
+const OTHER_DATA_FIELDS: &[&str] = &["total_scanned_files", "file_format_url"];
+/// The fields of the flat layout that belong to the submitter data section.
+const SUBMITTER_DATA_FIELDS: &[&str] = &[
+    "scanned_by",
+    "scanned_by_email",
+    "scanned_on",
+    "refined_by",
+    "refined_by_email",
+];
+/// The flags of the flat layout that indicate which attributes were scanned for, and the pattern data
+/// field that should be discarded if the flag is false.
+const SCAN_FLAGS: &[(&str, &str)] = &[
+    ("scan_byte_sequences", "sequences"),
+    ("scan_sequences", "sequences"),
+    ("scan_strings", "strings"),
+    ("scan_byte_distribution", "average_entropy"),
+];
+/// The fields of the type data that have been renamed, given as the old name followed by the new name.
+/// This includes the misspelling of "mimetypes" used by some older versions.
+const RENAMED_TYPE_DATA_FIELDS: &[(&str, &str)] = &[
+    ("known_extensions", "extensions"),
+    ("known_mimetypes", "mimetypes"),
+    ("known_minetypes", "mimetypes"),
+    ("minetypes", "mimetypes"),
+];
+
+/// Get the format version of a JSON pattern document.
+///
+/// # Arguments
+///
+/// * `document` - The JSON document.
+///
+/// # Returns
+///
+/// An error if the recorded version isn't a valid version number, otherwise the version of the document.
+pub fn format_version(document: &Value) -> Result<u32, ItfError> {
+    match document.get("format_version") {
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| ItfError::InvalidPattern(format!("invalid format version: {v}"))),
+        None if document.get("td").is_some() => Ok(1),
+        None => Ok(0),
+    }
+}
+
+/// Upgrade a JSON pattern document, in place, to the current format version.
+///
+/// # Arguments
+///
+/// * `document` - The JSON document.
+///
+/// # Returns
+///
+/// An error if the document isn't an object, or was written by a newer version of the library. Otherwise,
+/// a description of each change that was needed to upgrade the document. Recording the format version
+/// alone isn't counted as a change.
+pub fn migrate(document: &mut Value) -> Result<Vec<String>, ItfError> {
+    let version = format_version(document)?;
+    if version > PATTERN_FORMAT_VERSION {
+        return Err(ItfError::UnsupportedFormatVersion {
+            found: version,
+            supported: PATTERN_FORMAT_VERSION,
+        });
+    }
+
+    let Some(fields) = document.as_object_mut() else {
+        return Err(ItfError::InvalidPattern(
+            "the pattern document must be an object".to_string(),
+        ));
+    };
+
+    let mut changes = Vec::new();
+    if version == 0 {
+        migrate_flat_layout(fields, &mut changes);
+    }
+
+    // Version 1 differs from version 2 only in that the version wasn't recorded.
+    fields.insert("format_version".to_string(), PATTERN_FORMAT_VERSION.into());
+
+    // Older versions may have written the misspelled field names, or an average entropy that couldn't be
+    // represented (such as NaN, which is written as null).
+    if version < PATTERN_FORMAT_VERSION {
+        if let Some(type_data) = fields.get_mut("td").and_then(Value::as_object_mut) {
+            rename_fields(type_data, &mut changes);
+        }
+        if let Some(data) = fields.get_mut("pd").and_then(Value::as_object_mut) {
+            if data.get("average_entropy").is_some_and(|e| !e.is_number()) {
+                data.remove("average_entropy");
+                changes.push("replaced an invalid average entropy with zero".to_string());
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Rename any fields of the type data that have since been renamed.
+///
+/// # Arguments
+///
+/// * `type_data` - The fields of the type data.
+/// * `changes` - The list of changes, to which any renamed fields will be added.
+fn rename_fields(type_data: &mut Map<String, Value>, changes: &mut Vec<String>) {
+    for (old, new) in RENAMED_TYPE_DATA_FIELDS {
+        if let Some(value) = type_data.remove(*old) {
+            if !type_data.contains_key(*new) {
+                type_data.insert(new.to_string(), value);
+            }
+            changes.push(format!("renamed '{old}' to '{new}'"));
+        }
+    }
+}
+
+/// Upgrade a document in the flat layout into the sectioned layout.
+///
+/// # Arguments
+///
+/// * `fields` - The top-level fields of the document.
+/// * `changes` - The list of changes, to which a description of each change will be added.
+fn migrate_flat_layout(fields: &mut Map<String, Value>, changes: &mut Vec<String>) {
+    let mut type_data = Map::new();
+    let mut other_data = Map::new();
+    let mut submitter_data = Map::new();
+    let mut data = Map::new();
+    let mut scan_flags = Map::new();
+
+    changes.push("converted the flat layout into the sectioned layout".to_string());
+
+    for (key, value) in std::mem::take(fields) {
+        let section = if TYPE_DATA_FIELDS.contains(&key.as_str())
+            || RENAMED_TYPE_DATA_FIELDS.iter().any(|(old, _)| *old == key)
+        {
+            &mut type_data
+        } else if OTHER_DATA_FIELDS.contains(&key.as_str()) {
+            &mut other_data
+        } else if SUBMITTER_DATA_FIELDS.contains(&key.as_str()) {
+            &mut submitter_data
+        } else if SCAN_FLAGS.iter().any(|(flag, _)| *flag == key) {
+            &mut scan_flags
+        } else {
+            &mut data
+        };
+        section.insert(key, value);
+    }
+
+    rename_fields(&mut type_data, changes);
+
+    // An attribute that wasn't scanned for holds no meaningful data.
+    for (flag, field) in SCAN_FLAGS {
+        if scan_flags.get(*flag).and_then(Value::as_bool) == Some(false)
+            && data.remove(*field).is_some()
+        {
+            changes.push(format!("discarded '{field}', as '{flag}' was false"));
+        }
+    }
+
+    // The flat layout didn't require these fields, but the sectioned layout does.
+    if !type_data.contains_key("uuid") {
+        type_data.insert("uuid".to_string(), utils::make_uuid().into());
+        changes.push("generated a UUID".to_string());
+    }
+    other_data
+        .entry("total_scanned_files")
+        .or_insert_with(|| 0.into());
+    for field in ["scanned_by", "scanned_by_email", "scanned_on"] {
+        submitter_data
+            .entry(field)
+            .or_insert_with(|| String::new().into());
+    }
+
+    fields.insert("td".to_string(), type_data.into());
+    fields.insert("pd".to_string(), data.into());
+    fields.insert("od".to_string(), other_data.into());
+    fields.insert("sd".to_string(), submitter_data.into());
+}
+
+#[cfg(test)]
+mod tests_pattern_migration {
+    use serde_json::json;
+
+    use crate::{
+        error::ItfError,
+        interner::{intern_all, intern_sequences},
+        pattern::Pattern,
+    };
+
+    use super::{format_version, migrate, PATTERN_FORMAT_VERSION};
+
+    #[test]
+    fn test_migrate_flat_layout() {
+        let document = json!({
+            "name": "Test",
+            "known_extensions": ["TEST"],
+            "sequences": [[0, [77, 90]]],
+            "strings": ["HEADER"],
+            "average_entropy": 4.5,
+            "scan_byte_sequences": true,
+            "scan_strings": false,
+            "scan_byte_distribution": true,
+            "total_scanned_files": 3,
+            "scanned_by": "someone"
+        });
+        assert_eq!(format_version(&document).ok(), Some(0));
+
+        let pattern = Pattern::from_json_str(&document.to_string()).unwrap();
+        assert_eq!(pattern.format_version, PATTERN_FORMAT_VERSION);
+        assert_eq!(pattern.type_data.name, "Test");
+        assert_eq!(pattern.type_data.known_extensions, intern_all(&["TEST"]));
+        assert!(!pattern.type_data.uuid.is_empty());
+        assert_eq!(
+            pattern.data.sequences,
+            intern_sequences(vec![(0, b"MZ".to_vec())])
+        );
+        assert_eq!(pattern.data.average_entropy, 4.5);
+        assert_eq!(pattern.other_data.total_scanned_files, 3);
+        assert_eq!(pattern.submitter_data.scanned_by, "someone");
+
+        // The strings weren't scanned for, and so are discarded.
+        assert!(pattern.data.strings.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_versions() {
+        let pattern = Pattern::new("Test", "", vec!["TEST".to_string()], vec![]);
+        let json = serde_json::to_string(&pattern).unwrap();
+        assert_eq!(pattern.format_version, PATTERN_FORMAT_VERSION);
+
+        // A sectioned document without a version is upgraded, with both parsers.
+        let mut document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        document.as_object_mut().unwrap().remove("format_version");
+        assert_eq!(format_version(&document).ok(), Some(1));
+        let pattern = Pattern::from_simd_json_str(&document.to_string()).unwrap();
+        assert_eq!(pattern.format_version, PATTERN_FORMAT_VERSION);
+        assert_eq!(pattern.type_data.known_extensions, intern_all(&["TEST"]));
+
+        // A document written by a newer version can't be understood.
+        document["format_version"] = (PATTERN_FORMAT_VERSION + 1).into();
+        assert!(matches!(
+            migrate(&mut document.clone()),
+            Err(ItfError::UnsupportedFormatVersion { .. })
+        ));
+        assert!(Pattern::from_simd_json_str(&document.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_migrate_legacy_fields() {
+        let document = json!({
+            "name": "Legacy",
+            "minetypes": ["application/x-legacy"],
+            "sequences": [[0, [77, 90]]],
+            "strings": ["HEADER"],
+            "average_entropy": null,
+            "scan_sequences": false,
+            "scan_strings": true
+        });
+
+        let pattern = Pattern::from_json_str(&document.to_string()).unwrap();
+        assert_eq!(
+            pattern.type_data.known_mimetypes,
+            intern_all(&["application/x-legacy"])
+        );
+        assert!(pattern.data.sequences.is_empty());
+        assert!(pattern.data.strings.contains("HEADER"));
+        assert_eq!(pattern.data.average_entropy, 0.0);
+
+        for change in [
+            "renamed 'minetypes' to 'mimetypes'",
+            "discarded 'sequences', as 'scan_sequences' was false",
+            "replaced an invalid average entropy with zero",
+        ] {
+            assert!(
+                pattern.migrations.iter().any(|m| m == change),
+                "missing '{change}'"
+            );
+        }
+
+        // A pattern in the current format needs no changes.
+        let json = serde_json::to_string(&pattern).unwrap();
+        assert!(Pattern::from_json_str(&json).unwrap().migrations.is_empty());
+    }
+}
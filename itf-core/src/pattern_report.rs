@@ -0,0 +1,487 @@
+use std::fmt::Write;
+
+use crate::pattern::Pattern;
+
+/// The number of bytes displayed on each line of a hex view.
+const HEX_VIEW_WIDTH: usize = 16;
+
+/// The formats in which a pattern report can be rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A Markdown document.
+    Markdown,
+    /// A standalone HTML document.
+    Html,
+}
+
+impl ReportFormat {
+    /// Get the report format implied by the extension of a file path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    ///
+    /// # Returns
+    ///
+    /// HTML for a path ending with `.html` or `.htm`, otherwise Markdown.
+    pub fn from_path(path: &str) -> Self {
+        let path = path.to_ascii_lowercase();
+        if path.ends_with(".html") || path.ends_with(".htm") {
+            ReportFormat::Html
+        } else {
+            ReportFormat::Markdown
+        }
+    }
+}
+
+/// A block of content within a report, which is independent of the format in which it will be rendered.
+enum Block {
+    /// A heading, given as its level followed by its text.
+    Heading(usize, String),
+    /// A paragraph of text.
+    Paragraph(String),
+    /// A table of fields, given as the name of each field followed by its value.
+    Fields(Vec<(&'static str, String)>),
+    /// A table with a header row.
+    Table(Vec<&'static str>, Vec<Vec<String>>),
+    /// A block of preformatted text.
+    Code(String),
+    /// A list of literal values, such as strings.
+    Literals(Vec<String>),
+}
+
+/// Render a human-readable report describing a pattern, suitable for documenting the pattern.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+/// * `format` - The format in which the report should be rendered.
+///
+/// # Returns
+///
+/// The rendered report.
+///
+/// # Notes
+///
+/// The report contains the type data, a hex view of each byte sequence, the strings, the entropy band
+/// and the history of the submitters. Sections for which the pattern has no data are omitted.
+pub fn render(pattern: &Pattern, format: ReportFormat) -> String {
+    let blocks = build_blocks(pattern);
+    match format {
+        ReportFormat::Markdown => render_markdown(&blocks),
+        ReportFormat::Html => render_html(&pattern.type_data.name, &blocks),
+    }
+}
+
+/// Build the blocks of content describing a pattern.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+fn build_blocks(pattern: &Pattern) -> Vec<Block> {
+    let type_data = &pattern.type_data;
+    let data = &pattern.data;
+    let other_data = &pattern.other_data;
+
+    let mut blocks = vec![Block::Heading(1, type_data.name.clone())];
+    if !type_data.description.is_empty() {
+        blocks.push(Block::Paragraph(type_data.description.clone()));
+    }
+
+    let mut fields = vec![("UUID", type_data.uuid.clone())];
+    for (name, values) in [
+        ("Extensions", &type_data.known_extensions),
+        ("MIME types", &type_data.known_mimetypes),
+        ("Categories", &type_data.categories),
+    ] {
+        if !values.is_empty() {
+            fields.push((name, values.join(", ")));
+        }
+    }
+    if type_data.priority != 0 {
+        fields.push(("Priority", type_data.priority.to_string()));
+    }
+    for (name, value) in [
+        ("Format documentation", &other_data.file_format_url),
+        ("Source", &other_data.source),
+        ("License", &other_data.license),
+        ("Origin", &other_data.origin_url),
+    ] {
+        if !value.is_empty() {
+            fields.push((name, value.clone()));
+        }
+    }
+    fields.push(("Sample files", other_data.total_scanned_files.to_string()));
+    if pattern.max_points > 0 {
+        fields.push(("Maximum points", pattern.max_points.to_string()));
+    }
+    blocks.push(Block::Fields(fields));
+
+    let has_sequences = !data.sequences.is_empty()
+        || !data.trailer_sequences.is_empty()
+        || !data.masked_sequences.is_empty()
+        || !data.ranged_sequences.is_empty()
+        || !data.alternative_sequences.is_empty()
+        || !data.regexes.is_empty();
+    if has_sequences {
+        blocks.push(Block::Heading(2, "Byte sequences".to_string()));
+    }
+
+    let mut sequences = data.sequences.clone();
+    sequences.sort_unstable_by_key(|(position, _)| *position);
+    for (position, bytes) in &sequences {
+        let bytes: Vec<_> = bytes.iter().copied().map(Some).collect();
+        blocks.push(Block::Heading(3, format!("At offset {position}")));
+        blocks.push(Block::Code(hex_view(*position, &bytes)));
+    }
+    for (position, bytes) in &data.masked_sequences {
+        blocks.push(Block::Heading(
+            3,
+            format!("At offset {position}, with masked bytes"),
+        ));
+        blocks.push(Block::Code(hex_view(*position, bytes)));
+    }
+    for (start, end, bytes) in &data.ranged_sequences {
+        let bytes: Vec<_> = bytes.iter().copied().map(Some).collect();
+        blocks.push(Block::Heading(
+            3,
+            format!("Starting anywhere from offset {start} to {end}"),
+        ));
+        blocks.push(Block::Code(hex_view(0, &bytes)));
+    }
+    for (i, group) in data.alternative_sequences.iter().enumerate() {
+        blocks.push(Block::Heading(3, format!("Alternative group {}", i + 1)));
+        blocks.push(Block::Paragraph(
+            "Any one of the following sequences must be present.".to_string(),
+        ));
+        for (position, bytes) in group {
+            let bytes: Vec<_> = bytes.iter().copied().map(Some).collect();
+            blocks.push(Block::Code(hex_view(*position, &bytes)));
+        }
+    }
+    for (distance, bytes) in &data.trailer_sequences {
+        let bytes: Vec<_> = bytes.iter().copied().map(Some).collect();
+        blocks.push(Block::Heading(
+            3,
+            format!("{distance} bytes from the end of the file"),
+        ));
+        blocks.push(Block::Code(hex_view(0, &bytes)));
+    }
+    if !data.regexes.is_empty() {
+        blocks.push(Block::Heading(3, "Regexes".to_string()));
+        blocks.push(Block::Table(
+            vec!["Offset", "Regex"],
+            data.regexes
+                .iter()
+                .map(|(position, regex)| vec![position.to_string(), regex.clone()])
+                .collect(),
+        ));
+    }
+
+    if !data.strings.is_empty() || !data.anchored_strings.is_empty() {
+        blocks.push(Block::Heading(2, "Strings".to_string()));
+
+        let mut strings: Vec<_> = data.strings.iter().cloned().collect();
+        strings.sort_unstable();
+        if !strings.is_empty() {
+            blocks.push(Block::Literals(strings));
+        }
+        if !data.anchored_strings.is_empty() {
+            blocks.push(Block::Table(
+                vec!["Offset", "String"],
+                data.anchored_strings
+                    .iter()
+                    .map(|(position, string)| vec![position.to_string(), string.clone()])
+                    .collect(),
+            ));
+        }
+    }
+
+    if data.average_entropy > 0.0 || !data.entropy_windows.is_empty() {
+        blocks.push(Block::Heading(2, "Entropy".to_string()));
+
+        let mut fields = vec![("Average entropy", format!("{:.3}", data.average_entropy))];
+        let band = data
+            .entropy_windows
+            .iter()
+            .fold(None, |band, (_, min, max)| {
+                Some(match band {
+                    None => (*min, *max),
+                    Some((low, high)) => (min.min(low), max.max(high)),
+                })
+            });
+        if let Some((low, high)) = band {
+            fields.push(("Entropy band", format!("{low:.3} to {high:.3}")));
+        }
+        if let Some((low, high)) = data.chi_square {
+            fields.push(("Chi-square", format!("{low:.1} to {high:.1}")));
+        }
+        blocks.push(Block::Fields(fields));
+
+        if !data.entropy_windows.is_empty() {
+            blocks.push(Block::Table(
+                vec!["Window", "Minimum", "Maximum"],
+                data.entropy_windows
+                    .iter()
+                    .map(|(window, min, max)| {
+                        vec![window.to_string(), format!("{min:.3}"), format!("{max:.3}")]
+                    })
+                    .collect(),
+            ));
+        }
+    }
+
+    let submitter_data = &pattern.submitter_data;
+    blocks.push(Block::Heading(2, "Submitters".to_string()));
+    let mut history = vec![vec![
+        "Scanned".to_string(),
+        contributor(&submitter_data.scanned_by, &submitter_data.scanned_by_email),
+        submitter_data.scanned_on.clone(),
+    ]];
+    for (i, name) in submitter_data.refined_by.iter().enumerate() {
+        let email = submitter_data
+            .refined_by_email
+            .get(i)
+            .map(String::as_str)
+            .unwrap_or_default();
+        history.push(vec![
+            "Refined".to_string(),
+            contributor(name, email),
+            String::new(),
+        ]);
+    }
+    blocks.push(Block::Table(vec!["Action", "By", "On"], history));
+
+    blocks
+}
+
+/// Describe a contributor to a pattern.
+///
+/// # Arguments
+///
+/// * `name` - The name of the contributor. May be empty.
+/// * `email` - The email address of the contributor. May be empty.
+fn contributor(name: &str, email: &str) -> String {
+    match (name.is_empty(), email.is_empty()) {
+        (true, true) => "Unknown".to_string(),
+        (false, true) => name.to_string(),
+        (true, false) => email.to_string(),
+        (false, false) => format!("{name} ({email})"),
+    }
+}
+
+/// Build a hex view of a byte sequence, giving the offset, the bytes as hex and the printable bytes on each line.
+///
+/// # Arguments
+///
+/// * `start` - The offset of the first byte.
+/// * `bytes` - The bytes, with a masked byte represented by `None`.
+fn hex_view(start: usize, bytes: &[Option<u8>]) -> String {
+    let mut view = String::new();
+    for (i, line) in bytes.chunks(HEX_VIEW_WIDTH).enumerate() {
+        let hex: Vec<_> = line
+            .iter()
+            .map(|b| b.map_or("??".to_string(), |b| format!("{b:02X}")))
+            .collect();
+        let printable: String = line
+            .iter()
+            .map(|b| match b {
+                Some(b) if b.is_ascii_graphic() || *b == b' ' => *b as char,
+                _ => '.',
+            })
+            .collect();
+
+        let _ = writeln!(
+            view,
+            "{:08X}  {:<width$}  {printable}",
+            start + i * HEX_VIEW_WIDTH,
+            hex.join(" "),
+            width = HEX_VIEW_WIDTH * 3 - 1
+        );
+    }
+
+    view
+}
+
+/// Render the blocks of a report as Markdown.
+///
+/// # Arguments
+///
+/// * `blocks` - The blocks.
+fn render_markdown(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                let _ = writeln!(out, "{} {}", "#".repeat(*level), escape_markdown(text));
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(out, "{}", escape_markdown(text));
+            }
+            Block::Fields(fields) => {
+                out.push_str("| Field | Value |\n| --- | --- |\n");
+                for (name, value) in fields {
+                    let _ = writeln!(out, "| {name} | {} |", escape_markdown(value));
+                }
+            }
+            Block::Table(headers, rows) => {
+                let _ = writeln!(out, "| {} |", headers.join(" | "));
+                let _ = writeln!(out, "|{}", " --- |".repeat(headers.len()));
+                for row in rows {
+                    let cells: Vec<_> = row.iter().map(|c| escape_markdown(c)).collect();
+                    let _ = writeln!(out, "| {} |", cells.join(" | "));
+                }
+            }
+            Block::Code(text) => {
+                let _ = write!(out, "```text\n{text}```\n");
+            }
+            Block::Literals(literals) => {
+                for literal in literals {
+                    // A code span must be delimited by more backticks than it contains.
+                    let fence = "`".repeat(literal.matches('`').count() + 1);
+                    let _ = writeln!(out, "- {fence} {literal} {fence}");
+                }
+            }
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Render the blocks of a report as a standalone HTML document.
+///
+/// # Arguments
+///
+/// * `title` - The title of the document.
+/// * `blocks` - The blocks.
+fn render_html(title: &str, blocks: &[Block]) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+        escape_html(title)
+    );
+
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                let _ = writeln!(out, "<h{level}>{}</h{level}>", escape_html(text));
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(out, "<p>{}</p>", escape_html(text));
+            }
+            Block::Fields(fields) => {
+                out.push_str("<table>\n");
+                for (name, value) in fields {
+                    let _ = writeln!(
+                        out,
+                        "<tr><th>{name}</th><td>{}</td></tr>",
+                        escape_html(value)
+                    );
+                }
+                out.push_str("</table>\n");
+            }
+            Block::Table(headers, rows) => {
+                out.push_str("<table>\n<tr>");
+                for header in headers {
+                    let _ = write!(out, "<th>{header}</th>");
+                }
+                out.push_str("</tr>\n");
+                for row in rows {
+                    out.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(out, "<td>{}</td>", escape_html(cell));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+            Block::Code(text) => {
+                let _ = writeln!(out, "<pre>{}</pre>", escape_html(text));
+            }
+            Block::Literals(literals) => {
+                out.push_str("<ul>\n");
+                for literal in literals {
+                    let _ = writeln!(out, "<li><code>{}</code></li>", escape_html(literal));
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+
+    out
+}
+
+/// Escape the characters that have a special meaning within Markdown text.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Escape the characters that have a special meaning within HTML.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests_pattern_report {
+    use crate::{interner::intern_sequences, pattern::Pattern};
+
+    use super::{hex_view, render, ReportFormat};
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            hex_view(16, &[Some(0x4D), None, Some(b'Z')]),
+            format!("00000010  4D ?? 5A{}  M.Z\n", " ".repeat(39))
+        );
+
+        let mut pattern = Pattern::new(
+            "Test <image>",
+            "A test format.",
+            vec!["TST".to_string()],
+            vec![],
+        );
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        pattern.data.strings.insert("A|B".to_string());
+        pattern.data.entropy_windows = vec![(0, 3.5, 4.0), (1, 2.0, 3.0)];
+        pattern.add_submitter_data("Someone", "someone@example.com");
+
+        let markdown = render(&pattern, ReportFormat::Markdown);
+        assert!(markdown.starts_with("# Test \\<image\\>\n"));
+        assert!(markdown.contains("| Extensions | TST |"));
+        assert!(markdown.contains("00000000  4D 41 47 49 43"));
+        assert!(markdown.contains("- ` A|B `"));
+        assert!(markdown.contains("| Entropy band | 2.000 to 4.000 |"));
+        assert!(markdown.contains("Someone (someone@example.com)"));
+
+        let html = render(&pattern, ReportFormat::Html);
+        assert!(html.contains("<h1>Test &lt;image&gt;</h1>"));
+        assert!(html.contains("<li><code>A|B</code></li>"));
+
+        assert_eq!(ReportFormat::from_path("out.HTML"), ReportFormat::Html);
+        assert_eq!(ReportFormat::from_path("out.md"), ReportFormat::Markdown);
+    }
+}
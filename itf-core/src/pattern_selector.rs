@@ -0,0 +1,113 @@
+/// The prefix of a selector that selects a pattern by its UUID.
+const UUID_PREFIX: &str = "uuid:";
+/// The prefix of a selector that selects a pattern by its name.
+const NAME_PREFIX: &str = "name:";
+
+/// A selection of the patterns that should be loaded, parsed from a target pattern string.
+///
+/// The string is a comma-separated list of selectors. A selector of the form `uuid:...` selects the pattern
+/// with that UUID, and one of the form `name:...` selects the pattern with that name, both without regard to
+/// case. Any other selector selects the patterns whose source (such as the path of the pattern file) contains it.
+/// An empty string selects every pattern.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatternSelector {
+    /// The strings, one of which the source of a selected pattern must contain.
+    sources: Vec<String>,
+    /// The UUIDs of the selected patterns.
+    uuids: Vec<String>,
+    /// The names of the selected patterns.
+    names: Vec<String>,
+}
+
+impl PatternSelector {
+    /// Parse a target pattern string into a [`PatternSelector`].
+    ///
+    /// # Arguments
+    ///
+    /// * `target_pattern` - The target pattern string. Empty selectors are ignored.
+    pub fn parse(target_pattern: &str) -> Self {
+        let mut selector = Self::default();
+        for s in target_pattern
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            if let Some(uuid) = s.strip_prefix(UUID_PREFIX) {
+                selector.uuids.push(uuid.trim().to_string());
+            } else if let Some(name) = s.strip_prefix(NAME_PREFIX) {
+                selector.names.push(name.trim().to_string());
+            } else {
+                selector.sources.push(s.to_string());
+            }
+        }
+
+        selector
+    }
+
+    /// Does the [`PatternSelector`] select every pattern?
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty() && self.uuids.is_empty() && self.names.is_empty()
+    }
+
+    /// Could a pattern be selected, based only on its source? This allows the patterns that can't be selected
+    /// to be skipped before they are parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source of the pattern.
+    ///
+    /// # Returns
+    ///
+    /// True if the pattern could be selected. This is always true when there are any UUID or name selectors,
+    /// as those can only be applied once the pattern has been parsed.
+    pub fn could_select(&self, source: &str) -> bool {
+        self.is_empty()
+            || !self.uuids.is_empty()
+            || !self.names.is_empty()
+            || self.sources.iter().any(|s| source.contains(s.as_str()))
+    }
+
+    /// Is a pattern selected?
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source of the pattern.
+    /// * `uuid` - The UUID of the pattern.
+    /// * `name` - The name of the pattern.
+    pub fn selects(&self, source: &str, uuid: &str, name: &str) -> bool {
+        self.is_empty()
+            || self.sources.iter().any(|s| source.contains(s.as_str()))
+            || self.uuids.iter().any(|u| u.eq_ignore_ascii_case(uuid))
+            || self.names.iter().any(|n| n.eq_ignore_ascii_case(name))
+    }
+}
+
+#[cfg(test)]
+mod tests_pattern_selector {
+    use super::PatternSelector;
+
+    #[test]
+    fn test_selectors() {
+        let uuid = "6f5b8da4-1461-8242-6900-625ec9c036c3";
+
+        assert!(PatternSelector::parse("").is_empty());
+        assert!(PatternSelector::parse(" , ").is_empty());
+        assert!(PatternSelector::parse("").selects("any.json", uuid, "Any"));
+
+        // A plain selector matches the source, and so can be applied before the pattern is parsed.
+        let path = PatternSelector::parse("mkv");
+        assert!(path.could_select("patterns/mkv.json"));
+        assert!(!path.could_select("patterns/png.json"));
+        assert!(path.selects("patterns/mkv.json", uuid, "Matroska"));
+
+        // UUID and name selectors are only resolved once the pattern has been parsed.
+        let parsed = PatternSelector::parse(&format!(
+            "uuid:{}, name:portable network graphics",
+            uuid.to_uppercase()
+        ));
+        assert!(parsed.could_select("patterns/renamed.json"));
+        assert!(parsed.selects("patterns/renamed.json", uuid, "Matroska"));
+        assert!(parsed.selects("patterns/png.json", "", "Portable Network Graphics"));
+        assert!(!parsed.selects("patterns/mkv.json", "", "Matroska"));
+    }
+}
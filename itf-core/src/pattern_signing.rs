@@ -0,0 +1,230 @@
+use ed25519_dalek::{Signature, Signer, SECRET_KEY_LENGTH};
+use rand::RngCore;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{error::ItfError, pattern::Pattern, utils};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// The extension appended to the path of a pattern file to give the path of its detached signature.
+pub const SIGNATURE_EXTENSION: &str = ".sig";
+
+/// An ed25519 signature of a [`Pattern`], along with the public key of the signer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PatternSignature {
+    /// The public key of the signer, as hex.
+    pub key: String,
+    /// The signature, as hex.
+    pub signature: String,
+}
+
+/// Generate a new signing key.
+pub fn generate_key() -> SigningKey {
+    let mut secret = [0; SECRET_KEY_LENGTH];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+
+    SigningKey::from_bytes(&secret)
+}
+
+/// Parse a signing key from its secret key, given as hex.
+///
+/// # Arguments
+///
+/// * `hex` - The secret key, as hex.
+pub fn signing_key_from_hex(hex: &str) -> Result<SigningKey, ItfError> {
+    utils::decode_hex(hex.trim())
+        .and_then(|b| <[u8; SECRET_KEY_LENGTH]>::try_from(b).ok())
+        .map(|b| SigningKey::from_bytes(&b))
+        .ok_or_else(|| ItfError::InvalidSignature("invalid secret key".to_string()))
+}
+
+/// Parse a public key, given as hex.
+///
+/// # Arguments
+///
+/// * `hex` - The public key, as hex.
+pub fn verifying_key_from_hex(hex: &str) -> Result<VerifyingKey, ItfError> {
+    utils::decode_hex(hex.trim())
+        .and_then(|b| <[u8; 32]>::try_from(b).ok())
+        .and_then(|b| VerifyingKey::from_bytes(&b).ok())
+        .ok_or_else(|| ItfError::InvalidSignature(format!("invalid public key '{}'", hex.trim())))
+}
+
+/// Read the public keys listed within a file of trusted keys.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file, which lists one public key per line as hex. Blank lines and lines
+///   starting with `#` are ignored.
+pub fn read_trusted_keys<P: AsRef<Path>>(path: P) -> Result<Vec<VerifyingKey>, ItfError> {
+    fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(verifying_key_from_hex)
+        .collect()
+}
+
+/// Get the bytes of a pattern that are covered by its signature.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+///
+/// # Notes
+///
+/// The signature and checksum are excluded, and the strings are sorted, as they are held within a set
+/// and so may be serialized in any order.
+pub fn canonical_bytes(pattern: &Pattern) -> Result<Vec<u8>, ItfError> {
+    let mut document = serde_json::to_value(pattern)?;
+    if let Some(fields) = document.as_object_mut() {
+        fields.remove("sig");
+        fields.remove("checksum");
+    }
+    if let Some(strings) = document
+        .pointer_mut("/pd/strings")
+        .and_then(Value::as_array_mut)
+    {
+        strings.sort_unstable_by(|a, b| a.as_str().cmp(&b.as_str()));
+    }
+
+    Ok(serde_json::to_vec(&document)?)
+}
+
+/// Sign a pattern.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+/// * `key` - The signing key.
+///
+/// # Returns
+///
+/// An error if the pattern could not be serialized, otherwise the signature.
+pub fn sign(pattern: &Pattern, key: &SigningKey) -> Result<PatternSignature, ItfError> {
+    let signature = key.sign(&canonical_bytes(pattern)?);
+
+    Ok(PatternSignature {
+        key: utils::encode_hex(key.verifying_key().as_bytes()),
+        signature: utils::encode_hex(&signature.to_bytes()),
+    })
+}
+
+/// Verify that a pattern has a valid signature from one of a set of trusted keys.
+///
+/// # Arguments
+///
+/// * `pattern` - The pattern.
+/// * `trusted_keys` - The public keys of the trusted signers.
+///
+/// # Returns
+///
+/// An error if the pattern isn't signed, was signed by an untrusted key, or has been modified since it was signed.
+pub fn verify(pattern: &Pattern, trusted_keys: &[VerifyingKey]) -> Result<(), ItfError> {
+    let name = &pattern.type_data.name;
+    let Some(signature) = &pattern.signature else {
+        return Err(ItfError::InvalidSignature(format!(
+            "the pattern '{name}' isn't signed"
+        )));
+    };
+
+    let key = verifying_key_from_hex(&signature.key)?;
+    if !trusted_keys.contains(&key) {
+        return Err(ItfError::InvalidSignature(format!(
+            "the pattern '{name}' was signed by the untrusted key '{}'",
+            signature.key
+        )));
+    }
+
+    let invalid = || ItfError::InvalidSignature(format!("the signature of '{name}' is invalid"));
+    let signature = utils::decode_hex(&signature.signature)
+        .and_then(|b| Signature::from_slice(&b).ok())
+        .ok_or_else(invalid)?;
+
+    key.verify_strict(&canonical_bytes(pattern)?, &signature)
+        .map_err(|_| invalid())
+}
+
+/// Get the path to the detached signature of a pattern file.
+///
+/// # Arguments
+///
+/// * `path` - The path to the pattern file.
+pub fn detached_signature_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut path = path.as_ref().as_os_str().to_owned();
+    path.push(SIGNATURE_EXTENSION);
+
+    PathBuf::from(path)
+}
+
+/// Read the detached signature of a pattern file, if it has one.
+///
+/// # Arguments
+///
+/// * `path` - The path to the pattern file.
+///
+/// # Returns
+///
+/// None if the pattern file has no detached signature, otherwise the signature. An error will be
+/// returned if the signature file could not be read or parsed.
+pub fn read_detached_signature<P: AsRef<Path>>(
+    path: P,
+) -> Result<Option<PatternSignature>, ItfError> {
+    match fs::read_to_string(detached_signature_path(path)) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| ItfError::InvalidSignature(format!("invalid detached signature: {e}"))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests_pattern_signing {
+    use crate::{
+        error::ItfError,
+        interner::{intern_bytes, intern_sequences},
+        pattern::Pattern,
+        utils,
+    };
+
+    use super::{generate_key, sign, signing_key_from_hex, verify};
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = generate_key();
+        let other_key = generate_key();
+
+        let mut pattern = Pattern::new("Test", "test", vec!["TEST".to_string()], vec![]);
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        for string in ["FIRST", "SECOND", "THIRD", "FOURTH"] {
+            pattern.data.strings.insert(string.to_string());
+        }
+        assert!(verify(&pattern, &[key.verifying_key()]).is_err());
+
+        pattern.signature = Some(sign(&pattern, &key).unwrap());
+        assert!(verify(&pattern, &[key.verifying_key()]).is_ok());
+        assert!(verify(&pattern, &[other_key.verifying_key()]).is_err());
+
+        // The signature must survive a round trip through JSON, in which the strings may be reordered.
+        let json = serde_json::to_string(&pattern).unwrap();
+        let reloaded = Pattern::from_simd_json_str(&json).unwrap();
+        assert!(verify(&reloaded, &[key.verifying_key()]).is_ok());
+
+        // Any modification invalidates the signature.
+        pattern.data.sequences[0].1 = intern_bytes(b"NAGIC");
+        assert!(matches!(
+            verify(&pattern, &[key.verifying_key()]),
+            Err(ItfError::InvalidSignature(_))
+        ));
+
+        // Keys can be restored from their hex form.
+        let restored = signing_key_from_hex(&utils::encode_hex(&key.to_bytes())).unwrap();
+        assert_eq!(restored.verifying_key(), key.verifying_key());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{error::ItfError, identifier::MatchResult};
+
+/// The placeholders that may be used within a template.
+const PLACEHOLDERS: &[&str] = &[
+    "file",
+    "name",
+    "mime",
+    "extension",
+    "uuid",
+    "points",
+    "max_points",
+    "percentage",
+];
+
+/// A part of a parsed template.
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    /// Text that is written as-is.
+    Literal(String),
+    /// A placeholder, which is replaced by the value of a field of the result.
+    Placeholder(&'static str),
+}
+
+/// A user-supplied template describing how the result for a file should be displayed,
+/// such as `{name}\t{mime}\t{percentage}`.
+///
+/// # Notes
+///
+/// The placeholders are `{file}`, `{name}`, `{mime}`, `{extension}`, `{uuid}`, `{points}`, `{max_points}` and
+/// `{percentage}`. A literal brace is written as `{{` or `}}`, and the escape sequences `\t`, `\n` and `\\` are
+/// supported so that the template can be given on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResultTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl ResultTemplate {
+    /// Parse a template.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The template.
+    ///
+    /// # Returns
+    ///
+    /// The parsed template, or an error if it contains an unknown placeholder or an unmatched brace.
+    pub fn parse(template: &str) -> Result<Self, ItfError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(ItfError::InvalidTemplate(format!(
+                                    "the placeholder '{{{name}' is never closed"
+                                )))
+                            }
+                        }
+                    }
+
+                    let Some(placeholder) = PLACEHOLDERS.iter().find(|p| **p == name) else {
+                        return Err(ItfError::InvalidTemplate(format!(
+                            "unknown placeholder '{{{name}}}', expected one of: {}",
+                            PLACEHOLDERS.join(", ")
+                        )));
+                    };
+
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(TemplatePart::Placeholder(placeholder));
+                }
+                '}' => {
+                    return Err(ItfError::InvalidTemplate(
+                        "unmatched '}', which should be written as '}}'".to_string(),
+                    ))
+                }
+                '\\' => match chars.peek() {
+                    Some('t') => {
+                        chars.next();
+                        literal.push('\t');
+                    }
+                    Some('n') => {
+                        chars.next();
+                        literal.push('\n');
+                    }
+                    Some('\\') => {
+                        chars.next();
+                        literal.push('\\');
+                    }
+                    _ => literal.push('\\'),
+                },
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Render the template for the result of a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The path of the file.
+    /// * `result` - The top result for the file, if it matched any pattern.
+    /// * `unknown_mimetype` - The MIME type given for a file without a result, or a result without a MIME type.
+    ///
+    /// # Returns
+    ///
+    /// The rendered template. The fields of a file without a result are empty, other than its name,
+    /// which is given as `Unknown`.
+    pub fn render(
+        &self,
+        file: &str,
+        result: Option<&MatchResult>,
+        unknown_mimetype: &str,
+    ) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => output.push_str(text),
+                TemplatePart::Placeholder(placeholder) => {
+                    output.push_str(&Self::field(placeholder, file, result, unknown_mimetype))
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Get the value of a placeholder.
+    ///
+    /// # Arguments
+    ///
+    /// * `placeholder` - The name of the placeholder.
+    /// * `file` - The path of the file.
+    /// * `result` - The top result for the file, if there is one.
+    /// * `unknown_mimetype` - The MIME type given when none is known.
+    fn field(
+        placeholder: &str,
+        file: &str,
+        result: Option<&MatchResult>,
+        unknown_mimetype: &str,
+    ) -> String {
+        match (placeholder, result) {
+            ("file", _) => file.to_string(),
+            ("name", None) => "Unknown".to_string(),
+            ("mime", r) => r
+                .and_then(|r| r.mimetype.as_deref())
+                .unwrap_or(unknown_mimetype)
+                .to_string(),
+            (_, None) => String::new(),
+            ("name", Some(r)) => r.name.clone(),
+            ("extension", Some(r)) => r.extension.clone().unwrap_or_default(),
+            ("uuid", Some(r)) => r.uuid.clone(),
+            ("points", Some(r)) => r.points.to_string(),
+            ("max_points", Some(r)) => r.max_points.to_string(),
+            ("percentage", Some(r)) => r.percentage.to_string(),
+            _ => unreachable!("unknown placeholder '{placeholder}'"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_result_template {
+    use crate::{identifier::MatchResult, interner::intern_sequences, pattern::Pattern};
+
+    use super::ResultTemplate;
+
+    #[test]
+    fn test_render() {
+        let mut pattern = Pattern::new("Test", "test", vec!["tst".to_string()], vec![]);
+        pattern.type_data.known_mimetypes = vec!["application/x-test".into()];
+        pattern.data.sequences = intern_sequences(vec![(0, b"MAGIC".to_vec())]);
+        pattern.other_data.total_scanned_files = 1;
+        pattern.compute_attributes();
+        let result = MatchResult::new(&pattern, Default::default());
+
+        let template =
+            ResultTemplate::parse(r"{file}\t{name}\t{mime}\t{extension} {{{max_points}}}").unwrap();
+        assert_eq!(
+            template.render("a.tst", Some(&result), "application/octet-stream"),
+            "a.tst\tTest\tapplication/x-test\tTST {8}"
+        );
+        assert_eq!(
+            template.render("b.bin", None, "application/octet-stream"),
+            "b.bin\tUnknown\tapplication/octet-stream\t {}"
+        );
+
+        assert!(ResultTemplate::parse("{nme}").is_err());
+        assert!(ResultTemplate::parse("{name").is_err());
+        assert!(ResultTemplate::parse("name}").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use crate::error::ItfError;
+
+/// The strings that are common to many unrelated file types. These are stored in uppercase,
+/// as that is how strings are stored within a pattern.
+const DEFAULT_BLOCKED_STRINGS: &[&str] = &[
+    "HTTP://",
+    "HTTPS://",
+    "WWW.",
+    ".COM",
+    "CREATED BY",
+    "COPYRIGHT",
+    "ALL RIGHTS RESERVED",
+    "UNTITLED",
+    "LOREM IPSUM",
+];
+
+/// A list of strings that are common to many unrelated file types, and so shouldn't be added to a pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StringBlocklist {
+    entries: Vec<String>,
+}
+
+impl StringBlocklist {
+    /// Create an empty [`StringBlocklist`], without any of the built-in entries.
+    pub fn empty() -> Self {
+        Self { entries: vec![] }
+    }
+
+    /// Add an entry to the [`StringBlocklist`].
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The entry. Any string containing the entry will be blocked, regardless of case.
+    pub fn add(&mut self, entry: &str) {
+        let entry = entry.trim().to_uppercase();
+        if !entry.is_empty() && !self.entries.contains(&entry) {
+            self.entries.push(entry);
+        }
+    }
+
+    /// Add the entries from a file to the [`StringBlocklist`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file. Each line gives an entry, and lines starting with `#` are ignored.
+    ///
+    /// # Returns
+    ///
+    /// An error if the file could not be read.
+    pub fn extend_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ItfError> {
+        let contents = std::fs::read_to_string(path)?;
+        for line in contents
+            .lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+        {
+            self.add(line);
+        }
+
+        Ok(())
+    }
+
+    /// Should a string be blocked?
+    ///
+    /// # Arguments
+    ///
+    /// * `string` - The string, which should be in uppercase.
+    pub fn is_blocked(&self, string: &str) -> bool {
+        self.entries.iter().any(|e| string.contains(e.as_str()))
+    }
+}
+
+impl Default for StringBlocklist {
+    fn default() -> Self {
+        let mut blocklist = Self::empty();
+        for entry in DEFAULT_BLOCKED_STRINGS {
+            blocklist.add(entry);
+        }
+
+        blocklist
+    }
+}
+
+#[cfg(test)]
+mod tests_string_blocklist {
+    use super::StringBlocklist;
+
+    #[test]
+    fn test_default_entries() {
+        let blocklist = StringBlocklist::default();
+        assert!(blocklist.is_blocked("CREATED BY SOMETHING"));
+        assert!(blocklist.is_blocked("HTTP://EXAMPLE.ORG"));
+        assert!(!blocklist.is_blocked("IHDR"));
+
+        assert!(!StringBlocklist::empty().is_blocked("CREATED BY SOMETHING"));
+    }
+
+    #[test]
+    fn test_added_entries() {
+        let mut blocklist = StringBlocklist::empty();
+        blocklist.add("  generator ");
+        blocklist.add("");
+        assert!(blocklist.is_blocked("GENERATOR: TEST"));
+        assert!(!blocklist.is_blocked(""));
+    }
+}
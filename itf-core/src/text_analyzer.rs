@@ -0,0 +1,296 @@
+use std::fmt;
+
+/// The UTF-8 byte order mark.
+const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+/// The UTF-16 (little-endian) byte order mark.
+const UTF16_LE_BOM: [u8; 2] = [0xFF, 0xFE];
+/// The UTF-16 (big-endian) byte order mark.
+const UTF16_BE_BOM: [u8; 2] = [0xFE, 0xFF];
+/// The minimum proportion of code units that must contain a zero byte for BOM-less data to be considered UTF-16.
+const UTF16_ZERO_BYTE_RATIO: f32 = 0.3;
+/// The maximum proportion of control characters that may be present for data to be considered text.
+const MAX_CONTROL_CHARACTER_RATIO: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Utf16Be => "UTF-16BE",
+            TextEncoding::Latin1 => "Latin-1",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// No line breaks were found.
+    None,
+    /// Unix-style line breaks (\n).
+    Lf,
+    /// Windows-style line breaks (\r\n).
+    CrLf,
+    /// Classic Mac OS-style line breaks (\r).
+    Cr,
+    /// More than one style of line break was found.
+    Mixed,
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LineEnding::None => "no line breaks",
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::Cr => "CR",
+            LineEnding::Mixed => "mixed line breaks",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// The result of analysing a block of data as text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextInfo {
+    /// The detected encoding of the text.
+    pub encoding: TextEncoding,
+    /// Did the data begin with a byte order mark?
+    pub has_bom: bool,
+    /// The style of line breaks used within the text.
+    pub line_ending: LineEnding,
+}
+
+impl fmt::Display for TextInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Plain text ({}", self.encoding)?;
+        if self.has_bom {
+            write!(f, " with BOM")?;
+        }
+        write!(f, ", {})", self.line_ending)
+    }
+}
+
+/// Attempt to analyse a block of data as text.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to be analysed. This should be the header chunk of a file.
+///
+/// # Returns
+///
+/// A [`TextInfo`] describing the text, or `None` if the data does not appear to be text.
+pub fn analyze(bytes: &[u8]) -> Option<TextInfo> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let (encoding, has_bom, body) = if let Some(body) = bytes.strip_prefix(&UTF8_BOM) {
+        (TextEncoding::Utf8, true, body)
+    } else if let Some(body) = bytes.strip_prefix(&UTF16_LE_BOM) {
+        (TextEncoding::Utf16Le, true, body)
+    } else if let Some(body) = bytes.strip_prefix(&UTF16_BE_BOM) {
+        (TextEncoding::Utf16Be, true, body)
+    } else {
+        (detect_encoding(bytes)?, false, bytes)
+    };
+
+    let text = decode(body, encoding)?;
+    if !is_printable(&text) {
+        return None;
+    }
+
+    Some(TextInfo {
+        encoding,
+        has_bom,
+        line_ending: detect_line_ending(&text),
+    })
+}
+
+/// Decode a block of data using the specified encoding.
+///
+/// # Notes
+///
+/// The data is assumed to be a chunk of a larger file, so a truncated character at the end is ignored.
+fn decode(bytes: &[u8], encoding: TextEncoding) -> Option<String> {
+    match encoding {
+        TextEncoding::Utf8 => match std::str::from_utf8(bytes) {
+            Ok(s) => Some(s.to_string()),
+            Err(e) if e.error_len().is_none() => {
+                Some(String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned())
+            }
+            Err(_) => None,
+        },
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+            let units = bytes.chunks_exact(2).map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if encoding == TextEncoding::Utf16Le {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            });
+
+            // As with UTF-8, a truncated surrogate pair at the end of the chunk is permitted.
+            let mut text = String::new();
+            let mut decoder = char::decode_utf16(units).peekable();
+            while let Some(c) = decoder.next() {
+                match c {
+                    Ok(c) => text.push(c),
+                    Err(_) if decoder.peek().is_none() => break,
+                    Err(_) => return None,
+                }
+            }
+
+            Some(text)
+        }
+        TextEncoding::Latin1 => Some(bytes.iter().map(|b| *b as char).collect()),
+    }
+}
+
+/// Attempt to detect the encoding of a block of data that has no byte order mark.
+fn detect_encoding(bytes: &[u8]) -> Option<TextEncoding> {
+    let pairs = bytes.len() / 2;
+    if pairs > 0 {
+        let (even_zeros, odd_zeros) = bytes.chunks_exact(2).fold((0, 0), |(even, odd), pair| {
+            (
+                even + (pair[0] == 0) as usize,
+                odd + (pair[1] == 0) as usize,
+            )
+        });
+
+        // Mostly-ASCII UTF-16 text will have a zero in one half of the majority of code units.
+        let threshold = (pairs as f32 * UTF16_ZERO_BYTE_RATIO) as usize;
+        if odd_zeros > threshold && even_zeros == 0 {
+            return Some(TextEncoding::Utf16Le);
+        }
+        if even_zeros > threshold && odd_zeros == 0 {
+            return Some(TextEncoding::Utf16Be);
+        }
+    }
+
+    if bytes.contains(&0) {
+        return None;
+    }
+
+    if decode(bytes, TextEncoding::Utf8).is_some() {
+        Some(TextEncoding::Utf8)
+    } else {
+        Some(TextEncoding::Latin1)
+    }
+}
+
+/// Determine the style of line breaks used within a block of text.
+fn detect_line_ending(text: &str) -> LineEnding {
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' if chars.peek() == Some(&'\n') => {
+                chars.next();
+                crlf += 1;
+            }
+            '\r' => cr += 1,
+            '\n' => lf += 1,
+            _ => {}
+        }
+    }
+
+    match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => LineEnding::None,
+        (true, false, false) => LineEnding::Lf,
+        (false, true, false) => LineEnding::CrLf,
+        (false, false, true) => LineEnding::Cr,
+        _ => LineEnding::Mixed,
+    }
+}
+
+/// Is the text mostly free of control characters, other than common whitespace?
+fn is_printable(text: &str) -> bool {
+    let mut total = 0;
+    let mut control = 0;
+    for c in text.chars() {
+        total += 1;
+        if c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0C' | '\x1B') {
+            control += 1;
+        }
+    }
+
+    total > 0 && control as f32 <= total as f32 * MAX_CONTROL_CHARACTER_RATIO
+}
+
+#[cfg(test)]
+mod tests_text_analyzer {
+    use super::{analyze, LineEnding, TextEncoding, TextInfo};
+
+    #[test]
+    fn test_utf8() {
+        let info = analyze("h\u{e9}llo\nworld\n".as_bytes()).unwrap();
+        assert_eq!(info.encoding, TextEncoding::Utf8);
+        assert!(!info.has_bom);
+        assert_eq!(info.line_ending, LineEnding::Lf);
+        assert_eq!(info.to_string(), "Plain text (UTF-8, LF)");
+
+        // A character truncated by the end of the chunk should be ignored.
+        let bytes = "abc\u{e9}".as_bytes();
+        let info = analyze(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(info.encoding, TextEncoding::Utf8);
+    }
+
+    #[test]
+    fn test_utf8_bom() {
+        let info = analyze(b"\xEF\xBB\xBFhello\r\nworld\r\n").unwrap();
+        assert_eq!(
+            info,
+            TextInfo {
+                encoding: TextEncoding::Utf8,
+                has_bom: true,
+                line_ending: LineEnding::CrLf,
+            }
+        );
+    }
+
+    #[test]
+    fn test_utf16() {
+        let le: Vec<u8> = "hello\rworld"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let info = analyze(&le).unwrap();
+        assert_eq!(info.encoding, TextEncoding::Utf16Le);
+        assert!(!info.has_bom);
+        assert_eq!(info.line_ending, LineEnding::Cr);
+
+        let mut be = vec![0xFE, 0xFF];
+        be.extend("hello\nworld\r\n".encode_utf16().flat_map(u16::to_be_bytes));
+        let info = analyze(&be).unwrap();
+        assert_eq!(info.encoding, TextEncoding::Utf16Be);
+        assert!(info.has_bom);
+        assert_eq!(info.line_ending, LineEnding::Mixed);
+    }
+
+    #[test]
+    fn test_latin1() {
+        let info = analyze(b"caf\xE9 au lait").unwrap();
+        assert_eq!(info.encoding, TextEncoding::Latin1);
+        assert_eq!(info.line_ending, LineEnding::None);
+    }
+
+    #[test]
+    fn test_binary() {
+        assert!(analyze(b"").is_none());
+        assert!(analyze(b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR").is_none());
+        assert!(analyze(&[0x01, 0x02, 0x03, 0x04, 0x05]).is_none());
+    }
+}
@@ -1,12 +1,23 @@
+use globset::{GlobBuilder, GlobSetBuilder};
 use rand::Rng;
-use std::path::Path;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, RwLock},
+};
 use walkdir::WalkDir;
 
+use crate::error::ItfError;
+
 /// The characters that may not appear in a NTFS file name.
 const NTFS_INVALID_CHARS: &str = "\\/:*?\"<>|";
 /// The characters that may not appear in a UNIX file name.
 const UNIX_INVALID_CHARS: &str = "/";
 
+/// The thread pool in which the parallel work of the library is performed, if the number of threads has been limited.
+static THREAD_POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+
 /// Calculate the Shannon entropy for a block of bytes.
 ///
 /// # Arguments
@@ -35,6 +46,62 @@ pub fn calculate_shannon_entropy(frequencies: &[usize; 256]) -> f32 {
     entropy
 }
 
+/// Calculate the chi-square statistic for a block of bytes, relative to a uniform byte distribution.
+///
+/// # Arguments
+///
+/// * `frequencies` - An array containing the byte frequencies.
+///
+/// # Returns
+///
+/// The chi-square statistic. Truly random data will give a value close to 256, while
+/// compressed data will typically give a value that is considerably higher.
+#[inline(always)]
+pub fn calculate_chi_square(frequencies: &[usize; 256]) -> f32 {
+    let total_bytes = frequencies.iter().sum::<usize>() as f32;
+    if total_bytes == 0.0 {
+        return 0.0;
+    }
+
+    let expected = total_bytes / 256.0;
+    frequencies
+        .iter()
+        .map(|&count| {
+            let diff = count as f32 - expected;
+            diff * diff / expected
+        })
+        .sum()
+}
+
+/// Decode a string of hex digits into bytes.
+///
+/// # Arguments
+///
+/// * `hex` - The hex digits, without any separators.
+///
+/// # Returns
+///
+/// None if the string contains anything other than pairs of hex digits, otherwise the bytes.
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Encode bytes as a string of lowercase hex digits.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes.
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
 /// Check that a directory exist.
 pub fn directory_exists<P: AsRef<Path>>(path: P) -> bool {
     path.as_ref().is_dir()
@@ -45,6 +112,11 @@ pub fn file_exists<P: AsRef<Path>>(path: P) -> bool {
     path.as_ref().is_file()
 }
 
+/// The compound file extensions that are recognised by default, such as the `TAR.GZ` of `archive.tar.gz`.
+pub const DEFAULT_COMPOUND_EXTENSIONS: &[&str] = &[
+    "TAR.BZ2", "TAR.GZ", "TAR.LZ", "TAR.LZMA", "TAR.XZ", "TAR.Z", "TAR.ZST",
+];
+
 /// Get the extension of a file.
 ///
 /// # Arguments
@@ -62,6 +134,84 @@ pub fn get_file_extension<P: AsRef<Path>>(path: P) -> String {
     }
 }
 
+/// Get the extension of a file, recognising compound extensions that span several components of the file name.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `compound_extensions` - The compound extensions to be recognised (e.g. `TAR.GZ`).
+///
+/// # Returns
+///
+/// The longest of the compound extensions with which the file name ends, in upper case. If there are none then
+/// the last extension of the file will be returned, as with [`get_file_extension`].
+pub fn get_compound_file_extension<P: AsRef<Path>, S: AsRef<str>>(
+    path: P,
+    compound_extensions: &[S],
+) -> String {
+    compound_extensions
+        .iter()
+        .map(|e| e.as_ref().trim_start_matches('.').to_uppercase())
+        .filter(|e| has_file_extension(path.as_ref(), e))
+        .max_by_key(String::len)
+        .unwrap_or_else(|| get_file_extension(path))
+}
+
+/// Does the name of a file end with a specific extension? The extension may be a compound extension.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `extension` - The extension, which is compared case-insensitively.
+pub fn has_file_extension<P: AsRef<Path>>(path: P, extension: &str) -> bool {
+    let Some(name) = path.as_ref().file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy().to_uppercase();
+    let extension = extension.trim_start_matches('.').to_uppercase();
+
+    // The name must have a stem preceding the extension.
+    !extension.is_empty()
+        && name.len() > extension.len() + 1
+        && name.ends_with(&extension)
+        && name.as_bytes()[name.len() - extension.len() - 1] == b'.'
+}
+
+/// List all of the files within a source directory, regardless of their file extension.
+///
+/// # Arguments
+///
+/// * `source_directory` - The source directory containing all of the files.
+///
+/// # Returns
+///
+/// A vector of strings giving the paths to all of the files.
+pub fn list_files<P: AsRef<Path>>(source_directory: P) -> Vec<String> {
+    list_files_to_depth(source_directory, usize::MAX)
+}
+
+/// List all of the files within a source directory, descending no further than a maximum depth.
+///
+/// # Arguments
+///
+/// * `source_directory` - The source directory containing all of the files.
+/// * `max_depth` - The maximum depth of the files. A depth of 1 lists only the files directly within
+///   the source directory.
+///
+/// # Returns
+///
+/// A vector of strings giving the paths to all of the files.
+pub fn list_files_to_depth<P: AsRef<Path>>(source_directory: P, max_depth: usize) -> Vec<String> {
+    WalkDir::new(source_directory)
+        .max_depth(max_depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.path().to_str().map(|s| s.to_string()))
+        .collect()
+}
+
 /// List all of the files within a source directory that have a specific file extension.
 ///
 /// # Arguments
@@ -76,15 +226,166 @@ pub fn list_files_of_type<P: AsRef<Path>>(
     source_directory: P,
     target_extension: &str,
 ) -> Vec<String> {
+    list_files_of_types(source_directory, &[target_extension])
+}
+
+/// List all of the files within a source directory that have any of a set of file extensions.
+///
+/// # Arguments
+///
+/// * `source_directory` - The source directory containing all of the files.
+/// * `target_extensions` - The file extensions, any one of which the files must possess.
+///
+/// # Returns
+///
+/// A vector of strings giving the paths to all of the matching files.
+pub fn list_files_of_types<P: AsRef<Path>, S: AsRef<str>>(
+    source_directory: P,
+    target_extensions: &[S],
+) -> Vec<String> {
+    let target_extensions: Vec<String> = target_extensions
+        .iter()
+        .map(|e| e.as_ref().to_uppercase())
+        .collect();
+
     WalkDir::new(source_directory)
+        .sort_by_file_name()
         .into_iter()
         .filter_map(Result::ok)
         .filter(|e| e.path().is_file())
-        .filter(|e| get_file_extension(e.path()) == target_extension.to_uppercase())
+        .filter(|e| {
+            target_extensions
+                .iter()
+                .any(|ext| has_file_extension(e.path(), ext))
+        })
         .filter_map(|e| e.path().to_str().map(|s| s.to_string()))
         .collect()
 }
 
+/// List all of the files within a source directory that are selected by a set of globs.
+///
+/// # Arguments
+///
+/// * `source_directory` - The source directory containing all of the files.
+/// * `globs` - The globs, which are matched against the path of each file relative to the source directory.
+///   A glob starting with `!` excludes any files that it matches.
+///
+/// # Returns
+///
+/// A vector of strings giving the paths to all of the files that match at least one of the globs and none of the
+/// excluding globs, or an error if any of the globs could not be parsed.
+///
+/// # Notes
+///
+/// A `*` doesn't match across directory separators, while a `**` does. If only excluding globs are given,
+/// every file that isn't excluded will be selected.
+pub fn list_files_matching<P: AsRef<Path>, S: AsRef<str>>(
+    source_directory: P,
+    globs: &[S],
+) -> Result<Vec<String>, ItfError> {
+    let mut include = GlobSetBuilder::new();
+    let mut exclude = GlobSetBuilder::new();
+    let mut has_includes = false;
+    for glob in globs {
+        let glob = glob.as_ref();
+        let (builder, glob) = match glob.strip_prefix('!') {
+            Some(g) => (&mut exclude, g),
+            None => {
+                has_includes = true;
+                (&mut include, glob)
+            }
+        };
+
+        builder.add(
+            GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| ItfError::InvalidGlob(e.to_string()))?,
+        );
+    }
+
+    let include = include
+        .build()
+        .map_err(|e| ItfError::InvalidGlob(e.to_string()))?;
+    let exclude = exclude
+        .build()
+        .map_err(|e| ItfError::InvalidGlob(e.to_string()))?;
+
+    let source_directory = source_directory.as_ref();
+    Ok(WalkDir::new(source_directory)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().is_file())
+        .filter(|e| {
+            let relative = e.path().strip_prefix(source_directory).unwrap_or(e.path());
+            (!has_includes || include.is_match(relative)) && !exclude.is_match(relative)
+        })
+        .filter_map(|e| e.path().to_str().map(|s| s.to_string()))
+        .collect())
+}
+
+/// Does a path contain any glob metacharacters, and so need to be expanded?
+///
+/// # Arguments
+///
+/// * `path` - The path.
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '[', '{'])
+}
+
+/// List all of the files that are selected by a glob, such as `downloads/**/*.bin`.
+///
+/// # Arguments
+///
+/// * `glob` - The glob. This may be relative to the current directory, or absolute.
+///
+/// # Returns
+///
+/// A vector of strings giving the paths to all of the matching files, or an error if the glob could not be parsed.
+///
+/// # Notes
+///
+/// Only the directory given by the part of the glob preceding the first metacharacter is searched. This allows
+/// globs to be used on platforms whose shell doesn't expand them, such as Windows.
+pub fn list_files_of_glob(glob: &str) -> Result<Vec<String>, ItfError> {
+    let first_metacharacter = glob.find(['*', '?', '[', '{']).unwrap_or(glob.len());
+    let split = glob[..first_metacharacter]
+        .rfind(['/', '\\'])
+        .map_or(0, |i| i + 1);
+    let (source_directory, glob) = glob.split_at(split);
+
+    if source_directory.is_empty() {
+        Ok(list_files_matching(".", &[glob])?
+            .into_iter()
+            .map(|f| f.strip_prefix("./").map(str::to_string).unwrap_or(f))
+            .collect())
+    } else {
+        list_files_matching(source_directory, &[glob])
+    }
+}
+
+/// Split a comma-separated list of file extensions.
+///
+/// # Arguments
+///
+/// * `extensions` - The list of file extensions, such as `jpg,jpeg,jfif`. Any leading dots will be removed.
+///
+/// # Returns
+///
+/// A vector of the unique, non-empty extensions in uppercase, in the order in which they were listed.
+pub fn split_extensions(extensions: &str) -> Vec<String> {
+    let mut split: Vec<String> = Vec::new();
+    for extension in extensions.split(',') {
+        let extension = extension.trim().trim_start_matches('.').to_uppercase();
+        if !extension.is_empty() && !split.contains(&extension) {
+            split.push(extension);
+        }
+    }
+
+    split
+}
+
 /// Generate a random UUID.
 pub fn make_uuid() -> String {
     // Generate a random u128 value.
@@ -115,6 +416,59 @@ pub fn round_to_dp(value: f32, decimal_places: usize) -> f32 {
     (value * multiplier).round() / multiplier
 }
 
+/// Limit the number of threads used by the library for parallel work, such as identification and pattern building.
+///
+/// # Arguments
+///
+/// * `threads` - The maximum number of threads. If this is zero, the limit will be removed.
+///
+/// # Returns
+///
+/// An error if the threads could not be created.
+///
+/// # Notes
+///
+/// The work is performed in a dedicated thread pool, rather than the global pool, and so any other parallel work
+/// within the application is unaffected.
+pub fn set_thread_limit(threads: usize) -> Result<(), ItfError> {
+    let pool = if threads == 0 {
+        None
+    } else {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("itf-worker-{i}"))
+            .build()
+            .map_err(io::Error::other)?;
+        Some(Arc::new(pool))
+    };
+
+    *THREAD_POOL.write().unwrap_or_else(|e| e.into_inner()) = pool;
+
+    Ok(())
+}
+
+/// Run an operation within the thread pool whose size was set by [`set_thread_limit`], so that any parallel
+/// iterators within it are bound by the limit. If no limit has been set, the operation is run directly.
+///
+/// # Arguments
+///
+/// * `op` - The operation.
+///
+/// # Returns
+///
+/// The value returned by the operation.
+pub fn in_thread_pool<R: Send>(op: impl FnOnce() -> R + Send) -> R {
+    let pool = THREAD_POOL
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
 /// Sanitize a file name by removing invalid characters from the file name string.
 ///
 /// # Arguments
@@ -2,7 +2,9 @@
 
 use flate2::Compression;
 use flate2::{read::DeflateDecoder, write::DeflateEncoder};
-use itf_core::{file_point_calculator::FilePointCalculator, file_processor, pattern::Pattern};
+use itf_core::{
+    file_point_calculator::FilePointCalculator, file_processor, pattern::Pattern, utils,
+};
 use std::io::Cursor;
 use std::{
     env,
@@ -88,19 +90,19 @@ fn compress_string(input: &str) -> Vec<u8> {
 }
 
 fn compute_match(pattern: &Pattern, target: &str) -> usize {
-    let chunk = if let Ok(b) = file_processor::read_file_header_chunk(target) {
-        b
+    let sample = if let Ok(s) = file_processor::read_file_sample(target) {
+        s
     } else {
         return 0;
     };
 
-    let mut frequencies = [0; 256];
-
-    if pattern.data.should_scan_sequences() || pattern.data.should_scan_composition() {
-        file_processor::count_byte_frequencies(&chunk, &mut frequencies);
-    }
-
-    FilePointCalculator::compute(pattern, &chunk, target, false)
+    FilePointCalculator::compute(
+        pattern,
+        &sample,
+        Some(target),
+        utils::DEFAULT_COMPOUND_EXTENSIONS,
+        false,
+    )
 }
 
 fn copy_exe() -> Option<PathBuf> {